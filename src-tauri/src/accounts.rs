@@ -0,0 +1,92 @@
+// Premium file-host accounts. The account list lives in state.json; passwords and API keys are kept
+// in the OS keyring under the account id and only handed to resolver plugins when they run.
+
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+
+const KEYRING_SERVICE: &str = "velodown";
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "camelCase")]
+pub(crate) enum AccountStatus { #[default] Unchecked, Valid, Invalid, Expired }
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct Account {
+    pub(crate) id: String,
+    pub(crate) host: String,
+    pub(crate) username: String,
+    #[serde(default)]
+    pub(crate) status: AccountStatus,
+    // As last reported by the host's plugin; `None` means unlimited or unknown
+    #[serde(default)]
+    pub(crate) traffic_left: Option<u64>,
+    #[serde(default)]
+    pub(crate) valid_until: Option<DateTime<Local>>,
+    #[serde(default)]
+    pub(crate) last_checked: Option<DateTime<Local>>,
+    #[serde(default)]
+    pub(crate) error: Option<String>,
+}
+
+impl Account {
+    // Accounts that are known to be dead or out of traffic are skipped when resolving
+    pub(crate) fn usable(&self) -> bool {
+        matches!(self.status, AccountStatus::Unchecked | AccountStatus::Valid)
+            && self.traffic_left != Some(0)
+            && self.valid_until.is_none_or(|until| until > Local::now())
+    }
+
+    pub(crate) fn apply_report(&mut self, report: AccountReport) {
+        self.status = match report.valid {
+            false => AccountStatus::Invalid,
+            true if report.valid_until.is_some_and(|until| until <= Local::now()) => AccountStatus::Expired,
+            true => AccountStatus::Valid,
+        };
+        self.traffic_left = report.traffic_left;
+        self.valid_until = report.valid_until;
+        self.error = report.error;
+        self.last_checked = Some(Local::now());
+    }
+}
+
+// What a plugin says about the account it was given
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct AccountReport {
+    pub(crate) valid: bool,
+    #[serde(default)]
+    pub(crate) traffic_left: Option<u64>,
+    #[serde(default)]
+    pub(crate) valid_until: Option<DateTime<Local>>,
+    #[serde(default)]
+    pub(crate) error: Option<String>,
+}
+
+// The keyring APIs block (D-Bus, Keychain), so they run off the async runtime
+pub(crate) async fn store_secret(id: &str, secret: &str) -> Result<(), String> {
+    let (id, secret) = (id.to_string(), secret.to_string());
+    tokio::task::spawn_blocking(move || keyring::Entry::new(KEYRING_SERVICE, &id)?.set_password(&secret))
+        .await
+        .map_err(|e| e.to_string())?
+        .map_err(|e| format!("Could not save to the keyring: {}", e))
+}
+
+pub(crate) async fn load_secret(id: &str) -> Result<String, String> {
+    let id = id.to_string();
+    tokio::task::spawn_blocking(move || keyring::Entry::new(KEYRING_SERVICE, &id)?.get_password())
+        .await
+        .map_err(|e| e.to_string())?
+        .map_err(|e| format!("Could not read from the keyring: {}", e))
+}
+
+pub(crate) async fn delete_secret(id: &str) -> Result<(), String> {
+    let id = id.to_string();
+    let result = tokio::task::spawn_blocking(move || keyring::Entry::new(KEYRING_SERVICE, &id)?.delete_credential())
+        .await
+        .map_err(|e| e.to_string())?;
+    match result {
+        Ok(()) | Err(keyring::Error::NoEntry) => Ok(()),
+        Err(e) => Err(format!("Could not remove from the keyring: {}", e)),
+    }
+}
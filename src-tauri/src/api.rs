@@ -0,0 +1,209 @@
+// Optional HTTP API so scripts, cron jobs and other machines can drive the queue without the GUI.
+// Every request must carry `Authorization: Bearer <api_token>`.
+
+use std::fmt::Write;
+use std::net::Ipv4Addr;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+
+use axum::extract::{Path, Query, Request, State as Api};
+use axum::http::{header, StatusCode};
+use axum::middleware::{self, Next};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use serde::Deserialize;
+use tauri::{AppHandle, Manager, State};
+
+use crate::{AddDownloadPayload, AppState, DownloadStatus, DownloadTask};
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct AddRequest {
+    url: String,
+    // Resolved from the server like the add dialog does when omitted
+    file_name: Option<String>,
+    save_path: Option<String>,
+    checksum: Option<String>,
+    // `{ "url": ".sig URL", "key": "armored key, key URL or fingerprint" }`
+    signature: Option<crate::signature::SignatureCheck>,
+    // Lets a plain-HTTP link through HTTPS-only mode
+    #[serde(default)]
+    allow_http: bool,
+    // `{ "method": "POST", "body": "...", "contentType": "..." }` for endpoints that won't answer a GET
+    #[serde(default)]
+    request: Option<crate::CustomRequest>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(rename_all = "camelCase", default)]
+struct RemoveQuery { delete_file: bool, cache_partial: bool }
+
+struct ApiError(StatusCode, String);
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        (self.0, Json(serde_json::json!({ "error": self.1 }))).into_response()
+    }
+}
+
+impl From<String> for ApiError {
+    fn from(message: String) -> Self { ApiError(StatusCode::BAD_REQUEST, message) }
+}
+
+// Starts, restarts or stops the server to match the current settings
+pub(crate) async fn apply_settings(app_handle: AppHandle) {
+    let state: State<AppState> = app_handle.state();
+    if let Some(previous) = state.api_server.lock().await.take() {
+        // Aborting drops the listener right away, so the port can be bound again below
+        previous.abort();
+        let _ = previous.await;
+    }
+    let settings = state.persistent.lock().await.settings.clone();
+    if !settings.api_enabled { return; }
+    let token = settings.api_token.trim().to_string();
+    if token.is_empty() {
+        log::warn!("Remote API is enabled but has no token; not starting it");
+        return;
+    }
+    let address = if settings.api_allow_lan { Ipv4Addr::UNSPECIFIED } else { Ipv4Addr::LOCALHOST };
+    let listener = match tokio::net::TcpListener::bind((address, settings.api_port)).await {
+        Ok(listener) => listener,
+        Err(e) => {
+            log::error!("Could not start remote API on {}:{}: {}", address, settings.api_port, e);
+            return;
+        }
+    };
+    let mut router = Router::new()
+        .route("/api/downloads", get(list_downloads).post(add))
+        .route("/api/downloads/{id}", get(get_download).delete(remove))
+        .route("/api/downloads/{id}/pause", post(pause))
+        .route("/api/downloads/{id}/resume", post(resume));
+    if settings.metrics_enabled {
+        router = router.route("/metrics", get(metrics));
+    }
+    let router = router
+        .layer(middleware::from_fn_with_state(Arc::<str>::from(token), require_token))
+        .with_state(app_handle.clone());
+    let server = tauri::async_runtime::spawn(async move {
+        if let Err(e) = axum::serve(listener, router).await {
+            log::error!("Remote API stopped: {}", e);
+        }
+    });
+    *state.api_server.lock().await = Some(server);
+}
+
+async fn require_token(Api(token): Api<Arc<str>>, request: Request, next: Next) -> Response {
+    let provided = request.headers().get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "));
+    if provided.is_some_and(|p| constant_time_eq(p.as_bytes(), token.as_bytes())) {
+        next.run(request).await
+    } else {
+        ApiError(StatusCode::UNAUTHORIZED, "Missing or invalid API token".to_string()).into_response()
+    }
+}
+
+// Compares every byte so response timing doesn't reveal how much of a guessed token was right
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+async fn find_task(app: &AppHandle, id: &str) -> Result<DownloadTask, ApiError> {
+    let state: State<AppState> = app.state();
+    let state_guard = state.persistent.lock().await;
+    state_guard.downloads.iter().find(|t| t.id == id).cloned()
+        .ok_or_else(|| ApiError(StatusCode::NOT_FOUND, "Download not found".to_string()))
+}
+
+async fn list_downloads(Api(app): Api<AppHandle>) -> Json<Vec<DownloadTask>> {
+    Json(app.state::<AppState>().persistent.lock().await.downloads.clone())
+}
+
+async fn get_download(Api(app): Api<AppHandle>, Path(id): Path<String>) -> Result<Json<DownloadTask>, ApiError> {
+    Ok(Json(find_task(&app, &id).await?))
+}
+
+async fn add(Api(app): Api<AppHandle>, Json(request): Json<AddRequest>) -> Result<(StatusCode, Json<DownloadTask>), ApiError> {
+    url::Url::parse(&request.url).map_err(|e| format!("Invalid URL: {}", e))?;
+    let source_url = Some(request.url.clone());
+    let (url, file_name, total_size, headers, redirect_chain) = match request.file_name {
+        Some(file_name) => (request.url, file_name, None, Default::default(), Vec::new()),
+        // Probing would send the custom request an extra time
+        None if request.request.is_some() => {
+            let file_name = url::Url::parse(&request.url).ok().as_ref().and_then(crate::file_name_from_url)
+                .unwrap_or_else(|| format!("download_{}.tmp", chrono::Local::now().timestamp()));
+            (request.url, file_name, None, Default::default(), Vec::new())
+        }
+        None => {
+            let client = crate::http_client_for(&app.state(), &request.url).await;
+            let info = crate::resolve_download_info(&app, &client, &request.url).await?;
+            (info.final_url, info.file_name, info.total_size, info.headers, info.redirect_chain)
+        }
+    };
+    let payload = AddDownloadPayload {
+        url, file_name, total_size, custom_path: request.save_path, checksum: request.checksum, headers, redirect_chain, parent_id: None,
+        signature: request.signature, allow_http: request.allow_http, proxy: None, request: request.request, source_url,
+    };
+    let task = crate::add_download(payload, app.state(), app.clone()).await?;
+    Ok((StatusCode::CREATED, Json(task)))
+}
+
+async fn pause(Api(app): Api<AppHandle>, Path(id): Path<String>) -> Result<StatusCode, ApiError> {
+    find_task(&app, &id).await?;
+    crate::pause_download(id, app.state(), app.clone()).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn resume(Api(app): Api<AppHandle>, Path(id): Path<String>) -> Result<StatusCode, ApiError> {
+    find_task(&app, &id).await?;
+    // The batch variant skips tasks that are already running or completed
+    crate::resume_downloads(vec![id], app.state(), app.clone()).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn remove(Api(app): Api<AppHandle>, Path(id): Path<String>, Query(query): Query<RemoveQuery>) -> Result<StatusCode, ApiError> {
+    find_task(&app, &id).await?;
+    crate::cancel_download(id, Some(query.delete_file), Some(query.cache_partial), app.state(), app.clone()).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+// Prometheus text exposition format
+async fn metrics(Api(app): Api<AppHandle>) -> impl IntoResponse {
+    let state: State<AppState> = app.state();
+    let active = state.download_handles.lock().await.len();
+    let (speed, counts) = {
+        let state_guard = state.persistent.lock().await;
+        let speed: u64 = state_guard.downloads.iter()
+            .filter(|t| t.status == DownloadStatus::Downloading)
+            .map(|t| t.speed)
+            .sum();
+        let statuses = [
+            DownloadStatus::Queued, DownloadStatus::Downloading, DownloadStatus::Paused, DownloadStatus::Completed,
+            DownloadStatus::Failed, DownloadStatus::Verifying, DownloadStatus::Retrying,
+        ];
+        let counts: Vec<(String, usize)> = statuses.into_iter()
+            .map(|status| {
+                let count = state_guard.downloads.iter().filter(|t| t.status == status).count();
+                (serde_json::to_value(&status).ok().and_then(|v| v.as_str().map(str::to_string)).unwrap_or_default(), count)
+            })
+            .collect();
+        (speed, counts)
+    };
+    let queued = counts.iter().find(|(status, _)| status == "queued").map_or(0, |(_, count)| *count);
+
+    let mut body = String::new();
+    let mut metric = |name: &str, kind: &str, help: &str, value: u64| {
+        let _ = writeln!(body, "# HELP {} {}\n# TYPE {} {}\n{} {}", name, help, name, kind, name, value);
+    };
+    metric("velodown_active_downloads", "gauge", "Downloads with an open transfer", active as u64);
+    metric("velodown_download_speed_bytes", "gauge", "Combined speed of all transfers in bytes per second", speed);
+    metric("velodown_queue_length", "gauge", "Tasks waiting to start", queued as u64);
+    metric("velodown_downloaded_bytes_total", "counter", "Bytes received since the app started", state.metrics.bytes_downloaded.load(Ordering::Relaxed));
+    metric("velodown_failures_total", "counter", "Downloads that failed for good since the app started", state.metrics.failures.load(Ordering::Relaxed));
+    let _ = writeln!(body, "# HELP velodown_tasks Tasks in the list by status\n# TYPE velodown_tasks gauge");
+    for (status, count) in counts {
+        let _ = writeln!(body, "velodown_tasks{{status=\"{}\"}} {}", status, count);
+    }
+    ([(header::CONTENT_TYPE, "text/plain; version=0.0.4")], body)
+}
@@ -0,0 +1,128 @@
+// Sign-in prompts for downloads a site answers with 401, or a proxy with 407. Rather than failing, the
+// task pauses with the challenge on it and `auth_required` asks the window for a user name and
+// password; `provide_credentials` resumes the download sending them with Basic authentication, as an
+// Authorization header for a site or in the proxy URL for a proxy. Credentials the user chose to
+// remember are kept in the OS keyring under the host, and get one try before the user is asked again.
+
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager, State};
+use url::Url;
+
+use crate::{accounts, AppState, DownloadTask};
+
+pub(crate) const AUTH_REQUIRED_ERROR: &str = "Sign-in required";
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct AuthChallenge {
+    // The site's host, or the proxy's when `proxy`
+    pub(crate) host: String,
+    pub(crate) realm: Option<String>,
+    #[serde(default)]
+    pub(crate) proxy: bool,
+}
+
+#[derive(Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct AuthRequired {
+    pub(crate) id: String,
+    #[serde(flatten)]
+    pub(crate) challenge: AuthChallenge,
+}
+
+// The challenge of a 401 or 407, or None when the server only offers schemes other than Basic
+pub(crate) fn challenge(response: &reqwest::Response, proxy_url: Option<&str>) -> Option<AuthChallenge> {
+    let proxy = response.status() == reqwest::StatusCode::PROXY_AUTHENTICATION_REQUIRED;
+    let header = if proxy { reqwest::header::PROXY_AUTHENTICATE } else { reqwest::header::WWW_AUTHENTICATE };
+    let offered: Vec<String> = response.headers().get_all(header).iter()
+        .filter_map(|v| v.to_str().ok())
+        .map(str::to_string)
+        .collect();
+    let offered = offered.join(", ");
+    // A challenge without the header is taken to be Basic, as browsers do
+    let realm = if offered.trim().is_empty() { None } else {
+        let basic = regex::Regex::new(r"(?i)(?:^|[\s,])basic(?:\s|,|$)").expect("valid regex");
+        let start = basic.find(&offered)?.end();
+        let realm = regex::Regex::new(r#"(?i)realm\s*=\s*"([^"]*)""#).expect("valid regex");
+        realm.captures(&offered[start..]).map(|c| c[1].to_string())
+    };
+    if proxy { return Some(AuthChallenge { realm, ..proxy_challenge(proxy_url) }); }
+    Some(AuthChallenge { host: response.url().host_str()?.to_string(), realm, proxy })
+}
+
+// A proxy that wants a sign-in refuses the tunnel of an HTTPS download, so there's no response to read
+pub(crate) fn is_proxy_refusal(error: &reqwest::Error) -> bool {
+    let mut source: Option<&dyn std::error::Error> = Some(error);
+    while let Some(error) = source {
+        if error.to_string().contains("proxy authorization required") { return true; }
+        source = error.source();
+    }
+    false
+}
+
+pub(crate) fn proxy_challenge(proxy_url: Option<&str>) -> AuthChallenge {
+    let host = proxy_url.and_then(|p| Url::parse(p.trim()).ok()?.host_str().map(str::to_string));
+    AuthChallenge { host: host.unwrap_or_else(|| "the proxy".to_string()), realm: None, proxy: true }
+}
+
+// Keeps the challenge on the task for the prompt; the error pauses the download instead of failing it
+pub(crate) async fn require(app_handle: &AppHandle, id: &str, challenge: AuthChallenge) -> anyhow::Error {
+    let error = anyhow::anyhow!("{}: {}{}", AUTH_REQUIRED_ERROR, challenge.host, challenge.realm.as_ref().map(|r| format!(" ({})", r)).unwrap_or_default());
+    let state: State<AppState> = app_handle.state();
+    let mut state_guard = state.persistent.lock().await;
+    if let Some(task) = state_guard.downloads.iter_mut().find(|t| t.id == id) {
+        task.auth_challenge = Some(challenge);
+    }
+    error
+}
+
+// Puts the credentials on the task; false when it already had these ones, which the server just refused
+pub(crate) fn apply(task: &mut DownloadTask, challenge: &AuthChallenge, proxy_url: Option<&str>, user: &str, pass: &str) -> Result<bool, String> {
+    if challenge.proxy {
+        let proxy = task.proxy.as_deref().or(proxy_url).ok_or("No proxy is set")?;
+        let mut url = Url::parse(proxy.trim()).map_err(|e| format!("Invalid proxy {}: {}", proxy, e))?;
+        url.set_username(user).map_err(|_| "This proxy can't take a user name")?;
+        url.set_password(Some(pass)).map_err(|_| "This proxy can't take a password")?;
+        let changed = task.proxy.as_deref() != Some(url.as_str());
+        task.proxy = Some(url.to_string());
+        return Ok(changed);
+    }
+    let value = format!("Basic {}", base64::engine::general_purpose::STANDARD.encode(format!("{}:{}", user, pass)));
+    let changed = !task.headers.iter().any(|(name, existing)| name.eq_ignore_ascii_case("authorization") && *existing == value);
+    task.headers.retain(|name, _| !name.eq_ignore_ascii_case("authorization"));
+    task.headers.insert("Authorization".to_string(), value);
+    Ok(changed)
+}
+
+fn keyring_id(challenge: &AuthChallenge) -> String {
+    format!("{}:{}", if challenge.proxy { "proxy" } else { "site" }, challenge.host)
+}
+
+pub(crate) async fn remember(challenge: &AuthChallenge, user: &str, pass: &str) -> Result<(), String> {
+    accounts::store_secret(&keyring_id(challenge), &format!("{}:{}", user, pass)).await
+}
+
+// Applies credentials remembered for the task's challenge; false when there are none, or the server
+// already refused them
+pub(crate) async fn apply_remembered(app_handle: &AppHandle, id: &str) -> bool {
+    let state: State<AppState> = app_handle.state();
+    let challenge = {
+        let state_guard = state.persistent.lock().await;
+        state_guard.downloads.iter().find(|t| t.id == id).and_then(|t| t.auth_challenge.clone())
+    };
+    let Some(challenge) = challenge else { return false };
+    let Ok(secret) = accounts::load_secret(&keyring_id(&challenge)).await else { return false };
+    let Some((user, pass)) = secret.split_once(':') else { return false };
+    let mut state_guard = state.persistent.lock().await;
+    let proxy_url = state_guard.settings.proxy_url.clone().filter(|p| !p.trim().is_empty());
+    let Some(task) = state_guard.downloads.iter_mut().find(|t| t.id == id) else { return false };
+    let applied = apply(task, &challenge, proxy_url.as_deref(), user, pass).unwrap_or(false);
+    if applied { task.auth_challenge = None; }
+    drop(state_guard);
+    if applied {
+        crate::request_save(&state);
+        crate::tasklog::write(app_handle, id, &format!("Signing in to {} with the remembered credentials", challenge.host)).await;
+    }
+    applied
+}
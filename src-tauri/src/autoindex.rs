@@ -0,0 +1,170 @@
+// Open directories: the "Index of /" pages nginx, Apache, lighttpd and friends generate. Entries are
+// the links to children of the listed directory; their dates and sizes come from the text beside the
+// link (`<pre>` listings) or the other cells of its row (Apache's table listings).
+
+use std::collections::VecDeque;
+use std::path::PathBuf;
+
+use percent_encoding::percent_decode_str;
+use reqwest::Client;
+use serde::Serialize;
+use tokio::time::Duration;
+use url::Url;
+
+use crate::{AddDownloadPayload, ScanFilters};
+
+// Directories visited by one recursive enqueue; open directories can be enormous
+const MAX_DIRECTORIES: usize = 200;
+
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct DirectoryListing {
+    // After redirects, always ending in `/`
+    pub(crate) url: String,
+    pub(crate) parent: Option<String>,
+    pub(crate) entries: Vec<DirectoryEntry>,
+}
+
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct DirectoryEntry {
+    pub(crate) name: String,
+    pub(crate) url: String,
+    pub(crate) is_dir: bool,
+    // Listings often round (`1.2M`), so this is approximate
+    pub(crate) size: Option<u64>,
+    // As the server printed it; formats differ between servers
+    pub(crate) modified: Option<String>,
+}
+
+pub(crate) async fn browse(client: &Client, url: &str) -> Result<DirectoryListing, String> {
+    let mut url = Url::parse(url.trim()).map_err(|e| format!("Invalid URL: {}", e))?;
+    if !url.path().ends_with('/') { url.set_path(&format!("{}/", url.path())); }
+    let response = client.get(url.as_str())
+        .header("Accept", "text/html")
+        .timeout(Duration::from_secs(20))
+        .send()
+        .await
+        .map_err(|e| format!("Request failed: {}", e))?;
+    if !response.status().is_success() {
+        return Err(format!("Server returned error: {}", response.status()));
+    }
+    let dir_url = response.url().clone();
+    let html = crate::read_capped_body(response, crate::MAX_SCAN_PAGE_BYTES).await;
+    let (is_index, entries) = parse_listing(&html, &dir_url);
+    if !is_index && entries.is_empty() {
+        return Err("Not a directory listing".to_string());
+    }
+    let parent = (dir_url.path() != "/").then(|| dir_url.join("..").ok()).flatten().map(|u| u.to_string());
+    Ok(DirectoryListing { url: dir_url.to_string(), parent, entries })
+}
+
+// Whether the page calls itself an index, and its entries. Kept free of awaits: kuchikiki's DOM isn't `Send`.
+fn parse_listing(html: &str, dir_url: &Url) -> (bool, Vec<DirectoryEntry>) {
+    use kuchikiki::traits::TendrilSink;
+    let document = kuchikiki::parse_html().one(html);
+    let is_index = document.select_first("title, h1").is_ok_and(|t| t.text_contents().trim_start().starts_with("Index of"));
+    let Ok(anchors) = document.select("a[href]") else { return (is_index, Vec::new()) };
+
+    let mut entries: Vec<DirectoryEntry> = Vec::new();
+    for anchor in anchors {
+        let Some(href) = anchor.attributes.borrow().get("href").map(str::to_string) else { continue };
+        // Column-sorting links (`?C=N;O=D`) and in-page anchors
+        if href.starts_with('?') || href.starts_with('#') { continue; }
+        let Ok(mut url) = dir_url.join(&href) else { continue };
+        url.set_fragment(None);
+        url.set_query(None);
+        if url.origin() != dir_url.origin() { continue; }
+        // Only direct children: not the parent, the directory itself or deeper paths
+        let Some(rest) = url.path().strip_prefix(dir_url.path()) else { continue };
+        let name = rest.trim_end_matches('/');
+        if name.is_empty() || name.contains('/') { continue; }
+        if entries.iter().any(|e| e.url == url.as_str()) { continue; }
+        let name = percent_decode_str(name).decode_utf8_lossy().to_string();
+
+        let details = match anchor.as_node().ancestors().find(|n| n.as_element().is_some_and(|e| e.name.local.as_ref() == "tr")) {
+            // Every cell but the one holding the link
+            Some(row) => row.children()
+                .filter(|cell| !cell.descendants().any(|d| d == *anchor.as_node()))
+                .map(|cell| cell.text_contents())
+                .collect::<Vec<_>>()
+                .join(" "),
+            // `<pre>` listings: the text after the link, up to the end of its line
+            None => anchor.as_node().next_sibling()
+                .and_then(|n| n.as_text().map(|t| t.borrow().split('\n').next().unwrap_or_default().to_string()))
+                .unwrap_or_default(),
+        };
+        let (modified, size) = parse_details(&details);
+        entries.push(DirectoryEntry { is_dir: rest.ends_with('/'), url: url.to_string(), name, size, modified });
+    }
+    (is_index, entries)
+}
+
+// `16-Jan-2024 10:00    1234` (nginx), `2024-01-16 10:00  1.2K  Description` (Apache)
+fn parse_details(text: &str) -> (Option<String>, Option<u64>) {
+    let tokens: Vec<&str> = text.split_whitespace().collect();
+    let Some(date_at) = tokens.iter().position(|t| {
+        t.chars().filter(char::is_ascii_digit).count() >= 4 && (t.contains('-') || t.contains('/'))
+    }) else {
+        return (None, tokens.first().and_then(|t| parse_size(t)));
+    };
+    let mut next = date_at + 1;
+    let mut modified = tokens[date_at].to_string();
+    if let Some(time) = tokens.get(next).filter(|t| t.contains(':')) {
+        modified = format!("{} {}", modified, time);
+        next += 1;
+    }
+    (Some(modified), tokens.get(next).and_then(|t| parse_size(t)))
+}
+
+// Exact byte counts, or `1.2K`/`34M`-style rounded ones (binary multiples); `-` for directories
+fn parse_size(token: &str) -> Option<u64> {
+    let token = token.trim_end_matches(['B', 'b']).trim_end_matches(['i']);
+    let (number, multiplier) = match token.chars().last()?.to_ascii_uppercase() {
+        'K' => (&token[..token.len() - 1], 1u64 << 10),
+        'M' => (&token[..token.len() - 1], 1 << 20),
+        'G' => (&token[..token.len() - 1], 1 << 30),
+        'T' => (&token[..token.len() - 1], 1 << 40),
+        _ => (token, 1),
+    };
+    let value: f64 = number.parse().ok()?;
+    (value >= 0.0).then_some((value * multiplier as f64) as u64)
+}
+
+// The files under `url` that pass `filters`, walking subdirectories when `recursive`. They land in
+// `<folder>/<directory name>/...`, mirroring the listing's structure.
+pub(crate) async fn collect_files(
+    client: &Client, url: &str, recursive: bool, filters: &ScanFilters, folder: &str,
+) -> Result<Vec<AddDownloadPayload>, String> {
+    let root = browse(client, url).await?;
+    let root_url = Url::parse(&root.url).map_err(|e| e.to_string())?;
+    let top = root_url.path_segments().and_then(|mut s| s.rfind(|s| !s.is_empty()))
+        .map(|s| percent_decode_str(s).decode_utf8_lossy().to_string())
+        .filter(|name| crate::is_valid_file_name(name))
+        .unwrap_or_else(|| root_url.host_str().unwrap_or("download").to_string());
+
+    let mut payloads = Vec::new();
+    let mut pending = VecDeque::from([(root, PathBuf::from(folder).join(top))]);
+    let mut visited = 1;
+    while let Some((listing, path)) = pending.pop_front() {
+        for entry in listing.entries {
+            if !crate::is_valid_file_name(&entry.name) { continue; }
+            if entry.is_dir {
+                if !recursive || visited >= MAX_DIRECTORIES { continue; }
+                visited += 1;
+                // A subdirectory that fails to list is skipped rather than failing the whole batch
+                match browse(client, &entry.url).await {
+                    Ok(sub) => pending.push_back((sub, path.join(&entry.name))),
+                    Err(e) => log::warn!("Could not list {}: {}", crate::redact::url(&entry.url), crate::redact::text(&e)),
+                }
+            } else if filters.matches_extension(&entry.name) && filters.matches_size(entry.size) {
+                payloads.push(AddDownloadPayload {
+                    url: entry.url, file_name: entry.name, total_size: entry.size,
+                    custom_path: Some(path.to_string_lossy().to_string()), checksum: None,
+                    headers: Default::default(), redirect_chain: Vec::new(), parent_id: None, signature: None, allow_http: false, proxy: None, request: None, source_url: None,
+                });
+            }
+        }
+    }
+    Ok(payloads)
+}
@@ -0,0 +1,21 @@
+// Launch at login: a LaunchAgent on macOS, the Run registry key on Windows and an XDG autostart entry
+// on Linux, all through the autostart plugin. The login launch passes `--minimized` so the app starts
+// in the tray, and downloads that were running when it last closed pick up again on their own.
+
+use tauri::AppHandle;
+use tauri_plugin_autostart::ManagerExt;
+
+pub(crate) const MINIMIZED_ARG: &str = "--minimized";
+
+// Registers or unregisters the login item to match the setting. Registering again when already
+// registered refreshes the recorded path, in case the app has moved since.
+pub(crate) fn apply(app_handle: &AppHandle, enabled: bool) -> Result<(), String> {
+    let autolaunch = app_handle.autolaunch();
+    if enabled {
+        autolaunch.enable().map_err(|e| format!("Could not set the app to start at login: {}", e))
+    } else if autolaunch.is_enabled().unwrap_or(false) {
+        autolaunch.disable().map_err(|e| format!("Could not stop the app starting at login: {}", e))
+    } else {
+        Ok(())
+    }
+}
@@ -0,0 +1,142 @@
+// A single zip of the app's data, to keep before reinstalling or to move to another machine: the state
+// file (downloads, settings, subscriptions, mirrors), the app logs and the download logs, plus a
+// manifest with each file's SHA-256. Restoring checks every hash and that the state loads before
+// anything is replaced. Passwords in the system keyring aren't part of it.
+
+use std::collections::BTreeMap;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::PersistentState;
+
+const FORMAT: &str = "velodown-backup";
+const FORMAT_VERSION: u32 = 1;
+const MANIFEST: &str = "manifest.json";
+const STATE: &str = "state.json";
+const LOGS: &str = "logs/";
+const TASK_LOGS: &str = "logs/tasks/";
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Manifest {
+    format: String,
+    version: u32,
+    app_version: String,
+    created_at: DateTime<Local>,
+    // Entry name to hex SHA-256
+    files: BTreeMap<String, String>,
+}
+
+// Where each part of the data lives on this machine
+pub(crate) struct DataPaths {
+    pub(crate) state: PathBuf,
+    pub(crate) logs: Option<PathBuf>,
+    pub(crate) task_logs: Option<PathBuf>,
+}
+
+// Returns the number of files written besides the manifest
+pub(crate) async fn backup(paths: DataPaths, path: PathBuf) -> Result<usize, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let mut files = vec![(STATE.to_string(), std::fs::read(&paths.state).map_err(|e| format!("Could not read the state file: {}", e))?)];
+        for (dir, prefix) in [(&paths.logs, LOGS), (&paths.task_logs, TASK_LOGS)] {
+            let Some(dir) = dir else { continue };
+            let Ok(entries) = std::fs::read_dir(dir) else { continue };
+            for entry in entries.flatten() {
+                if !entry.path().is_file() { continue; }
+                // A log rotated away meanwhile is just left out
+                let Ok(content) = std::fs::read(entry.path()) else { continue };
+                files.push((format!("{}{}", prefix, entry.file_name().to_string_lossy()), content));
+            }
+        }
+        let manifest = Manifest {
+            format: FORMAT.to_string(),
+            version: FORMAT_VERSION,
+            app_version: env!("CARGO_PKG_VERSION").to_string(),
+            created_at: Local::now(),
+            files: files.iter().map(|(name, content)| (name.clone(), hex::encode(Sha256::digest(content)))).collect(),
+        };
+
+        // Written next to the target and renamed, so a failed backup never replaces a good one
+        let tmp_path = path.with_extension("zip.tmp");
+        let file = std::fs::File::create(&tmp_path).map_err(|e| format!("Could not create {}: {}", path.display(), e))?;
+        let mut zip = zip::ZipWriter::new(file);
+        let options = zip::write::SimpleFileOptions::default().compression_method(zip::CompressionMethod::Deflated);
+        let manifest_json = serde_json::to_vec_pretty(&manifest).map_err(|e| e.to_string())?;
+        for (name, content) in std::iter::once((MANIFEST.to_string(), manifest_json)).chain(files.iter().cloned()) {
+            zip.start_file(name, options).map_err(|e| e.to_string())?;
+            zip.write_all(&content).map_err(|e| e.to_string())?;
+        }
+        zip.finish().map_err(|e| e.to_string())?;
+        std::fs::rename(&tmp_path, &path).map_err(|e| e.to_string())?;
+        Ok(files.len())
+    }).await.map_err(|e| e.to_string())?
+}
+
+// Reads and checks a backup without touching anything; returns the state and the log files to put back
+pub(crate) async fn read(path: PathBuf) -> Result<(PersistentState, Vec<(String, Vec<u8>)>), String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let file = std::fs::File::open(&path).map_err(|e| format!("Could not open {}: {}", path.display(), e))?;
+        let mut zip = zip::ZipArchive::new(file).map_err(|e| format!("Not a backup archive: {}", e))?;
+        let manifest: Manifest = serde_json::from_slice(&entry(&mut zip, MANIFEST)?)
+            .map_err(|_| "Not a backup made by this app".to_string())?;
+        if manifest.format != FORMAT { return Err("Not a backup made by this app".to_string()); }
+        if manifest.version > FORMAT_VERSION {
+            return Err(format!("This backup is from version {} of the app; update first", manifest.app_version));
+        }
+        let mut state = None;
+        let mut logs = Vec::new();
+        for (name, hash) in &manifest.files {
+            if name != STATE && log_file_name(name).is_none() {
+                return Err(format!("The backup has an unexpected entry: {}", name));
+            }
+            let content = entry(&mut zip, name)?;
+            if hex::encode(Sha256::digest(&content)) != *hash {
+                return Err(format!("The backup is damaged: {} doesn't match its checksum", name));
+            }
+            if name == STATE {
+                state = Some(serde_json::from_slice::<PersistentState>(&content)
+                    .map_err(|e| format!("The backup's download list can't be read: {}", e))?);
+            } else {
+                logs.push((name.clone(), content));
+            }
+        }
+        let state = state.ok_or("The backup has no download list")?;
+        Ok((state, logs))
+    }).await.map_err(|e| e.to_string())?
+}
+
+fn entry(zip: &mut zip::ZipArchive<std::fs::File>, name: &str) -> Result<Vec<u8>, String> {
+    let mut file = zip.by_name(name).map_err(|_| format!("The backup is incomplete: {} is missing", name))?;
+    let mut content = Vec::new();
+    file.read_to_end(&mut content).map_err(|e| format!("The backup is damaged: {}", e))?;
+    Ok(content)
+}
+
+// `logs/<file>` or `logs/tasks/<file>`, and nothing that could step outside those folders
+fn log_file_name(name: &str) -> Option<(bool, &str)> {
+    let (is_task_log, file_name) = match name.strip_prefix(TASK_LOGS) {
+        Some(file_name) => (true, file_name),
+        None => (false, name.strip_prefix(LOGS)?),
+    };
+    let safe = !file_name.is_empty() && !file_name.starts_with('.') && !file_name.contains(['/', '\\']);
+    safe.then_some((is_task_log, file_name))
+}
+
+// Puts the log files from `read` back in their folders
+pub(crate) async fn restore_logs(paths: &DataPaths, logs: Vec<(String, Vec<u8>)>) -> Result<(), String> {
+    for (name, content) in logs {
+        let Some((is_task_log, file_name)) = log_file_name(&name) else { continue };
+        let Some(dir) = (if is_task_log { &paths.task_logs } else { &paths.logs }) else { continue };
+        write(dir, file_name, &content).await.map_err(|e| format!("Could not restore {}: {}", name, e))?;
+    }
+    Ok(())
+}
+
+async fn write(dir: &Path, file_name: &str, content: &[u8]) -> std::io::Result<()> {
+    tokio::fs::create_dir_all(dir).await?;
+    tokio::fs::write(dir.join(file_name), content).await
+}
@@ -0,0 +1,144 @@
+// Captive portals (hotel and airport Wi-Fi) answer every request with a redirect to their login page
+// until the user signs in, which otherwise shows up as HTML-page and TLS errors that burn through
+// every retry. When an attempt fails, a known plain-HTTP URL that should answer 204 is probed; if
+// something else answers, every download is parked under its own status until the probe passes again.
+
+use std::sync::Arc;
+
+use tauri::{AppHandle, Emitter, Manager, State};
+use tokio::time::{Duration, Instant};
+
+use crate::i18n::{tr, Language, Text};
+use crate::{AppState, DownloadStatus};
+
+const PROBE_URL: &str = "http://connectivitycheck.gstatic.com/generate_204";
+// Failed attempts within this long of a probe reuse its answer
+const PROBE_COOLDOWN: Duration = Duration::from_secs(20);
+const RECHECK_INTERVAL: Duration = Duration::from_secs(10);
+
+#[derive(Default)]
+pub(crate) struct CaptivePortal {
+    // The login page while downloads are parked
+    login: std::sync::Mutex<Option<String>>,
+    last_probe: tokio::sync::Mutex<Option<Instant>>,
+    detected: tokio::sync::Notify,
+}
+
+// `Unknown` when the probe couldn't get through or got an answer no portal would give
+enum Probe { Online, Portal(String), Unknown }
+
+async fn probe(app_handle: &AppHandle) -> Probe {
+    let settings = app_handle.state::<AppState>().persistent.lock().await.settings.clone();
+    // The portal's cookies stay out of the shared jar
+    let client = crate::http_client_builder(&settings, Arc::new(crate::cookies::CookieJar::default()))
+        .and_then(|builder| Ok(builder.redirect(reqwest::redirect::Policy::none()).timeout(Duration::from_secs(10)).build()?));
+    let client = match client {
+        Ok(client) => client,
+        Err(e) => {
+            log::warn!("Can't probe for a captive portal: {}", e);
+            return Probe::Unknown;
+        }
+    };
+    let response = match client.get(PROBE_URL).send().await {
+        Ok(response) => response,
+        Err(_) => return Probe::Unknown,
+    };
+    // Some portals redirect, others serve the login page in place of the empty answer
+    match response.status() {
+        reqwest::StatusCode::NO_CONTENT => Probe::Online,
+        status if status.is_redirection() => Probe::Portal(response.headers().get(reqwest::header::LOCATION)
+            .and_then(|l| l.to_str().ok())
+            .map_or_else(|| PROBE_URL.to_string(), str::to_string)),
+        reqwest::StatusCode::OK => Probe::Portal(PROBE_URL.to_string()),
+        _ => Probe::Unknown,
+    }
+}
+
+fn message(login: &str) -> String {
+    format!("Waiting for you to sign in to the network (login page: {})", login)
+}
+
+// Called after a failed attempt. When the failure was a captive portal this returns the message the
+// task should show as it stops retrying and takes the `CaptivePortal` status.
+pub(crate) async fn check(app_handle: &AppHandle) -> Option<String> {
+    let state: State<AppState> = app_handle.state();
+    if !state.persistent.lock().await.settings.detect_captive_portal { return None; }
+    {
+        let mut last_probe = state.captive.last_probe.lock().await;
+        if last_probe.is_some_and(|t| t.elapsed() < PROBE_COOLDOWN) {
+            return state.captive.login.lock().unwrap().as_deref().map(message);
+        }
+        *last_probe = Some(Instant::now());
+    }
+    let Probe::Portal(login) = probe(app_handle).await else { return None };
+    if state.captive.login.lock().unwrap().replace(login.clone()).is_none() {
+        log::warn!("Captive portal detected; downloads wait for sign-in at {}", crate::redact::url(&login));
+        state.captive.detected.notify_one();
+    }
+    Some(message(&login))
+}
+
+// Parks every running download, then waits for the portal to let traffic through
+async fn park(app_handle: &AppHandle, login: &str) {
+    let state: State<AppState> = app_handle.state();
+    let running: Vec<String> = state.download_handles.lock().await.keys().cloned().collect();
+    futures::future::join_all(running.iter().map(|id| crate::abort_download(&state, id))).await;
+    let settings = {
+        let mut state_guard = state.persistent.lock().await;
+        for task in state_guard.downloads.iter_mut().filter(|t| running.contains(&t.id)) {
+            task.status = DownloadStatus::CaptivePortal;
+            task.speed = 0;
+            task.error_message = Some(message(login));
+            app_handle.emit("task_updated", &*task).unwrap();
+        }
+        state_guard.settings.clone()
+    };
+    crate::request_save(&state);
+    app_handle.emit("captive_portal", Some(login)).unwrap();
+    let language = Language::from_setting(&settings.language);
+    crate::notifications::show(app_handle, &settings, tr(language, Text::SignInToNetwork), tr(language, Text::SignInToNetworkBody));
+    wait_and_resume(app_handle).await;
+}
+
+async fn wait_and_resume(app_handle: &AppHandle) {
+    loop {
+        tokio::time::sleep(RECHECK_INTERVAL).await;
+        if let Probe::Online = probe(app_handle).await { break; }
+    }
+    log::info!("Captive portal cleared; resuming downloads");
+    let state: State<AppState> = app_handle.state();
+    *state.captive.login.lock().unwrap() = None;
+    app_handle.emit("captive_portal", None::<String>).unwrap();
+    let parked: Vec<String> = {
+        let mut state_guard = state.persistent.lock().await;
+        state_guard.downloads.iter_mut()
+            .filter(|t| t.status == DownloadStatus::CaptivePortal)
+            .map(|t| {
+                // Attempts lost to the portal don't count against the task
+                t.resume_attempts = 0;
+                t.status = DownloadStatus::Paused;
+                t.error_message = None;
+                t.id.clone()
+            })
+            .collect()
+    };
+    for id in parked {
+        if let Err(e) = crate::start_download_task(id, app_handle.clone()).await {
+            log::warn!("Could not resume a download after the captive portal: {}", e);
+        }
+    }
+}
+
+pub(crate) async fn run_captive_watch(app_handle: AppHandle) {
+    let state: State<AppState> = app_handle.state();
+    // Downloads parked when the app last closed continue once the probe passes
+    let parked = state.persistent.lock().await.downloads.iter().any(|t| t.status == DownloadStatus::CaptivePortal);
+    if parked && state.captive.login.lock().unwrap().replace(PROBE_URL.to_string()).is_none() {
+        wait_and_resume(&app_handle).await;
+    }
+    loop {
+        state.captive.detected.notified().await;
+        let Some(login) = state.captive.login.lock().unwrap().clone() else { continue };
+        park(&app_handle, &login).await;
+    }
+}
@@ -0,0 +1,156 @@
+// Click'n'Load v2: the local listener JDownloader runs on port 9666. Pages with a CnL button probe
+// `/jdcheck.js`, then submit their links as a form to `/flash/add` (plain) or `/flash/addcrypted2`
+// (AES-encrypted), and the links land in the queue.
+
+use std::collections::HashMap;
+use std::net::Ipv4Addr;
+use std::path::PathBuf;
+
+use aes::cipher::{block_padding::NoPadding, BlockDecryptMut, KeyIvInit};
+use axum::extract::State as Cnl;
+use axum::http::{header, StatusCode};
+use axum::response::IntoResponse;
+use axum::routing::{get, post};
+use axum::{Form, Router};
+use base64::Engine;
+use futures::StreamExt;
+use tauri::{AppHandle, Manager, State};
+
+use crate::{AddDownloadPayload, AppState};
+
+// Fixed by the protocol; the buttons on web pages only ever talk to this port
+const CLICK_N_LOAD_PORT: u16 = 9666;
+
+type Aes128CbcDec = cbc::Decryptor<aes::Aes128>;
+
+// Starts or stops the listener to match the current settings
+pub(crate) async fn apply_settings(app_handle: AppHandle) {
+    let state: State<AppState> = app_handle.state();
+    if let Some(previous) = state.click_n_load_server.lock().await.take() {
+        previous.abort();
+        let _ = previous.await;
+    }
+    if !state.persistent.lock().await.settings.click_n_load_enabled { return; }
+    // Browsers on this machine are the only legitimate callers, so never listen beyond localhost
+    let listener = match tokio::net::TcpListener::bind((Ipv4Addr::LOCALHOST, CLICK_N_LOAD_PORT)).await {
+        Ok(listener) => listener,
+        Err(e) => {
+            log::error!("Could not start Click'n'Load on port {}: {}", CLICK_N_LOAD_PORT, e);
+            return;
+        }
+    };
+    let router = Router::new()
+        .route("/", get(|| async { "JDownloader" }))
+        .route("/jdcheck.js", get(|| async { ([(header::CONTENT_TYPE, "text/javascript")], "jdownloader=true;\n") }))
+        .route("/crossdomain.xml", get(crossdomain))
+        .route("/flash/add", post(add))
+        .route("/flash/addcrypted2", post(add_crypted))
+        .with_state(app_handle.clone());
+    let server = tauri::async_runtime::spawn(async move {
+        if let Err(e) = axum::serve(listener, router).await {
+            log::error!("Click'n'Load stopped: {}", e);
+        }
+    });
+    *state.click_n_load_server.lock().await = Some(server);
+}
+
+// Flash-based buttons refuse to post without this
+async fn crossdomain() -> impl IntoResponse {
+    (
+        [(header::CONTENT_TYPE, "text/xml")],
+        "<?xml version=\"1.0\"?>\n<!DOCTYPE cross-domain-policy SYSTEM \"http://www.macromedia.com/xml/dtds/cross-domain-policy.dtd\">\n\
+         <cross-domain-policy>\n<allow-access-from domain=\"*\" />\n</cross-domain-policy>\n",
+    )
+}
+
+async fn add(Cnl(app): Cnl<AppHandle>, Form(form): Form<HashMap<String, String>>) -> (StatusCode, String) {
+    let urls = form.get("urls").map(String::as_str).unwrap_or_default();
+    respond(queue_links(&app, urls, &form).await)
+}
+
+async fn add_crypted(Cnl(app): Cnl<AppHandle>, Form(form): Form<HashMap<String, String>>) -> (StatusCode, String) {
+    let links = match (form.get("jk"), form.get("crypted")) {
+        (Some(jk), Some(crypted)) => decrypt_links(jk, crypted),
+        _ => Err("Missing jk or crypted field".to_string()),
+    };
+    match links {
+        Ok(links) => respond(queue_links(&app, &links, &form).await),
+        Err(e) => respond(Err(e)),
+    }
+}
+
+// The page-side scripts only check for the `success` line
+fn respond(result: Result<(), String>) -> (StatusCode, String) {
+    match result {
+        Ok(()) => (StatusCode::OK, "success\r\n".to_string()),
+        Err(e) => {
+            log::warn!("Click'n'Load request rejected: {}", e);
+            (StatusCode::BAD_REQUEST, format!("failed {}\r\n", e))
+        }
+    }
+}
+
+// `jk` is a JavaScript function returning the key as 32 hex digits. Real JavaScript isn't run, so
+// only the usual `function f(){ return '<hex>'; }` form is understood. The key doubles as the IV.
+fn decrypt_links(jk: &str, crypted: &str) -> Result<String, String> {
+    let key = jk.split(['\'', '"'])
+        .find(|part| part.len() == 32 && part.chars().all(|c| c.is_ascii_hexdigit()))
+        .and_then(|hex_key| hex::decode(hex_key).ok())
+        .ok_or("Unsupported jk key function")?;
+    let mut data = base64::engine::general_purpose::STANDARD.decode(crypted.trim())
+        .map_err(|e| format!("Invalid crypted payload: {}", e))?;
+    let plain = Aes128CbcDec::new_from_slices(&key, &key)
+        .map_err(|e| e.to_string())?
+        .decrypt_padded_mut::<NoPadding>(&mut data)
+        .map_err(|_| "Invalid crypted payload length".to_string())?;
+    // Padded with NULs up to the block size
+    Ok(String::from_utf8_lossy(plain).trim_end_matches('\0').to_string())
+}
+
+// Probes each link for its real name and size, then adds them as one batch. `package` becomes a
+// subfolder of the download folder, and `source` (the page with the button) is sent as the Referer.
+async fn queue_links(app: &AppHandle, links: &str, form: &HashMap<String, String>) -> Result<(), String> {
+    let urls: Vec<String> = links.split(['\r', '\n'])
+        .map(str::trim)
+        .filter(|l| l.starts_with("http://") || l.starts_with("https://"))
+        .map(str::to_string)
+        .collect();
+    if urls.is_empty() { return Err("No links".to_string()); }
+
+    let state: State<AppState> = app.state();
+    let package = form.get("package").map(|p| p.trim()).filter(|p| crate::is_valid_file_name(p));
+    let custom_path = match package {
+        Some(package) => {
+            let folder = state.persistent.lock().await.settings.download_folder.clone();
+            Some(PathBuf::from(folder).join(package).to_string_lossy().to_string())
+        }
+        None => None,
+    };
+    let referer = form.get("source").map(|s| s.trim()).filter(|s| s.starts_with("http://") || s.starts_with("https://"));
+
+    let client = crate::http_client(&state).await;
+    let payloads: Vec<AddDownloadPayload> = futures::stream::iter(urls)
+        .map(|url| {
+            let client = &client;
+            let custom_path = custom_path.clone();
+            async move {
+                let (url, file_name, total_size, redirect_chain) = match crate::probe_download_info(client, &url).await {
+                    Ok(info) => (info.final_url, info.file_name, info.total_size, info.redirect_chain),
+                    Err(_) => {
+                        let parsed = url::Url::parse(&url).map_err(|e| e.to_string())?;
+                        let file_name = crate::file_name_from_url(&parsed).unwrap_or_else(|| "download".to_string());
+                        (url, file_name, None, Vec::new())
+                    }
+                };
+                let headers = referer.map(|r| [("Referer".to_string(), r.to_string())].into()).unwrap_or_default();
+                Ok::<_, String>(AddDownloadPayload {
+                    url, file_name, total_size, custom_path, checksum: None, headers, redirect_chain, parent_id: None, signature: None, allow_http: false, proxy: None, request: None, source_url: None,
+                })
+            }
+        })
+        .buffered(crate::SCAN_PROBE_CONCURRENCY)
+        .filter_map(|payload| async move { payload.ok() })
+        .collect()
+        .await;
+    crate::add_downloads(payloads, state, app.clone()).await.map(|_| ())
+}
@@ -0,0 +1,71 @@
+// Client certificates (mutual TLS) for servers that ask for one. A certificate without a host is
+// presented to every server; one with a host pattern (as in `host_rules`) gets an HTTP client of its
+// own, used to probe and download URLs on matching hosts. PKCS#12 bundles are converted to PEM, the
+// only identity format the rustls backend takes.
+
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Default)]
+#[serde(rename_all = "camelCase", default)]
+pub(crate) struct ClientCertificate {
+    // `example.com` (and subdomains) or a `*` pattern; empty applies to every host
+    pub(crate) host: String,
+    // A PKCS#12 bundle (.p12/.pfx), or a PEM file with the certificate chain
+    pub(crate) path: String,
+    // PEM private key, when it isn't in the same file as the certificate
+    pub(crate) key_path: String,
+    // Unlocks a PKCS#12 bundle
+    pub(crate) password: String,
+}
+
+impl ClientCertificate {
+    pub(crate) fn host_pattern(&self) -> Option<String> {
+        Some(self.host.trim().trim_end_matches('.').to_ascii_lowercase()).filter(|h| !h.is_empty())
+    }
+
+    pub(crate) fn identity(&self) -> Result<reqwest::Identity, String> {
+        reqwest::Identity::from_pem(&self.pem()?).map_err(|e| format!("Invalid client certificate {}: {}", self.path.trim(), e))
+    }
+
+    // The certificate chain and private key as one PEM document
+    pub(crate) fn pem(&self) -> Result<Vec<u8>, String> {
+        let path = self.path.trim();
+        let data = std::fs::read(path).map_err(|e| format!("Could not read client certificate {}: {}", path, e))?;
+        let mut pem = if is_pkcs12(path, &data) {
+            pkcs12_to_pem(&data, &self.password).map_err(|e| format!("Could not open client certificate {}: {}", path, e))?
+        } else {
+            data
+        };
+        let key_path = self.key_path.trim();
+        if !key_path.is_empty() {
+            let key = std::fs::read(key_path).map_err(|e| format!("Could not read client key {}: {}", key_path, e))?;
+            pem.push(b'\n');
+            pem.extend_from_slice(&key);
+        }
+        Ok(pem)
+    }
+}
+
+// PKCS#12 is DER, which opens with a SEQUENCE tag; PEM is text
+fn is_pkcs12(path: &str, data: &[u8]) -> bool {
+    let lower = path.to_ascii_lowercase();
+    lower.ends_with(".p12") || lower.ends_with(".pfx") || data.first() == Some(&0x30)
+}
+
+fn pkcs12_to_pem(data: &[u8], password: &str) -> Result<Vec<u8>, String> {
+    let keystore = p12_keystore::KeyStore::from_pkcs12(data, password).map_err(|e| e.to_string())?;
+    let (_, chain) = keystore.private_key_chain().ok_or("the bundle has no private key")?;
+    // The key comes out as PKCS#8, the leaf certificate first in the chain
+    let mut pem = pem_block("PRIVATE KEY", chain.key());
+    for cert in chain.chain() {
+        pem.push_str(&pem_block("CERTIFICATE", cert.as_der()));
+    }
+    Ok(pem.into_bytes())
+}
+
+pub(crate) fn pem_block(label: &str, der: &[u8]) -> String {
+    let encoded = base64::engine::general_purpose::STANDARD.encode(der);
+    let lines: Vec<&str> = encoded.as_bytes().chunks(64).map(|c| std::str::from_utf8(c).unwrap_or_default()).collect();
+    format!("-----BEGIN {label}-----\n{}\n-----END {label}-----\n", lines.join("\n"))
+}
@@ -0,0 +1,97 @@
+// The cookie jar every client shares, so the info probe, the download and each of its connections
+// send back what a host set on an earlier request, such as the session cookie a landing page hands
+// out before it serves the file. Cookies are kept per domain and path as the host scoped them. With
+// `persist_cookies` the jar is also written to `cookies.json` in the app data folder and read again at
+// startup, so logins survive a restart; session cookies end with the app, as they do in a browser.
+
+use std::io::BufReader;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::RwLock;
+
+use reqwest::header::HeaderValue;
+use tauri::{AppHandle, Manager, State};
+use tokio::time::Duration;
+use url::Url;
+
+use crate::AppState;
+
+const SAVE_TICK: Duration = Duration::from_secs(60);
+
+#[derive(Default)]
+pub(crate) struct CookieJar {
+    store: RwLock<cookie_store::CookieStore>,
+    // Set by every change, cleared when written out
+    changed: AtomicBool,
+}
+
+impl reqwest::cookie::CookieStore for CookieJar {
+    fn set_cookies(&self, cookie_headers: &mut dyn Iterator<Item = &HeaderValue>, url: &Url) {
+        let cookies = cookie_headers
+            .filter_map(|value| std::str::from_utf8(value.as_bytes()).ok())
+            .filter_map(|value| cookie_store::RawCookie::parse(value.to_string()).ok())
+            .collect::<Vec<_>>();
+        if cookies.is_empty() { return; }
+        self.store.write().unwrap().store_response_cookies(cookies.into_iter(), url);
+        self.changed.store(true, Ordering::Relaxed);
+    }
+
+    fn cookies(&self, url: &Url) -> Option<HeaderValue> {
+        let header = self.store.read().unwrap()
+            .get_request_values(url)
+            .map(|(name, value)| format!("{}={}", name, value))
+            .collect::<Vec<_>>()
+            .join("; ");
+        if header.is_empty() { None } else { HeaderValue::from_str(&header).ok() }
+    }
+}
+
+impl CookieJar {
+    // The saved jar when `persist` is set and there is one, else an empty one
+    pub(crate) fn load(app_handle: &AppHandle, persist: bool) -> Self {
+        let saved = persist.then(|| jar_path(app_handle)).flatten()
+            .and_then(|path| std::fs::File::open(path).ok())
+            .and_then(|file| match cookie_store::serde::json::load(BufReader::new(file)) {
+                Ok(store) => Some(store),
+                Err(e) => {
+                    log::warn!("Could not read the saved cookies: {}", e);
+                    None
+                }
+            });
+        CookieJar { store: RwLock::new(saved.unwrap_or_default()), changed: AtomicBool::new(false) }
+    }
+
+    pub(crate) fn clear(&self) {
+        self.store.write().unwrap().clear();
+        self.changed.store(true, Ordering::Relaxed);
+    }
+}
+
+fn jar_path(app_handle: &AppHandle) -> Option<PathBuf> {
+    Some(app_handle.path().app_data_dir().ok()?.join("cookies.json"))
+}
+
+pub(crate) async fn run_cookie_save(app_handle: AppHandle) {
+    loop {
+        tokio::time::sleep(SAVE_TICK).await;
+        save(&app_handle).await;
+    }
+}
+
+// Writes the jar out if it changed, or removes the saved one once `persist_cookies` is off
+pub(crate) async fn save(app_handle: &AppHandle) {
+    let state: State<AppState> = app_handle.state();
+    let persist = state.persistent.lock().await.settings.persist_cookies;
+    let Some(path) = jar_path(app_handle) else { return };
+    if !persist {
+        if path.exists() { let _ = tokio::fs::remove_file(&path).await; }
+        return;
+    }
+    if !state.cookie_jar.changed.swap(false, Ordering::Relaxed) && path.exists() { return; }
+    let mut json = Vec::new();
+    if let Err(e) = cookie_store::serde::json::save(&state.cookie_jar.store.read().unwrap(), &mut json) {
+        log::warn!("Could not save cookies: {}", e);
+        return;
+    }
+    if let Err(e) = tokio::fs::write(&path, json).await { log::warn!("Could not save cookies: {}", e); }
+}
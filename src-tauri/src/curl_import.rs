@@ -0,0 +1,337 @@
+// Turning a `curl` command, as the browser's devtools "Copy as cURL" gives it, into a download. The
+// URL, headers, cookies (`-b`), user agent, referer and credentials (`-u`) go on the task; `-o` names
+// the file, and picks its folder when it has one; `-x` sets a proxy for this download alone; `-X` and
+// `--data` (or `--json`) make it a custom request. Both the bash and the Windows cmd flavours are
+// understood. Options that don't change what is fetched (`--compressed`, `-L`, `-s`...) are ignored,
+// and uploads (`-F`, `-T`) are refused.
+
+use std::collections::BTreeMap;
+use std::iter::Peekable;
+use std::path::{Path, PathBuf};
+use std::str::Chars;
+
+use base64::Engine;
+use chrono::Local;
+use percent_encoding::{utf8_percent_encode, NON_ALPHANUMERIC};
+use tauri::{AppHandle, Manager, State};
+use url::Url;
+
+use crate::{AddDownloadPayload, AppState, CustomRequest, DownloadTask};
+
+const UNCLOSED_QUOTE: &str = "The command has an unclosed quote";
+
+// Long options that take a value, so the value isn't mistaken for the URL
+const TAKES_VALUE: &[&str] = &[
+    "url", "header", "cookie", "user-agent", "referer", "user", "oauth2-bearer", "output", "output-dir", "proxy",
+    "request", "data", "data-raw", "data-binary", "data-ascii", "data-urlencode", "json", "form", "form-string",
+    "upload-file", "range", "continue-at", "cookie-jar", "max-time", "connect-timeout", "write-out", "config",
+    "cert", "key", "cacert", "capath", "proxy-user", "proxy-header", "speed-limit", "speed-time", "time-cond",
+    "limit-rate", "max-redirs", "max-filesize", "retry", "retry-delay", "retry-max-time", "resolve", "connect-to",
+    "interface", "dns-servers", "ciphers", "tls-max", "unix-socket", "etag-save", "etag-compare", "dump-header",
+    "trace", "trace-ascii", "stderr", "keepalive-time", "expect100-timeout", "url-query",
+];
+
+// Headers the downloader sets itself for ranges, resumes and decoding
+const CONTROLLED_HEADERS: &[&str] = &[
+    "range", "if-range", "if-none-match", "if-modified-since", "accept-encoding", "content-length", "host", "connection",
+];
+
+fn long_name(short: char) -> Option<&'static str> {
+    Some(match short {
+        'H' => "header", 'b' => "cookie", 'A' => "user-agent", 'e' => "referer", 'u' => "user", 'o' => "output",
+        'x' => "proxy", 'X' => "request", 'd' => "data", 'F' => "form", 'T' => "upload-file", 'G' => "get",
+        'I' => "head", 'r' => "range", 'C' => "continue-at", 'c' => "cookie-jar", 'm' => "max-time",
+        'w' => "write-out", 'K' => "config", 'E' => "cert", 'U' => "proxy-user", 'Y' => "speed-limit",
+        'y' => "speed-time", 'z' => "time-cond", 'D' => "dump-header",
+        _ => return None,
+    })
+}
+
+struct CurlRequest {
+    url: String,
+    headers: BTreeMap<String, String>,
+    // From `-o`; the folder may be relative to the download folder
+    file_name: Option<String>,
+    folder: Option<PathBuf>,
+    proxy: Option<String>,
+    request: Option<CustomRequest>,
+}
+
+pub(crate) async fn import(app_handle: &AppHandle, command: &str) -> Result<DownloadTask, String> {
+    let request = parse(command)?;
+    let state: State<AppState> = app_handle.state();
+    let download_folder = state.persistent.lock().await.settings.download_folder.clone();
+    let client = match &request.proxy {
+        Some(proxy) => crate::proxied_client(&state, proxy).await.map_err(|e| format!("Invalid proxy {}: {}", proxy, e))?,
+        None => crate::http_client_for(&state, &request.url).await,
+    };
+    // Probed with the copied headers, since the link may only answer the session they came from. One
+    // that won't answer a probe is added anyway, and its download says why. A custom request isn't
+    // probed at all, as sending it could have effects of its own.
+    let info = match &request.request {
+        Some(_) => None,
+        None => crate::probe_download_info_with_headers(&client, &request.url, &request.headers).await.ok(),
+    };
+    let (url, total_size, redirect_chain, probed_name) = match info {
+        Some(info) => (info.final_url, info.total_size, info.redirect_chain, Some(info.file_name)),
+        None => (request.url, None, Vec::new(), None),
+    };
+    let file_name = request.file_name.filter(|name| crate::is_valid_file_name(name))
+        .or(probed_name)
+        .or_else(|| Url::parse(&url).ok().as_ref().and_then(crate::file_name_from_url))
+        .unwrap_or_else(|| format!("download_{}.tmp", Local::now().timestamp()));
+    let payload = AddDownloadPayload {
+        url, file_name, total_size,
+        custom_path: request.folder.map(|folder| Path::new(&download_folder).join(folder).to_string_lossy().to_string()),
+        checksum: None, headers: request.headers, redirect_chain, parent_id: None, signature: None, allow_http: false,
+        proxy: request.proxy, request: request.request, source_url: None,
+    };
+    crate::add_download(payload, state, app_handle.clone()).await
+}
+
+fn parse(command: &str) -> Result<CurlRequest, String> {
+    let mut words = split_words(command)?.into_iter();
+    let program = words.next().unwrap_or_default();
+    if Path::new(&program).file_stem().is_none_or(|stem| stem != "curl") {
+        return Err("Not a curl command".to_string());
+    }
+    // Every option as its long name and value, so `-H x`, `-Hx` and `--header x` read the same
+    let mut options: Vec<(&'static str, String)> = Vec::new();
+    let mut urls = Vec::new();
+    let mut only_urls = false;
+    while let Some(word) = words.next() {
+        if only_urls || !word.starts_with('-') || word == "-" {
+            urls.push(word);
+        } else if word == "--" {
+            only_urls = true;
+        } else if let Some(long) = word.strip_prefix("--") {
+            match TAKES_VALUE.iter().find(|name| **name == long) {
+                Some(name) => options.push((name, words.next().ok_or_else(|| format!("--{} needs a value", long))?)),
+                None if long == "get" => options.push(("get", String::new())),
+                None if long == "head" => options.push(("head", String::new())),
+                None => {}
+            }
+        } else {
+            // A cluster of short flags such as `-sSL`; the first that takes a value takes the rest
+            let cluster = &word[1..];
+            for (at, short) in cluster.char_indices() {
+                let Some(name) = long_name(short) else { continue };
+                if !TAKES_VALUE.contains(&name) {
+                    options.push((name, String::new()));
+                    continue;
+                }
+                let rest = &cluster[at + short.len_utf8()..];
+                let value = if rest.is_empty() { words.next().ok_or_else(|| format!("-{} needs a value", short))? } else { rest.to_string() };
+                options.push((name, value));
+                break;
+            }
+        }
+    }
+
+    let mut headers = BTreeMap::new();
+    let mut cookies = Vec::new();
+    let mut data = Vec::new();
+    let mut get = false;
+    let mut method = None;
+    let mut output = None;
+    let mut output_dir = None;
+    let mut proxy = None;
+    for (name, value) in options {
+        match name {
+            "url" => urls.push(value),
+            "header" => if let Some((name, value)) = value.split_once(':') { set_header(&mut headers, name.trim(), value.trim()) },
+            // A value without `=` names a cookie file, which isn't ours to read
+            "cookie" if value.contains('=') => cookies.push(value),
+            "user-agent" => set_header(&mut headers, "User-Agent", &value),
+            "referer" => set_header(&mut headers, "Referer", value.trim_end_matches(";auto")),
+            "user" => {
+                let credentials = if value.contains(':') { value } else { format!("{}:", value) };
+                let encoded = base64::engine::general_purpose::STANDARD.encode(credentials);
+                set_header(&mut headers, "Authorization", &format!("Basic {}", encoded));
+            }
+            "oauth2-bearer" => set_header(&mut headers, "Authorization", &format!("Bearer {}", value)),
+            "output" => output = Some(value),
+            "output-dir" => output_dir = Some(value),
+            "proxy" => proxy = Some(value).filter(|p| !p.trim().is_empty()),
+            "request" => method = Some(value.to_ascii_uppercase()),
+            "get" => get = true,
+            "head" => return Err("The command only asks for headers (-I)".to_string()),
+            "data" | "data-raw" | "data-binary" | "data-ascii" | "data-urlencode" | "json" => data.push((name, value)),
+            "form" | "form-string" | "upload-file" => return Err(format!("The command uploads data (--{}), which a download can't send", name)),
+            _ => {}
+        }
+    }
+
+    let mut urls = urls.into_iter();
+    let url = urls.next().ok_or("The command has no URL")?;
+    if urls.next().is_some() { return Err("The command fetches more than one URL; import them one at a time".to_string()); }
+    let mut url = Url::parse(&url).map_err(|e| format!("Invalid URL: {}", e))?;
+    let json = data.iter().any(|(name, _)| *name == "json");
+    let mut fields = Vec::with_capacity(data.len());
+    for (name, value) in data {
+        fields.push(match name {
+            "data-urlencode" => urlencode(&value),
+            "data-raw" | "json" => value,
+            _ if value.starts_with('@') => return Err(format!("The command reads its data from a file (--{} {})", name, value)),
+            // Like curl, which strips line breaks from `-d` but not `--data-binary`
+            "data" | "data-ascii" => value.replace(['\r', '\n'], ""),
+            _ => value,
+        });
+    }
+    let mut body = None;
+    if !fields.is_empty() {
+        // With `-G` the data is sent in the query string instead of a body
+        if get {
+            let mut query: Vec<String> = url.query().filter(|q| !q.is_empty()).map(str::to_string).into_iter().collect();
+            query.extend(fields);
+            url.set_query(Some(&query.join("&")));
+        } else {
+            body = Some(fields.join(if json { "" } else { "&" }));
+        }
+    }
+    if json && !headers.keys().any(|name| name.eq_ignore_ascii_case("accept")) {
+        headers.insert("Accept".to_string(), "application/json".to_string());
+    }
+    // The content type goes with the body rather than with every request's headers
+    let content_type = headers.keys().find(|name| name.eq_ignore_ascii_case("content-type")).cloned()
+        .and_then(|name| headers.remove(&name));
+    let request = match (method, body) {
+        (Some(method), None) if method == "GET" => None,
+        (None, None) => None,
+        (method, body) => {
+            let content_type = content_type.or_else(|| body.as_ref().map(|_| {
+                if json { "application/json" } else { "application/x-www-form-urlencoded" }.to_string()
+            }));
+            Some(CustomRequest { method: method.unwrap_or_else(|| "POST".to_string()), body, content_type })
+        }
+    };
+    if !cookies.is_empty() {
+        let existing = headers.iter().find(|(name, _)| name.eq_ignore_ascii_case("cookie")).map(|(_, value)| value.clone());
+        cookies.extend(existing);
+        set_header(&mut headers, "Cookie", &cookies.join("; "));
+    }
+
+    // `-o -` writes to the terminal, which leaves the name to us
+    let output = output.filter(|o| o != "-").map(PathBuf::from);
+    let file_name = output.as_ref().and_then(|o| o.file_name()).map(|n| n.to_string_lossy().to_string());
+    let folder = match (output_dir.map(PathBuf::from), output.as_ref().and_then(|o| o.parent()).filter(|p| !p.as_os_str().is_empty())) {
+        (Some(dir), Some(parent)) => Some(dir.join(parent)),
+        (dir, parent) => dir.or(parent.map(Path::to_path_buf)),
+    };
+    Ok(CurlRequest { url: url.to_string(), headers, file_name, folder, proxy, request })
+}
+
+// Replaces any header of the same name, whatever its case; `Name:` with nothing after it is curl's way
+// of removing a header, so it adds nothing
+fn set_header(headers: &mut BTreeMap<String, String>, name: &str, value: &str) {
+    if name.is_empty() || CONTROLLED_HEADERS.contains(&name.to_ascii_lowercase().as_str()) { return; }
+    headers.retain(|existing, _| !existing.eq_ignore_ascii_case(name));
+    if !value.is_empty() { headers.insert(name.to_string(), value.to_string()); }
+}
+
+// `--data-urlencode` forms: `content`, `=content` and `name=content`, of which only content is encoded
+fn urlencode(value: &str) -> String {
+    match value.split_once('=') {
+        Some((name, content)) if !name.is_empty() => format!("{}={}", name, utf8_percent_encode(content, NON_ALPHANUMERIC)),
+        Some((_, content)) => utf8_percent_encode(content, NON_ALPHANUMERIC).to_string(),
+        None => utf8_percent_encode(value, NON_ALPHANUMERIC).to_string(),
+    }
+}
+
+// Splits the command as a POSIX shell would, minus expansions: single quotes, double quotes with
+// backslash escapes, bash's `$'...'` strings and backslash line continuations
+fn split_words(command: &str) -> Result<Vec<String>, String> {
+    let command = if is_cmd(command) { unescape_cmd(command) } else { command.to_string() };
+    let mut words = Vec::new();
+    // `None` between words, so an empty quoted argument still counts as one
+    let mut word: Option<String> = None;
+    let mut chars = command.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some('\n') | None => {}
+                Some('\r') => { chars.next_if_eq(&'\n'); }
+                Some(c) => word.get_or_insert_with(String::new).push(c),
+            },
+            '\'' => {
+                let word = word.get_or_insert_with(String::new);
+                loop {
+                    match chars.next().ok_or(UNCLOSED_QUOTE)? {
+                        '\'' => break,
+                        c => word.push(c),
+                    }
+                }
+            }
+            '"' => {
+                let word = word.get_or_insert_with(String::new);
+                loop {
+                    match chars.next().ok_or(UNCLOSED_QUOTE)? {
+                        '"' => break,
+                        '\\' => match chars.next().ok_or(UNCLOSED_QUOTE)? {
+                            '\n' => {}
+                            c @ ('"' | '\\' | '$' | '`') => word.push(c),
+                            c => { word.push('\\'); word.push(c); }
+                        },
+                        c => word.push(c),
+                    }
+                }
+            }
+            '$' if chars.peek() == Some(&'\'') => {
+                chars.next();
+                ansi_c_string(&mut chars, word.get_or_insert_with(String::new))?;
+            }
+            c if c.is_whitespace() => words.extend(word.take()),
+            c => word.get_or_insert_with(String::new).push(c),
+        }
+    }
+    words.extend(word);
+    Ok(words)
+}
+
+// The rest of a `$'...'` string, which devtools uses for values with control or non-ASCII characters
+fn ansi_c_string(chars: &mut Peekable<Chars>, word: &mut String) -> Result<(), String> {
+    loop {
+        match chars.next().ok_or(UNCLOSED_QUOTE)? {
+            '\'' => return Ok(()),
+            '\\' => match chars.next().ok_or(UNCLOSED_QUOTE)? {
+                'n' => word.push('\n'),
+                't' => word.push('\t'),
+                'r' => word.push('\r'),
+                'e' | 'E' => word.push('\x1b'),
+                'x' => word.extend(hex_char(chars, 2)),
+                'u' => word.extend(hex_char(chars, 4)),
+                'U' => word.extend(hex_char(chars, 8)),
+                c @ ('\\' | '\'' | '"' | '?') => word.push(c),
+                c => { word.push('\\'); word.push(c); }
+            },
+            c => word.push(c),
+        }
+    }
+}
+
+fn hex_char(chars: &mut Peekable<Chars>, max_digits: usize) -> Option<char> {
+    let mut digits = String::new();
+    while digits.len() < max_digits {
+        let Some(digit) = chars.next_if(char::is_ascii_hexdigit) else { break };
+        digits.push(digit);
+    }
+    char::from_u32(u32::from_str_radix(&digits, 16).ok()?)
+}
+
+// "Copy as cURL (cmd)" quotes with `^"` and ends lines with `^`
+fn is_cmd(command: &str) -> bool {
+    command.contains("^\"") || command.lines().any(|line| line.trim_end().ends_with('^'))
+}
+
+// cmd escapes a character by putting `^` before it; what's left is double-quoted like bash
+fn unescape_cmd(command: &str) -> String {
+    let mut unescaped = String::with_capacity(command.len());
+    let mut chars = command.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '^' => unescaped.extend(chars.next()),
+            c => unescaped.push(c),
+        }
+    }
+    unescaped
+}
@@ -0,0 +1,109 @@
+// Downloads that wait for others: a task with `depends_on` set is held in the queue when started until
+// every one of those has completed, then started by the watcher here. If one fails or is removed, the
+// waiting task fails too, and so on down the chain. Setting dependencies refuses anything that would
+// make a cycle, so the graph is always a DAG.
+
+use std::collections::HashMap;
+
+use tauri::{AppHandle, Emitter, Manager, State};
+use tokio::sync::Notify;
+
+use crate::{AppState, DownloadStatus, DownloadTask, PersistentState};
+
+const WAITING_MESSAGE: &str = "Waiting for the downloads it depends on";
+
+#[derive(Default)]
+pub(crate) struct Dependencies {
+    // Something finished or was removed; waiting tasks may be ready now
+    changed: Notify,
+}
+
+impl Dependencies {
+    pub(crate) fn notify(&self) {
+        self.changed.notify_one();
+    }
+}
+
+// Whether `task` has to wait before it can start; the caller parks it with `park`
+pub(crate) fn should_wait(tasks: &[DownloadTask], task: &DownloadTask) -> bool {
+    task.depends_on.iter().any(|dep| !tasks.iter().any(|t| t.id == *dep && t.status == DownloadStatus::Completed))
+}
+
+pub(crate) fn park(task: &mut DownloadTask) {
+    task.status = DownloadStatus::Queued;
+    task.speed = 0;
+    task.error_message = Some(WAITING_MESSAGE.to_string());
+}
+
+fn is_waiting(task: &DownloadTask) -> bool {
+    task.status == DownloadStatus::Queued && task.error_message.as_deref() == Some(WAITING_MESSAGE)
+}
+
+// Checks `depends_on` for `id`: every entry must exist, and none may lead back to `id`
+pub(crate) fn validate(state: &PersistentState, id: &str, depends_on: &[String]) -> Result<(), String> {
+    let edges: HashMap<&str, &[String]> = state.downloads.iter().map(|t| (t.id.as_str(), t.depends_on.as_slice())).collect();
+    for dep in depends_on {
+        if dep == id { return Err("A download can't depend on itself".to_string()); }
+        if !edges.contains_key(dep.as_str()) { return Err(format!("Download not found: {}", dep)); }
+    }
+    // Depth-first from the new dependencies; reaching `id` means a cycle
+    let mut stack: Vec<&str> = depends_on.iter().map(String::as_str).collect();
+    let mut seen = std::collections::HashSet::new();
+    while let Some(current) = stack.pop() {
+        if current == id { return Err("That would make downloads wait for each other in a circle".to_string()); }
+        if !seen.insert(current) { continue; }
+        stack.extend(edges.get(current).into_iter().flat_map(|deps| deps.iter().map(String::as_str)));
+    }
+    Ok(())
+}
+
+// Fails waiting tasks whose dependencies failed or are gone, repeating until nothing changes so the
+// failure reaches the end of the chain; returns the failed tasks and the ids of those now ready
+fn settle(state: &mut PersistentState) -> (Vec<DownloadTask>, Vec<String>) {
+    let mut failed = Vec::new();
+    loop {
+        let statuses: HashMap<String, (DownloadStatus, String)> = state.downloads.iter()
+            .map(|t| (t.id.clone(), (t.status.clone(), t.file_name.clone())))
+            .collect();
+        let mut changed = false;
+        for task in state.downloads.iter_mut().filter(|t| is_waiting(t)) {
+            let reason = task.depends_on.iter().find_map(|dep| match statuses.get(dep) {
+                None => Some("a download it depends on was removed".to_string()),
+                Some((DownloadStatus::Failed, name)) => Some(format!("{} failed", name)),
+                _ => None,
+            });
+            if let Some(reason) = reason {
+                task.status = DownloadStatus::Failed;
+                task.error_message = Some(format!("Not started: {}", reason));
+                failed.push(task.clone());
+                changed = true;
+            }
+        }
+        if !changed { break; }
+    }
+    let ready: Vec<String> = state.downloads.iter()
+        .filter(|t| is_waiting(t) && !should_wait(&state.downloads, t))
+        .map(|t| t.id.clone())
+        .collect();
+    for task in state.downloads.iter_mut().filter(|t| ready.contains(&t.id)) { task.error_message = None; }
+    (failed, ready)
+}
+
+pub(crate) async fn run_dependencies(app_handle: AppHandle) {
+    let state: State<AppState> = app_handle.state();
+    // The first pass picks up tasks left waiting when the app last closed
+    loop {
+        let (failed, ready) = settle(&mut *state.persistent.lock().await);
+        for task in &failed {
+            crate::tasklog::write(&app_handle, &task.id, task.error_message.as_deref().unwrap_or_default()).await;
+            app_handle.emit("task_updated", task).unwrap();
+        }
+        if !failed.is_empty() || !ready.is_empty() { crate::request_save(&state); }
+        for id in ready {
+            if let Err(e) = crate::start_download_task(id, app_handle.clone()).await {
+                log::warn!("Could not start a download whose dependencies finished: {}", e);
+            }
+        }
+        state.dependencies.changed.notified().await;
+    }
+}
@@ -0,0 +1,68 @@
+// Caps on how fast downloads write to a disk, apart from the network limits, so a huge download to a
+// slow drive (a spinning NAS disk, say) leaves it enough IO for other programs. Each entry in
+// `disk_write_limits` names a folder; its cap covers the whole device that folder is on, shared by
+// every download saving anywhere on it. Two entries on the same device take the lower cap.
+
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+
+use crate::metered::SpeedLimit;
+use crate::AppSettings;
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Default)]
+#[serde(rename_all = "camelCase", default)]
+pub(crate) struct DiskWriteLimit {
+    // Any folder on the device
+    pub(crate) folder: String,
+    // 0 is no cap
+    pub(crate) limit_kbps: u64,
+}
+
+#[derive(Default)]
+pub(crate) struct DiskLimits {
+    // One per device seen; kept when the settings change so running downloads pick up the new cap
+    devices: std::sync::Mutex<HashMap<String, Arc<SpeedLimit>>>,
+}
+
+impl DiskLimits {
+    pub(crate) fn apply(&self, settings: &AppSettings) {
+        let mut caps: HashMap<String, u64> = HashMap::new();
+        for entry in settings.disk_write_limits.iter().filter(|e| e.limit_kbps > 0) {
+            let Some(device) = device_of(Path::new(entry.folder.trim())) else {
+                log::warn!("Disk write limit: could not find the device of {}", entry.folder);
+                continue;
+            };
+            let cap = caps.entry(device).or_insert(u64::MAX);
+            *cap = (*cap).min(entry.limit_kbps * 1024);
+        }
+        let mut devices = self.devices.lock().unwrap();
+        for (device, limit) in devices.iter() { limit.set(caps.get(device).copied().unwrap_or(0)); }
+        for (device, cap) in caps { devices.entry(device).or_default().set(cap); }
+    }
+
+    // The cap for writing to `path`, shared with everything else on its device; `None` when the device
+    // can't be told
+    pub(crate) fn for_path(&self, path: &Path) -> Option<Arc<SpeedLimit>> {
+        let device = device_of(path)?;
+        Some(self.devices.lock().unwrap().entry(device).or_default().clone())
+    }
+}
+
+// Identifies the device holding `path`, going up to the nearest folder that exists
+#[cfg(unix)]
+fn device_of(path: &Path) -> Option<String> {
+    use std::os::unix::fs::MetadataExt;
+    path.ancestors().find_map(|p| std::fs::metadata(p).ok()).map(|m| m.dev().to_string())
+}
+
+// The drive letter or `\\server\share` the path starts with
+#[cfg(not(unix))]
+fn device_of(path: &Path) -> Option<String> {
+    match path.components().next()? {
+        std::path::Component::Prefix(prefix) => Some(prefix.as_os_str().to_string_lossy().to_uppercase()),
+        _ => None,
+    }
+}
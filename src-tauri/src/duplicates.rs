@@ -0,0 +1,95 @@
+// Downloads whose content is already on disk. Each finished file's SHA-256 is kept on its task, so the
+// list itself is the index. When a new file hashes the same as another completed download whose file is
+// still there, it's flagged with `duplicate_of`; the user can then replace it with a hard link to the
+// other file, delete it, or keep both copies.
+
+use serde::Deserialize;
+use tauri::{AppHandle, Emitter, Manager, State};
+
+use crate::i18n::{tr, Language, Text};
+use crate::{AppState, DownloadStatus, DownloadTask};
+
+#[derive(Debug, Deserialize, Clone, Copy)]
+#[serde(rename_all = "camelCase")]
+pub(crate) enum Resolution { HardLink, Delete, Keep }
+
+pub(crate) async fn check_task(app_handle: AppHandle, task: DownloadTask) {
+    let settings = app_handle.state::<AppState>().persistent.lock().await.settings.clone();
+    if !settings.detect_duplicates { return; }
+    let path = crate::task_file_path(&task);
+    // A SHA-256 checksum was verified on completion, so it is the file's hash already
+    let verified = task.checksum.as_deref().and_then(crate::checksum_hasher)
+        .filter(|(hasher, _)| hasher.output_size() == 32)
+        .map(|(_, digest)| digest);
+    let hash = match verified {
+        Some(digest) => digest,
+        None => match crate::hash_file(&path, Box::new(sha2::Sha256::default())).await {
+            Ok(hash) => hash,
+            Err(e) => {
+                log::warn!("Could not hash {} to look for duplicates: {}", path.display(), e);
+                return;
+            }
+        },
+    };
+    let state: State<AppState> = app_handle.state();
+    let original = {
+        let mut state_guard = state.persistent.lock().await;
+        let original = state_guard.downloads.iter()
+            .filter(|t| t.id != task.id && t.status == DownloadStatus::Completed && t.content_hash.as_deref() == Some(hash.as_str()))
+            .find(|t| {
+                let other = crate::task_file_path(t);
+                other != path && other.exists()
+            })
+            .map(|t| (t.id.clone(), t.file_name.clone()));
+        let Some(current) = state_guard.downloads.iter_mut().find(|t| t.id == task.id) else { return };
+        current.content_hash = Some(hash);
+        current.duplicate_of = original.as_ref().map(|(id, _)| id.clone());
+        app_handle.emit("task_updated", &*current).unwrap();
+        original
+    };
+    crate::request_save(&state);
+    let Some((_, other_name)) = original else { return };
+    log::info!("{} has the same content as {}", task.file_name, other_name);
+    let language = Language::from_setting(&settings.language);
+    let body = tr(language, Text::DuplicateDownloadBody).replace("{file}", &task.file_name).replace("{other}", &other_name);
+    crate::notifications::show(&app_handle, &settings, tr(language, Text::DuplicateDownload), &body);
+}
+
+pub(crate) async fn resolve(app_handle: &AppHandle, id: &str, resolution: Resolution) -> Result<(), String> {
+    let state: State<AppState> = app_handle.state();
+    let (path, original) = {
+        let state_guard = state.persistent.lock().await;
+        let task = state_guard.downloads.iter().find(|t| t.id == id).ok_or("Download not found")?;
+        let original_id = task.duplicate_of.as_ref().ok_or("Not a duplicate")?;
+        let original = state_guard.downloads.iter().find(|t| t.id == *original_id).map(crate::task_file_path);
+        (crate::task_file_path(task), original)
+    };
+    match resolution {
+        Resolution::Keep => {}
+        Resolution::Delete => return crate::delete_download_with_file(id.to_string(), state, app_handle.clone()).await,
+        Resolution::HardLink => {
+            let original = original.ok_or("The other download has been removed")?;
+            let ours = tokio::fs::metadata(&path).await.map_err(|e| format!("Could not read {}: {}", path.display(), e))?;
+            let theirs = tokio::fs::metadata(&original).await.map_err(|e| format!("Could not read {}: {}", original.display(), e))?;
+            if ours.len() != theirs.len() { return Err("The files no longer match".to_string()); }
+            // Linked beside the file first, so a failure (another drive, say) leaves it as it was
+            let mut link = path.clone().into_os_string();
+            link.push(".link");
+            tokio::fs::hard_link(&original, &link).await.map_err(|e| format!("Could not link to {}: {}", original.display(), e))?;
+            if let Err(e) = tokio::fs::rename(&link, &path).await {
+                let _ = tokio::fs::remove_file(&link).await;
+                return Err(format!("Could not replace {}: {}", path.display(), e));
+            }
+            crate::tasklog::write(app_handle, id, &format!("Replaced with a hard link to {}", original.display())).await;
+        }
+    }
+    {
+        let mut state_guard = state.persistent.lock().await;
+        if let Some(task) = state_guard.downloads.iter_mut().find(|t| t.id == id) {
+            task.duplicate_of = None;
+            app_handle.emit("task_updated", &*task).unwrap();
+        }
+    }
+    crate::request_save(&state);
+    Ok(())
+}
@@ -0,0 +1,352 @@
+// Archives unpacked after they download: zip, tar, tar.gz and 7z in process, rar through an external
+// `unrar` when one is configured. Each archive gets a folder named after it, next to the archive or in
+// the configured extraction folder. Passwords are tried in order: the one given, none, then the saved
+// list. Progress is reported on the task's `extraction`, leaving its download status alone.
+
+use std::fs::File;
+use std::io::{self, BufReader, Read, Write};
+use std::path::{Component, Path, PathBuf};
+use std::process::Stdio;
+
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Emitter, Manager, State};
+use tokio::io::AsyncReadExt;
+use tokio::sync::watch;
+use tokio::time::Duration;
+
+use crate::{AppState, DownloadStatus, DownloadTask};
+
+const COPY_BUFFER: usize = 64 * 1024;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub(crate) enum ExtractionStatus { Extracting, Done, Failed }
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct Extraction {
+    pub(crate) status: ExtractionStatus,
+    pub(crate) progress: f64,
+    // Where the files went
+    pub(crate) folder: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) error: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Kind { Zip, Tar, TarGz, SevenZip, Rar }
+
+enum Failure { Password, Other(String) }
+
+impl From<io::Error> for Failure {
+    fn from(e: io::Error) -> Self { Failure::Other(e.to_string()) }
+}
+
+fn kind(file_name: &str) -> Option<Kind> {
+    let name = file_name.to_ascii_lowercase();
+    if name.ends_with(".tar.gz") || name.ends_with(".tgz") { return Some(Kind::TarGz); }
+    match name.rsplit_once('.')?.1 {
+        "zip" => Some(Kind::Zip),
+        "tar" => Some(Kind::Tar),
+        "7z" => Some(Kind::SevenZip),
+        // Later volumes of a `.partN.rar` set are read through the first one
+        "rar" if rar_volume(&name).is_none_or(|n| n == 1) => Some(Kind::Rar),
+        _ => None,
+    }
+}
+
+fn rar_volume(name: &str) -> Option<u32> {
+    name.strip_suffix(".rar")?.rsplit_once(".part")?.1.parse().ok()
+}
+
+pub(crate) fn is_archive(file_name: &str) -> bool {
+    kind(file_name).is_some()
+}
+
+// `photos.tar.gz` and `photos.part1.rar` both unpack into `photos`
+fn folder_name(file_name: &str) -> String {
+    let lower = file_name.to_ascii_lowercase();
+    let cut = [".tar.gz", ".tgz"].iter().find(|s| lower.ends_with(*s)).map(|s| s.len())
+        .or_else(|| rar_volume(&lower).map(|n| format!(".part{}.rar", n).len()))
+        .or_else(|| file_name.rfind('.').map(|dot| file_name.len() - dot))
+        .unwrap_or(0);
+    let name = file_name[..file_name.len() - cut].trim();
+    if crate::is_valid_file_name(name) { name.to_string() } else { "extracted".to_string() }
+}
+
+// Runs after a download finishes; failures land on the task
+pub(crate) async fn process_task(app_handle: AppHandle, task: DownloadTask) {
+    let enabled = app_handle.state::<AppState>().persistent.lock().await.settings.auto_extract;
+    if !enabled || !is_archive(&task.file_name) { return; }
+    if let Err(e) = extract(&app_handle, &task.id, None).await {
+        log::warn!("Could not extract {}: {}", task.file_name, e);
+    }
+}
+
+pub(crate) async fn extract(app_handle: &AppHandle, id: &str, password: Option<String>) -> Result<(), String> {
+    let state: State<AppState> = app_handle.state();
+    // Checked and marked under one lock so two requests can't both start
+    let (task, settings, kind, dest) = {
+        let mut state_guard = state.persistent.lock().await;
+        let settings = state_guard.settings.clone();
+        let task = state_guard.downloads.iter_mut().find(|t| t.id == id).ok_or("Task not found")?;
+        if !matches!(task.status, DownloadStatus::Completed) { return Err("The download hasn't finished".to_string()); }
+        if task.extraction.as_ref().is_some_and(|e| e.status == ExtractionStatus::Extracting) {
+            return Err("Already extracting".to_string());
+        }
+        let kind = kind(&task.file_name).ok_or("Not a supported archive")?;
+        let base = Some(settings.extract_folder.trim()).filter(|f| !f.is_empty()).unwrap_or(&task.save_path);
+        let dest = PathBuf::from(base).join(folder_name(&task.file_name));
+        task.extraction = Some(Extraction {
+            status: ExtractionStatus::Extracting, progress: 0.0, folder: dest.to_string_lossy().to_string(), error: None,
+        });
+        app_handle.emit("task_updated", &*task).unwrap();
+        (task.clone(), settings, kind, dest)
+    };
+    let archive = PathBuf::from(&task.save_path).join(&task.file_name);
+
+    let (sender, receiver) = watch::channel(0.0);
+    let forwarder = tauri::async_runtime::spawn(forward_progress(app_handle.clone(), id.to_string(), receiver));
+    let candidates: Vec<Option<String>> = match password.filter(|p| !p.is_empty()) {
+        Some(password) => vec![Some(password)],
+        None => std::iter::once(None)
+            .chain(settings.archive_passwords.lines().map(str::trim).filter(|p| !p.is_empty()).map(|p| Some(p.to_string())))
+            .collect(),
+    };
+    let mut result = Err(Failure::Password);
+    for password in candidates {
+        sender.send_replace(0.0);
+        result = match kind {
+            Kind::Rar => extract_rar(settings.unrar_path.trim(), &archive, &dest, password.as_deref(), &sender).await,
+            _ => {
+                let (archive, dest, sender) = (archive.clone(), dest.clone(), sender.clone());
+                tokio::task::spawn_blocking(move || {
+                    let mut progress = Progress { sender, total: 0, done: 0 };
+                    match kind {
+                        Kind::Zip => extract_zip(&archive, &dest, password.as_deref(), &mut progress),
+                        Kind::Tar | Kind::TarGz => extract_tar(&archive, &dest, kind == Kind::TarGz, &mut progress),
+                        _ => extract_7z(&archive, &dest, password.as_deref(), &mut progress),
+                    }
+                }).await.unwrap_or_else(|e| Err(Failure::Other(e.to_string())))
+            }
+        };
+        if !matches!(result, Err(Failure::Password)) { break; }
+    }
+    drop(sender);
+    let _ = forwarder.await;
+
+    let error = match result {
+        Ok(()) => None,
+        Err(Failure::Password) => Some("Wrong or missing password".to_string()),
+        Err(Failure::Other(e)) => Some(e),
+    };
+    if error.is_none() && settings.extract_delete_archive {
+        if let Err(e) = tokio::fs::remove_file(&archive).await { log::warn!("Could not delete {}: {}", archive.display(), e); }
+    }
+    set_extraction(app_handle, id, Extraction {
+        status: if error.is_some() { ExtractionStatus::Failed } else { ExtractionStatus::Done },
+        progress: if error.is_some() { 0.0 } else { 100.0 },
+        folder: dest.to_string_lossy().to_string(),
+        error: error.clone(),
+    }).await;
+    error.map_or(Ok(()), Err)
+}
+
+async fn set_extraction(app_handle: &AppHandle, id: &str, extraction: Extraction) {
+    let state: State<AppState> = app_handle.state();
+    {
+        let mut state_guard = state.persistent.lock().await;
+        let Some(task) = state_guard.downloads.iter_mut().find(|t| t.id == id) else { return };
+        task.extraction = Some(extraction);
+        app_handle.emit("task_updated", &*task).unwrap();
+    }
+    crate::request_save(&state);
+}
+
+// Copies the latest percentage onto the task a few times a second, until the extraction drops its sender
+async fn forward_progress(app_handle: AppHandle, id: String, mut receiver: watch::Receiver<f64>) {
+    while receiver.changed().await.is_ok() {
+        let progress = *receiver.borrow_and_update();
+        {
+            let state: State<AppState> = app_handle.state();
+            let mut state_guard = state.persistent.lock().await;
+            if let Some(task) = state_guard.downloads.iter_mut().find(|t| t.id == id) {
+                if let Some(extraction) = task.extraction.as_mut().filter(|e| e.status == ExtractionStatus::Extracting) {
+                    extraction.progress = progress;
+                    app_handle.emit("task_updated", &*task).unwrap();
+                }
+            }
+        }
+        tokio::time::sleep(Duration::from_millis(250)).await;
+    }
+}
+
+// An extraction the app closed in the middle of can't be resumed
+pub(crate) fn mark_interrupted(tasks: &mut [DownloadTask]) {
+    for extraction in tasks.iter_mut().filter_map(|t| t.extraction.as_mut()) {
+        if extraction.status == ExtractionStatus::Extracting {
+            extraction.status = ExtractionStatus::Failed;
+            extraction.error = Some("Interrupted when the app closed".to_string());
+        }
+    }
+}
+
+struct Progress { sender: watch::Sender<f64>, total: u64, done: u64 }
+
+impl Progress {
+    fn advance(&mut self, bytes: u64) {
+        self.done += bytes;
+        if self.total == 0 { return; }
+        let percent = (self.done as f64 / self.total as f64 * 100.0).min(100.0);
+        self.sender.send_if_modified(|current| {
+            let changed = percent - *current >= 0.5;
+            if changed { *current = percent; }
+            changed
+        });
+    }
+}
+
+// Counts compressed bytes for formats whose unpacked size isn't known up front
+struct ProgressReader<'a, R> { inner: R, progress: &'a mut Progress }
+
+impl<R: Read> Read for ProgressReader<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        self.progress.advance(read as u64);
+        Ok(read)
+    }
+}
+
+fn copy_with_progress(reader: &mut dyn Read, path: &Path, progress: &mut Progress) -> io::Result<()> {
+    if let Some(parent) = path.parent() { std::fs::create_dir_all(parent)?; }
+    let mut file = io::BufWriter::new(File::create(path)?);
+    let mut buffer = vec![0u8; COPY_BUFFER];
+    loop {
+        let read = reader.read(&mut buffer)?;
+        if read == 0 { break; }
+        file.write_all(&buffer[..read])?;
+        progress.advance(read as u64);
+    }
+    file.flush()
+}
+
+// Entry names are untrusted: absolute paths and `..` would write outside the destination
+fn enclosed_path(dest: &Path, name: &str) -> Option<PathBuf> {
+    let name = name.replace('\\', "/");
+    let relative = Path::new(&name);
+    relative.components().all(|c| matches!(c, Component::Normal(_) | Component::CurDir)).then(|| dest.join(relative))
+}
+
+fn extract_zip(archive: &Path, dest: &Path, password: Option<&str>, progress: &mut Progress) -> Result<(), Failure> {
+    let mut zip = zip::ZipArchive::new(BufReader::new(File::open(archive)?)).map_err(|e| Failure::Other(e.to_string()))?;
+    progress.total = (0..zip.len()).filter_map(|i| zip.by_index_raw(i).ok().map(|f| f.size())).sum();
+    for index in 0..zip.len() {
+        let entry = match password {
+            Some(password) => zip.by_index_decrypt(index, password.as_bytes()),
+            None => zip.by_index(index),
+        };
+        let mut entry = match entry {
+            Ok(entry) => entry,
+            Err(zip::result::ZipError::InvalidPassword) => return Err(Failure::Password),
+            Err(zip::result::ZipError::UnsupportedArchive(message)) if message == zip::result::ZipError::PASSWORD_REQUIRED => {
+                return Err(Failure::Password);
+            }
+            Err(e) => return Err(Failure::Other(e.to_string())),
+        };
+        // Symlinks could point the next entry outside the destination
+        if entry.is_symlink() { continue; }
+        let Some(path) = entry.enclosed_name().map(|name| dest.join(name)) else { continue };
+        if entry.is_dir() {
+            std::fs::create_dir_all(&path)?;
+            continue;
+        }
+        copy_with_progress(&mut entry, &path, progress)?;
+        #[cfg(unix)]
+        if let Some(mode) = entry.unix_mode() {
+            use std::os::unix::fs::PermissionsExt;
+            let _ = std::fs::set_permissions(&path, std::fs::Permissions::from_mode(mode & 0o777));
+        }
+    }
+    Ok(())
+}
+
+fn extract_tar(archive: &Path, dest: &Path, gzip: bool, progress: &mut Progress) -> Result<(), Failure> {
+    let file = File::open(archive)?;
+    progress.total = file.metadata()?.len();
+    let reader = ProgressReader { inner: BufReader::new(file), progress };
+    let reader: Box<dyn Read + '_> = if gzip { Box::new(flate2::read::GzDecoder::new(reader)) } else { Box::new(reader) };
+    std::fs::create_dir_all(dest)?;
+    let mut tar = tar::Archive::new(reader);
+    // `unpack_in` skips entries that would land outside `dest`
+    for entry in tar.entries()? {
+        entry?.unpack_in(dest)?;
+    }
+    Ok(())
+}
+
+fn extract_7z(archive: &Path, dest: &Path, password: Option<&str>, progress: &mut Progress) -> Result<(), Failure> {
+    let failure = |e: sevenz_rust::Error| match e {
+        sevenz_rust::Error::PasswordRequired | sevenz_rust::Error::MaybeBadPassword(_) => Failure::Password,
+        // Data decrypted with the wrong key fails its checksum
+        sevenz_rust::Error::ChecksumVerificationFailed if password.is_some() => Failure::Password,
+        e => Failure::Other(e.to_string()),
+    };
+    let key = password.map(sevenz_rust::Password::from).unwrap_or_else(sevenz_rust::Password::empty);
+    let mut reader = sevenz_rust::SevenZReader::open(archive, key).map_err(failure)?;
+    progress.total = reader.archive().files.iter().map(|f| f.size()).sum();
+    std::fs::create_dir_all(dest)?;
+    reader.for_each_entries(|entry, data| {
+        match enclosed_path(dest, entry.name()) {
+            Some(path) if entry.is_directory() => std::fs::create_dir_all(&path)?,
+            Some(path) => copy_with_progress(data, &path, progress)?,
+            // Still read through: entries of a solid block share one stream
+            None => { io::copy(data, &mut io::sink())?; }
+        }
+        Ok(true)
+    }).map_err(failure)
+}
+
+async fn extract_rar(unrar: &str, archive: &Path, dest: &Path, password: Option<&str>, sender: &watch::Sender<f64>) -> Result<(), Failure> {
+    if unrar.is_empty() {
+        return Err(Failure::Other("Set the unrar program in Settings to extract RAR archives".to_string()));
+    }
+    tokio::fs::create_dir_all(dest).await?;
+    let mut command = tokio::process::Command::new(unrar);
+    // `x` keeps the stored paths, `-o+` overwrites what an earlier password attempt left, and `-p-`
+    // stops it asking for a password on a terminal that isn't there
+    command.arg("x").arg("-y").arg("-o+")
+        .arg(password.map_or_else(|| "-p-".to_string(), |p| format!("-p{}", p)))
+        .arg(archive)
+        .arg(format!("{}{}", dest.display(), std::path::MAIN_SEPARATOR))
+        .stdin(Stdio::null()).stdout(Stdio::piped()).stderr(Stdio::piped());
+    #[cfg(target_os = "windows")]
+    command.creation_flags(0x0800_0000); // CREATE_NO_WINDOW
+    let mut child = command.spawn().map_err(|e| Failure::Other(format!("Could not run {}: {}", unrar, e)))?;
+
+    // unrar redraws `NN%` on its output as it goes
+    let percent = regex::Regex::new(r"(\d{1,3})%").expect("valid regex");
+    let mut stdout = child.stdout.take().expect("piped stdout");
+    let mut stderr = child.stderr.take().expect("piped stderr");
+    let read_stdout = async {
+        let mut buffer = vec![0u8; 4096];
+        while let Ok(read) = stdout.read(&mut buffer).await {
+            if read == 0 { break; }
+            let text = String::from_utf8_lossy(&buffer[..read]);
+            if let Some(value) = percent.captures_iter(&text).last().and_then(|c| c[1].parse::<f64>().ok()) {
+                sender.send_replace(value.min(100.0));
+            }
+        }
+    };
+    let mut errors = String::new();
+    let _ = tokio::join!(read_stdout, stderr.read_to_string(&mut errors));
+    let status = child.wait().await?;
+    match status.code() {
+        // 1 is a warning, such as a locked file skipped
+        Some(0 | 1) => Ok(()),
+        Some(11) => Err(Failure::Password),
+        _ if errors.to_ascii_lowercase().contains("password") => Err(Failure::Password),
+        _ => Err(Failure::Other(format!(
+            "unrar failed ({}): {}", status, errors.lines().rfind(|l| !l.trim().is_empty()).unwrap_or_default().trim(),
+        ))),
+    }
+}
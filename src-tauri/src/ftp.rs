@@ -0,0 +1,332 @@
+// A small FTP client for `ftp://` and `ftps://` links: binary transfers over passive connections,
+// MLSD listings (LIST on servers without it), and REST to resume. `ftps://` is implicit TLS as curl
+// takes it, on port 990 unless another is given; on port 21 it upgrades with AUTH TLS instead. Data
+// connections are encrypted as well. Without credentials in the link it logs in as `anonymous`.
+// Paths are relative to the login directory, as in RFC 1738; `%2F` at the start makes one absolute.
+
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use percent_encoding::percent_decode_str;
+use rustls::pki_types::ServerName;
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufStream};
+use tokio::net::TcpStream;
+use tokio::time::{timeout, Duration};
+use tokio_rustls::TlsConnector;
+use url::Url;
+
+use crate::remote::RemoteEntry;
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(30);
+// A control connection that stays silent this long is a dead one
+const REPLY_TIMEOUT: Duration = Duration::from_secs(60);
+// Listings are read whole before they are parsed
+const MAX_LISTING_BYTES: usize = 16 * 1024 * 1024;
+const CHUNK_SIZE: usize = 64 * 1024;
+
+pub(crate) trait Stream: AsyncRead + AsyncWrite + Unpin + Send {}
+impl<T: AsyncRead + AsyncWrite + Unpin + Send> Stream for T {}
+
+pub(crate) struct Connection {
+    control: BufStream<Box<dyn Stream>>,
+    // Passive replies name an address, but it's often a private one behind NAT; the control peer isn't
+    peer: SocketAddr,
+    // Set for FTPS, whose data connections are wrapped too
+    tls: Option<(TlsConnector, ServerName<'static>)>,
+    mlsd: bool,
+    resumable: bool,
+    // The login directory, to return to after probing with CWD
+    home: String,
+    // The data connection of the transfer in progress
+    data: Option<Box<dyn Stream>>,
+}
+
+struct Reply {
+    code: u16,
+    text: String,
+}
+
+impl Reply {
+    fn error(&self, what: &str) -> String {
+        format!("{} failed: {} {}", what, self.code, self.text.lines().last().unwrap_or_default().trim())
+    }
+}
+
+// The path an FTP link names, as sent to the server
+pub(crate) fn path(url: &Url) -> String {
+    let path = percent_decode_str(url.path()).decode_utf8_lossy();
+    path.strip_prefix('/').unwrap_or(&path).to_string()
+}
+
+impl Connection {
+    // Connects and logs in; `tls` is required for `ftps://` links
+    pub(crate) async fn open(url: &Url, tls: Option<rustls::ClientConfig>) -> Result<Connection, String> {
+        let host = url.host_str().ok_or("The link has no host")?.trim_start_matches('[').trim_end_matches(']').to_string();
+        let secure = url.scheme() == "ftps";
+        let implicit = secure && url.port() != Some(21);
+        let port = url.port().unwrap_or(if secure { 990 } else { 21 });
+        let tcp = timeout(CONNECT_TIMEOUT, TcpStream::connect((host.as_str(), port))).await
+            .map_err(|_| format!("Connecting to {} timed out", host))?
+            .map_err(|e| format!("Could not connect to {}: {}", host, e))?;
+        let peer = tcp.peer_addr().map_err(|e| e.to_string())?;
+        let tls = match tls.filter(|_| secure) {
+            Some(config) => Some((
+                TlsConnector::from(Arc::new(config)),
+                ServerName::try_from(host.clone()).map_err(|e| format!("Invalid host {}: {}", host, e))?,
+            )),
+            None if secure => return Err("FTPS needs TLS settings".to_string()),
+            None => None,
+        };
+
+        let control: Box<dyn Stream> = match &tls {
+            Some((connector, name)) if implicit => Box::new(handshake(connector, name, tcp).await?),
+            Some((connector, name)) => {
+                let mut plain = BufStream::new(tcp);
+                expect(read_reply(&mut plain).await?, &[220], "Connecting")?;
+                send(&mut plain, "AUTH TLS").await?;
+                expect(read_reply(&mut plain).await?, &[234], "AUTH TLS")?;
+                Box::new(handshake(connector, name, plain.into_inner()).await?)
+            }
+            None => Box::new(tcp),
+        };
+        let mut connection = Connection { control: BufStream::new(control), peer, tls, mlsd: false, resumable: false, home: String::new(), data: None };
+        // The explicit upgrade already read the greeting
+        if implicit || !secure {
+            expect(read_reply(&mut connection.control).await?, &[220], "Connecting")?;
+        }
+        connection.login(url).await?;
+        Ok(connection)
+    }
+
+    async fn login(&mut self, url: &Url) -> Result<(), String> {
+        let decode = |s: &str| percent_decode_str(s).decode_utf8_lossy().into_owned();
+        let user = Some(decode(url.username())).filter(|u| !u.is_empty()).unwrap_or_else(|| "anonymous".to_string());
+        let password = url.password().map(decode).unwrap_or_else(|| if user == "anonymous" { "anonymous@".to_string() } else { String::new() });
+        let reply = self.command(&format!("USER {}", user)).await?;
+        match reply.code {
+            230 => {}
+            331 | 332 => {
+                let reply = self.command(&format!("PASS {}", password)).await?;
+                if !matches!(reply.code, 202 | 230) { return Err(reply.error(&format!("Logging in as {}", user))); }
+            }
+            _ => return Err(reply.error(&format!("Logging in as {}", user))),
+        }
+        if self.tls.is_some() {
+            expect(self.command("PBSZ 0").await?, &[200], "PBSZ")?;
+            expect(self.command("PROT P").await?, &[200], "PROT P")?;
+        }
+        expect(self.command("TYPE I").await?, &[200], "TYPE I")?;
+        // Servers without FEAT predate both
+        let features = self.command("FEAT").await?;
+        if features.code == 211 {
+            let features: Vec<String> = features.text.lines().map(|l| l.trim().to_ascii_uppercase()).collect();
+            self.mlsd = features.iter().any(|f| f.starts_with("MLST"));
+            self.resumable = features.iter().any(|f| f.starts_with("REST STREAM"));
+        }
+        // `257 "/home/user" is the current directory`
+        let reply = self.command("PWD").await?;
+        if let Some((_, rest)) = reply.text.split_once('"') {
+            self.home = rest.rsplit_once('"').map_or(rest, |(home, _)| home).replace("\"\"", "\"");
+        }
+        Ok(())
+    }
+
+    pub(crate) fn resumable(&self) -> bool { self.resumable }
+
+    async fn command(&mut self, command: &str) -> Result<Reply, String> {
+        send(&mut self.control, command).await?;
+        read_reply(&mut self.control).await
+    }
+
+    // A command with the path as its argument, or none for the login directory
+    async fn path_command(&mut self, command: &str, path: &str) -> Result<Reply, String> {
+        if path.is_empty() { self.command(command).await } else { self.command(&format!("{} {}", command, path)).await }
+    }
+
+    // Whether `path` is a directory, and its size if it's a file
+    pub(crate) async fn stat(&mut self, path: &str) -> Result<(bool, Option<u64>), String> {
+        if path.is_empty() || path.ends_with('/') { return Ok((true, None)); }
+        if self.mlsd {
+            let reply = self.path_command("MLST", path).await?;
+            if reply.code != 250 { return Err(reply.error(&format!("Looking up {}", path))); }
+            // The facts are on the indented line between the first and last
+            let entry = reply.text.lines().filter(|l| l.starts_with(' ')).find_map(|l| parse_mlsd_line(l.trim_start()));
+            return Ok(entry.map_or((false, None), |e| (e.is_dir, e.size)));
+        }
+        let reply = self.path_command("SIZE", path).await?;
+        if reply.code == 213 {
+            return Ok((false, reply.text.lines().last().and_then(|l| l.split_whitespace().nth(1)?.parse().ok())));
+        }
+        // SIZE refuses directories; so does CWD files
+        let reply = self.path_command("CWD", path).await?;
+        if reply.code == 250 {
+            let home = if self.home.is_empty() { "/".to_string() } else { self.home.clone() };
+            expect(self.command(&format!("CWD {}", home)).await?, &[250], "CWD")?;
+            return Ok((true, None));
+        }
+        Ok((false, None))
+    }
+
+    pub(crate) async fn list(&mut self, path: &str) -> Result<Vec<RemoteEntry>, String> {
+        let command = if self.mlsd { "MLSD" } else { "LIST" };
+        let data = self.open_data(command, path).await?;
+        let mut text = Vec::new();
+        let read = timeout(REPLY_TIMEOUT, data.take(MAX_LISTING_BYTES as u64).read_to_end(&mut text)).await;
+        read.map_err(|_| "The listing timed out".to_string())?.map_err(|e| format!("Reading the listing failed: {}", e))?;
+        expect(read_reply(&mut self.control).await?, &[226, 250], "The listing")?;
+        let text = String::from_utf8_lossy(&text);
+        let parse = if self.mlsd { parse_mlsd_line } else { parse_list_line };
+        Ok(text.lines().filter_map(parse).filter(|e| e.name != "." && e.name != "..").collect())
+    }
+
+    // Starts reading `path` from `offset`; false when the server can't start anywhere but the beginning
+    pub(crate) async fn retrieve(&mut self, path: &str, offset: u64) -> Result<bool, String> {
+        let resumed = offset > 0 && self.command(&format!("REST {}", offset)).await?.code == 350;
+        self.data = Some(self.open_data("RETR", path).await?);
+        Ok(resumed)
+    }
+
+    // The next bytes of the transfer `retrieve` started, or None once the server confirms it complete
+    pub(crate) async fn read_chunk(&mut self) -> Result<Option<Vec<u8>>, String> {
+        let Some(data) = self.data.as_mut() else { return Ok(None) };
+        let mut chunk = Vec::with_capacity(CHUNK_SIZE);
+        match data.read_buf(&mut chunk).await {
+            Ok(0) => {
+                self.data = None;
+                expect(read_reply(&mut self.control).await?, &[226, 250], "The transfer")?;
+                Ok(None)
+            }
+            Ok(_) => Ok(Some(chunk)),
+            Err(e) => Err(format!("Reading from the server failed: {}", e)),
+        }
+    }
+
+    async fn open_data(&mut self, command: &str, path: &str) -> Result<Box<dyn Stream>, String> {
+        let reply = self.command("EPSV").await?;
+        let port = if reply.code == 229 { parse_epsv(&reply.text) } else {
+            let reply = self.command("PASV").await?;
+            if reply.code != 227 { return Err(reply.error("PASV")); }
+            parse_pasv(&reply.text)
+        };
+        let address = SocketAddr::new(self.peer.ip(), port.ok_or("The server's passive reply wasn't understood")?);
+        let tcp = timeout(CONNECT_TIMEOUT, TcpStream::connect(address)).await
+            .map_err(|_| "Opening the data connection timed out".to_string())?
+            .map_err(|e| format!("Could not open the data connection: {}", e))?;
+        let reply = self.path_command(command, path).await?;
+        if !matches!(reply.code, 125 | 150) { return Err(reply.error(&format!("{} {}", command, path))); }
+        // Servers start TLS on the data connection once they have accepted the command
+        match &self.tls {
+            Some((connector, name)) => Ok(Box::new(handshake(connector, name, tcp).await?)),
+            None => Ok(Box::new(tcp)),
+        }
+    }
+}
+
+async fn handshake(connector: &TlsConnector, name: &ServerName<'static>, tcp: TcpStream) -> Result<tokio_rustls::client::TlsStream<TcpStream>, String> {
+    timeout(CONNECT_TIMEOUT, connector.connect(name.clone(), tcp)).await
+        .map_err(|_| "The TLS handshake timed out".to_string())?
+        .map_err(|e| format!("TLS handshake failed: {}", e))
+}
+
+async fn send<S: AsyncWrite + Unpin>(stream: &mut S, command: &str) -> Result<(), String> {
+    stream.write_all(format!("{}\r\n", command).as_bytes()).await.map_err(|e| format!("The connection dropped: {}", e))?;
+    stream.flush().await.map_err(|e| format!("The connection dropped: {}", e))
+}
+
+// A reply, multi-line ones (`123-` up to `123 `) joined
+async fn read_reply<S: AsyncBufRead + Unpin>(stream: &mut S) -> Result<Reply, String> {
+    let mut text = String::new();
+    let mut code = None;
+    loop {
+        let mut line = String::new();
+        let read = timeout(REPLY_TIMEOUT, stream.read_line(&mut line)).await
+            .map_err(|_| "The server stopped answering".to_string())?
+            .map_err(|e| format!("The connection dropped: {}", e))?;
+        if read == 0 { return Err("The server closed the connection".to_string()); }
+        let line = line.trim_end_matches(['\r', '\n']);
+        let this_code = line.get(..3).and_then(|c| c.parse::<u16>().ok());
+        let last = this_code.is_some() && line.as_bytes().get(3) != Some(&b'-') && code.is_none_or(|c| Some(c) == this_code);
+        code = code.or(this_code);
+        if !text.is_empty() { text.push('\n'); }
+        text.push_str(line);
+        if last { break; }
+    }
+    Ok(Reply { code: code.unwrap_or_default(), text })
+}
+
+fn expect(reply: Reply, codes: &[u16], what: &str) -> Result<Reply, String> {
+    if codes.contains(&reply.code) { Ok(reply) } else { Err(reply.error(what)) }
+}
+
+// `229 Entering Extended Passive Mode (|||6446|)`
+fn parse_epsv(text: &str) -> Option<u16> {
+    let inner = &text[text.find('(')? + 1..text.rfind(')')?];
+    let delimiter = inner.chars().next()?;
+    inner.split(delimiter).nth(3)?.parse().ok()
+}
+
+// `227 Entering Passive Mode (192,168,1,2,19,137)`; only the port is used
+fn parse_pasv(text: &str) -> Option<u16> {
+    let start = text.get(4..)?.find(|c: char| c.is_ascii_digit())? + 4;
+    let numbers: Vec<u16> = text[start..].split(',')
+        .map(|n| n.chars().take_while(char::is_ascii_digit).collect::<String>())
+        .map_while(|n| n.parse().ok())
+        .collect();
+    (numbers.len() >= 6 && numbers[4] < 256 && numbers[5] < 256).then(|| numbers[4] * 256 + numbers[5])
+}
+
+// `type=file;size=1024;modify=20240116100000; name`
+fn parse_mlsd_line(line: &str) -> Option<RemoteEntry> {
+    let (facts, name) = line.split_once(' ')?;
+    let mut entry = RemoteEntry { name: name.to_string(), is_dir: false, size: None, modified: None };
+    let mut kind = None;
+    for fact in facts.split(';') {
+        let Some((key, value)) = fact.split_once('=') else { continue };
+        match key.to_ascii_lowercase().as_str() {
+            "type" => kind = Some(value.to_ascii_lowercase()),
+            "size" => entry.size = value.parse().ok(),
+            "modify" if value.len() >= 12 => entry.modified = Some(format!(
+                "{}-{}-{} {}:{}", &value[..4], &value[4..6], &value[6..8], &value[8..10], &value[10..12],
+            )),
+            _ => {}
+        }
+    }
+    // `cdir` and `pdir` are the directory itself and its parent; links and devices aren't followed
+    match kind.as_deref() {
+        Some("file") => Some(entry),
+        Some("dir") => Some(RemoteEntry { is_dir: true, size: None, ..entry }),
+        _ => None,
+    }
+}
+
+// Unix `ls -l` lines, or the DOS style IIS answers with
+fn parse_list_line(line: &str) -> Option<RemoteEntry> {
+    let (fields, name) = split_fields(line, 8)?;
+    let kind = fields[0].chars().next()?;
+    if matches!(kind, 'd' | '-') && fields[0].len() >= 10 {
+        let is_dir = kind == 'd';
+        return Some(RemoteEntry {
+            name: name.to_string(), is_dir,
+            size: if is_dir { None } else { fields[4].parse().ok() },
+            modified: Some(fields[5..8].join(" ")),
+        });
+    }
+    // `01-16-24  10:00AM       <DIR>          name` and `01-16-24  10:00AM   1024 name`
+    let (fields, name) = split_fields(line, 3)?;
+    if !fields[0].contains('-') || !fields[1].contains(':') { return None; }
+    let is_dir = fields[2].eq_ignore_ascii_case("<DIR>");
+    let size = if is_dir { None } else { Some(fields[2].parse().ok()?) };
+    Some(RemoteEntry { name: name.to_string(), is_dir, size, modified: Some(format!("{} {}", fields[0], fields[1])) })
+}
+
+// The first `count` whitespace-separated fields and the rest of the line, which may contain spaces
+fn split_fields(line: &str, count: usize) -> Option<(Vec<&str>, &str)> {
+    let mut fields = Vec::with_capacity(count);
+    let mut rest = line.trim_start();
+    while fields.len() < count {
+        let end = rest.find(char::is_whitespace)?;
+        fields.push(&rest[..end]);
+        rest = rest[end..].trim_start();
+    }
+    (!rest.is_empty()).then_some((fields, rest))
+}
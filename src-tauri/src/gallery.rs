@@ -0,0 +1,182 @@
+// Gallery mode: the images of one page saved as `001.jpg`, `002.jpg`, ... in a folder named after
+// the page. Images are picked by a CSS selector and/or a URL regex; without either, the largest run of
+// URLs that differ only in a number (`img_01.jpg`, `img_02.jpg`, ...) is taken as the gallery.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::PathBuf;
+
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use tokio::time::Duration;
+use url::Url;
+
+use crate::AddDownloadPayload;
+
+const IMAGE_EXTENSIONS: &[&str] = &["jpg", "jpeg", "png", "gif", "webp", "avif", "bmp", "tif", "tiff", "jxl"];
+// Attributes lazy-loading scripts keep the real image in, most specific first
+const SOURCE_ATTRIBUTES: &[&str] = &["data-src", "data-original", "data-lazy-src", "data-full", "src", "href"];
+// A numbered run shorter than this is more likely navigation or icons than a gallery
+const MIN_SEQUENCE: usize = 3;
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct GalleryRequest {
+    pub(crate) page_url: String,
+    // CSS selector for the image elements, or for containers whose `<img>`s are wanted
+    #[serde(default)]
+    pub(crate) selector: Option<String>,
+    // Where the URL is on the selected elements; the usual `src`/`data-src`/`href` when empty
+    #[serde(default)]
+    pub(crate) attribute: Option<String>,
+    // Regex the absolute image URLs must match
+    #[serde(default)]
+    pub(crate) pattern: Option<String>,
+    #[serde(default)]
+    pub(crate) folder: Option<String>,
+}
+
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct Gallery {
+    pub(crate) page_url: String,
+    pub(crate) title: String,
+    pub(crate) images: Vec<String>,
+}
+
+pub(crate) async fn scan(client: &Client, request: &GalleryRequest) -> Result<Gallery, String> {
+    let pattern = match request.pattern.as_deref().map(str::trim).filter(|p| !p.is_empty()) {
+        Some(pattern) => Some(regex::Regex::new(pattern).map_err(|e| format!("Invalid pattern: {}", e))?),
+        None => None,
+    };
+    let response = client.get(request.page_url.trim())
+        .header("Accept", "text/html,application/xhtml+xml,application/xml;q=0.9,*/*;q=0.8")
+        .timeout(Duration::from_secs(20))
+        .send()
+        .await
+        .map_err(|e| format!("Request failed: {}", e))?;
+    if !response.status().is_success() {
+        return Err(format!("Server returned error: {}", response.status()));
+    }
+    let page_url = response.url().clone();
+    let html = crate::read_capped_body(response, crate::MAX_SCAN_PAGE_BYTES).await;
+    let selector = request.selector.as_deref().map(str::trim).filter(|s| !s.is_empty());
+    let attribute = request.attribute.as_deref().map(str::trim).filter(|a| !a.is_empty());
+    let (title, mut images) = collect_images(&html, &page_url, selector, attribute)?;
+
+    if let Some(pattern) = &pattern {
+        images.retain(|url| pattern.is_match(url));
+    } else if selector.is_none() {
+        if let Some(run) = numbered_run(&images) { images = run; }
+    }
+    if images.is_empty() { return Err("No images found".to_string()); }
+    let title = Some(title).filter(|t| !t.is_empty()).unwrap_or_else(|| page_url.host_str().unwrap_or("gallery").to_string());
+    Ok(Gallery { page_url: page_url.to_string(), title, images })
+}
+
+fn is_image_url(url: &Url) -> bool {
+    crate::file_name_from_url(url)
+        .and_then(|name| name.rsplit_once('.').map(|(_, ext)| ext.to_ascii_lowercase()))
+        .is_some_and(|ext| IMAGE_EXTENSIONS.contains(&ext.as_str()))
+}
+
+// The page title and the image URLs in page order. Without a selector, links to images win over
+// `<img>`s when there are enough of them, since galleries link thumbnails to the full-size files.
+// Kept free of awaits: kuchikiki's DOM isn't `Send`.
+fn collect_images(html: &str, page_url: &Url, selector: Option<&str>, attribute: Option<&str>) -> Result<(String, Vec<String>), String> {
+    use kuchikiki::traits::TendrilSink;
+    let document = kuchikiki::parse_html().one(html);
+    let title = document.select_first("title").map(|t| t.text_contents().split_whitespace().collect::<Vec<_>>().join(" ")).unwrap_or_default();
+    let source = |node: &kuchikiki::NodeRef| -> Option<Url> {
+        let element = node.as_element()?;
+        let attributes = element.attributes.borrow();
+        let raw = match attribute {
+            Some(attribute) => attributes.get(attribute)?,
+            None => SOURCE_ATTRIBUTES.iter().find_map(|a| attributes.get(*a))?,
+        };
+        let mut url = page_url.join(raw.trim()).ok()?;
+        url.set_fragment(None);
+        matches!(url.scheme(), "http" | "https").then_some(url)
+    };
+
+    let mut images: Vec<Url> = Vec::new();
+    match selector {
+        Some(selector) => {
+            let elements = document.select(selector).map_err(|_| format!("Invalid selector: {}", selector))?;
+            for element in elements {
+                match source(element.as_node()) {
+                    Some(url) => images.push(url),
+                    // A container: take the images inside it
+                    None => images.extend(element.as_node().descendants().filter(|n| {
+                        n.as_element().is_some_and(|e| e.name.local.as_ref() == "img")
+                    }).filter_map(|n| source(&n))),
+                }
+            }
+        }
+        None => {
+            let linked: Vec<Url> = document.select("a[href]").into_iter().flatten()
+                .filter_map(|a| source(a.as_node()))
+                .filter(is_image_url)
+                .collect();
+            images = if linked.len() >= MIN_SEQUENCE {
+                linked
+            } else {
+                document.select("img").into_iter().flatten().filter_map(|img| source(img.as_node())).collect()
+            };
+        }
+    }
+    let mut seen = HashSet::new();
+    Ok((title, images.into_iter().map(|u| u.to_string()).filter(|u| seen.insert(u.clone())).collect()))
+}
+
+// The longest set of URLs that are the same apart from one number (the last one that varies),
+// ordered by that number
+fn numbered_run(urls: &[String]) -> Option<Vec<String>> {
+    let mut groups: HashMap<String, Vec<(u64, &String)>> = HashMap::new();
+    for url in urls {
+        // Each run of digits in turn stands in for the counter
+        let bytes = url.as_bytes();
+        let mut start = None;
+        for i in 0..=bytes.len() {
+            let digit = bytes.get(i).is_some_and(u8::is_ascii_digit);
+            match (start, digit) {
+                (None, true) => start = Some(i),
+                (Some(s), false) => {
+                    if let Ok(n) = url[s..i].parse() {
+                        groups.entry(format!("{}\u{0}{}", &url[..s], &url[i..])).or_default().push((n, url));
+                    }
+                    start = None;
+                }
+                _ => {}
+            }
+        }
+    }
+    let (_, mut run) = groups.into_iter()
+        .filter(|(_, members)| members.len() >= MIN_SEQUENCE)
+        // Ties go to the template with the later counter, i.e. the image number rather than a year
+        .max_by_key(|(template, members)| (members.len(), template.find('\u{0}')))?;
+    run.sort_by_key(|(n, _)| *n);
+    Some(run.into_iter().map(|(_, url)| url.clone()).collect())
+}
+
+// One download per image, numbered in gallery order with enough zero padding to sort by name.
+// The page goes along as the Referer, which image hosts often check.
+pub(crate) fn payloads(gallery: &Gallery, folder: &str) -> Vec<AddDownloadPayload> {
+    let name: String = gallery.title.chars()
+        .map(|c| if c.is_control() || matches!(c, '/' | '\\' | '<' | '>' | ':' | '"' | '|' | '?' | '*') { '_' } else { c })
+        .collect();
+    let name = name.trim().trim_matches('.').chars().take(100).collect::<String>();
+    let path = PathBuf::from(folder).join(if crate::is_valid_file_name(&name) { name.as_str() } else { "gallery" });
+    let width = gallery.images.len().to_string().len().max(3);
+    let headers: BTreeMap<String, String> = [("Referer".to_string(), gallery.page_url.clone())].into();
+    gallery.images.iter().enumerate().map(|(index, url)| {
+        let ext = Url::parse(url).ok().as_ref().and_then(crate::file_name_from_url)
+            .and_then(|name| name.rsplit_once('.').map(|(_, ext)| ext.to_ascii_lowercase()))
+            .filter(|ext| IMAGE_EXTENSIONS.contains(&ext.as_str()))
+            .unwrap_or_else(|| "jpg".to_string());
+        AddDownloadPayload {
+            url: url.clone(), file_name: format!("{:0width$}.{}", index + 1, ext, width = width),
+            total_size: None, custom_path: Some(path.to_string_lossy().to_string()), checksum: None,
+            headers: headers.clone(), redirect_chain: Vec::new(), parent_id: None, signature: None, allow_http: false, proxy: None, request: None, source_url: None,
+        }
+    }).collect()
+}
@@ -0,0 +1,98 @@
+// GitHub release lookup, so the add dialog can offer a release's assets to pick from.
+// Accepts `github://owner/repo[@tag]` and github.com repository or release page URLs.
+
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+const API_BASE: &str = "https://api.github.com";
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct GithubRelease {
+    pub(crate) repo: String,
+    pub(crate) tag_name: String,
+    pub(crate) name: Option<String>,
+    pub(crate) published_at: Option<String>,
+    pub(crate) assets: Vec<GithubAsset>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct GithubAsset {
+    pub(crate) name: String,
+    pub(crate) url: String,
+    pub(crate) size: u64,
+    pub(crate) content_type: Option<String>,
+    pub(crate) download_count: u64,
+}
+
+// Field names as the REST API sends them
+#[derive(Deserialize)]
+struct ApiRelease {
+    tag_name: String,
+    name: Option<String>,
+    published_at: Option<String>,
+    assets: Vec<ApiAsset>,
+}
+
+#[derive(Deserialize)]
+struct ApiAsset {
+    name: String,
+    browser_download_url: String,
+    size: u64,
+    content_type: Option<String>,
+    download_count: u64,
+}
+
+// `owner/repo` and the tag, `None` meaning the latest release
+pub(crate) fn parse_release_ref(input: &str) -> Option<(String, Option<String>)> {
+    let input = input.trim();
+    if let Some(rest) = input.strip_prefix("github://") {
+        let (repo, tag) = match rest.split_once('@') {
+            Some((repo, tag)) => (repo, Some(tag.trim_matches('/').to_string()).filter(|t| !t.is_empty())),
+            None => (rest, None),
+        };
+        let repo = repo.trim_matches('/');
+        let (owner, name) = repo.split_once('/')?;
+        return (!owner.is_empty() && !name.is_empty() && !name.contains('/')).then(|| (repo.to_string(), tag));
+    }
+    let url = Url::parse(input).ok()?;
+    if !matches!(url.host_str()?, "github.com" | "www.github.com") { return None; }
+    let segments: Vec<&str> = url.path_segments()?.filter(|s| !s.is_empty()).collect();
+    match segments.as_slice() {
+        [owner, repo] | [owner, repo, "releases"] | [owner, repo, "releases", "latest"] => Some((format!("{}/{}", owner, repo), None)),
+        [owner, repo, "releases", "tag", tag @ ..] if !tag.is_empty() => Some((format!("{}/{}", owner, repo), Some(tag.join("/")))),
+        _ => None,
+    }
+}
+
+pub(crate) async fn fetch_release(client: &Client, repo: &str, tag: Option<&str>, token: &str) -> Result<GithubRelease, String> {
+    let endpoint = match tag {
+        Some(tag) => format!("{}/repos/{}/releases/tags/{}", API_BASE, repo, tag),
+        None => format!("{}/repos/{}/releases/latest", API_BASE, repo),
+    };
+    let mut request = client.get(&endpoint)
+        .header("Accept", "application/vnd.github+json")
+        .header("X-GitHub-Api-Version", "2022-11-28");
+    if !token.trim().is_empty() { request = request.bearer_auth(token.trim()); }
+    let response = request.send().await.map_err(|e| format!("Request failed: {}", e))?;
+    match response.status().as_u16() {
+        200 => {}
+        404 => return Err(format!("No release {} found for {}", tag.unwrap_or("latest"), repo)),
+        // Unauthenticated callers get 60 requests an hour
+        403 | 429 => return Err("GitHub rate limit reached; add a token in Settings".to_string()),
+        _ => return Err(format!("GitHub returned error: {}", response.status())),
+    }
+    let body = response.bytes().await.map_err(|e| format!("Request failed: {}", e))?;
+    let release: ApiRelease = serde_json::from_slice(&body).map_err(|e| format!("Unexpected GitHub response: {}", e))?;
+    Ok(GithubRelease {
+        repo: repo.to_string(),
+        tag_name: release.tag_name,
+        name: release.name.filter(|n| !n.is_empty()),
+        published_at: release.published_at,
+        assets: release.assets.into_iter().map(|a| GithubAsset {
+            name: a.name, url: a.browser_download_url, size: a.size, content_type: a.content_type, download_count: a.download_count,
+        }).collect(),
+    })
+}
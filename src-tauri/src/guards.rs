@@ -0,0 +1,130 @@
+// Two guards on what downloads may take. One known to be larger than `confirm_over_mb` doesn't start
+// until the user confirms it, waiting under its own status. While the drive a download writes to has
+// less than `min_free_space_mb` free, downloads to it are paused under another status, and resume on
+// their own once there's room again.
+
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+use tauri::{AppHandle, Emitter, Manager, State};
+use tokio::time::Duration;
+
+use crate::i18n::{tr, Language, Text};
+use crate::{AppSettings, AppState, DownloadStatus, DownloadTask};
+
+const CHECK_TICK: Duration = Duration::from_secs(10);
+const MB: u64 = 1024 * 1024;
+// Room needed beyond the minimum before paused downloads resume, so they don't stop and start
+// around the line
+const RESUME_MARGIN_MB: u64 = 256;
+
+fn needs_confirmation(settings: &AppSettings, task: &DownloadTask) -> bool {
+    settings.confirm_over_mb > 0 && !task.size_confirmed && task.total_size > settings.confirm_over_mb * MB
+}
+
+// Free bytes on the drive `path` is on, when below the minimum
+fn short_of_space(settings: &AppSettings, path: &Path, margin_mb: u64) -> Option<u64> {
+    if settings.min_free_space_mb == 0 { return None; }
+    free_space(path).filter(|free| *free < (settings.min_free_space_mb + margin_mb) * MB)
+}
+
+fn low_space_message(free: u64) -> String {
+    format!("Paused: only {} MB left on the drive", free / MB)
+}
+
+// Parks `task` under the guard that keeps it from starting, if any; the caller has the lock
+pub(crate) fn hold(settings: &AppSettings, task: &mut DownloadTask) -> bool {
+    if needs_confirmation(settings, task) {
+        task.status = DownloadStatus::AwaitingConfirmation;
+        task.error_message = Some(format!("Larger than {} MB; waiting for you to confirm it", settings.confirm_over_mb));
+    } else if let Some(free) = short_of_space(settings, &crate::task_file_path(task), 0) {
+        task.status = DownloadStatus::LowDiskSpace;
+        task.error_message = Some(low_space_message(free));
+    } else {
+        return false;
+    }
+    task.speed = 0;
+    true
+}
+
+pub(crate) async fn run_space_watch(app_handle: AppHandle) {
+    loop {
+        tokio::time::sleep(CHECK_TICK).await;
+        check_space(&app_handle).await;
+    }
+}
+
+async fn check_space(app_handle: &AppHandle) {
+    let state: State<AppState> = app_handle.state();
+    let running: HashSet<String> = state.download_handles.lock().await.keys().cloned().collect();
+    let (settings, tasks): (AppSettings, Vec<(String, PathBuf, bool)>) = {
+        let state_guard = state.persistent.lock().await;
+        let tasks = state_guard.downloads.iter()
+            .filter(|t| running.contains(&t.id) || t.status == DownloadStatus::LowDiskSpace)
+            .map(|t| (t.id.clone(), crate::task_file_path(t), t.status == DownloadStatus::LowDiskSpace))
+            .collect();
+        (state_guard.settings.clone(), tasks)
+    };
+    let mut to_pause = Vec::new();
+    let mut to_resume = Vec::new();
+    for (id, path, parked) in tasks {
+        if !parked {
+            if let Some(free) = short_of_space(&settings, &path, 0) { to_pause.push((id, free)); }
+        } else if short_of_space(&settings, &path, RESUME_MARGIN_MB).is_none() {
+            to_resume.push(id);
+        }
+    }
+    if !to_pause.is_empty() {
+        futures::future::join_all(to_pause.iter().map(|(id, _)| crate::abort_download(&state, id))).await;
+        {
+            let mut state_guard = state.persistent.lock().await;
+            for (id, free) in &to_pause {
+                let Some(task) = state_guard.downloads.iter_mut().find(|t| t.id == *id) else { continue };
+                task.status = DownloadStatus::LowDiskSpace;
+                task.speed = 0;
+                task.error_message = Some(low_space_message(*free));
+                app_handle.emit("task_updated", &*task).unwrap();
+            }
+        }
+        crate::request_save(&state);
+        log::warn!("Paused {} download(s) for lack of disk space", to_pause.len());
+        let language = Language::from_setting(&settings.language);
+        let body = tr(language, Text::LowDiskSpaceBody).replace("{count}", &to_pause.len().to_string());
+        crate::notifications::show(app_handle, &settings, tr(language, Text::LowDiskSpace), &body);
+    }
+    if to_resume.is_empty() { return; }
+    {
+        let mut state_guard = state.persistent.lock().await;
+        for task in state_guard.downloads.iter_mut().filter(|t| to_resume.contains(&t.id) && t.status == DownloadStatus::LowDiskSpace) {
+            task.status = DownloadStatus::Paused;
+            task.resume_attempts = 0;
+            task.error_message = None;
+        }
+    }
+    for id in to_resume {
+        if let Err(e) = crate::start_download_task(id, app_handle.clone()).await {
+            log::warn!("Could not resume a download after disk space freed up: {}", e);
+        }
+    }
+}
+
+// Free bytes for the user on the drive holding `path`, going up to the nearest folder that exists
+#[cfg(unix)]
+fn free_space(path: &Path) -> Option<u64> {
+    use std::os::unix::ffi::OsStrExt;
+    let dir = path.ancestors().find(|p| p.exists())?;
+    let dir = std::ffi::CString::new(dir.as_os_str().as_bytes()).ok()?;
+    let mut stats: libc::statvfs = unsafe { std::mem::zeroed() };
+    if unsafe { libc::statvfs(dir.as_ptr(), &mut stats) } != 0 { return None; }
+    Some(stats.f_bavail as u64 * stats.f_frsize as u64)
+}
+
+#[cfg(windows)]
+fn free_space(path: &Path) -> Option<u64> {
+    use windows::core::HSTRING;
+    use windows::Win32::Storage::FileSystem::GetDiskFreeSpaceExW;
+    let dir = path.ancestors().find(|p| p.exists())?;
+    let mut free = 0u64;
+    unsafe { GetDiskFreeSpaceExW(&HSTRING::from(dir), Some(&mut free as *mut u64), None, None) }.ok()?;
+    Some(free)
+}
@@ -0,0 +1,102 @@
+// The download list as a CSV or JSON file for record keeping: one row per download with its link,
+// file, size, hash, timestamps and status. Link tokens are masked when `redact_saved_secrets` is on,
+// as they are in state.json.
+
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+
+use crate::{redact, DownloadStatus, DownloadTask};
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum ExportFormat { Csv, Json }
+
+// Every field narrows the export; the default keeps everything
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub(crate) struct HistoryFilter {
+    statuses: Vec<DownloadStatus>,
+    // Matched case-insensitively against the file name and link
+    search: String,
+    // Bounds on when the download was added
+    from: Option<DateTime<Local>>,
+    to: Option<DateTime<Local>>,
+}
+
+impl HistoryFilter {
+    fn matches(&self, task: &DownloadTask) -> bool {
+        let search = self.search.trim().to_lowercase();
+        (self.statuses.is_empty() || self.statuses.contains(&task.status))
+            && (search.is_empty() || task.file_name.to_lowercase().contains(&search) || task.url.to_lowercase().contains(&search))
+            && self.from.is_none_or(|from| task.created_at >= from)
+            && self.to.is_none_or(|to| task.created_at <= to)
+    }
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct HistoryRow {
+    url: String,
+    file_name: String,
+    path: String,
+    size: u64,
+    // The verified checksum, else the SHA-256 a VirusTotal lookup computed
+    hash: String,
+    added: DateTime<Local>,
+    completed: Option<DateTime<Local>>,
+    status: DownloadStatus,
+    error: String,
+}
+
+const CSV_HEADER: [&str; 9] = ["URL", "File Name", "Path", "Size (bytes)", "Hash", "Added", "Completed", "Status", "Error"];
+
+fn row(task: &DownloadTask, redact_links: bool) -> HistoryRow {
+    let hash = match (&task.checksum, &task.virus_scan) {
+        (Some(checksum), _) if task.status == DownloadStatus::Completed => checksum.clone(),
+        (_, Some(scan)) => format!("sha256:{}", scan.sha256),
+        _ => String::new(),
+    };
+    let error = task.error_message.clone().unwrap_or_default();
+    HistoryRow {
+        url: if redact_links { redact::url(&task.url).into_owned() } else { task.url.clone() },
+        file_name: task.file_name.clone(),
+        path: std::path::Path::new(&task.save_path).join(&task.file_name).to_string_lossy().to_string(),
+        size: if task.total_size > 0 { task.total_size } else { task.downloaded_size },
+        hash,
+        added: task.created_at,
+        completed: task.completed_at,
+        status: task.status.clone(),
+        error: if redact_links { redact::text(&error).into_owned() } else { error },
+    }
+}
+
+// Quotes a field when it holds a separator, quote or line break, doubling inner quotes (RFC 4180).
+// A leading `=`, `+`, `-` or `@` gets a `'` so spreadsheets don't run it as a formula.
+fn csv_field(value: &str) -> String {
+    let value = if value.starts_with(['=', '+', '-', '@']) { format!("'{}", value) } else { value.to_string() };
+    if value.contains([',', '"', '\n', '\r']) { format!("\"{}\"", value.replace('"', "\"\"")) } else { value }
+}
+
+// Returns how many downloads were written
+pub(crate) fn export(tasks: &[DownloadTask], format: ExportFormat, filter: &HistoryFilter, redact_links: bool) -> Result<(String, usize), String> {
+    let rows: Vec<HistoryRow> = tasks.iter().filter(|t| filter.matches(t)).map(|t| row(t, redact_links)).collect();
+    let count = rows.len();
+    let content = match format {
+        ExportFormat::Json => serde_json::to_string_pretty(&rows).map_err(|e| e.to_string())?,
+        ExportFormat::Csv => {
+            let mut out = CSV_HEADER.join(",");
+            out.push_str("\r\n");
+            for row in &rows {
+                let status = serde_json::to_value(&row.status).ok().and_then(|v| v.as_str().map(str::to_string)).unwrap_or_default();
+                let fields = [
+                    row.url.clone(), row.file_name.clone(), row.path.clone(), row.size.to_string(), row.hash.clone(),
+                    row.added.to_rfc3339(), row.completed.map(|c| c.to_rfc3339()).unwrap_or_default(), status, row.error.clone(),
+                ];
+                out.push_str(&fields.iter().map(|f| csv_field(f)).collect::<Vec<_>>().join(","));
+                out.push_str("\r\n");
+            }
+            out
+        }
+    };
+    Ok((content, count))
+}
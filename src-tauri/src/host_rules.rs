@@ -0,0 +1,54 @@
+// Host allow/block lists from the settings, checked when a download is added (its URL and every hop
+// that led to it), before every attempt at it, and by the HTTP client's redirect policy on every
+// redirect. Patterns go one per line: `example.com` covers the domain and its subdomains, and `*`
+// matches any run of characters (`cdn*.example.net`). A non-empty allowlist admits only the hosts on
+// it; the blocklist wins over it.
+
+use url::Url;
+
+use crate::AppSettings;
+
+#[derive(Debug, Clone, Default)]
+pub(crate) struct HostRules {
+    blocked: Vec<String>,
+    allowed: Vec<String>,
+}
+
+impl HostRules {
+    pub(crate) fn from_settings(settings: &AppSettings) -> Self {
+        HostRules { blocked: patterns(&settings.blocked_hosts), allowed: patterns(&settings.allowed_hosts) }
+    }
+
+    // Only URLs naming a server are subject to the lists; `s3://` and the like name buckets, not hosts
+    pub(crate) fn check(&self, url: &Url) -> Result<(), String> {
+        if !matches!(url.scheme(), "http" | "https" | "ftp" | "ftps" | "sftp") { return Ok(()); }
+        let host = url.host_str().unwrap_or_default().trim_start_matches('[').trim_end_matches(']').trim_end_matches('.').to_ascii_lowercase();
+        if self.blocked.iter().any(|p| matches(p, &host)) {
+            return Err(format!("{} is on the blocked hosts list", host));
+        }
+        if !self.allowed.is_empty() && !self.allowed.iter().any(|p| matches(p, &host)) {
+            return Err(format!("{} is not on the allowed hosts list", host));
+        }
+        Ok(())
+    }
+
+    pub(crate) fn check_str(&self, url: &str) -> Result<(), String> {
+        match Url::parse(url) {
+            Ok(url) => self.check(&url),
+            // Malformed URLs are reported by whatever parses them next
+            Err(_) => Ok(()),
+        }
+    }
+}
+
+fn patterns(list: &str) -> Vec<String> {
+    list.lines()
+        .map(|l| l.trim().trim_end_matches('.').to_ascii_lowercase())
+        .filter(|l| !l.is_empty() && !l.starts_with('#'))
+        .collect()
+}
+
+pub(crate) fn matches(pattern: &str, host: &str) -> bool {
+    if pattern.contains('*') { return crate::mirror::wildcard_match(pattern, host); }
+    host == pattern || host.strip_suffix(pattern).is_some_and(|rest| rest.ends_with('.'))
+}
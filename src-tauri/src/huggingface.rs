@@ -0,0 +1,111 @@
+// Hugging Face Hub repositories: lists a model, dataset or space's files so the add dialog can queue
+// the ones picked. Accepts `hf://[datasets/|spaces/]org/name[@revision]` and huggingface.co URLs.
+
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+const HUB: &str = "https://huggingface.co";
+
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct HfRepo {
+    // `org/name`, with a `datasets/` or `spaces/` prefix for those kinds
+    pub(crate) repo: String,
+    pub(crate) revision: String,
+    pub(crate) files: Vec<HfFile>,
+}
+
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct HfFile {
+    // Relative to the repository root, `/`-separated
+    pub(crate) path: String,
+    pub(crate) url: String,
+    pub(crate) size: u64,
+    // Only LFS files carry a SHA-256; small files are addressed by their git blob id
+    pub(crate) checksum: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct TreeEntry {
+    #[serde(rename = "type")]
+    kind: String,
+    path: String,
+    #[serde(default)]
+    size: u64,
+    lfs: Option<LfsInfo>,
+}
+
+#[derive(Deserialize)]
+struct LfsInfo { oid: String, size: u64 }
+
+// Repository path and revision (`main` when not given)
+pub(crate) fn parse_repo_ref(input: &str) -> Option<(String, String)> {
+    let input = input.trim();
+    let (path, revision) = if let Some(rest) = input.strip_prefix("hf://") {
+        match rest.split_once('@') {
+            Some((repo, revision)) => (repo.trim_matches('/').to_string(), Some(revision.to_string())),
+            None => (rest.trim_matches('/').to_string(), None),
+        }
+    } else {
+        let url = Url::parse(input).ok()?;
+        if !matches!(url.host_str()?, "huggingface.co" | "www.huggingface.co" | "hf.co") { return None; }
+        let segments: Vec<&str> = url.path_segments()?.filter(|s| !s.is_empty()).collect();
+        let repo_len = if matches!(segments.first(), Some(&"datasets" | &"spaces")) { 3 } else { 2 };
+        if segments.len() < repo_len { return None; }
+        // `/org/name/tree/<revision>` or `/org/name/blob/<revision>/...`
+        let revision = match segments.get(repo_len..repo_len + 2) {
+            Some(&["tree" | "blob" | "resolve", revision]) => Some(revision.to_string()),
+            _ => None,
+        };
+        (segments[..repo_len].join("/"), revision)
+    };
+    let parts: Vec<&str> = path.split('/').collect();
+    let valid = match parts.as_slice() {
+        ["datasets" | "spaces", owner, name] | [owner, name] => !owner.is_empty() && !name.is_empty(),
+        _ => false,
+    };
+    valid.then(|| (path, revision.unwrap_or_else(|| "main".to_string())))
+}
+
+fn api_repo_path(repo: &str) -> String {
+    match repo.split_once('/') {
+        Some(("datasets", rest)) => format!("datasets/{}", rest),
+        Some(("spaces", rest)) => format!("spaces/{}", rest),
+        _ => format!("models/{}", repo),
+    }
+}
+
+pub(crate) async fn list_files(client: &Client, repo: &str, revision: &str, token: &str) -> Result<HfRepo, String> {
+    let mut next = Some(format!("{}/api/{}/tree/{}?recursive=true", HUB, api_repo_path(repo), revision));
+    let mut files = Vec::new();
+    // Large repositories are paged, with the next page in the `Link` header
+    while let Some(endpoint) = next.take() {
+        let mut request = client.get(&endpoint);
+        if !token.trim().is_empty() { request = request.bearer_auth(token.trim()); }
+        let response = request.send().await.map_err(|e| format!("Request failed: {}", e))?;
+        match response.status().as_u16() {
+            200 => {}
+            401 | 403 => return Err(format!("{} is private or gated; add an access token in Settings", repo)),
+            404 => return Err(format!("No repository {} at revision {}", repo, revision)),
+            _ => return Err(format!("Hugging Face returned error: {}", response.status())),
+        }
+        next = response.headers().get("link").and_then(|v| v.to_str().ok()).and_then(next_page);
+        let body = response.bytes().await.map_err(|e| format!("Request failed: {}", e))?;
+        let entries: Vec<TreeEntry> = serde_json::from_slice(&body).map_err(|e| format!("Unexpected Hugging Face response: {}", e))?;
+        files.extend(entries.into_iter().filter(|e| e.kind == "file").map(|entry| HfFile {
+            url: format!("{}/{}/resolve/{}/{}", HUB, repo, revision, entry.path),
+            size: entry.lfs.as_ref().map_or(entry.size, |lfs| lfs.size),
+            checksum: entry.lfs.map(|lfs| format!("sha256:{}", lfs.oid)),
+            path: entry.path,
+        }));
+    }
+    Ok(HfRepo { repo: repo.to_string(), revision: revision.to_string(), files })
+}
+
+// The `rel="next"` target of a `Link: <url>; rel="next"` header
+fn next_page(link: &str) -> Option<String> {
+    link.split(',').find(|part| part.contains("rel=\"next\""))
+        .and_then(|part| part.split_once('<')?.1.split_once('>').map(|(url, _)| url.to_string()))
+}
@@ -0,0 +1,164 @@
+// Strings the backend shows on its own: notifications, failure reasons and the tray menu. The page
+// translates itself; these go out while it may not be open. `language` is a setting, `auto` following
+// the system locale, and anything without a translation falls back to English.
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Language { English, German, French, Spanish }
+
+impl Language {
+    pub(crate) fn from_setting(setting: &str) -> Language {
+        let code = match setting.trim() {
+            "" | "auto" => sys_locale::get_locale().unwrap_or_default(),
+            code => code.to_string(),
+        };
+        // `de-AT`, `fr_CA.UTF-8` and the like
+        match code.get(..2).map(|prefix| prefix.to_ascii_lowercase()).as_deref() {
+            Some("de") => Language::German,
+            Some("fr") => Language::French,
+            Some("es") => Language::Spanish,
+            _ => Language::English,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Text {
+    DownloadComplete,
+    // `{file}` is the file name
+    FinishedDownloading,
+    DownloadFailed,
+    Open,
+    ShowInFolder,
+    Retry,
+    StartOver,
+    Show,
+    SignInToNetwork,
+    SignInToNetworkBody,
+    DownloadsHeld,
+    // `{count}` is the number of downloads
+    DownloadsHeldBody,
+    BlockedByHttpsOnly,
+    RedirectRefused,
+    BlockedByHostRules,
+    HtmlPage,
+    ChecksumMismatch,
+    SignatureFailed,
+    SizeMismatch,
+    AccessDenied,
+    NotFound,
+    Stalled,
+    NetworkError,
+    ShowApp,
+    FullSpeed,
+    BackgroundSpeed,
+    PauseAll,
+    LowDiskSpace,
+    // `{count}` is the number of downloads
+    LowDiskSpaceBody,
+    QuotaUsedUp,
+    QuotaUsedUpBody,
+    NewVersion,
+    // `{file}` is the file name
+    NewVersionBody,
+    DuplicateDownload,
+    // `{file}` is the new file, `{other}` the one it matches
+    DuplicateDownloadBody,
+    Quit,
+}
+
+pub(crate) fn tr(language: Language, text: Text) -> &'static str {
+    let [english, german, french, spanish] = match text {
+        Text::DownloadComplete => ["Download Complete", "Download abgeschlossen", "Téléchargement terminé", "Descarga completada"],
+        Text::FinishedDownloading => [
+            "{file} has finished downloading",
+            "{file} wurde vollständig heruntergeladen",
+            "{file} a fini de se télécharger",
+            "{file} se ha terminado de descargar",
+        ],
+        Text::DownloadFailed => ["Download Failed", "Download fehlgeschlagen", "Échec du téléchargement", "Error en la descarga"],
+        Text::Open => ["Open", "Öffnen", "Ouvrir", "Abrir"],
+        Text::ShowInFolder => ["Show in Folder", "Im Ordner anzeigen", "Afficher dans le dossier", "Mostrar en la carpeta"],
+        Text::Retry => ["Retry", "Wiederholen", "Réessayer", "Reintentar"],
+        Text::StartOver => ["Start Over", "Neu beginnen", "Recommencer", "Empezar de nuevo"],
+        Text::Show => ["Show", "Anzeigen", "Afficher", "Mostrar"],
+        Text::SignInToNetwork => ["Sign In to the Network", "Im Netzwerk anmelden", "Connectez-vous au réseau", "Inicia sesión en la red"],
+        Text::SignInToNetworkBody => [
+            "This network wants you to sign in before anything can be downloaded. Downloads will continue afterwards.",
+            "Dieses Netzwerk verlangt eine Anmeldung, bevor etwas heruntergeladen werden kann. Die Downloads werden danach fortgesetzt.",
+            "Ce réseau demande de se connecter avant tout téléchargement. Les téléchargements reprendront ensuite.",
+            "Esta red pide iniciar sesión antes de poder descargar nada. Las descargas continuarán después.",
+        ],
+        Text::DownloadsHeld => ["Downloads Held", "Downloads zurückgehalten", "Téléchargements suspendus", "Descargas en espera"],
+        Text::DownloadsHeldBody => [
+            "{count} large download(s) will continue once you're off the metered connection",
+            "{count} große(r) Download(s) geht weiter, sobald die getaktete Verbindung getrennt ist",
+            "{count} téléchargement(s) volumineux reprendront hors de la connexion limitée",
+            "{count} descarga(s) grande(s) continuarán al salir de la conexión medida",
+        ],
+        Text::BlockedByHttpsOnly => ["Blocked by HTTPS-only mode", "Vom Nur-HTTPS-Modus blockiert", "Bloqué par le mode HTTPS uniquement", "Bloqueado por el modo solo HTTPS"],
+        Text::RedirectRefused => [
+            "Redirect refused by host rules",
+            "Weiterleitung durch Host-Regeln abgelehnt",
+            "Redirection refusée par les règles d'hôte",
+            "Redirección rechazada por las reglas de host",
+        ],
+        Text::BlockedByHostRules => ["Blocked by host rules", "Durch Host-Regeln blockiert", "Bloqué par les règles d'hôte", "Bloqueado por las reglas de host"],
+        Text::HtmlPage => [
+            "Server sent a web page instead of the file",
+            "Der Server hat eine Webseite statt der Datei geschickt",
+            "Le serveur a envoyé une page web au lieu du fichier",
+            "El servidor envió una página web en lugar del archivo",
+        ],
+        Text::ChecksumMismatch => ["Checksum mismatch", "Prüfsumme stimmt nicht überein", "Somme de contrôle incorrecte", "La suma de comprobación no coincide"],
+        Text::SignatureFailed => ["Signature check failed", "Signaturprüfung fehlgeschlagen", "Échec de la vérification de la signature", "Falló la comprobación de la firma"],
+        Text::SizeMismatch => ["File size mismatch", "Dateigröße stimmt nicht überein", "Taille de fichier incorrecte", "El tamaño del archivo no coincide"],
+        Text::AccessDenied => [
+            "Access denied (the link may have expired)",
+            "Zugriff verweigert (der Link ist womöglich abgelaufen)",
+            "Accès refusé (le lien a peut-être expiré)",
+            "Acceso denegado (puede que el enlace haya caducado)",
+        ],
+        Text::NotFound => ["File not found on the server", "Datei auf dem Server nicht gefunden", "Fichier introuvable sur le serveur", "Archivo no encontrado en el servidor"],
+        Text::Stalled => ["Transfer kept stalling", "Die Übertragung blieb immer wieder stehen", "Le transfert se bloquait sans cesse", "La transferencia se detenía una y otra vez"],
+        Text::NetworkError => ["Network error", "Netzwerkfehler", "Erreur réseau", "Error de red"],
+        Text::ShowApp => ["Show VeloDown", "VeloDown anzeigen", "Afficher VeloDown", "Mostrar VeloDown"],
+        Text::FullSpeed => ["Full Speed", "Volle Geschwindigkeit", "Pleine vitesse", "Velocidad máxima"],
+        Text::BackgroundSpeed => ["Background Speed", "Hintergrundgeschwindigkeit", "Vitesse réduite", "Velocidad reducida"],
+        Text::PauseAll => ["Pause All", "Alle pausieren", "Tout mettre en pause", "Pausar todo"],
+        Text::LowDiskSpace => ["Low Disk Space", "Wenig Speicherplatz", "Espace disque insuffisant", "Poco espacio en disco"],
+        Text::LowDiskSpaceBody => [
+            "{count} download(s) paused until there's more room on the drive",
+            "{count} Download(s) pausiert, bis wieder mehr Platz auf dem Laufwerk ist",
+            "{count} téléchargement(s) en pause jusqu'à ce qu'il y ait plus de place sur le disque",
+            "{count} descarga(s) en pausa hasta que haya más espacio en la unidad",
+        ],
+        Text::QuotaUsedUp => ["Data Quota Used Up", "Datenkontingent aufgebraucht", "Quota de données épuisé", "Cuota de datos agotada"],
+        Text::QuotaUsedUpBody => [
+            "Downloads are paused until the quota resets",
+            "Die Downloads sind pausiert, bis das Kontingent zurückgesetzt wird",
+            "Les téléchargements sont en pause jusqu'au renouvellement du quota",
+            "Las descargas están en pausa hasta que se renueve la cuota",
+        ],
+        Text::NewVersion => ["New Version Available", "Neue Version verfügbar", "Nouvelle version disponible", "Nueva versión disponible"],
+        Text::NewVersionBody => [
+            "The server has a newer version of {file}",
+            "Auf dem Server gibt es eine neuere Version von {file}",
+            "Le serveur a une version plus récente de {file}",
+            "El servidor tiene una versión más reciente de {file}",
+        ],
+        Text::DuplicateDownload => ["Duplicate Download", "Doppelter Download", "Téléchargement en double", "Descarga duplicada"],
+        Text::DuplicateDownloadBody => [
+            "{file} has the same content as {other}",
+            "{file} hat denselben Inhalt wie {other}",
+            "{file} a le même contenu que {other}",
+            "{file} tiene el mismo contenido que {other}",
+        ],
+        Text::Quit => ["Quit", "Beenden", "Quitter", "Salir"],
+    };
+    match language {
+        Language::English => english,
+        Language::German => german,
+        Language::French => french,
+        Language::Spanish => spanish,
+    }
+}
@@ -0,0 +1,188 @@
+// Downloads that only run while nobody is using the computer. A task with `when_idle` set waits in the
+// queue until there's been no keyboard or mouse input for `idle_minutes`, then starts; when the user
+// comes back it's paused to wait again, or with `idle_return_throttle` slowed to `idle_throttle_kbps`.
+// Idle time comes from GNOME or the freedesktop screensaver on Linux, the input timestamp on Windows
+// and the HID system on macOS. Where none answers, the computer counts as idle, so nothing waits forever.
+
+use std::collections::HashSet;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use tauri::{AppHandle, Emitter, Manager, State};
+use tokio::time::Duration;
+
+use crate::metered::SpeedLimit;
+use crate::{AppState, DownloadStatus, DownloadTask};
+
+const WAITING_MESSAGE: &str = "Waiting for the computer to be idle";
+const CHECK_TICK: Duration = Duration::from_secs(15);
+
+#[derive(Default)]
+pub(crate) struct Idle {
+    idle: AtomicBool,
+    // Applied to idle-only downloads while the user is back, with `idle_return_throttle`
+    pub(crate) limit: SpeedLimit,
+}
+
+// Whether an idle-only task about to start has to wait; the caller parks it with `park`
+pub(crate) fn should_wait(state: &AppState, task: &DownloadTask) -> bool {
+    task.when_idle && !state.idle.idle.load(Ordering::Relaxed)
+}
+
+pub(crate) fn park(task: &mut DownloadTask) {
+    task.status = DownloadStatus::Queued;
+    task.speed = 0;
+    task.error_message = Some(WAITING_MESSAGE.to_string());
+}
+
+pub(crate) fn is_waiting(task: &DownloadTask) -> bool {
+    task.status == DownloadStatus::Queued && task.error_message.as_deref() == Some(WAITING_MESSAGE)
+}
+
+pub(crate) async fn run_idle_watch(app_handle: AppHandle) {
+    let detector = Detector::new().await;
+    let mut warned = false;
+    loop {
+        let state: State<AppState> = app_handle.state();
+        let settings = state.persistent.lock().await.settings.clone();
+        let idle_for = detector.idle_time().await;
+        if idle_for.is_none() && !warned {
+            log::info!("Can't tell how long the computer has been idle; idle-only downloads run at any time");
+            warned = true;
+        }
+        let idle = idle_for.is_none_or(|t| t >= Duration::from_secs(settings.idle_minutes as u64 * 60));
+        if state.idle.idle.swap(idle, Ordering::Relaxed) != idle { log::debug!("Computer idle: {}", idle); }
+        let throttle = !idle && settings.idle_return_throttle;
+        state.idle.limit.set(if throttle { settings.idle_throttle_kbps * 1024 } else { 0 });
+        if idle {
+            start_waiting(&app_handle).await;
+        } else if !settings.idle_return_throttle {
+            park_running(&app_handle).await;
+        }
+        tokio::time::sleep(CHECK_TICK).await;
+    }
+}
+
+async fn start_waiting(app_handle: &AppHandle) {
+    let state: State<AppState> = app_handle.state();
+    let ready: Vec<String> = {
+        let mut state_guard = state.persistent.lock().await;
+        state_guard.downloads.iter_mut()
+            .filter(|t| is_waiting(t))
+            .map(|t| {
+                t.error_message = None;
+                t.id.clone()
+            })
+            .collect()
+    };
+    for id in ready {
+        if let Err(e) = crate::start_download_task(id, app_handle.clone()).await {
+            log::warn!("Could not start an idle-only download: {}", e);
+        }
+    }
+}
+
+// The user is back: idle-only downloads stop and wait for the next idle stretch
+async fn park_running(app_handle: &AppHandle) {
+    let state: State<AppState> = app_handle.state();
+    let running: HashSet<String> = state.download_handles.lock().await.keys().cloned().collect();
+    let to_park: Vec<String> = state.persistent.lock().await.downloads.iter()
+        .filter(|t| t.when_idle && running.contains(&t.id))
+        .map(|t| t.id.clone())
+        .collect();
+    if to_park.is_empty() { return; }
+    futures::future::join_all(to_park.iter().map(|id| crate::abort_download(&state, id))).await;
+    {
+        let mut state_guard = state.persistent.lock().await;
+        for task in state_guard.downloads.iter_mut().filter(|t| to_park.contains(&t.id)) {
+            park(task);
+            app_handle.emit("task_updated", &*task).unwrap();
+        }
+    }
+    crate::request_save(&state);
+}
+
+#[cfg(target_os = "linux")]
+struct Detector { connection: Option<zbus::Connection> }
+
+#[cfg(target_os = "linux")]
+#[zbus::proxy(
+    interface = "org.gnome.Mutter.IdleMonitor",
+    default_service = "org.gnome.Mutter.IdleMonitor",
+    default_path = "/org/gnome/Mutter/IdleMonitor/Core"
+)]
+trait IdleMonitor {
+    // Milliseconds
+    fn get_idletime(&self) -> zbus::Result<u64>;
+}
+
+#[cfg(target_os = "linux")]
+#[zbus::proxy(
+    interface = "org.freedesktop.ScreenSaver",
+    default_service = "org.freedesktop.ScreenSaver",
+    default_path = "/org/freedesktop/ScreenSaver"
+)]
+trait ScreenSaver {
+    // Milliseconds
+    fn get_session_idle_time(&self) -> zbus::Result<u32>;
+}
+
+#[cfg(target_os = "linux")]
+impl Detector {
+    async fn new() -> Self {
+        Detector { connection: zbus::Connection::session().await.ok() }
+    }
+
+    async fn idle_time(&self) -> Option<Duration> {
+        let connection = self.connection.as_ref()?;
+        if let Ok(ms) = async { IdleMonitorProxy::new(connection).await?.get_idletime().await }.await {
+            return Some(Duration::from_millis(ms));
+        }
+        let ms = async { ScreenSaverProxy::new(connection).await?.get_session_idle_time().await }.await.ok()?;
+        Some(Duration::from_millis(ms as u64))
+    }
+}
+
+#[cfg(target_os = "windows")]
+struct Detector;
+
+#[cfg(target_os = "windows")]
+impl Detector {
+    async fn new() -> Self { Detector }
+
+    async fn idle_time(&self) -> Option<Duration> {
+        use windows::Win32::System::SystemInformation::GetTickCount;
+        use windows::Win32::UI::Input::KeyboardAndMouse::{GetLastInputInfo, LASTINPUTINFO};
+        let mut info = LASTINPUTINFO { cbSize: std::mem::size_of::<LASTINPUTINFO>() as u32, dwTime: 0 };
+        if !unsafe { GetLastInputInfo(&mut info) }.as_bool() { return None; }
+        // Both wrap around every 49.7 days
+        let ms = unsafe { GetTickCount() }.wrapping_sub(info.dwTime);
+        Some(Duration::from_millis(ms as u64))
+    }
+}
+
+#[cfg(target_os = "macos")]
+struct Detector;
+
+#[cfg(target_os = "macos")]
+impl Detector {
+    async fn new() -> Self { Detector }
+
+    // `HIDIdleTime` is in nanoseconds
+    async fn idle_time(&self) -> Option<Duration> {
+        let output = tokio::process::Command::new("ioreg").args(["-c", "IOHIDSystem", "-d", "4"]).output().await.ok()?;
+        let text = String::from_utf8_lossy(&output.stdout);
+        let line = text.lines().find(|l| l.contains("\"HIDIdleTime\""))?;
+        let ns: u64 = line.rsplit('=').next()?.trim().parse().ok()?;
+        Some(Duration::from_nanos(ns))
+    }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "windows", target_os = "macos")))]
+struct Detector;
+
+#[cfg(not(any(target_os = "linux", target_os = "windows", target_os = "macos")))]
+impl Detector {
+    async fn new() -> Self { Detector }
+
+    async fn idle_time(&self) -> Option<Duration> { None }
+}
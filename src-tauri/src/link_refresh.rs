@@ -0,0 +1,89 @@
+// Fresh links for downloads whose direct link expired. A task keeps where its link came from: the
+// page, share link or plugin link it was resolved from (`source_url`), or else the first hop of its
+// redirects. When the link answers 403 or 410, that source is resolved again the way it was when the
+// download was added (resolver plugins, share-link resolvers, redirects and meta-refresh pages), the
+// host's site template supplies its headers afresh, and the download resumes from the bytes it has if
+// the old ETag vouches for the new link. A source that can't be resolved fails the download for good.
+// Presigned links with a refresh hook go through the hook instead; see `prepare_task_url`.
+
+use tauri::{AppHandle, Emitter, Manager, State};
+use url::Url;
+
+use crate::{AppState, DownloadTask};
+
+pub(crate) fn is_expired(error: &str) -> bool {
+    error.contains("403") || error.contains("410")
+}
+
+fn source(task: &DownloadTask) -> Option<&String> {
+    task.source_url.as_ref().or(task.redirect_chain.first()).filter(|source| **source != task.url)
+}
+
+// Whether a fresh link can be had for the task; a custom request can't be resolved again with a GET
+pub(crate) async fn can_refresh(app_handle: &AppHandle, id: &str) -> bool {
+    let state: State<AppState> = app_handle.state();
+    let state_guard = state.persistent.lock().await;
+    let Some(task) = state_guard.downloads.iter().find(|t| t.id == id) else { return false };
+    task.request.is_none()
+        && (source(task).is_some() || crate::site_templates::find(&state_guard.settings.site_templates, &task.url).is_some())
+}
+
+// Resolves the task's source again and saves the new link on it. Returns whether the bytes on disk
+// can be kept; when not, the task's ETag is dropped and the caller starts the file over.
+pub(crate) async fn refresh(app_handle: &AppHandle, id: &str) -> Result<bool, String> {
+    let state: State<AppState> = app_handle.state();
+    let (source, url, total_size) = {
+        let state_guard = state.persistent.lock().await;
+        let task = state_guard.downloads.iter().find(|t| t.id == id).ok_or("Download not found")?;
+        (source(task).cloned(), task.url.clone(), task.total_size)
+    };
+    let info = match &source {
+        Some(source) => {
+            let client = crate::http_client_for(&state, source).await;
+            Some(crate::resolve_download_info(app_handle, &client, source).await?)
+        }
+        None => None,
+    };
+    // The bytes on disk only fit the same file
+    if let Some(size) = info.as_ref().and_then(|i| i.total_size).filter(|size| total_size > 0 && *size != total_size) {
+        return Err(format!("The fresh link serves a different file ({} bytes, not {})", size, total_size));
+    }
+    let (fresh, resume) = {
+        let mut state_guard = state.persistent.lock().await;
+        let fresh_url = info.as_ref().map_or(&url, |i| &i.final_url);
+        let template = crate::site_templates::find(&state_guard.settings.site_templates, fresh_url).cloned();
+        let task = state_guard.downloads.iter_mut().find(|t| t.id == id).ok_or("Download not found")?;
+        let before = (task.url.clone(), task.headers.clone());
+        // A file of the same size can still be another file. Only the old link's strong ETag can vouch for
+        // the new link, and the resume sends it as If-Range, so a server whose file differs sends it whole.
+        let resume = match &info {
+            Some(info) if info.final_url != task.url => task.etag.as_deref()
+                .filter(|old| !old.starts_with("W/"))
+                .is_some_and(|old| info.etag.as_deref().is_none_or(|new| new == old)),
+            _ => true,
+        };
+        if !resume { task.etag = None; }
+        if let Some(info) = info {
+            task.url = info.final_url;
+            task.redirect_chain = info.redirect_chain;
+            task.headers.extend(info.headers);
+        }
+        // Newer credentials from the template (a re-exported cookies.txt, say) replace the task's
+        if let (Some(template), Ok(parsed)) = (template, Url::parse(&task.url)) {
+            for (name, value) in template.headers(&parsed) {
+                task.headers.retain(|existing, _| !existing.eq_ignore_ascii_case(&name));
+                task.headers.insert(name, value);
+            }
+        }
+        if (&task.url, &task.headers) == (&before.0, &before.1) { return Err("No fresher link was found".to_string()); }
+        app_handle.emit("task_updated", &*task).unwrap();
+        (task.url.clone(), resume)
+    };
+    crate::request_save(&state);
+    let from = source.unwrap_or_else(|| "the site template".to_string());
+    crate::tasklog::write(app_handle, id, &format!("Refreshed the expired link from {}", crate::redact::url(&from))).await;
+    if !resume {
+        crate::tasklog::write(app_handle, id, &format!("Nothing shows {} serves the same file; starting over", crate::redact::url(&fresh))).await;
+    }
+    Ok(resume)
+}
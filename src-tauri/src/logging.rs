@@ -0,0 +1,102 @@
+// Application logging through `tracing`: readable lines on stderr, and JSON lines in daily files under
+// the app's log folder, the last week of them kept. The existing `log` macros are bridged in. The level
+// is the `log_level` setting and can be changed while running; `RUST_LOG`, when set, overrides it at
+// startup. `collect_diagnostics` zips the logs with a redacted copy of the state for bug reports.
+
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use tracing_appender::non_blocking::WorkerGuard;
+use tracing_appender::rolling::{RollingFileAppender, Rotation};
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{fmt, reload, EnvFilter, Registry};
+
+const LEVELS: [&str; 5] = ["error", "warn", "info", "debug", "trace"];
+const KEPT_LOG_FILES: usize = 7;
+
+pub(crate) struct Logging {
+    filter: reload::Handle<EnvFilter, Registry>,
+    dir: PathBuf,
+    // Flushes the file writer's queue when dropped
+    _guard: WorkerGuard,
+}
+
+// The app's own messages at `level`; dependencies only when they warn
+fn directives(level: &str) -> Result<EnvFilter, String> {
+    let level = level.trim().to_ascii_lowercase();
+    if !LEVELS.contains(&level.as_str()) { return Err(format!("Unknown log level: {}", level)); }
+    EnvFilter::try_new(format!("warn,{}={}", env!("CARGO_CRATE_NAME"), level)).map_err(|e| e.to_string())
+}
+
+// Called once from setup, as soon as the settings are loaded; messages logged before then are lost
+pub(crate) fn init(dir: PathBuf, level: &str) -> Result<Logging, String> {
+    let filter = EnvFilter::try_from_default_env().or_else(|_| directives(level)).or_else(|_| directives("info"))?;
+    let (filter, handle) = reload::Layer::new(filter);
+    let appender = RollingFileAppender::builder()
+        .rotation(Rotation::DAILY)
+        .filename_prefix(env!("CARGO_PKG_NAME"))
+        .filename_suffix("log")
+        .max_log_files(KEPT_LOG_FILES)
+        .build(&dir)
+        .map_err(|e| format!("Could not open the log folder: {}", e))?;
+    let (writer, guard) = tracing_appender::non_blocking(appender);
+    tracing_subscriber::registry()
+        .with(filter)
+        .with(fmt::layer().with_writer(std::io::stderr))
+        .with(fmt::layer().json().with_writer(writer))
+        .try_init()
+        .map_err(|e| e.to_string())?;
+    // The `log` bridge takes the startup level as a ceiling; leave it to the filter so raising the level works
+    log::set_max_level(log::LevelFilter::Trace);
+    Ok(Logging { filter: handle, dir, _guard: guard })
+}
+
+impl Logging {
+    pub(crate) fn set_level(&self, level: &str) -> Result<(), String> {
+        self.filter.reload(directives(level)?).map_err(|e| e.to_string())
+    }
+
+    pub(crate) fn dir(&self) -> &Path {
+        &self.dir
+    }
+}
+
+// Validated before the setting is saved, so a typo can't leave the app logging nothing
+pub(crate) fn check_level(level: &str) -> Result<(), String> {
+    directives(level).map(|_| ())
+}
+
+// Writes a zip of `state` (already redacted) and every file in the log folders to `path`
+pub(crate) async fn collect_diagnostics(logging: Option<&Logging>, task_log_dir: Option<PathBuf>, state: String, path: PathBuf) -> Result<(), String> {
+    let log_dir = logging.map(|logging| logging.dir.clone());
+    tauri::async_runtime::spawn_blocking(move || -> Result<(), String> {
+        let file = std::fs::File::create(&path).map_err(|e| format!("Could not create {}: {}", path.display(), e))?;
+        let mut zip = zip::ZipWriter::new(file);
+        let options = zip::write::SimpleFileOptions::default().compression_method(zip::CompressionMethod::Deflated);
+        let info = format!("{} {}\n{} {}\n", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"), std::env::consts::OS, std::env::consts::ARCH);
+        zip.start_file("version.txt", options).map_err(|e| e.to_string())?;
+        zip.write_all(info.as_bytes()).map_err(|e| e.to_string())?;
+        zip.start_file("state.json", options).map_err(|e| e.to_string())?;
+        zip.write_all(state.as_bytes()).map_err(|e| e.to_string())?;
+        for (dir, folder) in [(log_dir, "logs"), (task_log_dir, "logs/tasks")] {
+            let Some(dir) = dir else { continue };
+            add_files(&mut zip, &dir, folder, options)?;
+        }
+        zip.finish().map_err(|e| e.to_string())?;
+        Ok(())
+    }).await.map_err(|e| e.to_string())?
+}
+
+fn add_files(zip: &mut zip::ZipWriter<std::fs::File>, dir: &Path, folder: &str, options: zip::write::SimpleFileOptions) -> Result<(), String> {
+    let Ok(entries) = std::fs::read_dir(dir) else { return Ok(()) };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if !path.is_file() { continue; }
+        // A file being rotated away meanwhile is just left out
+        let Ok(content) = std::fs::read(&path) else { continue };
+        zip.start_file(format!("{}/{}", folder, entry.file_name().to_string_lossy()), options).map_err(|e| e.to_string())?;
+        zip.write_all(&content).map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
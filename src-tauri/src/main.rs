@@ -1,32 +1,142 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
+mod accounts;
+mod api;
+mod auth_prompt;
+mod autoindex;
+mod autostart;
+mod backup;
+mod captive;
+mod clicknload;
+mod client_certs;
+mod cookies;
+mod curl_import;
+mod dependencies;
+mod disk_limits;
+mod dlc;
+mod duplicates;
+mod extract;
+mod ftp;
+mod gallery;
+mod github;
+mod guards;
+mod history;
+mod host_rules;
+mod huggingface;
+mod i18n;
+mod idle;
+mod link_refresh;
+mod logging;
+mod metered;
+mod mirror;
+mod netwatch;
+mod network;
+mod notifications;
+mod partial_cache;
+mod plugins;
+mod power;
+mod queue;
+mod quota;
+mod quick_actions;
+mod recurring;
+mod redact;
+mod remote;
+mod remote_watch;
+mod removed;
+mod retention;
+mod s3;
+mod settings_transfer;
+mod sftp;
+mod share_links;
+mod shutdown;
+mod signature;
+mod site_templates;
+mod speed_history;
+mod speed_mode;
+mod subscriptions;
+mod subtitles;
+mod tagging;
+mod taskbar;
+mod tasklog;
+mod tls_trust;
+mod tray;
+mod updater;
+mod vault;
+mod virustotal;
+
 use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::collections::BTreeMap;
 use std::fs;
 use std::path::PathBuf;
 use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU8, AtomicU64, Ordering};
 use tokio::sync::Mutex;
 use tauri::{AppHandle, Manager, State};
 use tokio::time::{Duration, Instant}; 
 use tauri::Emitter;
 use std::process::Command;
-use reqwest::{cookie::Jar};
+use reqwest::cookie::CookieStore;
 use futures::StreamExt;
 use url::Url;
 use chrono::{DateTime, Local};
-use tauri_plugin_notification::NotificationExt;
 use tauri_plugin_dialog::{DialogExt, FilePath};
 use tokio::sync::oneshot;
-use tokio::io::AsyncWriteExt;
+use tokio::io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt};
+use sha2::digest::DynDigest;
+use hmac::{Hmac, Mac};
+use tokio_util::sync::CancellationToken;
 use reqwest::{Client};
 use tokio::time::timeout;
 
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(250);
+const CANCEL_GRACE: Duration = Duration::from_secs(5);
+const SAVE_DEBOUNCE: Duration = Duration::from_secs(1);
+// While downloads run, progress is persisted at most this often so a crash loses little
+const PROGRESS_SAVE_INTERVAL: Duration = Duration::from_secs(10);
+// How often a writer syncs the file; progress past the last sync isn't persisted
+const SYNC_INTERVAL: Duration = Duration::from_secs(5);
+const MAX_ERROR_PAGE_BYTES: usize = 64 * 1024;
+// HTTP redirects plus meta-refresh interstitials followed before giving up; same as browsers' default
+const MAX_REDIRECTS: usize = 10;
+// Hosts whose whole job is redirecting elsewhere, so leaving their domain is expected
+const URL_SHORTENERS: &[&str] = &[
+    "bit.ly", "t.co", "tinyurl.com", "goo.gl", "ow.ly", "buff.ly", "is.gd", "rb.gy", "cutt.ly", "shorturl.at",
+    "tiny.cc", "lnkd.in", "t.ly", "rebrand.ly",
+];
+const MAX_SCAN_PAGE_BYTES: usize = 8 * 1024 * 1024;
+// Parallel HEAD probes when a page scan has to look up sizes
+const SCAN_PROBE_CONCURRENCY: usize = 8;
+const HTML_PAGE_ERROR: &str = "Server returned an HTML page instead of the file";
+const STALLED_ERROR: &str = "Transfer stalled";
+// Refusals by settings policy; retrying can't change the outcome
+const HTTPS_ONLY_ERROR: &str = "Refused by HTTPS-only mode";
+const REDIRECT_REFUSED_ERROR: &str = "Redirect refused";
+const HOST_REFUSED_ERROR: &str = "Refused by the host rules";
+// Less than this many bytes over a whole stall window counts as no progress
+const STALL_MIN_BYTES: u64 = 1024;
+// Matches the limit the settings page allows for the global default
+const MAX_CONNECTIONS: u8 = 16;
+// Upper bound on `get_segment_map` resolution
+const MAX_MAP_BLOCKS: u32 = 2000;
+
 const USER_AGENT: &str = "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/108.0.0.0 Safari/537.36";
 
 // --- STRUCTS & ENUMS ---
 
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 #[serde(rename_all = "lowercase")]
-enum DownloadStatus { Queued, Downloading, Paused, Completed, Failed, Verifying, Retrying } // NEW: Added Retrying status
+enum DownloadStatus {
+    Queued, Downloading, Paused, Completed, Failed, Verifying, Retrying, // NEW: Added Retrying status
+    // Parked until the user signs in to the network; see `captive`
+    #[serde(rename = "captivePortal")]
+    CaptivePortal,
+    // Held back by the size and free-space guards; see `guards`
+    #[serde(rename = "awaitingConfirmation")]
+    AwaitingConfirmation,
+    #[serde(rename = "lowDiskSpace")]
+    LowDiskSpace,
+}
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 #[serde(rename_all = "camelCase")]
@@ -37,17 +147,286 @@ struct DownloadTask {
     created_at: DateTime<Local>, completed_at: Option<DateTime<Local>>,
     file_type: String, connections: u8,
     resume_attempts: u8,
+    // Body of an unexpected HTML page (quota/error pages), kept for diagnostics
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    error_page: Option<String>,
+    // Expected digest as `sha256:<hex>`, `sha512:<hex>` or bare hex
+    #[serde(default)]
+    checksum: Option<String>,
+    #[serde(default)]
+    etag: Option<String>,
+    // Byte ranges of a segmented transfer, so a resume continues every part where it left off
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    segments: Vec<Segment>,
+    // Extra request headers (cookies, auth, referer) sent with every request for this file
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    headers: BTreeMap<String, String>,
+    // Fetched through this proxy instead of `proxy_url`, as a `curl -x` command asked
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    proxy: Option<String>,
+    // Made with another method or a body instead of a plain GET
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    request: Option<CustomRequest>,
+    // The page or link `url` was resolved from, resolved again when `url` expires
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    source_url: Option<String>,
+    // How the link the user added led to `url`, from shortener to file
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    redirect_chain: Vec<String>,
+    // The mirror job that queued this file
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    parent_id: Option<String>,
+    // Unpacking of a finished archive
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    extraction: Option<extract::Extraction>,
+    // Detached signature that must verify before the download counts as completed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    signature: Option<signature::SignatureCheck>,
+    // VirusTotal's verdict on the finished file
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    virus_scan: Option<virustotal::VirusScan>,
+    // The last `verify_download` of the finished file
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    verification: Option<Verification>,
+    // SHA-256 of the finished file, and another download with the same content; see `duplicates`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    content_hash: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    duplicate_of: Option<String>,
+    // Downloads over plain HTTP even in HTTPS-only mode
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    allow_http: bool,
+    // Neither held nor speed-capped on a metered connection
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    allow_metered: bool,
+    // Fetched again every so often; see `recurring`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    repeat: Option<recurring::Repeat>,
+    // The source is checked every so often for a newer version; see `remote_watch`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    watch: Option<remote_watch::Watch>,
+    // Tasks that must complete before this one starts; see `dependencies`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    depends_on: Vec<String>,
+    // Where the unfinished file is, when not in `save_path`: a folder of its own under `temp_folder`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    working_dir: Option<String>,
+    // Started by the user despite being over `confirm_over_mb`
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    size_confirmed: bool,
+    // Neither capped nor held by the data quota
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    ignore_quota: bool,
+    // Only runs while the computer is idle; see `idle`
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    when_idle: bool,
+    // The sign-in the server or proxy asked for, while the download waits for it; see `auth_prompt`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    auth_challenge: Option<auth_prompt::AuthChallenge>,
+}
+
+// The request for a download that isn't a plain GET, such as an export endpoint that wants a POST with
+// a form. Sending it again could start another export, so these downloads are never split or resumed
+// with a range: every attempt sends the request once and takes the whole response.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+struct CustomRequest {
+    method: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    body: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    content_type: Option<String>,
+}
+
+impl CustomRequest {
+    fn method(&self) -> Result<reqwest::Method, String> {
+        reqwest::Method::from_bytes(self.method.trim().to_ascii_uppercase().as_bytes()).map_err(|_| format!("Invalid method: {}", self.method))
+    }
+
+    fn build(&self, client: &Client, url: &str) -> Result<reqwest::RequestBuilder, String> {
+        let mut request = client.request(self.method()?, url);
+        if let Some(content_type) = &self.content_type { request = request.header(reqwest::header::CONTENT_TYPE, content_type); }
+        if let Some(body) = &self.body { request = request.body(body.clone()); }
+        Ok(request)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+struct Verification {
+    checked_at: DateTime<Local>,
+    // What doesn't match; empty when the file is intact
+    problems: Vec<String>,
+    // Why the server couldn't be asked, when it was meant to be
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    remote_error: Option<String>,
 }
 
+// `[start, end)` of the file owned by one connection, with `pos` the next byte to write
 #[derive(Debug, Serialize, Deserialize, Clone)]
 #[serde(rename_all = "camelCase")]
+struct Segment {
+    start: u64, end: u64, pos: u64,
+    #[serde(skip)]
+    active: bool,
+    // `pos` as of the worker's last sync; only that much is persisted
+    #[serde(skip)]
+    synced: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase", default)]
 struct AppSettings {
     download_folder: String, max_concurrent_downloads: u32, max_connections_per_download: u8,
     auto_start: bool, show_notifications: bool, min_split_size: u64,
+    // Launch minimized at login; see `autostart`
+    autostart: bool,
+    // Closing the window hides it to the tray and downloads keep going; see `tray`
+    close_to_tray: bool,
+    // For notifications and the tray menu; `auto` follows the system. See `i18n`
+    language: String,
+    // Also notify when a download fails for good, not only when one completes
+    notify_on_failure: bool,
     auto_resume_downloads: bool,
     max_resume_attempts: u8,
     resume_delay_seconds: u64,
     min_fail_duration_seconds: u64,
+    progress_event_interval_ms: u64,
+    write_buffer_size: u64,
+    preallocate_files: bool,
+    // Downloads are written here and moved to their folder once verified; empty writes in place
+    temp_folder: String,
+    // Room for the partial files of cancelled downloads, reused when they're added again (0 is off);
+    // see `partial_cache`
+    partial_cache_mb: u64,
+    // Downloads known to be larger wait for confirmation, and downloads pause while their drive has
+    // less free (0 turns each off); see `guards`
+    confirm_over_mb: u64,
+    min_free_space_mb: u64,
+    // Transfer allowance per day or billing month (0 is none), capped past `quota_throttle_percent`
+    // and paused once used up; see `quota`
+    data_quota_gb: u64,
+    data_quota_period: quota::QuotaPeriod,
+    quota_reset_day: u8,
+    quota_throttle_percent: u8,
+    quota_throttle_kbps: u64,
+    // Idle-only downloads start after this long without input, and when the user returns are paused,
+    // or slowed to `idle_throttle_kbps` with `idle_return_throttle`; see `idle`
+    idle_minutes: u32,
+    idle_return_throttle: bool,
+    idle_throttle_kbps: u64,
+    stall_timeout_seconds: u64,
+    user_agent: String,
+    // Keep cookies hosts set across restarts, in cookies.json next to the state file; see `cookies`
+    persist_cookies: bool,
+    proxy_url: Option<String>,
+    // Remote-control HTTP API; off by default and localhost-only unless `api_allow_lan` is set
+    api_enabled: bool,
+    api_port: u16,
+    api_token: String,
+    api_allow_lan: bool,
+    // Adds a Prometheus `/metrics` route to the remote API
+    metrics_enabled: bool,
+    webhooks: Vec<Webhook>,
+    // Local Click'n'Load listener on port 9666 that web pages send link lists to
+    click_n_load_enabled: bool,
+    // Optional personal access token for GitHub release lookups; raises the API rate limit
+    github_token: String,
+    // Sent to the Hugging Face API and with downloads from it, for private and gated repositories
+    huggingface_token: String,
+    // `s3://` sources; the secret key lives in the keyring, and AWS_* env vars apply when these are empty
+    s3_access_key_id: String,
+    s3_region: String,
+    s3_endpoint: String,
+    s3_path_style: bool,
+    // Shell command that prints a fresh URL for an expired presigned one, given as $VELODOWN_URL
+    url_refresh_command: String,
+    // Fetch `.srt` files from OpenSubtitles next to finished videos
+    subtitles_enabled: bool,
+    opensubtitles_api_key: String,
+    // Comma-separated language codes, e.g. `en,de`
+    subtitle_languages: String,
+    // Tag finished audio files; the pattern reads fields like `{artist} - {title}` from the file name
+    audio_tagging_enabled: bool,
+    audio_filename_pattern: String,
+    // Move tagged audio into `Artist/Album` folders under its save folder
+    audio_organize: bool,
+    // Unpack finished archives into a folder named after each one, under `extract_folder` when set
+    auto_extract: bool,
+    extract_folder: String,
+    extract_delete_archive: bool,
+    // Tried in order on encrypted archives, one per line
+    archive_passwords: String,
+    // External program for RAR archives; RAR isn't extracted when empty
+    unrar_path: String,
+    // GnuPG program used to check signatures; `gpg` on the PATH when empty
+    gpg_path: String,
+    // Look up finished files' SHA-256 on VirusTotal with the user's API key
+    virustotal_enabled: bool,
+    virustotal_api_key: String,
+    // Hash finished files and flag ones with the same content as another download
+    detect_duplicates: bool,
+    // Host patterns, one per line, enforced on added links and every redirect; see `host_rules`
+    blocked_hosts: String,
+    allowed_hosts: String,
+    // Refuse plain-HTTP links unless a task opts in, and any redirect from HTTPS down to HTTP
+    https_only: bool,
+    // IPv4/IPv6 preference, and the local address or interface name outgoing connections bind to
+    ip_family: network::IpFamily,
+    bind_interface: String,
+    // On a metered connection: hold downloads with more than this left (0 never holds) and cap the
+    // rest (0 is no cap); see `metered`
+    metered_detection: bool,
+    metered_pause_over_mb: u64,
+    metered_speed_limit_kbps: u64,
+    // The cap while the speed switch is on "background"; see `speed_mode`
+    background_speed_limit_kbps: u64,
+    // Write caps per disk, apart from the network limits; see `disk_limits`
+    disk_write_limits: Vec<disk_limits::DiskWriteLimit>,
+    // Pause running downloads when the network drops or changes, and resume once it's back
+    pause_on_network_change: bool,
+    // Notifications between these `HH:MM` times are only logged; see `notifications`
+    quiet_hours_enabled: bool,
+    quiet_hours_start: String,
+    quiet_hours_end: String,
+    // Probe for a Wi-Fi login page when downloads fail, and wait for sign-in instead of retrying
+    detect_captive_portal: bool,
+    // Mask URL tokens, cookies and auth headers in state.json, keeping them in the keyring; see `vault`
+    redact_saved_secrets: bool,
+    // `error` to `trace`; applied immediately, see `logging`
+    log_level: String,
+    // One download at a time, either overall or within the listed file types; see `queue`
+    sequential_downloads: bool,
+    sequential_categories: Vec<String>,
+    // How long removed downloads can be restored; 0 removes them for good right away
+    removed_retention_hours: u32,
+    // Completed downloads older than this many days, or beyond the newest so many, are taken off the
+    // list (0 turns each off); see `retention`
+    cleanup_after_days: u32,
+    cleanup_keep_last: u32,
+    cleanup_delete_files: bool,
+    // Identities presented to servers that ask for a client certificate
+    client_certificates: Vec<client_certs::ClientCertificate>,
+    // Private CAs and self-signed certificates the user confirmed for specific hosts
+    trusted_certificates: Vec<tls_trust::TrustedCertificate>,
+    // Headers, cookies, referer and file naming added to every download from a host; see `site_templates`
+    site_templates: Vec<site_templates::SiteTemplate>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "camelCase")]
+enum WebhookEvent { Completed, Failed, QueueEmpty }
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+struct Webhook {
+    url: String,
+    // When set, each body is signed as `X-Velodown-Signature: sha256=<hex HMAC>`
+    #[serde(default)]
+    secret: String,
+    // Empty subscribes to every event
+    #[serde(default)]
+    events: Vec<WebhookEvent>,
 }
 
 impl Default for AppSettings {
@@ -58,33 +437,345 @@ impl Default for AppSettings {
         Self {
             download_folder, max_concurrent_downloads: 4, max_connections_per_download: 8,
             auto_start: true, show_notifications: true, min_split_size: 10 * 1024 * 1024,
+            autostart: false,
+            close_to_tray: true,
+            language: "auto".to_string(),
+            notify_on_failure: true,
             auto_resume_downloads: true,
             max_resume_attempts: 5,
             resume_delay_seconds: 10,
             min_fail_duration_seconds: 20,
+            progress_event_interval_ms: 250,
+            write_buffer_size: 1024 * 1024,
+            preallocate_files: true,
+            temp_folder: String::new(),
+            partial_cache_mb: 2048,
+            confirm_over_mb: 0,
+            min_free_space_mb: 1024,
+            data_quota_gb: 0,
+            data_quota_period: quota::QuotaPeriod::Monthly,
+            quota_reset_day: 1,
+            quota_throttle_percent: 90,
+            quota_throttle_kbps: 256,
+            idle_minutes: 10,
+            idle_return_throttle: false,
+            idle_throttle_kbps: 256,
+            stall_timeout_seconds: 30,
+            user_agent: USER_AGENT.to_string(),
+            persist_cookies: false,
+            proxy_url: None,
+            api_enabled: false,
+            api_port: 6802,
+            api_token: uuid::Uuid::new_v4().simple().to_string(),
+            api_allow_lan: false,
+            metrics_enabled: false,
+            webhooks: Vec::new(),
+            click_n_load_enabled: false,
+            github_token: String::new(),
+            huggingface_token: String::new(),
+            s3_access_key_id: String::new(),
+            s3_region: String::new(),
+            s3_endpoint: String::new(),
+            s3_path_style: false,
+            url_refresh_command: String::new(),
+            subtitles_enabled: false,
+            opensubtitles_api_key: String::new(),
+            subtitle_languages: "en".to_string(),
+            audio_tagging_enabled: false,
+            audio_filename_pattern: "{artist} - {title}".to_string(),
+            audio_organize: false,
+            auto_extract: false,
+            extract_folder: String::new(),
+            extract_delete_archive: false,
+            archive_passwords: String::new(),
+            unrar_path: String::new(),
+            gpg_path: String::new(),
+            virustotal_enabled: false,
+            virustotal_api_key: String::new(),
+            detect_duplicates: true,
+            blocked_hosts: String::new(),
+            allowed_hosts: String::new(),
+            https_only: false,
+            ip_family: network::IpFamily::Auto,
+            bind_interface: String::new(),
+            metered_detection: true,
+            metered_pause_over_mb: 100,
+            metered_speed_limit_kbps: 256,
+            background_speed_limit_kbps: 512,
+            disk_write_limits: Vec::new(),
+            pause_on_network_change: true,
+            quiet_hours_enabled: false,
+            quiet_hours_start: "22:00".to_string(),
+            quiet_hours_end: "07:00".to_string(),
+            detect_captive_portal: true,
+            redact_saved_secrets: true,
+            log_level: "info".to_string(),
+            sequential_downloads: false,
+            sequential_categories: Vec::new(),
+            removed_retention_hours: 72,
+            cleanup_after_days: 0,
+            cleanup_keep_last: 0,
+            cleanup_delete_files: false,
+            client_certificates: Vec::new(),
+            trusted_certificates: Vec::new(),
+            site_templates: Vec::new(),
         }
     }
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-struct PersistentState { downloads: Vec<DownloadTask>, settings: AppSettings }
-impl Default for PersistentState { fn default() -> Self { Self { downloads: Vec::new(), settings: AppSettings::default() } } }
+#[derive(Debug, Serialize, Deserialize, Default)]
+struct PersistentState {
+    downloads: Vec<DownloadTask>, settings: AppSettings,
+    #[serde(default)]
+    accounts: Vec<accounts::Account>,
+    #[serde(default)]
+    subscriptions: Vec<subscriptions::Subscription>,
+    #[serde(default)]
+    mirrors: Vec<mirror::MirrorJob>,
+    // Taken off the list but still restorable; see `removed`
+    #[serde(default)]
+    removed: Vec<removed::RemovedDownload>,
+    // Bytes downloaded this quota period; see `quota`
+    #[serde(default)]
+    data_usage: quota::Usage,
+    #[serde(default)]
+    data_today: quota::Usage,
+}
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 #[serde(rename_all = "camelCase")]
 struct DownloadInfo {
     final_url: String, file_name: String, total_size: Option<u64>, file_type: String,
+    etag: Option<String>,
+    // Set when a resolver plugin produced `final_url`; its headers must go with the task
+    #[serde(default)]
+    resolved_by: Option<String>,
+    #[serde(default)]
+    headers: BTreeMap<String, String>,
+    // Every URL from the one asked for to `final_url`; empty when nothing redirected
+    #[serde(default)]
+    redirect_chain: Vec<String>,
+    #[serde(default)]
+    redirect_warning: Option<String>,
 }
 
 #[derive(Deserialize)]
 #[serde(rename_all = "camelCase")]
 struct AddDownloadPayload {
     url: String, file_name: String, total_size: Option<u64>, custom_path: Option<String>,
+    checksum: Option<String>,
+    #[serde(default)]
+    headers: BTreeMap<String, String>,
+    #[serde(default)]
+    redirect_chain: Vec<String>,
+    #[serde(default)]
+    parent_id: Option<String>,
+    #[serde(default)]
+    signature: Option<signature::SignatureCheck>,
+    #[serde(default)]
+    allow_http: bool,
+    #[serde(default)]
+    proxy: Option<String>,
+    #[serde(default)]
+    request: Option<CustomRequest>,
+    #[serde(default)]
+    source_url: Option<String>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(rename_all = "camelCase", default)]
+struct ScanFilters {
+    // With or without the leading dot; empty keeps every link
+    extensions: Vec<String>,
+    // Checking sizes costs a HEAD per link, so it only happens when a bound is set
+    min_size: Option<u64>, max_size: Option<u64>,
+}
+
+impl ScanFilters {
+    fn matches_extension(&self, file_name: &str) -> bool {
+        let extensions: Vec<String> = self.extensions.iter()
+            .map(|e| e.trim().trim_start_matches('.').to_ascii_lowercase())
+            .filter(|e| !e.is_empty())
+            .collect();
+        extensions.is_empty() || file_name.rsplit_once('.').is_some_and(|(_, e)| extensions.contains(&e.to_ascii_lowercase()))
+    }
+
+    // Unknown sizes pass
+    fn matches_size(&self, size: Option<u64>) -> bool {
+        size.is_none_or(|size| self.min_size.is_none_or(|min| size >= min) && self.max_size.is_none_or(|max| size <= max))
+    }
+}
+
+// A link found by `scan_page`, shaped so the UI can turn a selection straight into `add_downloads`
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+struct LinkCandidate {
+    url: String, tag: String, text: Option<String>, file_name: String, file_type: String,
+    total_size: Option<u64>,
+}
+
+#[derive(Debug, Serialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "camelCase")]
+enum LinkState { Alive, Dead, Unknown }
+
+// One row of `check_links`; `input` echoes the task id or URL it was asked about
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+struct LinkCheck {
+    input: String, url: String, state: LinkState, status: Option<u16>,
+    total_size: Option<u64>, file_name: Option<String>, error: Option<String>,
+}
+
+// Snapshot of the task fields a single download attempt needs, taken under the lock
+struct DownloadJob {
+    url: String, save_path: String, file_name: String, resume_from: u64, checksum: Option<String>,
+    segments: Vec<Segment>, headers: BTreeMap<String, String>, signature: Option<signature::SignatureCheck>,
+    allow_http: bool, allow_metered: bool, ignore_quota: bool, when_idle: bool, working_dir: Option<String>,
+    etag: Option<String>, proxy: Option<String>, request: Option<CustomRequest>,
 }
 
 struct AppState {
     persistent: Arc<Mutex<PersistentState>>,
     download_handles: Arc<Mutex<std::collections::HashMap<String, tokio::task::JoinHandle<()>>>>,
+    live_progress: Arc<Mutex<std::collections::HashMap<String, Arc<LiveProgress>>>>,
+    // Shared by info-fetch and downloads so connections and TLS sessions are reused;
+    // rebuilt by `update_settings` when the user agent, proxy or host lists change.
+    http_client: Arc<Mutex<Client>>,
+    // Clients carrying a host-specific client certificate or trusted certificates, by host pattern
+    host_clients: Arc<Mutex<Vec<(String, Client)>>>,
+    cookie_jar: Arc<cookies::CookieJar>,
+    save_requested: Arc<tokio::sync::Notify>,
+    // What each task's latest attempt saw on the wire, for `get_task_details`; not persisted
+    diagnostics: Arc<Mutex<std::collections::HashMap<String, TaskDiagnostics>>>,
+    api_server: Mutex<Option<tauri::async_runtime::JoinHandle<()>>>,
+    click_n_load_server: Mutex<Option<tauri::async_runtime::JoinHandle<()>>>,
+    // Running site crawls by mirror job id
+    mirror_crawls: Mutex<std::collections::HashMap<String, CancellationToken>>,
+    metrics: Arc<Metrics>,
+    metered: Arc<metered::Metered>,
+    quota: Arc<quota::Quota>,
+    idle: Arc<idle::Idle>,
+    captive: captive::CaptivePortal,
+    taskbar: taskbar::Taskbar,
+    shutdown: shutdown::Shutdown,
+    updater: updater::PendingUpdate,
+    dependencies: dependencies::Dependencies,
+    queue: queue::Queue,
+    speed_mode: speed_mode::SpeedSwitch,
+    speed_history: speed_history::SpeedHistories,
+    disk_limits: disk_limits::DiskLimits,
+    partial_cache: partial_cache::PartialCache,
+    vault: vault::Vault,
+    // `None` when the log folder couldn't be set up
+    logging: Option<logging::Logging>,
+}
+
+// Process-lifetime counters for the `/metrics` endpoint
+#[derive(Default)]
+struct Metrics { bytes_downloaded: AtomicU64, failures: AtomicU64 }
+
+// One entry of the batched `tasks_progress` event
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+struct TaskProgressDelta {
+    id: String, downloaded_size: u64, total_size: u64, progress: f64, speed: u64, time_remaining: Option<u64>,
+}
+
+// Hot-path progress counters for a running download, written without taking the `persistent` lock.
+// The progress aggregator copies them into the task list once per tick.
+#[derive(Default)]
+struct LiveProgress {
+    downloaded: AtomicU64, total_size: AtomicU64, speed: AtomicU64, dirty: AtomicBool,
+    // What a single stream had written as of its last sync
+    synced: AtomicU64,
+    // Cooperative stop so the writer can flush and record an exact offset before the task ends
+    cancel: CancellationToken,
+    // Worker count the segmenter aims for; `set_task_connections` changes it mid-download
+    connections: AtomicU8, connections_changed: tokio::sync::Notify,
+    // Shared with the segment workers; empty for servers that can't serve ranges
+    segments: Arc<std::sync::Mutex<Vec<Segment>>>,
+}
+
+impl LiveProgress {
+    fn record(&self, downloaded: u64, speed: u64) {
+        self.downloaded.store(downloaded, Ordering::Relaxed);
+        self.speed.store(speed, Ordering::Relaxed);
+        self.dirty.store(true, Ordering::Release);
+    }
+    fn segments(&self) -> Vec<Segment> { self.segments.lock().unwrap().clone() }
+    // The progress to persist: only what was synced, since a preallocated file is full length whether or
+    // not the bytes made it to disk, and a resume can't tell the zeros from data
+    fn durable(&self) -> (u64, Vec<Segment>) {
+        let segments: Vec<Segment> = self.segments.lock().unwrap().iter()
+            .map(|s| Segment { pos: s.synced.min(s.pos).max(s.start), ..s.clone() })
+            .collect();
+        let downloaded = if segments.is_empty() { self.synced.load(Ordering::Relaxed) } else { segments.iter().map(|s| s.pos - s.start).sum() };
+        (downloaded, segments)
+    }
+}
+
+#[derive(Debug, Serialize, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+struct TaskDiagnostics {
+    // Every URL the last request went through, ending with the one that served the file
+    redirect_chain: Vec<String>,
+    status: Option<u16>, response_headers: BTreeMap<String, String>,
+    server: Option<String>, accept_ranges: Option<String>,
+    segments: Vec<SegmentInfo>,
+    // Kept across attempts, unlike the rest which describes only the latest one
+    retries: Vec<RetryRecord>,
+    timing: TimingBreakdown,
+}
+
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+struct SegmentInfo { start: u64, end: Option<u64>, downloaded: u64 }
+
+// Totals for the summary bar, so the page doesn't add up every task itself
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Dashboard {
+    // Across every running download, in bytes per second
+    speed: u64,
+    // Seconds until every running and queued download is done at the current speed; unknown while
+    // nothing is moving or a size is unknown
+    queue_eta: Option<u64>,
+    // By status, as serialized
+    counts: BTreeMap<String, usize>,
+    transferred_today: u64,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SegmentMap {
+    // Blocks are empty while the size is unknown
+    total_size: u64, block_size: u64,
+    // Percent of each block on disk
+    blocks: Vec<u8>,
+    // Segments a connection is fetching right now
+    active: Vec<SegmentInfo>,
+}
+
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+struct RetryRecord { attempt: u8, at: DateTime<Local>, error: String }
+
+// reqwest doesn't expose connect and TLS separately, so `headers_ms` covers both plus server think time
+#[derive(Debug, Serialize, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+struct TimingBreakdown { dns_ms: Option<u64>, headers_ms: Option<u64>, first_byte_ms: Option<u64> }
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct TaskDetails {
+    task: DownloadTask,
+    #[serde(flatten)]
+    diagnostics: TaskDiagnostics,
+}
+
+tokio::task_local! {
+    // Filled in by the client's redirect policy, which runs inside the task polling the request
+    static REDIRECT_CHAIN: RefCell<Vec<String>>;
 }
 
 // --- HELPER FUNCTIONS (Unchanged) ---
@@ -97,139 +788,2215 @@ fn get_filename_from_response(response: &reqwest::Response, url: &Url) -> String
             }
         }
     }
-    if let Some(segments) = url.path_segments() {
-        if let Some(last_segment) = segments.last() {
-            if !last_segment.is_empty() { return last_segment.to_string(); }
-        }
-    }
-    format!("download_{}.tmp", chrono::Local::now().timestamp())
+    file_name_from_url(url).unwrap_or_else(|| format!("download_{}.tmp", chrono::Local::now().timestamp()))
+}
+fn file_name_from_url(url: &Url) -> Option<String> {
+    url.path_segments()?.next_back().filter(|s| !s.is_empty()).map(str::to_string)
+}
+fn header_content_length(headers: &reqwest::header::HeaderMap) -> Option<u64> {
+    headers.get(reqwest::header::CONTENT_LENGTH)?.to_str().ok()?.trim().parse().ok()
+}
+fn header_etag(headers: &reqwest::header::HeaderMap) -> Option<String> {
+    headers.get(reqwest::header::ETAG)?.to_str().ok().map(str::to_string)
+}
+// Weak comparison: `W/"abc"` and `"abc"` name the same content
+fn etags_match(a: &str, b: &str) -> bool {
+    a.trim_start_matches("W/") == b.trim_start_matches("W/")
+}
+// Parses the total from a `Content-Range: bytes 0-0/12345` header
+fn content_range_total(headers: &reqwest::header::HeaderMap) -> Option<u64> {
+    let value = headers.get(reqwest::header::CONTENT_RANGE)?.to_str().ok()?;
+    value.rsplit('/').next()?.trim().parse().ok()
 }
 fn get_file_type(filename: &str) -> String {
-    let extension = filename.split('.').last().unwrap_or("").to_lowercase();
+    let extension = filename.split('.').next_back().unwrap_or("").to_lowercase();
     match extension.as_str() {
         "mp4" | "avi" | "mkv" | "mov" | "wmv" => "Video", "mp3" | "wav" | "flac" | "aac" | "ogg" => "Audio",
-        "jpg" | "jpeg" | "png" | "gif" | "bmp" | "svg" => "Image", "zip" | "rar" | "7z" | "tar" | "gz" => "Archive",
+        "jpg" | "jpeg" | "png" | "gif" | "bmp" | "svg" => "Image", "zip" | "rar" | "7z" | "tar" | "gz" | "tgz" => "Archive",
         "exe" | "msi" | "dmg" | "deb" | "rpm" => "Executable", "pdf" | "doc" | "docx" | "txt" | "odt" => "Document",
         _ => "Other",
     }.to_string()
 }
+// A text/html response for a file that isn't itself a web page is almost always an error page
+fn is_unexpected_html(response: &reqwest::Response, file_name: &str) -> bool {
+    let is_html = response.headers()
+        .get(reqwest::header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .map(|v| v.to_ascii_lowercase().starts_with("text/html"))
+        .unwrap_or(false);
+    let expects_html = matches!(
+        file_name.rsplit('.').next().map(|e| e.to_ascii_lowercase()).as_deref(),
+        Some("html" | "htm" | "xhtml" | "php" | "asp" | "aspx")
+    );
+    is_html && !expects_html
+}
+async fn read_capped_body(response: reqwest::Response, max_bytes: usize) -> String {
+    let mut body = Vec::new();
+    let mut stream = response.bytes_stream();
+    while let Some(Ok(chunk)) = stream.next().await {
+        body.extend_from_slice(&chunk);
+        if body.len() >= max_bytes { body.truncate(max_bytes); break; }
+    }
+    String::from_utf8_lossy(&body).into_owned()
+}
+// Reserves the full size up front so fragmentation is reduced and a full disk fails now, not at 97%.
+// Falls back to extending the file (sparse on most filesystems) where real allocation is unsupported.
+async fn preallocate_file(file: &tokio::fs::File, len: u64) -> std::io::Result<()> {
+    #[cfg(target_os = "linux")]
+    {
+        use std::os::unix::io::AsRawFd;
+        let result = unsafe { libc::fallocate(file.as_raw_fd(), 0, 0, len as libc::off_t) };
+        if result == 0 { return Ok(()); }
+        let err = std::io::Error::last_os_error();
+        if !matches!(err.raw_os_error(), Some(libc::EOPNOTSUPP) | Some(libc::ENOSYS)) { return Err(err); }
+    }
+    file.set_len(len).await
+}
+// Renames when possible; across filesystems copies to a temp name beside the destination, fsyncs,
+// renames it into place and only then removes the source, reporting (copied, total) as it goes.
+async fn move_file(src: &std::path::Path, dst: &std::path::Path, mut on_progress: impl FnMut(u64, u64)) -> std::io::Result<()> {
+    match tokio::fs::rename(src, dst).await {
+        Ok(()) => return Ok(()),
+        Err(e) if e.kind() == std::io::ErrorKind::CrossesDevices => {}
+        Err(e) => return Err(e),
+    }
+    let mut tmp_name = dst.file_name().unwrap_or_default().to_os_string();
+    tmp_name.push(".moving");
+    let tmp_path = dst.with_file_name(tmp_name);
+    let total = tokio::fs::metadata(src).await?.len();
+    let copy = async {
+        let mut reader = tokio::fs::File::open(src).await?;
+        let mut writer = tokio::fs::File::create(&tmp_path).await?;
+        let mut buf = vec![0u8; 1024 * 1024];
+        let mut copied = 0u64;
+        let mut last_report = Instant::now();
+        loop {
+            let n = reader.read(&mut buf).await?;
+            if n == 0 { break; }
+            writer.write_all(&buf[..n]).await?;
+            copied += n as u64;
+            if last_report.elapsed() > PROGRESS_INTERVAL {
+                on_progress(copied, total);
+                last_report = Instant::now();
+            }
+        }
+        writer.sync_all().await?;
+        drop(writer);
+        tokio::fs::rename(&tmp_path, dst).await
+    };
+    if let Err(e) = copy.await {
+        let _ = tokio::fs::remove_file(&tmp_path).await;
+        return Err(e);
+    }
+    on_progress(total, total);
+    tokio::fs::remove_file(src).await
+}
+// Query parameters that only record where a click came from; `utm_*` are matched by prefix
+const TRACKING_PARAMS: &[&str] = &["fbclid", "gclid", "dclid", "msclkid", "yclid", "igshid", "mc_cid", "mc_eid", "_ga"];
+// The form of a link used to tell whether two are the same download: scheme and host lowercased and
+// default ports dropped (both done by `Url`), tracking parameters and the fragment removed and the
+// rest of the query sorted. Only ever compared, never requested.
+fn canonical_url(url: &str) -> String {
+    let Ok(mut parsed) = Url::parse(url.trim()) else { return url.trim().to_string() };
+    parsed.set_fragment(None);
+    let mut pairs: Vec<(String, String)> = parsed.query_pairs()
+        .filter(|(name, _)| {
+            let name = name.to_ascii_lowercase();
+            !name.starts_with("utm_") && !TRACKING_PARAMS.contains(&name.as_str())
+        })
+        .map(|(name, value)| (name.into_owned(), value.into_owned()))
+        .collect();
+    pairs.sort();
+    if pairs.is_empty() {
+        parsed.set_query(None);
+    } else {
+        parsed.query_pairs_mut().clear().extend_pairs(pairs);
+    }
+    parsed.to_string()
+}
+// The host rules and HTTPS-only mode, for a URL a task is about to request
+fn check_url_policy(settings: &AppSettings, allow_http: bool, url: &str) -> Result<(), String> {
+    host_rules::HostRules::from_settings(settings).check_str(url).map_err(|e| format!("{}: {}", HOST_REFUSED_ERROR, e))?;
+    if settings.https_only && !allow_http && is_plain_http(url) {
+        return Err(format!("{}: {} is plain HTTP", HTTPS_ONLY_ERROR, url));
+    }
+    Ok(())
+}
+fn is_plain_http(url: &str) -> bool {
+    Url::parse(url).is_ok_and(|u| u.scheme() == "http")
+}
+// A bare file name that is safe on every platform we ship to
+fn is_valid_file_name(name: &str) -> bool {
+    !name.is_empty() && name != "." && name != ".."
+        && !name.chars().any(|c| c.is_control() || matches!(c, '/' | '\\' | '<' | '>' | ':' | '"' | '|' | '?' | '*'))
+}
+// Returns a hasher for the checksum's algorithm and the expected lowercase hex digest
+fn checksum_hasher(checksum: &str) -> Option<(Box<dyn DynDigest + Send>, String)> {
+    let (algorithm, digest) = match checksum.split_once(':') {
+        Some((algorithm, digest)) => (algorithm.trim().to_ascii_lowercase(), digest.trim()),
+        None => (String::new(), checksum.trim()),
+    };
+    let hasher: Box<dyn DynDigest + Send> = match (algorithm.as_str(), digest.len()) {
+        ("sha256", _) | ("", 64) => Box::new(sha2::Sha256::default()),
+        ("sha512", _) | ("", 128) => Box::new(sha2::Sha512::default()),
+        _ => return None,
+    };
+    if digest.len() != hasher.output_size() * 2 || !digest.chars().all(|c| c.is_ascii_hexdigit()) { return None; }
+    Some((hasher, digest.to_ascii_lowercase()))
+}
+async fn hash_file(path: &std::path::Path, hasher: Box<dyn DynDigest + Send>) -> std::io::Result<String> {
+    Ok(hex::encode(hash_file_from(path, hasher, 0).await?.finalize()))
+}
+// Feeds `hasher` the file from `offset` to its end
+async fn hash_file_from(path: &std::path::Path, mut hasher: Box<dyn DynDigest + Send>, offset: u64) -> std::io::Result<Box<dyn DynDigest + Send>> {
+    let mut file = tokio::fs::File::open(path).await?;
+    file.seek(std::io::SeekFrom::Start(offset)).await?;
+    let mut buf = vec![0u8; 1024 * 1024];
+    loop {
+        let n = file.read(&mut buf).await?;
+        if n == 0 { break; }
+        hasher.update(&buf[..n]);
+    }
+    Ok(hasher)
+}
 fn get_state_path(app_handle: &AppHandle) -> anyhow::Result<PathBuf> {
     let path = app_handle.path().app_data_dir()?.join("state.json");
     if let Some(parent) = path.parent() { fs::create_dir_all(parent)?; }
     Ok(path)
 }
-async fn save_state(state: &State<'_, AppState>, app_handle: &AppHandle) -> anyhow::Result<()> {
+fn http_client_builder(settings: &AppSettings, cookie_jar: Arc<cookies::CookieJar>) -> anyhow::Result<reqwest::ClientBuilder> {
+    let host_rules = host_rules::HostRules::from_settings(settings);
+    let https_only = settings.https_only;
+    let mut builder = Client::builder()
+        .user_agent(settings.user_agent.as_str())
+        .redirect(reqwest::redirect::Policy::custom(move |attempt| {
+            let _ = REDIRECT_CHAIN.try_with(|chain| {
+                *chain.borrow_mut() = attempt.previous().iter().chain(std::iter::once(attempt.url())).map(Url::to_string).collect();
+            });
+            if let Err(e) = host_rules.check(attempt.url()) { return attempt.error(format!("{}: {}", REDIRECT_REFUSED_ERROR, e)); }
+            if https_only && attempt.url().scheme() == "http" && attempt.previous().last().is_some_and(|p| p.scheme() == "https") {
+                let message = format!("{}: redirect to {} downgrades to plain HTTP", HTTPS_ONLY_ERROR, attempt.url());
+                return attempt.error(message);
+            }
+            if attempt.previous().len() >= MAX_REDIRECTS { attempt.error("too many redirects") } else { attempt.follow() }
+        }))
+        .cookie_provider(cookie_jar)
+        .connect_timeout(Duration::from_secs(30))
+        // A read timeout rather than a total one, so long transfers aren't cut off mid-stream
+        .read_timeout(Duration::from_secs(60))
+        .pool_idle_timeout(Duration::from_secs(90))
+        .pool_max_idle_per_host(10)
+        .tcp_keepalive(Some(Duration::from_secs(60)));
+    if let Some(proxy) = settings.proxy_url.as_deref().map(str::trim).filter(|p| !p.is_empty()) {
+        builder = builder.proxy(reqwest::Proxy::all(proxy)?);
+    }
+    network::apply(builder, settings.ip_family, &settings.bind_interface).map_err(anyhow::Error::msg)
+}
+// The shared client, presenting the client certificate that isn't tied to a host if there is one
+fn build_http_client(settings: &AppSettings, cookie_jar: Arc<cookies::CookieJar>) -> anyhow::Result<Client> {
+    let mut builder = http_client_builder(settings, cookie_jar)?;
+    if let Some(cert) = settings.client_certificates.iter().find(|c| c.host_pattern().is_none()) {
+        builder = builder.identity(cert.identity().map_err(anyhow::Error::msg)?);
+    }
+    Ok(builder.build()?)
+}
+// reqwest takes one identity and one set of roots per client, so each host with its own client
+// certificate or trusted certificates gets a client of its own
+fn build_host_clients(settings: &AppSettings, cookie_jar: Arc<cookies::CookieJar>) -> anyhow::Result<Vec<(String, Client)>> {
+    let hosts = settings.client_certificates.iter().filter_map(|c| c.host_pattern())
+        .chain(settings.trusted_certificates.iter().filter_map(|c| c.host_pattern()));
+    let mut clients: Vec<(String, Client)> = Vec::new();
+    for host in hosts {
+        if clients.iter().any(|(h, _)| *h == host) { continue; }
+        let identity = settings.client_certificates.iter().find(|c| c.host_pattern().as_ref() == Some(&host));
+        let trusted: Vec<&tls_trust::TrustedCertificate> = settings.trusted_certificates.iter()
+            .filter(|c| c.host_pattern().as_ref() == Some(&host))
+            .collect();
+        let mut builder = http_client_builder(settings, cookie_jar.clone())?;
+        builder = if !trusted.is_empty() {
+            let identity = identity.map(|c| c.pem()).transpose().map_err(anyhow::Error::msg)?;
+            builder.use_preconfigured_tls(tls_trust::client_config(&trusted, identity.as_deref()).map_err(anyhow::Error::msg)?)
+        } else if let Some(cert) = identity {
+            builder.identity(cert.identity().map_err(anyhow::Error::msg)?)
+        } else {
+            builder
+        };
+        clients.push((host, builder.build()?));
+    }
+    Ok(clients)
+}
+async fn http_client(state: &AppState) -> Client { state.http_client.lock().await.clone() }
+// A client like the shared one but going through `proxy`, for a download with a proxy of its own.
+// reqwest sets the proxy per client, so one is built for each attempt.
+async fn proxied_client(state: &AppState, proxy: &str) -> anyhow::Result<Client> {
+    let mut settings = state.persistent.lock().await.settings.clone();
+    settings.proxy_url = Some(proxy.to_string());
+    build_http_client(&settings, state.cookie_jar.clone())
+}
+// The client to probe or download `url` with: the one holding that host's client certificate, if any
+async fn http_client_for(state: &AppState, url: &str) -> Client {
+    if let Some(host) = Url::parse(url).ok().and_then(|u| u.host_str().map(str::to_ascii_lowercase)) {
+        let host_clients = state.host_clients.lock().await;
+        if let Some((_, client)) = host_clients.iter().find(|(pattern, _)| host_rules::matches(pattern, &host)) {
+            return client.clone();
+        }
+    }
+    http_client(state).await
+}
+fn with_headers(request: reqwest::RequestBuilder, headers: &BTreeMap<String, String>) -> reqwest::RequestBuilder {
+    headers.iter().fold(request, |request, (name, value)| request.header(name.as_str(), value.as_str()))
+}
+async fn update_diagnostics(state: &AppState, id: &str, f: impl FnOnce(&mut TaskDiagnostics)) {
+    f(state.diagnostics.lock().await.entry(id.to_string()).or_default());
+}
+async fn abort_download(state: &AppState, id: &str) {
+    let live = state.live_progress.lock().await.get(id).cloned();
+    if let Some(live) = &live { live.cancel.cancel(); }
+    if let Some(mut handle) = state.download_handles.lock().await.remove(id) {
+        // Give the task a chance to flush its buffer; only hard-abort if it doesn't stop in time
+        if timeout(CANCEL_GRACE, &mut handle).await.is_err() { handle.abort(); }
+        state.queue.notify();
+    }
+    state.live_progress.lock().await.remove(id);
+}
+// Posts `{ event, timestamp, task }` to every webhook subscribed to `event`. Each delivery runs in
+// the background so a slow endpoint never holds up the download that triggered it.
+async fn fire_webhooks(app_handle: &AppHandle, event: WebhookEvent, task: Option<&DownloadTask>) {
+    let state: State<AppState> = app_handle.state();
+    let hooks: Vec<Webhook> = state.persistent.lock().await.settings.webhooks.iter()
+        .filter(|h| h.events.is_empty() || h.events.contains(&event))
+        .cloned()
+        .collect();
+    if hooks.is_empty() { return; }
+    let body = serde_json::json!({ "event": event, "timestamp": Local::now(), "task": task }).to_string();
+    let client = http_client(&state).await;
+    for hook in hooks {
+        let (client, body) = (client.clone(), body.clone());
+        tauri::async_runtime::spawn(async move {
+            let mut request = client.post(&hook.url)
+                .header("Content-Type", "application/json")
+                .timeout(Duration::from_secs(15));
+            if !hook.secret.is_empty() {
+                let mut mac = Hmac::<sha2::Sha256>::new_from_slice(hook.secret.as_bytes()).expect("HMAC accepts any key length");
+                Mac::update(&mut mac, body.as_bytes());
+                request = request.header("X-Velodown-Signature", format!("sha256={}", hex::encode(mac.finalize().into_bytes())));
+            }
+            match request.body(body).send().await {
+                Ok(response) if !response.status().is_success() => log::warn!("Webhook {} returned {}", redact::url(&hook.url), response.status()),
+                Err(e) => log::warn!("Webhook {} failed: {}", redact::url(&hook.url), redact::text(&e.to_string())),
+                Ok(_) => {}
+            }
+        });
+    }
+}
+// Stops every running download for a system event (network change, sleep), marking each with
+// `reason` so only those are picked up again by `resume_paused`
+async fn pause_running(app_handle: &AppHandle, reason: &str) -> Vec<String> {
+    let state: State<AppState> = app_handle.state();
+    let running: Vec<String> = state.download_handles.lock().await.keys().cloned().collect();
+    if running.is_empty() { return running; }
+    futures::future::join_all(running.iter().map(|id| abort_download(&state, id))).await;
+    {
+        let mut state_guard = state.persistent.lock().await;
+        for task in state_guard.downloads.iter_mut().filter(|t| running.contains(&t.id)) {
+            task.status = DownloadStatus::Paused;
+            task.speed = 0;
+            task.error_message = Some(reason.to_string());
+            app_handle.emit("task_updated", &*task).unwrap();
+        }
+    }
+    request_save(&state);
+    running
+}
+// Restarts what `pause_running` stopped, skipping tasks the user removed or restarted meanwhile. The
+// interruption wasn't the server's fault, so retry counters start over.
+async fn resume_paused(app_handle: &AppHandle, ids: impl IntoIterator<Item = String>, reason: &str) {
+    let ids: std::collections::HashSet<String> = ids.into_iter().collect();
+    let resumable: Vec<String> = {
+        let state: State<AppState> = app_handle.state();
+        let mut state_guard = state.persistent.lock().await;
+        state_guard.downloads.iter_mut()
+            .filter(|t| ids.contains(&t.id) && t.status == DownloadStatus::Paused && t.error_message.as_deref() == Some(reason))
+            .map(|t| {
+                t.resume_attempts = 0;
+                t.error_message = None;
+                t.id.clone()
+            })
+            .collect()
+    };
+    for id in resumable {
+        if let Err(e) = start_download_task(id, app_handle.clone()).await {
+            log::warn!("Could not resume a paused download: {}", e);
+        }
+    }
+}
+// The gist of a permanent failure, for the notification title line
+fn failure_reason(error: &str) -> i18n::Text {
+    use i18n::Text;
+    if error.starts_with(HTTPS_ONLY_ERROR) { Text::BlockedByHttpsOnly }
+    else if error.starts_with(REDIRECT_REFUSED_ERROR) { Text::RedirectRefused }
+    else if error.starts_with(HOST_REFUSED_ERROR) { Text::BlockedByHostRules }
+    else if error.contains(HTML_PAGE_ERROR) { Text::HtmlPage }
+    else if error.contains("Checksum mismatch") { Text::ChecksumMismatch }
+    else if error.starts_with("Signature check failed") { Text::SignatureFailed }
+    else if error.contains("File size mismatch") { Text::SizeMismatch }
+    else if error.contains("403") { Text::AccessDenied }
+    else if error.contains("404") { Text::NotFound }
+    else if error.starts_with(STALLED_ERROR) { Text::Stalled }
+    else { Text::NetworkError }
+}
+// The error itself stays as the server or OS worded it
+fn notify_failure(app_handle: &AppHandle, settings: &AppSettings, task: &DownloadTask) {
+    use i18n::{tr, Text};
+    let language = i18n::Language::from_setting(&settings.language);
+    let error = task.error_message.as_deref().unwrap_or_default();
+    let body = format!("{}: {}\n{}", task.file_name, tr(language, failure_reason(error)), redact::text(error));
+    let mut actions = Vec::new();
+    if task.resume_capability { actions.push((tr(language, Text::Retry), notifications::Action::Retry(task.id.clone()))); }
+    actions.push((tr(language, Text::StartOver), notifications::Action::Restart(task.id.clone())));
+    notifications::show_with_actions(app_handle, settings, tr(language, Text::DownloadFailed), &body, actions);
+}
+// Downloads that were running when the app last closed (or the machine shut down) carry on if
+// auto-resume is on, and are shown as paused otherwise
+async fn resume_interrupted(app_handle: AppHandle) {
+    let state: State<AppState> = app_handle.state();
+    let interrupted = {
+        let mut state_guard = state.persistent.lock().await;
+        let mut interrupted = Vec::new();
+        for task in state_guard.downloads.iter_mut().filter(|t| matches!(t.status, DownloadStatus::Downloading | DownloadStatus::Retrying)) {
+            task.status = DownloadStatus::Paused;
+            task.speed = 0;
+            interrupted.push(task.id.clone());
+        }
+        if !state_guard.settings.auto_resume_downloads { interrupted.clear(); }
+        interrupted
+    };
+    request_save(&state);
+    for id in interrupted {
+        if let Err(e) = start_download_task(id, app_handle.clone()).await {
+            log::warn!("Could not resume an interrupted download: {}", e);
+        }
+    }
+}
+// Called when a download ends for good; fires `queueEmpty` once nothing is running or waiting
+async fn notify_if_queue_empty(app_handle: &AppHandle) {
+    let state: State<AppState> = app_handle.state();
+    if !state.download_handles.lock().await.is_empty() { return; }
+    let waiting = state.persistent.lock().await.downloads.iter().any(|t| t.status == DownloadStatus::Queued);
+    if !waiting { fire_webhooks(app_handle, WebhookEvent::QueueEmpty, None).await; }
+}
+// Schedules a debounced save; the persistence actor coalesces bursts of changes into one write
+fn request_save(state: &AppState) { state.save_requested.notify_one(); }
+// Serializes under the lock, then writes to a temp file and renames it over state.json so a
+// crash mid-write never leaves a truncated file. Skips the write when nothing changed.
+async fn write_state(state: &AppState, app_handle: &AppHandle, last_written: &mut Option<String>) -> anyhow::Result<()> {
+    let (json, secrets) = {
+        let state_guard = state.persistent.lock().await;
+        let mut value = serde_json::to_value(&*state_guard)?;
+        let secrets = vault::mask(&state_guard, &mut value)?;
+        (serde_json::to_string(&value)?, secrets)
+    };
+    state.vault.save(secrets).await;
+    if last_written.as_deref() == Some(json.as_str()) { return Ok(()); }
     let path = get_state_path(app_handle)?;
-    let state_guard = state.persistent.lock().await;
-    fs::write(path, serde_json::to_string_pretty(&*state_guard)?)?;
+    let tmp_path = path.with_extension("json.tmp");
+    let mut file = tokio::fs::File::create(&tmp_path).await?;
+    file.write_all(json.as_bytes()).await?;
+    file.sync_all().await?;
+    drop(file);
+    tokio::fs::rename(&tmp_path, &path).await?;
+    *last_written = Some(json);
     Ok(())
 }
+async fn run_persistence(app_handle: AppHandle) {
+    let state: State<AppState> = app_handle.state();
+    let mut last_written = None;
+    loop {
+        state.save_requested.notified().await;
+        tokio::time::sleep(SAVE_DEBOUNCE).await;
+        if let Err(e) = write_state(&state, &app_handle, &mut last_written).await {
+            log::error!("Failed to save state: {}", e);
+        }
+    }
+}
+// Writes state now rather than after the debounce, for when the process may not get another chance
+async fn flush_state(app_handle: &AppHandle) {
+    if let Err(e) = write_state(&app_handle.state(), app_handle, &mut None).await {
+        log::error!("Failed to save state: {}", e);
+    }
+}
+// Folds every running download's live counters into the task list under a single lock per tick,
+// and emits them as one batched `tasks_progress` event. Status changes still go out as `task_updated`.
+async fn run_progress_aggregator(app_handle: AppHandle) {
+    let mut last_progress_save = Instant::now();
+    let mut last_speed_sample = Instant::now();
+    loop {
+        let state: State<AppState> = app_handle.state();
+        let interval_ms = state.persistent.lock().await.settings.progress_event_interval_ms.max(50);
+        tokio::time::sleep(Duration::from_millis(interval_ms)).await;
+
+        let live: Vec<(String, Arc<LiveProgress>)> = state.live_progress.lock().await.iter()
+            .filter(|(_, p)| p.dirty.load(Ordering::Acquire))
+            .map(|(id, p)| (id.clone(), p.clone()))
+            .collect();
+        if live.is_empty() { continue; }
+
+        let mut deltas = Vec::with_capacity(live.len());
+        {
+            let mut state_guard = state.persistent.lock().await;
+            for (id, p) in live {
+                if !p.dirty.swap(false, Ordering::AcqRel) { continue; }
+                let Some(task) = state_guard.downloads.iter_mut().find(|t| t.id == id) else { continue };
+                // Paused/verifying/completed tasks own their fields; don't overwrite them with stale counters
+                if task.status != DownloadStatus::Downloading { continue; }
+                let downloaded = p.downloaded.load(Ordering::Relaxed);
+                let total_size = p.total_size.load(Ordering::Relaxed);
+                let speed = p.speed.load(Ordering::Relaxed);
+                // Shown live, but saved only as far as synced
+                (task.downloaded_size, task.segments) = p.durable();
+                task.speed = speed;
+                task.time_remaining = total_size.saturating_sub(downloaded).checked_div(speed);
+                task.progress = if total_size > 0 { (downloaded as f64 / total_size as f64) * 100.0 } else { 0.0 };
+                deltas.push(TaskProgressDelta {
+                    id: task.id.clone(), downloaded_size: downloaded, total_size: task.total_size,
+                    progress: task.progress, speed, time_remaining: task.time_remaining,
+                });
+            }
+        }
+        if deltas.is_empty() { continue; }
+        app_handle.emit("tasks_progress", &deltas).unwrap();
+        state.speed_history.record(deltas.iter().map(|d| (d.id.as_str(), d.speed)));
+        if last_speed_sample.elapsed() >= tasklog::SPEED_SAMPLE_INTERVAL {
+            let samples: Vec<(String, String)> = deltas.iter()
+                .map(|d| (d.id.clone(), format!("{} KiB/s, {} of {} bytes", d.speed / 1024, d.downloaded_size, d.total_size)))
+                .collect();
+            let app_handle = app_handle.clone();
+            tauri::async_runtime::spawn(async move {
+                for (id, sample) in samples { tasklog::write(&app_handle, &id, &sample).await; }
+            });
+            last_speed_sample = Instant::now();
+        }
+        if last_progress_save.elapsed() >= PROGRESS_SAVE_INTERVAL {
+            request_save(&state);
+            last_progress_save = Instant::now();
+        }
+    }
+}
 
 // --- TAURI COMMANDS ---
 
 #[tauri::command]
-async fn get_download_info(url: String) -> Result<DownloadInfo, String> {
-    let cookie_jar = Arc::new(Jar::default());
-    let client = Client::builder()
-        .user_agent(USER_AGENT)
-        .redirect(reqwest::redirect::Policy::default())
-        .cookie_provider(cookie_jar) // Use the cookie jar
-        .timeout(Duration::from_secs(20))
-        .build()
-        .map_err(|e| e.to_string())?;
+async fn get_download_info(url: String, state: State<'_, AppState>, app_handle: AppHandle) -> Result<DownloadInfo, String> {
+    resolve_download_info(&app_handle, &http_client_for(&state, &url).await, &url).await
+}
 
-    let response = client.get(&url)
-        .header("Accept", "text/html,application/xhtml+xml,application/xml;q=0.9,image/avif,image/webp,*/*;q=0.8")
-        .header("Accept-Language", "en-US,en;q=0.5")
-        .header("Referer", &url) // Add a Referer header
-        .send()
-        .await
-        .map_err(|e| format!("Request failed: {}", e))?;
+// Lets a resolver plugin turn the URL into direct links first, then probes those in order until one
+// answers. URLs no plugin claims are probed as they are.
+async fn resolve_download_info(app_handle: &AppHandle, client: &Client, url: &str) -> Result<DownloadInfo, String> {
+    let settings = app_handle.state::<AppState>().persistent.lock().await.settings.clone();
+    // Refused before anything is fetched; the client's redirect policy covers the hops after this
+    host_rules::HostRules::from_settings(&settings).check_str(url)?;
+    if s3::is_s3_url(url) {
+        // The task keeps the s3:// URL and is presigned again for every attempt
+        let mut info = probe_download_info(client, &request_url(&settings, url).await?).await?;
+        info.final_url = url.to_string();
+        info.redirect_chain.clear();
+        info.redirect_warning = None;
+        return Ok(info);
+    }
+    let Some((plugin, resolved)) = plugins::resolve(app_handle, url).await? else {
+        // Installed plugins take precedence over the built-in share-link resolvers
+        if let Some((service, direct_url)) = share_links::resolve(client, url).await? {
+            let mut info = probe_download_info(client, &direct_url).await?;
+            info.resolved_by = Some(service.to_string());
+            return Ok(info);
+        }
+        if remote::handles(url) {
+            return remote::probe(app_handle, url).await;
+        }
+        // A site template's headers go with the probe, so a site that wants an API key answers it
+        let template = site_templates::find(&settings.site_templates, url);
+        let headers = match (template, Url::parse(url)) {
+            (Some(template), Ok(parsed)) => template.headers(&parsed),
+            _ => BTreeMap::new(),
+        };
+        let mut info = probe_download_info_with_headers(client, url, &headers).await?;
+        if site_templates::is_guessed_name(&info.file_name, &info.final_url) {
+            let urls = [url.to_string(), info.final_url.clone()];
+            if let Some(file_name) = site_templates::file_name(&settings.site_templates, &urls) {
+                info.file_type = get_file_type(&file_name);
+                info.file_name = file_name;
+            }
+        }
+        return Ok(info);
+    };
+    let mut last_error = String::new();
+    for direct_url in &resolved.urls {
+        match probe_download_info_with_headers(client, direct_url, &resolved.headers).await {
+            Ok(mut info) => {
+                if let Some(file_name) = resolved.file_name.filter(|name| is_valid_file_name(name)) {
+                    info.file_type = get_file_type(&file_name);
+                    info.file_name = file_name;
+                }
+                info.resolved_by = Some(plugin);
+                info.headers = resolved.headers;
+                return Ok(info);
+            }
+            Err(e) => last_error = e,
+        }
+    }
+    Err(format!("No link from plugin {} worked: {}", plugin, last_error))
+}
 
-    if !response.status().is_success() {
-        return Err(format!("Server returned error: {}", response.status()));
+// The URL to actually request for a task URL: `s3://` sources become a freshly presigned HTTPS URL
+async fn request_url(settings: &AppSettings, url: &str) -> Result<String, String> {
+    if !s3::is_s3_url(url) { return Ok(url.to_string()); }
+    let credentials = s3::credentials(settings).await?;
+    s3::presign(url, settings, &credentials, chrono::Utc::now())
+}
+
+// Like `request_url`, and also swaps an expired presigned URL for a fresh one from the refresh hook,
+// saving it on the task. `force` refreshes even if the clock says the URL should still work.
+async fn prepare_task_url(app_handle: &AppHandle, id: &str, url: &str, settings: &AppSettings, force: bool) -> Result<String, String> {
+    let hook = settings.url_refresh_command.trim();
+    if s3::is_s3_url(url) || hook.is_empty() || s3::presigned_expiry(url).is_none() || !(force || s3::needs_refresh(url)) {
+        return request_url(settings, url).await;
+    }
+    let fresh = s3::run_refresh_hook(hook, url).await?;
+    let state: State<AppState> = app_handle.state();
+    if let Some(task) = state.persistent.lock().await.downloads.iter_mut().find(|t| t.id == id) {
+        task.url = fresh.clone();
+        app_handle.emit("task_updated", &*task).unwrap();
     }
+    request_save(&state);
+    Ok(fresh)
+}
 
-    let final_url = response.url().to_string();
-    let file_name = get_filename_from_response(&response, response.url());
-    let total_size = response.content_length();
-    let file_type = get_file_type(&file_name);
+// Shown to the user for confirmation before the certificate is added to `trusted_certificates`
+#[tauri::command]
+async fn inspect_server_certificate(host: String, state: State<'_, AppState>) -> Result<tls_trust::CertificateInfo, String> {
+    let proxy = state.persistent.lock().await.settings.proxy_url.clone();
+    tls_trust::inspect_server(&host, proxy.as_deref()).await
+}
 
-    Ok(DownloadInfo { final_url, file_name, total_size, file_type })
+#[tauri::command]
+async fn read_certificate_file(path: String) -> Result<tls_trust::CertificateInfo, String> {
+    tls_trust::read_file(&path)
 }
 
 #[tauri::command]
-async fn choose_download_folder(app_handle: AppHandle) -> Result<String, String> {
-    let (tx, rx) = oneshot::channel();
-    app_handle.dialog().file().set_title("Choose Download Folder")
-        .pick_folder(move |folder_path: Option<FilePath>| {
-            let _ = tx.send(folder_path);
-        });
-    match rx.await {
-        Ok(Some(file_path)) => match file_path.as_path() {
-            Some(path) => Ok(path.to_string_lossy().to_string()),
-            None => Err("Invalid path returned from dialog".to_string()),
-        },
-        Ok(None) => Err("No folder selected".to_string()),
-        Err(_) => Err("Dialog was cancelled".to_string()),
+async fn set_s3_secret_key(secret: String) -> Result<(), String> {
+    s3::store_secret_access_key(secret.trim()).await
+}
+
+#[tauri::command]
+async fn list_plugins(app_handle: AppHandle) -> Result<Vec<plugins::PluginManifest>, String> {
+    Ok(plugins::discover(&app_handle).await)
+}
+
+#[tauri::command]
+async fn list_accounts(state: State<'_, AppState>) -> Result<Vec<accounts::Account>, String> {
+    Ok(state.persistent.lock().await.accounts.clone())
+}
+
+// The secret (password or API key) goes to the OS keyring; only the account metadata is persisted
+#[tauri::command]
+async fn add_account(host: String, username: String, secret: String, state: State<'_, AppState>) -> Result<accounts::Account, String> {
+    let host = host.trim().trim_start_matches("https://").trim_start_matches("http://").trim_end_matches('/').to_ascii_lowercase();
+    if host.is_empty() { return Err("Host is required".to_string()); }
+    let account = accounts::Account {
+        id: format!("account-{}", uuid::Uuid::new_v4()), host, username: username.trim().to_string(),
+        status: Default::default(), traffic_left: None, valid_until: None, last_checked: None, error: None,
+    };
+    accounts::store_secret(&account.id, &secret).await?;
+    state.persistent.lock().await.accounts.push(account.clone());
+    request_save(&state);
+    Ok(account)
+}
+
+#[tauri::command]
+async fn remove_account(id: String, state: State<'_, AppState>) -> Result<(), String> {
+    {
+        let mut state_guard = state.persistent.lock().await;
+        let before = state_guard.accounts.len();
+        state_guard.accounts.retain(|a| a.id != id);
+        if state_guard.accounts.len() == before { return Err("Account not found".to_string()); }
     }
+    request_save(&state);
+    accounts::delete_secret(&id).await
 }
 
-// This command now correctly receives the final URL from the info-fetch step
 #[tauri::command]
-async fn add_download(payload: AddDownloadPayload, state: State<'_, AppState>, app_handle: AppHandle) -> Result<DownloadTask, String> {
-    let id = format!("task-{}", uuid::Uuid::new_v4());
-    let file_type = get_file_type(&payload.file_name);
-    let (default_save_path, max_connections, auto_start) = {
-        let settings = &state.persistent.lock().await.settings;
-        (settings.download_folder.clone(), settings.max_connections_per_download, settings.auto_start)
+async fn check_account(id: String, state: State<'_, AppState>, app_handle: AppHandle) -> Result<accounts::Account, String> {
+    let account = state.persistent.lock().await.accounts.iter().find(|a| a.id == id).cloned().ok_or("Account not found")?;
+    plugins::check_account(&app_handle, &account).await
+}
+
+#[tauri::command]
+async fn list_mirrors(state: State<'_, AppState>) -> Result<Vec<mirror::MirrorJob>, String> {
+    Ok(state.persistent.lock().await.mirrors.clone())
+}
+
+// Starts crawling in the background; pages are saved as they're fetched and files queued as tasks
+#[tauri::command]
+async fn start_mirror(request: mirror::MirrorRequest, state: State<'_, AppState>, app_handle: AppHandle) -> Result<mirror::MirrorJob, String> {
+    let job = {
+        let mut state_guard = state.persistent.lock().await;
+        let folder = request.folder.clone().filter(|f| !f.trim().is_empty()).unwrap_or_else(|| state_guard.settings.download_folder.clone());
+        let job = mirror::new_job(&request, folder)?;
+        state_guard.mirrors.push(job.clone());
+        job
     };
-    let save_path = payload.custom_path.unwrap_or(default_save_path);
-    let new_task = DownloadTask {
-        id: id.clone(), url: payload.url, status: DownloadStatus::Queued, progress: 0.0,
-        file_name: payload.file_name, save_path, total_size: payload.total_size.unwrap_or(0),
-        downloaded_size: 0, speed: 0, time_remaining: None, resume_capability: false,
-        error_message: None, created_at: Local::now(), completed_at: None,
-        file_type, connections: max_connections,
-        resume_attempts: 0, // NEW: Initialize to 0
+    request_save(&state);
+    let cancel = CancellationToken::new();
+    state.mirror_crawls.lock().await.insert(job.id.clone(), cancel.clone());
+    tauri::async_runtime::spawn(mirror::run(app_handle, job.clone(), cancel));
+    Ok(job)
+}
+
+// Stops the crawl; files it already queued keep downloading
+#[tauri::command]
+async fn cancel_mirror(id: String, state: State<'_, AppState>) -> Result<(), String> {
+    let cancel = state.mirror_crawls.lock().await.get(&id).cloned().ok_or("Mirror is not crawling")?;
+    cancel.cancel();
+    Ok(())
+}
+
+// Forgets the job; its tasks stay in the download list as ordinary downloads
+#[tauri::command]
+async fn remove_mirror(id: String, state: State<'_, AppState>) -> Result<(), String> {
+    if let Some(cancel) = state.mirror_crawls.lock().await.remove(&id) { cancel.cancel(); }
+    {
+        let mut state_guard = state.persistent.lock().await;
+        let before = state_guard.mirrors.len();
+        state_guard.mirrors.retain(|m| m.id != id);
+        if state_guard.mirrors.len() == before { return Err("Mirror not found".to_string()); }
+    }
+    request_save(&state);
+    Ok(())
+}
+
+#[tauri::command]
+async fn list_subscriptions(state: State<'_, AppState>) -> Result<Vec<subscriptions::Subscription>, String> {
+    Ok(state.persistent.lock().await.subscriptions.clone())
+}
+
+// Checked right away, so the feed title fills in and the latest `initial_items` episodes are queued
+#[tauri::command]
+async fn add_subscription(
+    feed_url: String, folder: String, filename_template: String, interval_minutes: u32, initial_items: usize,
+    state: State<'_, AppState>, app_handle: AppHandle,
+) -> Result<subscriptions::Subscription, String> {
+    let feed_url = feed_url.trim().to_string();
+    Url::parse(&feed_url).map_err(|e| format!("Invalid URL: {}", e))?;
+    let subscription = subscriptions::Subscription {
+        id: format!("subscription-{}", uuid::Uuid::new_v4()), feed_url, title: String::new(), folder: folder.trim().to_string(),
+        filename_template: filename_template.trim().to_string(), interval_minutes: interval_minutes.max(1), initial_items,
+        enabled: true, last_checked: None, error: None, seen: Vec::new(),
+    };
+    {
+        let mut state_guard = state.persistent.lock().await;
+        if state_guard.subscriptions.iter().any(|s| s.feed_url == subscription.feed_url) {
+            return Err("Already subscribed to this feed".to_string());
+        }
+        state_guard.subscriptions.push(subscription.clone());
+    }
+    request_save(&state);
+    let id = subscription.id.clone();
+    tauri::async_runtime::spawn(async move {
+        if let Err(e) = subscriptions::check(&app_handle, &id).await { log::warn!("Feed check failed for {}: {}", id, e); }
+    });
+    Ok(subscription)
+}
+
+// Only the user-editable fields are taken; `seen` and the check results stay as they are
+#[tauri::command]
+async fn update_subscription(subscription: subscriptions::Subscription, state: State<'_, AppState>) -> Result<subscriptions::Subscription, String> {
+    let updated = {
+        let mut state_guard = state.persistent.lock().await;
+        let existing = state_guard.subscriptions.iter_mut().find(|s| s.id == subscription.id).ok_or("Subscription not found")?;
+        existing.title = subscription.title.trim().to_string();
+        existing.folder = subscription.folder.trim().to_string();
+        existing.filename_template = subscription.filename_template.trim().to_string();
+        existing.interval_minutes = subscription.interval_minutes.max(1);
+        existing.enabled = subscription.enabled;
+        existing.clone()
+    };
+    request_save(&state);
+    Ok(updated)
+}
+
+#[tauri::command]
+async fn remove_subscription(id: String, state: State<'_, AppState>) -> Result<(), String> {
+    {
+        let mut state_guard = state.persistent.lock().await;
+        let before = state_guard.subscriptions.len();
+        state_guard.subscriptions.retain(|s| s.id != id);
+        if state_guard.subscriptions.len() == before { return Err("Subscription not found".to_string()); }
+    }
+    request_save(&state);
+    Ok(())
+}
+
+// Returns how many new episodes were queued
+#[tauri::command]
+async fn check_subscription(id: String, app_handle: AppHandle) -> Result<usize, String> {
+    subscriptions::check(&app_handle, &id).await
+}
+
+async fn probe_download_info(client: &Client, url: &str) -> Result<DownloadInfo, String> {
+    probe_download_info_with_headers(client, url, &BTreeMap::new()).await
+}
+
+// Follows HTTP redirects and meta-refresh interstitials, recording every hop
+async fn probe_download_info_with_headers(client: &Client, url: &str, headers: &BTreeMap<String, String>) -> Result<DownloadInfo, String> {
+    let mut chain = vec![url.to_string()];
+    loop {
+        let current = chain.last().cloned().unwrap_or_default();
+        let (result, hops) = REDIRECT_CHAIN.scope(RefCell::new(Vec::new()), async {
+            let result = probe_once(client, &current, headers).await;
+            (result, REDIRECT_CHAIN.with(|chain| chain.take()))
+        }).await;
+        let (mut info, is_html) = result?;
+        // The policy's chain starts with the URL the request was made for
+        chain.extend(hops.into_iter().skip(1));
+        if is_html {
+            if let Some(target) = meta_refresh_target(client, &info.final_url, headers).await {
+                if chain.len() > MAX_REDIRECTS { return Err("Too many redirects".to_string()); }
+                chain.push(target);
+                continue;
+            }
+        }
+        info.redirect_warning = redirect_warning(&chain);
+        if chain.len() > 1 { info.redirect_chain = chain; }
+        return Ok(info);
+    }
+}
+
+// The target of a `<meta http-equiv="refresh" content="0; url=...">` page, as "please wait" interstitials use
+async fn meta_refresh_target(client: &Client, page_url: &str, headers: &BTreeMap<String, String>) -> Option<String> {
+    let response = with_headers(client.get(page_url), headers).timeout(Duration::from_secs(20)).send().await.ok()?;
+    let html = read_capped_body(response, MAX_ERROR_PAGE_BYTES).await;
+    let content = {
+        use kuchikiki::traits::TendrilSink;
+        let document = kuchikiki::parse_html().one(html);
+        let meta = document.select("meta[http-equiv][content]").ok()?
+            .find(|m| m.attributes.borrow().get("http-equiv").is_some_and(|v| v.eq_ignore_ascii_case("refresh")))?;
+        let content = meta.attributes.borrow().get("content")?.to_string();
+        content
+    };
+    let lower = content.to_ascii_lowercase();
+    let target = content[lower.find("url=")? + 4..].trim().trim_matches(['\'', '"']);
+    let target = Url::parse(page_url).ok()?.join(target).ok()?;
+    matches!(target.scheme(), "http" | "https").then(|| target.to_string())
+}
+
+// Registrable domain, approximated as the last two labels
+fn site_of(url: &Url) -> Option<String> {
+    let host = url.host_str()?.trim_start_matches("www.");
+    let labels: Vec<&str> = host.rsplitn(3, '.').collect();
+    Some(labels.iter().take(2).rev().copied().collect::<Vec<_>>().join("."))
+}
+
+// Flags chains that drop to plain HTTP or end up on a different site than the one linked to.
+// Shortener hops don't count as the origin, since leaving them is their whole job.
+fn redirect_warning(chain: &[String]) -> Option<String> {
+    let urls: Vec<Url> = chain.iter().filter_map(|u| Url::parse(u).ok()).collect();
+    if let Some(pair) = urls.windows(2).find(|pair| pair[0].scheme() == "https" && pair[1].scheme() == "http") {
+        return Some(format!("Redirect from {} downgraded to unencrypted HTTP", pair[0].host_str().unwrap_or_default()));
+    }
+    let origin = urls.iter().find(|u| u.host_str().is_none_or(|host| !URL_SHORTENERS.contains(&host.trim_start_matches("www."))))?;
+    let (origin_site, final_site) = (site_of(origin)?, site_of(urls.last()?)?);
+    (origin_site != final_site).then(|| format!("Redirected from {} to a different site: {}", origin_site, final_site))
+}
+
+async fn probe_once(client: &Client, url: &str, headers: &BTreeMap<String, String>) -> Result<(DownloadInfo, bool), String> {
+    // Probe with HEAD first so hosts that meter GETs don't count this as a download
+    let head = client.head(url)
+        .header("Accept", "text/html,application/xhtml+xml,application/xml;q=0.9,image/avif,image/webp,*/*;q=0.8")
+        .header("Accept-Language", "en-US,en;q=0.5")
+        .header("Referer", url) // Add a Referer header
+        .timeout(Duration::from_secs(20));
+    let head = with_headers(head, headers)
+        .send()
+        .await;
+
+    let (response, total_size) = match head {
+        Ok(resp) if resp.status().is_success() && header_content_length(resp.headers()).is_some() => {
+            let total_size = header_content_length(resp.headers());
+            (resp, total_size)
+        }
+        _ => {
+            // Some servers reject HEAD or omit the length; ask for a single byte instead
+            let _ = REDIRECT_CHAIN.try_with(|chain| chain.borrow_mut().clear());
+            let resp = client.get(url)
+                .header("Accept", "text/html,application/xhtml+xml,application/xml;q=0.9,image/avif,image/webp,*/*;q=0.8")
+                .header("Accept-Language", "en-US,en;q=0.5")
+                .header("Referer", url)
+                .header("Range", "bytes=0-0")
+                .timeout(Duration::from_secs(20));
+            let resp = with_headers(resp, headers)
+                .send()
+                .await
+                .map_err(|e| format!("Request failed: {}", e))?;
+            let total_size = if resp.status() == reqwest::StatusCode::PARTIAL_CONTENT {
+                content_range_total(resp.headers())
+            } else {
+                header_content_length(resp.headers())
+            };
+            (resp, total_size)
+        }
+    };
+
+    if !response.status().is_success() {
+        return Err(format!("Server returned error: {}", response.status()));
+    }
+
+    let final_url = response.url().to_string();
+    let file_name = get_filename_from_response(&response, response.url());
+    let file_type = get_file_type(&file_name);
+    let etag = header_etag(response.headers());
+    let is_html = response.headers().get(reqwest::header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.trim_start().starts_with("text/html"));
+    // Dropping the response here closes the connection without reading the body
+    drop(response);
+
+    let info = DownloadInfo {
+        final_url, file_name, total_size, file_type, etag, resolved_by: None, headers: BTreeMap::new(),
+        redirect_chain: Vec::new(), redirect_warning: None,
+    };
+    Ok((info, is_html))
+}
+
+// Checks many links at once without downloading them. Each input is a task id (checked with the
+// task's headers) or a URL.
+#[tauri::command]
+async fn check_links(ids_or_urls: Vec<String>, state: State<'_, AppState>) -> Result<Vec<LinkCheck>, String> {
+    let targets: Vec<(String, String, BTreeMap<String, String>)> = {
+        let state_guard = state.persistent.lock().await;
+        ids_or_urls.into_iter().map(|input| {
+            match state_guard.downloads.iter().find(|t| t.id == input) {
+                Some(task) => (input, task.url.clone(), task.headers.clone()),
+                None => (input.clone(), input, BTreeMap::new()),
+            }
+        }).collect()
+    };
+    let client = http_client(&state).await;
+    let settings = state.persistent.lock().await.settings.clone();
+    Ok(futures::stream::iter(targets)
+        .map(|(input, url, headers)| {
+            let (client, settings) = (&client, &settings);
+            async move {
+                match request_url(settings, &url).await {
+                    Ok(url) => check_link(client, input, url, &headers).await,
+                    Err(e) => LinkCheck {
+                        input, url, state: LinkState::Unknown, status: None, total_size: None, file_name: None, error: Some(e),
+                    },
+                }
+            }
+        })
+        .buffered(SCAN_PROBE_CONCURRENCY)
+        .collect()
+        .await)
+}
+
+async fn check_link(client: &Client, input: String, url: String, headers: &BTreeMap<String, String>) -> LinkCheck {
+    let mut check = LinkCheck { input, url, state: LinkState::Unknown, status: None, total_size: None, file_name: None, error: None };
+    if let Err(e) = Url::parse(&check.url) {
+        check.state = LinkState::Dead;
+        check.error = Some(format!("Invalid URL: {}", e));
+        return check;
+    }
+    let head = with_headers(client.head(&check.url), headers).timeout(Duration::from_secs(20)).send().await;
+    let response = match head {
+        Ok(resp) if resp.status().is_success() => Ok(resp),
+        // Some servers reject HEAD; a one-byte range costs next to nothing
+        _ => with_headers(client.get(&check.url), headers).header("Range", "bytes=0-0").timeout(Duration::from_secs(20)).send().await,
+    };
+    match response {
+        Ok(resp) => {
+            let status = resp.status();
+            check.status = Some(status.as_u16());
+            if status.is_success() {
+                check.state = LinkState::Alive;
+                check.total_size = if status == reqwest::StatusCode::PARTIAL_CONTENT {
+                    content_range_total(resp.headers())
+                } else {
+                    header_content_length(resp.headers())
+                };
+                check.file_name = Some(get_filename_from_response(&resp, resp.url()));
+            } else {
+                // Auth and rate-limit answers say nothing about whether the file still exists
+                if status.is_client_error() && !matches!(status.as_u16(), 401 | 403 | 429) { check.state = LinkState::Dead; }
+                check.error = Some(format!("Server returned error: {}", status));
+            }
+        }
+        Err(e) => check.error = Some(format!("Request failed: {}", e)),
+    }
+    check
+}
+
+// The assets of a GitHub release, for picking which ones to queue with `add_downloads`
+#[tauri::command]
+async fn get_github_release(input: String, state: State<'_, AppState>) -> Result<github::GithubRelease, String> {
+    let (repo, tag) = github::parse_release_ref(&input).ok_or("Not a GitHub repository or release link")?;
+    let token = state.persistent.lock().await.settings.github_token.clone();
+    github::fetch_release(&http_client(&state).await, &repo, tag.as_deref(), &token).await
+}
+
+// The files of a Hugging Face repository, for picking which ones to queue with `add_downloads`.
+// The token comes back as `headers` so the picked files can be fetched with it.
+#[tauri::command]
+async fn get_huggingface_repo(input: String, state: State<'_, AppState>) -> Result<HuggingfaceListing, String> {
+    let (repo, revision) = huggingface::parse_repo_ref(&input).ok_or("Not a Hugging Face repository link")?;
+    let token = state.persistent.lock().await.settings.huggingface_token.trim().to_string();
+    let listing = huggingface::list_files(&http_client(&state).await, &repo, &revision, &token).await?;
+    let headers = if token.is_empty() { BTreeMap::new() } else { [("Authorization".to_string(), format!("Bearer {}", token))].into() };
+    Ok(HuggingfaceListing { listing, headers })
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct HuggingfaceListing {
+    #[serde(flatten)]
+    listing: huggingface::HfRepo,
+    headers: BTreeMap<String, String>,
+}
+
+// Fetches a page and returns the file links on it, for an "add all links on this page" dialog.
+// Links whose size can't be determined are kept even when size bounds are set.
+#[tauri::command]
+async fn scan_page(url: String, filters: Option<ScanFilters>, state: State<'_, AppState>) -> Result<Vec<LinkCandidate>, String> {
+    let filters = filters.unwrap_or_default();
+    let client = http_client(&state).await;
+    let response = client.get(&url)
+        .header("Accept", "text/html,application/xhtml+xml,application/xml;q=0.9,*/*;q=0.8")
+        .timeout(Duration::from_secs(20))
+        .send()
+        .await
+        .map_err(|e| format!("Request failed: {}", e))?;
+    if !response.status().is_success() {
+        return Err(format!("Server returned error: {}", response.status()));
+    }
+    let page_url = response.url().clone();
+    let html = read_capped_body(response, MAX_SCAN_PAGE_BYTES).await;
+
+    let candidates: Vec<LinkCandidate> = extract_links(&html, &page_url).into_iter()
+        .filter(|c| filters.matches_extension(&c.file_name))
+        .collect();
+    if filters.min_size.is_none() && filters.max_size.is_none() {
+        return Ok(candidates);
+    }
+
+    let sized: Vec<LinkCandidate> = futures::stream::iter(candidates)
+        .map(|mut candidate| {
+            let client = &client;
+            async move {
+                if let Ok(info) = probe_download_info(client, &candidate.url).await {
+                    candidate.total_size = info.total_size;
+                    candidate.file_type = info.file_type;
+                    candidate.file_name = info.file_name;
+                }
+                candidate
+            }
+        })
+        .buffered(SCAN_PROBE_CONCURRENCY)
+        .collect()
+        .await;
+    Ok(sized.into_iter().filter(|c| filters.matches_size(c.total_size)).collect())
+}
+
+// The images a gallery download would fetch, for previewing the selection
+#[tauri::command]
+async fn scan_gallery(request: gallery::GalleryRequest, state: State<'_, AppState>) -> Result<gallery::Gallery, String> {
+    gallery::scan(&http_client(&state).await, &request).await
+}
+
+#[tauri::command]
+async fn download_gallery(request: gallery::GalleryRequest, state: State<'_, AppState>, app_handle: AppHandle) -> Result<Vec<AddDownloadResult>, String> {
+    let found = gallery::scan(&http_client(&state).await, &request).await?;
+    let folder = match request.folder.filter(|f| !f.trim().is_empty()) {
+        Some(folder) => folder,
+        None => state.persistent.lock().await.settings.download_folder.clone(),
+    };
+    add_downloads(gallery::payloads(&found, &folder), state, app_handle).await
+}
+
+// One level of an open directory ("Index of /..." page), for browsing and picking files
+#[tauri::command]
+async fn browse_directory(url: String, state: State<'_, AppState>) -> Result<autoindex::DirectoryListing, String> {
+    autoindex::browse(&http_client(&state).await, &url).await
+}
+
+// Every file below an FTP, FTPS or SFTP directory, for the add dialog to pick from; None for a file
+#[tauri::command]
+async fn list_remote_directory(url: String, state: State<'_, AppState>, app_handle: AppHandle) -> Result<Option<remote::RemoteTree>, String> {
+    let settings = state.persistent.lock().await.settings.clone();
+    host_rules::HostRules::from_settings(&settings).check_str(&url)?;
+    remote::list_tree(&app_handle, &url).await
+}
+
+// Queues every file of an open directory that passes `filters`, subdirectories included when
+// `recursive`, keeping the directory structure under the save folder
+#[tauri::command]
+async fn enqueue_directory(
+    url: String, recursive: bool, filters: Option<ScanFilters>, custom_path: Option<String>,
+    state: State<'_, AppState>, app_handle: AppHandle,
+) -> Result<Vec<AddDownloadResult>, String> {
+    let folder = match custom_path.filter(|p| !p.trim().is_empty()) {
+        Some(folder) => folder,
+        None => state.persistent.lock().await.settings.download_folder.clone(),
+    };
+    let payloads = autoindex::collect_files(&http_client(&state).await, &url, recursive, &filters.unwrap_or_default(), &folder).await?;
+    if payloads.is_empty() { return Err("No files matched".to_string()); }
+    add_downloads(payloads, state, app_handle).await
+}
+
+// Pulls the URLs out of `a`, `img`, `video` and `source` tags, resolved against the page (or its
+// `<base href>`), deduplicated in page order. Only http(s) links are kept.
+fn extract_links(html: &str, page_url: &Url) -> Vec<LinkCandidate> {
+    use kuchikiki::traits::TendrilSink;
+    let document = kuchikiki::parse_html().one(html);
+    let base = document.select_first("base[href]").ok()
+        .and_then(|base| base.attributes.borrow().get("href").and_then(|href| page_url.join(href.trim()).ok()))
+        .unwrap_or_else(|| page_url.clone());
+    let Ok(elements) = document.select("a[href], img[src], video[src], source[src]") else { return Vec::new() };
+
+    let mut seen = std::collections::HashSet::new();
+    let mut links = Vec::new();
+    for element in elements {
+        let tag = element.name.local.to_string();
+        let attributes = element.attributes.borrow();
+        let Some(raw) = attributes.get(if tag == "a" { "href" } else { "src" }) else { continue };
+        let Ok(mut link) = base.join(raw.trim()) else { continue };
+        if !matches!(link.scheme(), "http" | "https") { continue; }
+        link.set_fragment(None);
+        if !seen.insert(link.to_string()) { continue; }
+        let text = if tag == "a" {
+            element.as_node().text_contents()
+        } else {
+            attributes.get("alt").or_else(|| attributes.get("title")).unwrap_or_default().to_string()
+        };
+        let text = Some(text.split_whitespace().collect::<Vec<_>>().join(" ")).filter(|t| !t.is_empty());
+        let file_name = file_name_from_url(&link).unwrap_or_else(|| link.host_str().unwrap_or("download").to_string());
+        links.push(LinkCandidate {
+            file_type: get_file_type(&file_name), url: link.to_string(), tag, text, file_name, total_size: None,
+        });
+    }
+    links
+}
+
+#[tauri::command]
+async fn choose_download_folder(app_handle: AppHandle) -> Result<String, String> {
+    let (tx, rx) = oneshot::channel();
+    app_handle.dialog().file().set_title("Choose Download Folder")
+        .pick_folder(move |folder_path: Option<FilePath>| {
+            let _ = tx.send(folder_path);
+        });
+    match rx.await {
+        Ok(Some(file_path)) => match file_path.as_path() {
+            Some(path) => Ok(path.to_string_lossy().to_string()),
+            None => Err("Invalid path returned from dialog".to_string()),
+        },
+        Ok(None) => Err("No folder selected".to_string()),
+        Err(_) => Err("Dialog was cancelled".to_string()),
+    }
+}
+
+// This command now correctly receives the final URL from the info-fetch step
+#[tauri::command]
+async fn add_download(payload: AddDownloadPayload, state: State<'_, AppState>, app_handle: AppHandle) -> Result<DownloadTask, String> {
+    let (new_task, auto_start) = {
+        let mut state_guard = state.persistent.lock().await;
+        let mut seen = queued_downloads(&state_guard.downloads);
+        let new_task = claim_download(&mut seen, new_download_task(payload, &state_guard.settings)?)?;
+        state_guard.downloads.push(new_task.clone());
+        (new_task, state_guard.settings.auto_start)
+    };
+    let id = new_task.id.clone();
+    request_save(&state);
+    app_handle.emit("task_updated", &new_task).unwrap();
+    if auto_start { start_download_task(id, app_handle.clone()).await?; }
+    Ok(new_task)
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct AddDownloadResult { url: String, task: Option<DownloadTask>, error: Option<String> }
+
+// Creates many tasks under one lock with one save and one `tasks_added` event, reporting per-item results.
+// URLs already queued (or repeated within the batch) for the same folder are skipped.
+#[tauri::command]
+async fn add_downloads(payloads: Vec<AddDownloadPayload>, state: State<'_, AppState>, app_handle: AppHandle) -> Result<Vec<AddDownloadResult>, String> {
+    let mut results = Vec::with_capacity(payloads.len());
+    let mut added = Vec::new();
+    let auto_start = {
+        let mut state_guard = state.persistent.lock().await;
+        let mut seen = queued_downloads(&state_guard.downloads);
+        for payload in payloads {
+            let url = payload.url.clone();
+            let task = Url::parse(&url)
+                .map_err(|e| format!("Invalid URL: {}", e))
+                .and_then(|_| new_download_task(payload, &state_guard.settings))
+                .and_then(|task| claim_download(&mut seen, task));
+            match task {
+                Ok(task) => {
+                    added.push(task.clone());
+                    results.push(AddDownloadResult { url, task: Some(task), error: None });
+                }
+                Err(error) => results.push(AddDownloadResult { url, task: None, error: Some(error) }),
+            }
+        }
+        state_guard.downloads.extend(added.iter().cloned());
+        state_guard.settings.auto_start
+    };
+    if added.is_empty() { return Ok(results); }
+    request_save(&state);
+    app_handle.emit("tasks_added", &added).unwrap();
+    if auto_start {
+        for task in added { start_download_task(task.id, app_handle.clone()).await?; }
+    }
+    Ok(results)
+}
+
+// A download is a duplicate of one already queued when it fetches the same canonical URL into the same folder
+fn queued_downloads(downloads: &[DownloadTask]) -> std::collections::HashSet<(String, String)> {
+    downloads.iter().map(|t| (canonical_url(&t.url), t.save_path.clone())).collect()
+}
+fn claim_download(seen: &mut std::collections::HashSet<(String, String)>, task: DownloadTask) -> Result<DownloadTask, String> {
+    if seen.insert((canonical_url(&task.url), task.save_path.clone())) { Ok(task) } else { Err("Duplicate download".to_string()) }
+}
+
+fn new_download_task(mut payload: AddDownloadPayload, settings: &AppSettings) -> Result<DownloadTask, String> {
+    site_templates::apply(&settings.site_templates, &mut payload);
+    if let Some(checksum) = &payload.checksum {
+        checksum_hasher(checksum).ok_or_else(|| format!("Unsupported checksum format: {}", checksum))?;
+    }
+    if let Some(signature) = &payload.signature { signature.validate()?; }
+    if let Some(proxy) = &payload.proxy {
+        reqwest::Proxy::all(proxy.as_str()).map_err(|e| format!("Invalid proxy {}: {}", proxy, e))?;
+    }
+    // A bodiless GET is an ordinary download
+    let request = match payload.request {
+        Some(request) => {
+            let method = request.method()?;
+            (method != reqwest::Method::GET || request.body.is_some()).then(|| CustomRequest { method: method.to_string(), ..request })
+        }
+        None => None,
+    };
+    let source_url = payload.source_url.take().filter(|source| *source != payload.url);
+    let host_rules = host_rules::HostRules::from_settings(settings);
+    for url in payload.redirect_chain.iter().chain(std::iter::once(&payload.url)) {
+        host_rules.check_str(url)?;
+        if settings.https_only && !payload.allow_http && is_plain_http(url) {
+            return Err(format!("{}: {} is plain HTTP; allow HTTP for this download to add it anyway", HTTPS_ONLY_ERROR, url));
+        }
+    }
+    let file_type = get_file_type(&payload.file_name);
+    let save_path = payload.custom_path.unwrap_or_else(|| settings.download_folder.clone());
+    Ok(DownloadTask {
+        id: format!("task-{}", uuid::Uuid::new_v4()), url: payload.url, status: DownloadStatus::Queued, progress: 0.0,
+        file_name: payload.file_name, save_path, total_size: payload.total_size.unwrap_or(0),
+        downloaded_size: 0, speed: 0, time_remaining: None, resume_capability: false,
+        error_message: None, created_at: Local::now(), completed_at: None,
+        file_type, connections: settings.max_connections_per_download,
+        resume_attempts: 0, // NEW: Initialize to 0
+        error_page: None,
+        checksum: payload.checksum,
+        etag: None,
+        segments: Vec::new(),
+        headers: payload.headers,
+        proxy: payload.proxy,
+        request,
+        source_url,
+        redirect_chain: payload.redirect_chain,
+        parent_id: payload.parent_id,
+        extraction: None,
+        signature: payload.signature,
+        virus_scan: None,
+        verification: None,
+        content_hash: None,
+        duplicate_of: None,
+        allow_http: payload.allow_http,
+        allow_metered: false,
+        repeat: None,
+        watch: None,
+        depends_on: Vec::new(),
+        working_dir: None,
+        size_confirmed: false,
+        ignore_quota: false,
+        when_idle: false,
+        auth_challenge: None,
+    })
+}
+
+// Reads an aria2 input file: a URI line (tab-separated mirrors of one file, of which the first is
+// used) followed by indented `key=value` options. `dir`, `out`, `header`, `referer`, `user-agent` and
+// `checksum` map onto task fields; other options are ignored. Relative dirs resolve against `base_dir`.
+fn parse_aria2_input(content: &str, base_dir: &str) -> Vec<Result<AddDownloadPayload, (String, String)>> {
+    let mut entries: Vec<(String, Vec<(&str, &str)>)> = Vec::new();
+    let mut results = Vec::new();
+    for line in content.lines() {
+        if line.trim().is_empty() || line.trim_start().starts_with('#') { continue; }
+        if !line.starts_with([' ', '\t']) {
+            let url = line.split('\t').map(str::trim).find(|u| !u.is_empty()).unwrap_or_default();
+            entries.push((url.to_string(), Vec::new()));
+        } else if let Some((_, options)) = entries.last_mut() {
+            if let Some((key, value)) = line.trim().split_once('=') { options.push((key.trim(), value.trim())); }
+        } else {
+            results.push(Err((line.trim().to_string(), "Option before any URI".to_string())));
+        }
+    }
+
+    for (url, options) in entries {
+        let parsed = match Url::parse(&url) {
+            Ok(parsed) => parsed,
+            Err(e) => { results.push(Err((url, format!("Invalid URL: {}", e)))); continue; }
+        };
+        let mut dir = PathBuf::from(base_dir);
+        let mut out = None;
+        let mut payload = AddDownloadPayload {
+            file_name: file_name_from_url(&parsed).unwrap_or_else(|| format!("download_{}.tmp", Local::now().timestamp())),
+            url, total_size: None, custom_path: None, checksum: None, headers: BTreeMap::new(), redirect_chain: Vec::new(), parent_id: None, signature: None, allow_http: false, proxy: None, request: None, source_url: None,
+        };
+        for (key, value) in options {
+            match key {
+                "dir" => dir = PathBuf::from(base_dir).join(value),
+                "out" => out = Some(value),
+                "header" => if let Some((name, header_value)) = value.split_once(':') {
+                    payload.headers.insert(name.trim().to_string(), header_value.trim().to_string());
+                },
+                "referer" => { payload.headers.insert("Referer".to_string(), value.to_string()); }
+                "user-agent" => { payload.headers.insert("User-Agent".to_string(), value.to_string()); }
+                // aria2 writes `sha-256=<hex>`; ours is `sha256:<hex>`
+                "checksum" => if let Some((kind, digest)) = value.split_once('=') {
+                    payload.checksum = Some(format!("{}:{}", kind.replace('-', "").to_ascii_lowercase(), digest));
+                },
+                _ => {}
+            }
+        }
+        // `out` may carry subfolders, which go under `dir`
+        if let Some(out) = out.map(std::path::Path::new) {
+            if let Some(name) = out.file_name() { payload.file_name = name.to_string_lossy().to_string(); }
+            if let Some(parent) = out.parent() { dir = dir.join(parent); }
+        }
+        if dir != std::path::Path::new(base_dir) { payload.custom_path = Some(dir.to_string_lossy().to_string()); }
+        results.push(Ok(payload));
+    }
+    results
+}
+
+#[tauri::command]
+async fn choose_import_file(title: Option<String>, app_handle: AppHandle) -> Result<String, String> {
+    let (tx, rx) = oneshot::channel();
+    app_handle.dialog().file().set_title(title.unwrap_or_else(|| "Import Download List".to_string()))
+        .pick_file(move |file_path: Option<FilePath>| {
+            let _ = tx.send(file_path);
+        });
+    match rx.await {
+        Ok(Some(file_path)) => match file_path.as_path() {
+            Some(path) => Ok(path.to_string_lossy().to_string()),
+            None => Err("Invalid path returned from dialog".to_string()),
+        },
+        Ok(None) => Err("No file selected".to_string()),
+        Err(_) => Err("Dialog was cancelled".to_string()),
+    }
+}
+
+// Queues every entry of an aria2 input file through `add_downloads`; malformed entries are reported
+// alongside the per-URL results rather than failing the whole import
+#[tauri::command]
+async fn import_aria2_file(path: String, state: State<'_, AppState>, app_handle: AppHandle) -> Result<Vec<AddDownloadResult>, String> {
+    let content = tokio::fs::read_to_string(&path).await.map_err(|e| format!("Failed to read {}: {}", path, e))?;
+    let base_dir = state.persistent.lock().await.settings.download_folder.clone();
+    let (payloads, errors): (Vec<_>, Vec<_>) = parse_aria2_input(&content, &base_dir).into_iter().partition(Result::is_ok);
+    let mut results = add_downloads(payloads.into_iter().flatten().collect(), state, app_handle).await?;
+    results.extend(errors.into_iter().filter_map(Result::err).map(|(url, error)| AddDownloadResult { url, task: None, error: Some(error) }));
+    Ok(results)
+}
+
+// Expands a DLC container into queued tasks, one subfolder per named package
+#[tauri::command]
+async fn import_dlc_file(path: String, state: State<'_, AppState>, app_handle: AppHandle) -> Result<Vec<AddDownloadResult>, String> {
+    let content = tokio::fs::read_to_string(&path).await.map_err(|e| format!("Failed to read {}: {}", path, e))?;
+    let packages = dlc::decrypt(&http_client(&state).await, &content).await?;
+    let download_folder = state.persistent.lock().await.settings.download_folder.clone();
+    let payloads = packages.into_iter().flat_map(|package| {
+        let custom_path = package.name.filter(|name| is_valid_file_name(name))
+            .map(|name| PathBuf::from(&download_folder).join(name).to_string_lossy().to_string());
+        package.files.into_iter().map(move |file| {
+            let file_name = file.file_name.filter(|name| is_valid_file_name(name))
+                .or_else(|| Url::parse(&file.url).ok().as_ref().and_then(file_name_from_url))
+                .unwrap_or_else(|| format!("download_{}.tmp", Local::now().timestamp()));
+            AddDownloadPayload {
+                url: file.url, file_name, total_size: file.size, custom_path: custom_path.clone(), checksum: None,
+                headers: BTreeMap::new(), redirect_chain: Vec::new(), parent_id: None, signature: None, allow_http: false, proxy: None, request: None, source_url: None,
+            }
+        })
+    }).collect();
+    add_downloads(payloads, state, app_handle).await
+}
+
+#[tauri::command]
+async fn get_all_downloads(state: State<'_, AppState>) -> Result<Vec<DownloadTask>, String> { Ok(state.persistent.lock().await.downloads.clone()) }
+// Writes the downloads matching `filter` to `path` as CSV or JSON; returns how many there were
+#[tauri::command]
+async fn export_history(format: history::ExportFormat, path: String, filter: Option<history::HistoryFilter>, state: State<'_, AppState>) -> Result<usize, String> {
+    let (content, count) = {
+        let state_guard = state.persistent.lock().await;
+        history::export(&state_guard.downloads, format, &filter.unwrap_or_default(), state_guard.settings.redact_saved_secrets)?
+    };
+    tokio::fs::write(&path, content).await.map_err(|e| format!("Could not write {}: {}", path, e))?;
+    Ok(count)
+}
+// Writes a zip of the app logs, the download logs and the state with secrets masked, for a bug report
+#[tauri::command]
+async fn collect_diagnostics(path: String, state: State<'_, AppState>, app_handle: AppHandle) -> Result<(), String> {
+    let mut snapshot = {
+        let state_guard = state.persistent.lock().await;
+        let mut value = serde_json::to_value(&*state_guard).map_err(|e| e.to_string())?;
+        value["downloads"] = serde_json::to_value(state_guard.downloads.iter().map(redact::task).collect::<Vec<_>>()).map_err(|e| e.to_string())?;
+        value
+    };
+    redact::json(&mut snapshot);
+    let snapshot = serde_json::to_string_pretty(&snapshot).map_err(|e| e.to_string())?;
+    logging::collect_diagnostics(state.logging.as_ref(), tasklog::dir(&app_handle), snapshot, PathBuf::from(path)).await
+}
+fn backup_paths(state: &AppState, app_handle: &AppHandle) -> Result<backup::DataPaths, String> {
+    Ok(backup::DataPaths {
+        state: get_state_path(app_handle).map_err(|e| e.to_string())?,
+        logs: state.logging.as_ref().map(|logging| logging.dir().to_path_buf()),
+        task_logs: tasklog::dir(app_handle),
+    })
+}
+// Writes the state file and logs to one archive at `path`; returns how many files went in
+#[tauri::command]
+async fn backup_app_data(path: String, state: State<'_, AppState>, app_handle: AppHandle) -> Result<usize, String> {
+    flush_state(&app_handle).await;
+    backup::backup(backup_paths(&state, &app_handle)?, PathBuf::from(path)).await
+}
+// Checks the whole archive first, then stops downloads, puts its data in place and restarts the app
+#[tauri::command]
+async fn restore_app_data(path: String, state: State<'_, AppState>, app_handle: AppHandle) -> Result<(), String> {
+    let (mut restored, logs) = backup::read(PathBuf::from(path)).await?;
+    // Its masked values are in this machine's keyring, if anywhere
+    state.vault.unmask(&mut restored).await;
+    redact::mark_lost(&mut restored.downloads);
+    pause_running(&app_handle, "Restoring a backup").await;
+    *state.persistent.lock().await = restored;
+    flush_state(&app_handle).await;
+    backup::restore_logs(&backup_paths(&state, &app_handle)?, logs).await?;
+    // Clients, schedules and the rest are built from the state at startup
+    app_handle.restart();
+}
+#[tauri::command]
+async fn choose_save_file(title: String, file_name: String, app_handle: AppHandle) -> Result<String, String> {
+    let (tx, rx) = oneshot::channel();
+    app_handle.dialog().file().set_title(title).set_file_name(file_name)
+        .save_file(move |file_path: Option<FilePath>| {
+            let _ = tx.send(file_path);
+        });
+    match rx.await {
+        Ok(Some(file_path)) => match file_path.as_path() {
+            Some(path) => Ok(path.to_string_lossy().to_string()),
+            None => Err("Invalid path returned from dialog".to_string()),
+        },
+        Ok(None) => Err("No file selected".to_string()),
+        Err(_) => Err("Dialog was cancelled".to_string()),
+    }
+}
+// The task's log, or its last `tail` lines
+#[tauri::command]
+async fn get_task_log(id: String, tail: Option<usize>, app_handle: AppHandle) -> Result<Vec<String>, String> {
+    tasklog::read(&app_handle, &id, tail).await
+}
+#[tauri::command]
+async fn get_dashboard(state: State<'_, AppState>) -> Result<Dashboard, String> {
+    let state_guard = state.persistent.lock().await;
+    let mut counts = BTreeMap::new();
+    for task in &state_guard.downloads {
+        let status = serde_json::to_value(&task.status).ok().and_then(|v| v.as_str().map(str::to_string)).unwrap_or_default();
+        *counts.entry(status).or_insert(0) += 1;
+    }
+    let speed: u64 = state_guard.downloads.iter().filter(|t| t.status == DownloadStatus::Downloading).map(|t| t.speed).sum();
+    let remaining: Option<u64> = state_guard.downloads.iter()
+        .filter(|t| matches!(t.status, DownloadStatus::Downloading | DownloadStatus::Queued | DownloadStatus::Retrying))
+        .map(|t| (t.total_size > 0).then(|| t.total_size.saturating_sub(t.downloaded_size)))
+        .sum();
+    Ok(Dashboard {
+        speed,
+        queue_eta: remaining.and_then(|r| r.checked_div(speed)),
+        counts,
+        transferred_today: quota::transferred_today(&state, &state_guard),
+    })
+}
+// Average speeds over the last `window` seconds, for a speed graph
+#[tauri::command]
+async fn get_speed_history(id: String, window: u64, state: State<'_, AppState>) -> Result<speed_history::SpeedHistory, String> {
+    Ok(state.speed_history.get(&id, window))
+}
+// The task plus what its latest attempt saw on the wire, for debugging slow or failing downloads
+#[tauri::command]
+async fn get_task_details(id: String, state: State<'_, AppState>) -> Result<TaskDetails, String> {
+    let task = state.persistent.lock().await.downloads.iter().find(|t| t.id == id).cloned().ok_or("Download not found")?;
+    let mut diagnostics = state.diagnostics.lock().await.get(&id).cloned().unwrap_or_default();
+    let (downloaded, segments) = current_segments(&state, &task).await;
+    diagnostics.segments = if segments.is_empty() {
+        // Single-stream transfers are one segment from the start of the file
+        vec![SegmentInfo { start: 0, end: (task.total_size > 0).then_some(task.total_size), downloaded }]
+    } else {
+        segments.iter().map(|s| SegmentInfo { start: s.start, end: Some(s.end), downloaded: s.pos - s.start }).collect()
+    };
+    Ok(TaskDetails { task, diagnostics })
+}
+// Segment progress lives in the hot-path counters while running, in the task once stopped
+async fn current_segments(state: &AppState, task: &DownloadTask) -> (u64, Vec<Segment>) {
+    match state.live_progress.lock().await.get(&task.id) {
+        Some(live) => (live.downloaded.load(Ordering::Relaxed), live.segments()),
+        None => (task.downloaded_size, task.segments.clone()),
+    }
+}
+// The file cut into `blocks` equal parts (100 by default) with how much of each is on disk, for a
+// block-style progress view
+#[tauri::command]
+async fn get_segment_map(id: String, blocks: Option<u32>, state: State<'_, AppState>) -> Result<SegmentMap, String> {
+    let task = state.persistent.lock().await.downloads.iter().find(|t| t.id == id).cloned().ok_or("Download not found")?;
+    let (downloaded, segments) = current_segments(&state, &task).await;
+    let total_size = task.total_size;
+    // Byte ranges already written
+    let done: Vec<(u64, u64)> = if task.status == DownloadStatus::Completed {
+        vec![(0, total_size)]
+    } else if segments.is_empty() {
+        vec![(0, downloaded)]
+    } else {
+        segments.iter().map(|s| (s.start, s.pos)).collect()
+    };
+    let block_size = total_size.div_ceil(blocks.unwrap_or(100).clamp(1, MAX_MAP_BLOCKS) as u64);
+    let blocks = if total_size == 0 { Vec::new() } else {
+        // Rounding the block size up can leave fewer blocks than asked for, but never an empty one
+        (0..total_size.div_ceil(block_size)).map(|i| {
+            let (start, end) = (i * block_size, ((i + 1) * block_size).min(total_size));
+            let filled: u64 = done.iter().map(|(s, e)| (*e).min(end).saturating_sub((*s).max(start))).sum();
+            (filled * 100 / (end - start)) as u8
+        }).collect()
+    };
+    let active = segments.iter()
+        .filter(|s| s.active)
+        .map(|s| SegmentInfo { start: s.start, end: Some(s.end), downloaded: s.pos - s.start })
+        .collect();
+    Ok(SegmentMap { total_size, block_size, blocks, active })
+}
+
+#[derive(Debug, Deserialize, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+enum ExportFormat { Curl, Wget, Aria2c }
+
+// POSIX single quoting, so the line pastes safely into sh/bash/zsh
+fn shell_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', r"'\''"))
+}
+
+// An equivalent command line for finishing the download elsewhere, e.g. on a headless server
+#[tauri::command]
+async fn export_task_command(id: String, format: ExportFormat, state: State<'_, AppState>) -> Result<String, String> {
+    let (task, settings) = {
+        let state_guard = state.persistent.lock().await;
+        let task = state_guard.downloads.iter().find(|t| t.id == id).cloned().ok_or("Download not found")?;
+        (task, state_guard.settings.clone())
+    };
+    let mut headers = task.headers.clone();
+    if !headers.keys().any(|name| name.eq_ignore_ascii_case("cookie")) {
+        let url = Url::parse(&task.url).map_err(|e| format!("Invalid URL: {}", e))?;
+        if let Some(cookies) = state.cookie_jar.cookies(&url).and_then(|v| v.to_str().ok().map(str::to_string)) {
+            headers.insert("Cookie".to_string(), cookies);
+        }
+    }
+    if let Some(content_type) = task.request.as_ref().and_then(|r| r.content_type.clone()) {
+        headers.insert("Content-Type".to_string(), content_type);
+    }
+    let output = task_file_path(&task).to_string_lossy().to_string();
+    let proxy = task.proxy.as_deref().or(settings.proxy_url.as_deref()).map(str::trim).filter(|p| !p.is_empty());
+
+    // Every format resumes a partial file left at the output path, unless the request isn't a plain GET
+    let resume = task.request.is_none();
+    let mut args: Vec<String> = match format {
+        ExportFormat::Curl => {
+            let mut args = vec!["curl".to_string(), "-L".into()];
+            if resume { args.extend(["-C".into(), "-".into()]); }
+            args.extend(["-A".into(), shell_quote(&settings.user_agent), "-o".into(), shell_quote(&output)]);
+            args
+        }
+        ExportFormat::Wget => {
+            let mut args = vec!["wget".to_string()];
+            if resume { args.push("-c".into()); }
+            args.extend([format!("--user-agent={}", shell_quote(&settings.user_agent)), "-O".into(), shell_quote(&output)]);
+            args
+        }
+        ExportFormat::Aria2c => {
+            if !resume { return Err("aria2c can only make GET requests".to_string()); }
+            let mut args = vec![
+                "aria2c".to_string(), "-c".into(), format!("-x{}", task.connections.clamp(1, MAX_CONNECTIONS)),
+                format!("--user-agent={}", shell_quote(&settings.user_agent)),
+                format!("--dir={}", shell_quote(&task.save_path)), format!("--out={}", shell_quote(&task.file_name)),
+            ];
+            if let Some((hasher, digest)) = task.checksum.as_deref().and_then(checksum_hasher) {
+                let algorithm = if hasher.output_size() == 64 { "sha-512" } else { "sha-256" };
+                args.push(format!("--checksum={}", shell_quote(&format!("{}={}", algorithm, digest))));
+            }
+            args
+        }
+    };
+    for (name, value) in &headers {
+        let header = shell_quote(&format!("{}: {}", name, value));
+        args.push(match format {
+            ExportFormat::Curl => format!("-H {}", header),
+            ExportFormat::Wget | ExportFormat::Aria2c => format!("--header={}", header),
+        });
+    }
+    if let Some(request) = &task.request {
+        args.push(match format {
+            ExportFormat::Curl => format!("-X {}", shell_quote(&request.method)),
+            ExportFormat::Wget | ExportFormat::Aria2c => format!("--method={}", shell_quote(&request.method)),
+        });
+        if let Some(body) = &request.body {
+            args.push(match format {
+                ExportFormat::Curl => format!("--data-binary {}", shell_quote(body)),
+                ExportFormat::Wget | ExportFormat::Aria2c => format!("--body-data={}", shell_quote(body)),
+            });
+        }
+    }
+    if let Some(proxy) = proxy {
+        args.push(match format {
+            ExportFormat::Curl => format!("-x {}", shell_quote(proxy)),
+            ExportFormat::Wget => format!("-e use_proxy=yes -e https_proxy={} -e http_proxy={0}", shell_quote(proxy)),
+            ExportFormat::Aria2c => format!("--all-proxy={}", shell_quote(proxy)),
+        });
+    }
+    // `s3://` tasks export a presigned URL, which the tools can fetch without credentials
+    args.push(shell_quote(&request_url(&settings, &task.url).await?));
+    Ok(args.join(" "))
+}
+#[tauri::command]
+async fn get_settings(state: State<'_, AppState>) -> Result<AppSettings, String> { Ok(state.persistent.lock().await.settings.clone()) }
+#[tauri::command(rename_all = "camelCase")]
+async fn update_settings(settings: AppSettings, state: State<'_, AppState>, app_handle: AppHandle) -> Result<(), String> {
+    logging::check_level(&settings.log_level)?;
+    site_templates::validate(&settings.site_templates)?;
+    let (network_changed, api_changed, click_n_load_changed, autostart_changed, log_level_changed) = {
+        let current = &state.persistent.lock().await.settings;
+        (
+            current.user_agent != settings.user_agent || current.proxy_url != settings.proxy_url
+                || current.blocked_hosts != settings.blocked_hosts || current.allowed_hosts != settings.allowed_hosts
+                || current.https_only != settings.https_only || current.client_certificates != settings.client_certificates
+                || current.ip_family != settings.ip_family || current.bind_interface != settings.bind_interface
+                || current.trusted_certificates != settings.trusted_certificates,
+            (current.api_enabled, current.api_port, &current.api_token, current.api_allow_lan, current.metrics_enabled)
+                != (settings.api_enabled, settings.api_port, &settings.api_token, settings.api_allow_lan, settings.metrics_enabled),
+            current.click_n_load_enabled != settings.click_n_load_enabled,
+            current.autostart != settings.autostart,
+            current.log_level != settings.log_level,
+        )
+    };
+    if autostart_changed { autostart::apply(&app_handle, settings.autostart)?; }
+    if log_level_changed {
+        if let Some(logging) = &state.logging { logging.set_level(&settings.log_level)?; }
+    }
+    if network_changed {
+        let client = build_http_client(&settings, state.cookie_jar.clone()).map_err(|e| format!("Invalid network settings: {}", e))?;
+        let host_clients = build_host_clients(&settings, state.cookie_jar.clone()).map_err(|e| format!("Invalid network settings: {}", e))?;
+        *state.http_client.lock().await = client;
+        *state.host_clients.lock().await = host_clients;
+    }
+    state.speed_mode.apply_limit(&settings);
+    state.disk_limits.apply(&settings);
+    state.persistent.lock().await.settings = settings;
+    request_save(&state);
+    // Turning sequential mode off lets the waiting downloads go
+    state.queue.notify();
+    if api_changed { api::apply_settings(app_handle.clone()).await; }
+    if click_n_load_changed { clicknload::apply_settings(app_handle).await; }
+    Ok(())
+}
+#[tauri::command]
+async fn export_settings(path: String, include_host_rules: bool, include_secrets: bool, state: State<'_, AppState>) -> Result<(), String> {
+    let content = settings_transfer::export(&state.persistent.lock().await.settings, include_host_rules, include_secrets)?;
+    tokio::fs::write(&path, content).await.map_err(|e| format!("Could not write {}: {}", path, e))
+}
+// With `dry_run`, only reports what importing would change
+#[tauri::command]
+async fn import_settings(path: String, dry_run: bool, state: State<'_, AppState>, app_handle: AppHandle) -> Result<settings_transfer::SettingsImport, String> {
+    let content = tokio::fs::read_to_string(&path).await.map_err(|e| format!("Could not read {}: {}", path, e))?;
+    let current = state.persistent.lock().await.settings.clone();
+    let (settings, preview) = settings_transfer::import(&content, &current)?;
+    if !dry_run { update_settings(settings, state, app_handle).await?; }
+    Ok(preview)
+}
+#[tauri::command]
+async fn pause_download(id: String, state: State<'_, AppState>, app_handle: AppHandle) -> Result<(), String> {
+    abort_download(&state, &id).await;
+    let mut state_guard = state.persistent.lock().await;
+    if let Some(task) = state_guard.downloads.iter_mut().find(|t| t.id == id) {
+        task.status = DownloadStatus::Paused; task.speed = 0;
+        app_handle.emit("task_updated", &*task).unwrap();
+    }
+    drop(state_guard); request_save(&state);
+    Ok(())
+}
+#[tauri::command]
+async fn resume_download(id: String, app_handle: AppHandle) -> Result<(), String> { start_download_task(id, app_handle).await }
+
+// Answers the sign-in prompt of a paused download and resumes it, keeping the credentials in the
+// keyring for the host when `remember`
+#[tauri::command]
+async fn provide_credentials(id: String, user: String, pass: String, remember: bool, state: State<'_, AppState>, app_handle: AppHandle) -> Result<(), String> {
+    let challenge = {
+        let mut state_guard = state.persistent.lock().await;
+        let proxy_url = state_guard.settings.proxy_url.clone().filter(|p| !p.trim().is_empty());
+        let task = state_guard.downloads.iter_mut().find(|t| t.id == id).ok_or("Download not found")?;
+        let challenge = task.auth_challenge.clone().ok_or("This download isn't waiting for a sign-in")?;
+        auth_prompt::apply(task, &challenge, proxy_url.as_deref(), &user, &pass)?;
+        task.auth_challenge = None;
+        task.error_message = None;
+        app_handle.emit("task_updated", &*task).unwrap();
+        challenge
+    };
+    request_save(&state);
+    start_download_task(id, app_handle).await?;
+    if remember { auth_prompt::remember(&challenge, &user, &pass).await?; }
+    Ok(())
+}
+// Starts a download the size guard held back, and lets it through from now on
+#[tauri::command]
+async fn confirm_download(id: String, app_handle: AppHandle) -> Result<(), String> {
+    {
+        let state: State<AppState> = app_handle.state();
+        let mut state_guard = state.persistent.lock().await;
+        let task = state_guard.downloads.iter_mut().find(|t| t.id == id).ok_or("Download not found")?;
+        task.size_confirmed = true;
+        if task.status != DownloadStatus::AwaitingConfirmation { return Ok(()); }
+        task.status = DownloadStatus::Queued;
+        task.error_message = None;
+    }
+    start_download_task(id, app_handle).await
+}
+// Another full set of attempts for a failed download, continuing from what's on disk
+async fn retry_download(id: String, app_handle: AppHandle) -> Result<(), String> {
+    {
+        let state: State<AppState> = app_handle.state();
+        let mut state_guard = state.persistent.lock().await;
+        let task = state_guard.downloads.iter_mut().find(|t| t.id == id).ok_or("Download not found")?;
+        // Already picked up again from the window
+        if task.status != DownloadStatus::Failed { return Ok(()); }
+        task.resume_attempts = 0;
+        task.error_message = None;
+        task.error_page = None;
+    }
+    start_download_task(id, app_handle).await
+}
+#[tauri::command]
+async fn cancel_download(id: String, delete_file: Option<bool>, cache_partial: Option<bool>, state: State<'_, AppState>, app_handle: AppHandle) -> Result<(), String> {
+    abort_download(&state, &id).await;
+    let delete_file = delete_file.unwrap_or(false);
+    // A file the user asked to delete goes to the trash; only a kept one may be moved into the cache
+    let cached = !delete_file && cache_partial.unwrap_or(false) && partial_cache::store(&app_handle, &id).await;
+    if delete_file { trash_task_file(&state, &id).await?; }
+    take_downloads(&state, &app_handle, std::slice::from_ref(&id), delete_file || cached).await;
+    app_handle.emit("download_removed", &id).unwrap();
+    Ok(())
+}
+// Takes tasks off the list into the removed list, where they can be restored from for a while
+async fn take_downloads(state: &AppState, app_handle: &AppHandle, ids: &[String], file_deleted: bool) {
+    let expired = {
+        let mut state_guard = state.persistent.lock().await;
+        let (taken, kept) = std::mem::take(&mut state_guard.downloads).into_iter().partition(|t| ids.contains(&t.id));
+        state_guard.downloads = kept;
+        removed::keep(&mut state_guard, taken, file_deleted)
+    };
+    state.diagnostics.lock().await.retain(|id, _| !ids.contains(id));
+    state.speed_history.forget(ids);
+    request_save(state);
+    state.dependencies.notify();
+    removed::remove_logs(app_handle, expired).await;
+}
+// Where the task's file is now: its working folder until it completes, then `save_path`
+fn task_file_path(task: &DownloadTask) -> PathBuf {
+    PathBuf::from(task.working_dir.as_ref().unwrap_or(&task.save_path)).join(&task.file_name)
+}
+// Recycle bin rather than permanent deletion, so a mis-click is recoverable
+async fn trash_task_file(state: &AppState, id: &str) -> Result<(), String> {
+    let file_path = {
+        let state_guard = state.persistent.lock().await;
+        state_guard.downloads.iter().find(|t| t.id == id).map(task_file_path)
+    };
+    if let Some(path) = file_path.filter(|p| p.exists()) {
+        tokio::task::spawn_blocking(move || trash::delete(&path))
+            .await
+            .map_err(|e| e.to_string())?
+            .map_err(|e| format!("Failed to move file to trash: {}", e))?;
+    }
+    Ok(())
+}
+
+// Batch variants for multi-select: one save and one summary event instead of one per task
+#[tauri::command]
+async fn pause_downloads(ids: Vec<String>, state: State<'_, AppState>, app_handle: AppHandle) -> Result<(), String> {
+    futures::future::join_all(ids.iter().map(|id| abort_download(&state, id))).await;
+    let updated: Vec<DownloadTask> = {
+        let mut state_guard = state.persistent.lock().await;
+        state_guard.downloads.iter_mut()
+            .filter(|t| ids.contains(&t.id) && t.status != DownloadStatus::Completed)
+            .map(|task| {
+                task.status = DownloadStatus::Paused;
+                task.speed = 0;
+                task.clone()
+            })
+            .collect()
+    };
+    request_save(&state);
+    app_handle.emit("tasks_updated", &updated).unwrap();
+    Ok(())
+}
+// The outcome of a bulk resume, sent as `downloads_resumed`: started now, held back by a policy or the
+// queue, or refused with an error
+#[derive(Debug, Serialize, Default)]
+#[serde(rename_all = "camelCase")]
+struct ResumeSummary { resumed: Vec<String>, held: Vec<String>, failed: BTreeMap<String, String> }
+#[tauri::command]
+async fn resume_downloads(ids: Vec<String>, state: State<'_, AppState>, app_handle: AppHandle) -> Result<ResumeSummary, String> {
+    let running: Vec<String> = state.download_handles.lock().await.keys().cloned().collect();
+    let resumable: Vec<String> = {
+        let state_guard = state.persistent.lock().await;
+        state_guard.downloads.iter()
+            .filter(|t| ids.contains(&t.id) && !running.contains(&t.id) && t.status != DownloadStatus::Completed)
+            .map(|t| t.id.clone())
+            .collect()
+    };
+    let mut summary = ResumeSummary::default();
+    for id in resumable {
+        if let Err(e) = start_download_task(id.clone(), app_handle.clone()).await {
+            summary.failed.insert(id, e);
+        } else if state.download_handles.lock().await.contains_key(&id) {
+            summary.resumed.push(id);
+        } else {
+            summary.held.push(id);
+        }
+    }
+    app_handle.emit("downloads_resumed", &summary).unwrap();
+    Ok(summary)
+}
+#[tauri::command]
+async fn cancel_downloads(ids: Vec<String>, delete_file: Option<bool>, cache_partial: Option<bool>, state: State<'_, AppState>, app_handle: AppHandle) -> Result<(), String> {
+    futures::future::join_all(ids.iter().map(|id| abort_download(&state, id))).await;
+    let delete_file = delete_file.unwrap_or(false);
+    let mut errors = Vec::new();
+    let mut cached = Vec::new();
+    for id in &ids {
+        if delete_file {
+            if let Err(e) = trash_task_file(&state, id).await { errors.push(e); }
+        } else if cache_partial.unwrap_or(false) && partial_cache::store(&app_handle, id).await {
+            cached.push(id.clone());
+        }
+    }
+    let (gone, kept): (Vec<String>, Vec<String>) = ids.iter().cloned().partition(|id| delete_file || cached.contains(id));
+    take_downloads(&state, &app_handle, &gone, true).await;
+    take_downloads(&state, &app_handle, &kept, false).await;
+    app_handle.emit("downloads_removed", &ids).unwrap();
+    if errors.is_empty() { Ok(()) } else { Err(errors.join("\n")) }
+}
+
+// Changes one task's connection count; a running segmented download adds or stops workers right away
+#[tauri::command]
+async fn set_task_connections(id: String, connections: u8, state: State<'_, AppState>, app_handle: AppHandle) -> Result<DownloadTask, String> {
+    if !(1..=MAX_CONNECTIONS).contains(&connections) {
+        return Err(format!("Connections must be between 1 and {}", MAX_CONNECTIONS));
+    }
+    let task = {
+        let mut state_guard = state.persistent.lock().await;
+        let task = state_guard.downloads.iter_mut().find(|t| t.id == id).ok_or("Download not found")?;
+        task.connections = connections;
+        app_handle.emit("task_updated", &*task).unwrap();
+        task.clone()
+    };
+    if let Some(live) = state.live_progress.lock().await.get(&id) {
+        live.connections.store(connections, Ordering::Relaxed);
+        live.connections_changed.notify_one();
+    }
+    request_save(&state);
+    Ok(task)
+}
+// Exempts a task from the metered-connection policy, resuming it if the policy was holding it
+#[tauri::command]
+async fn set_allow_metered(id: String, allow: bool, state: State<'_, AppState>, app_handle: AppHandle) -> Result<DownloadTask, String> {
+    let (task, was_held) = {
+        let mut state_guard = state.persistent.lock().await;
+        let task = state_guard.downloads.iter_mut().find(|t| t.id == id).ok_or("Download not found")?;
+        task.allow_metered = allow;
+        let was_held = allow && metered::release(&state, &id) && task.status == DownloadStatus::Paused;
+        if was_held { task.error_message = None; }
+        app_handle.emit("task_updated", &*task).unwrap();
+        (task.clone(), was_held)
+    };
+    request_save(&state);
+    if was_held { start_download_task(id, app_handle).await?; }
+    Ok(task)
+}
+// Lets one download past the data quota, or puts it back under it; a held one starts right away
+#[tauri::command]
+async fn set_ignore_quota(id: String, ignore: bool, state: State<'_, AppState>, app_handle: AppHandle) -> Result<DownloadTask, String> {
+    let task = {
+        let mut state_guard = state.persistent.lock().await;
+        let task = state_guard.downloads.iter_mut().find(|t| t.id == id).ok_or("Download not found")?;
+        task.ignore_quota = ignore;
+        app_handle.emit("task_updated", &*task).unwrap();
+        task.clone()
+    };
+    request_save(&state);
+    if ignore { resume_paused(&app_handle, [id], quota::HELD_MESSAGE).await; }
+    Ok(task)
+}
+// Makes a download wait for the computer to be idle, or lets it run at any time; one that was waiting
+// starts right away
+#[tauri::command]
+async fn set_when_idle(id: String, when_idle: bool, state: State<'_, AppState>, app_handle: AppHandle) -> Result<DownloadTask, String> {
+    let (task, was_waiting) = {
+        let mut state_guard = state.persistent.lock().await;
+        let task = state_guard.downloads.iter_mut().find(|t| t.id == id).ok_or("Download not found")?;
+        task.when_idle = when_idle;
+        let was_waiting = !when_idle && idle::is_waiting(task);
+        if was_waiting { task.error_message = None; }
+        app_handle.emit("task_updated", &*task).unwrap();
+        (task.clone(), was_waiting)
+    };
+    request_save(&state);
+    if was_waiting { start_download_task(id, app_handle).await?; }
+    Ok(task)
+}
+#[tauri::command]
+async fn get_data_usage(state: State<'_, AppState>) -> Result<quota::UsageReport, String> {
+    Ok(quota::report(&*state.persistent.lock().await))
+}
+// Unlimited, the background cap, or everything paused; takes effect at once
+#[tauri::command]
+async fn set_speed_mode(mode: speed_mode::SpeedMode, app_handle: AppHandle) -> Result<(), String> {
+    speed_mode::set(&app_handle, mode).await;
+    Ok(())
+}
+#[tauri::command]
+async fn get_speed_mode(state: State<'_, AppState>) -> Result<speed_mode::SpeedMode, String> {
+    Ok(state.speed_mode.mode())
+}
+// Moves a task to `new_index` in the list, which is the order waiting downloads start in
+#[tauri::command]
+async fn move_task(id: String, new_index: usize, state: State<'_, AppState>, app_handle: AppHandle) -> Result<(), String> {
+    let order: Vec<String> = {
+        let mut state_guard = state.persistent.lock().await;
+        let from = state_guard.downloads.iter().position(|t| t.id == id).ok_or("Download not found")?;
+        let task = state_guard.downloads.remove(from);
+        let to = new_index.min(state_guard.downloads.len());
+        state_guard.downloads.insert(to, task);
+        state_guard.downloads.iter().map(|t| t.id.clone()).collect()
+    };
+    request_save(&state);
+    app_handle.emit("downloads_reordered", &order).unwrap();
+    Ok(())
+}
+#[tauri::command]
+async fn move_to_top(id: String, state: State<'_, AppState>, app_handle: AppHandle) -> Result<(), String> {
+    move_task(id, 0, state, app_handle).await
+}
+// Makes `id` wait for every task in `depends_on` to complete; an empty list lets it start on its own.
+// Applies the next time the task is started.
+#[tauri::command]
+async fn set_task_dependencies(id: String, depends_on: Vec<String>, state: State<'_, AppState>, app_handle: AppHandle) -> Result<DownloadTask, String> {
+    let task = {
+        let mut state_guard = state.persistent.lock().await;
+        dependencies::validate(&state_guard, &id, &depends_on)?;
+        let task = state_guard.downloads.iter_mut().find(|t| t.id == id).ok_or("Download not found")?;
+        task.depends_on = depends_on;
+        app_handle.emit("task_updated", &*task).unwrap();
+        task.clone()
+    };
+    request_save(&state);
+    // A waiting task may have just lost its last unfinished dependency
+    state.dependencies.notify();
+    Ok(task)
+}
+// Makes a download repeat every `every_hours`, or stops it repeating when `None`
+#[tauri::command]
+async fn set_task_repeat(id: String, every_hours: Option<u32>, naming: Option<recurring::RepeatNaming>, state: State<'_, AppState>, app_handle: AppHandle) -> Result<DownloadTask, String> {
+    let task = {
+        let mut state_guard = state.persistent.lock().await;
+        let task = state_guard.downloads.iter_mut().find(|t| t.id == id).ok_or("Download not found")?;
+        task.repeat = match (every_hours, task.repeat.take()) {
+            (None, _) => None,
+            // Changing the interval or naming keeps the history and the original link
+            (Some(every_hours), Some(mut repeat)) => {
+                if every_hours == 0 { return Err("The interval must be at least an hour".to_string()); }
+                repeat.next_run = repeat.next_run - chrono::Duration::hours(repeat.every_hours as i64) + chrono::Duration::hours(every_hours as i64);
+                repeat.every_hours = every_hours;
+                if let Some(naming) = naming { repeat.naming = naming; }
+                Some(repeat)
+            }
+            (Some(every_hours), None) => Some(recurring::Repeat::new(task, every_hours, naming.unwrap_or(recurring::RepeatNaming::Timestamped))?),
+        };
+        app_handle.emit("task_updated", &*task).unwrap();
+        task.clone()
+    };
+    request_save(&state);
+    Ok(task)
+}
+// Adds the download a `curl` command describes, such as one copied from the browser's devtools
+#[tauri::command]
+async fn import_curl(command: String, app_handle: AppHandle) -> Result<DownloadTask, String> {
+    curl_import::import(&app_handle, &command).await
+}
+// Forgets every cookie hosts have set, saved ones included
+#[tauri::command]
+async fn clear_cookies(app_handle: AppHandle) -> Result<(), String> {
+    app_handle.state::<AppState>().cookie_jar.clear();
+    cookies::save(&app_handle).await;
+    Ok(())
+}
+// Settles a download flagged as having the same content as another: replace it with a hard link to
+// the other file, delete it, or keep both
+#[tauri::command]
+async fn resolve_duplicate(id: String, resolution: duplicates::Resolution, app_handle: AppHandle) -> Result<(), String> {
+    duplicates::resolve(&app_handle, &id, resolution).await
+}
+// Watches a download's source for a newer version, or stops with `every_hours` unset. Changing the
+// interval or `auto_download` keeps the version already seen.
+#[tauri::command]
+async fn set_task_watch(id: String, every_hours: Option<u32>, auto_download: Option<bool>, state: State<'_, AppState>, app_handle: AppHandle) -> Result<DownloadTask, String> {
+    let task = {
+        let mut state_guard = state.persistent.lock().await;
+        let task = state_guard.downloads.iter_mut().find(|t| t.id == id).ok_or("Download not found")?;
+        task.watch = match (every_hours, task.watch.take()) {
+            (None, _) => None,
+            (Some(every_hours), Some(mut watch)) => {
+                if every_hours == 0 { return Err("The interval must be at least an hour".to_string()); }
+                watch.next_check = watch.next_check - chrono::Duration::hours(watch.every_hours as i64) + chrono::Duration::hours(every_hours as i64);
+                watch.every_hours = every_hours;
+                if let Some(auto_download) = auto_download { watch.auto_download = auto_download; }
+                Some(watch)
+            }
+            (Some(every_hours), None) => Some(remote_watch::Watch::new(task, every_hours, auto_download.unwrap_or(false))?),
+        };
+        app_handle.emit("task_updated", &*task).unwrap();
+        task.clone()
+    };
+    request_save(&state);
+    Ok(task)
+}
+// Renames the task and its partial or completed file; an active download is stopped and resumed around it
+#[tauri::command]
+async fn rename_download(id: String, new_name: String, state: State<'_, AppState>, app_handle: AppHandle) -> Result<DownloadTask, String> {
+    let new_name = new_name.trim().to_string();
+    if !is_valid_file_name(&new_name) { return Err(format!("Invalid file name: {}", new_name)); }
+    let (old_path, new_path) = {
+        let state_guard = state.persistent.lock().await;
+        let task = state_guard.downloads.iter().find(|t| t.id == id).ok_or("Download not found")?;
+        let dir = PathBuf::from(task.working_dir.as_ref().unwrap_or(&task.save_path));
+        (dir.join(&task.file_name), dir.join(&new_name))
+    };
+    if old_path != new_path && new_path.exists() {
+        return Err("A file with that name already exists".to_string());
+    }
+    let was_active = state.download_handles.lock().await.contains_key(&id);
+    if was_active { abort_download(&state, &id).await; }
+    let renamed = if old_path != new_path && old_path.exists() {
+        tokio::fs::rename(&old_path, &new_path).await.map_err(|e| format!("Failed to rename file: {}", e))
+    } else {
+        Ok(())
+    };
+    let task = {
+        let mut state_guard = state.persistent.lock().await;
+        let task = state_guard.downloads.iter_mut().find(|t| t.id == id).ok_or("Download not found")?;
+        if renamed.is_ok() {
+            task.file_type = get_file_type(&new_name);
+            task.file_name = new_name;
+            app_handle.emit("task_updated", &*task).unwrap();
+        }
+        task.clone()
+    };
+    request_save(&state);
+    // Resume even if the rename failed, so a rejected rename doesn't leave the download stopped
+    if was_active { start_download_task(id, app_handle).await?; }
+    renamed.map(|_| task)
+}
+// Points the task at a new folder, moving whatever is already on disk (partial or completed) along with it
+#[tauri::command]
+async fn set_save_path(id: String, new_dir: String, state: State<'_, AppState>, app_handle: AppHandle) -> Result<DownloadTask, String> {
+    let (old_path, new_path) = {
+        let state_guard = state.persistent.lock().await;
+        let task = state_guard.downloads.iter().find(|t| t.id == id).ok_or("Download not found")?;
+        // A file still in the temp folder stays there and is moved to the new folder when it completes
+        let old_path = task_file_path(task);
+        let new_path = if task.working_dir.is_some() { old_path.clone() } else { PathBuf::from(&new_dir).join(&task.file_name) };
+        (old_path, new_path)
+    };
+    if old_path != new_path && new_path.exists() {
+        return Err("A file with that name already exists in the destination folder".to_string());
+    }
+    tokio::fs::create_dir_all(&new_dir).await.map_err(|e| format!("Failed to create folder: {}", e))?;
+    let was_active = state.download_handles.lock().await.contains_key(&id);
+    if was_active { abort_download(&state, &id).await; }
+    let moved = if old_path != new_path && old_path.exists() {
+        let (progress_handle, progress_id) = (app_handle.clone(), id.clone());
+        move_file(&old_path, &new_path, move |moved, total| {
+            let _ = progress_handle.emit("move_progress", serde_json::json!({ "id": progress_id, "moved": moved, "total": total }));
+        }).await.map_err(|e| format!("Failed to move file: {}", e))
+    } else {
+        Ok(())
+    };
+    let task = {
+        let mut state_guard = state.persistent.lock().await;
+        let task = state_guard.downloads.iter_mut().find(|t| t.id == id).ok_or("Download not found")?;
+        if moved.is_ok() {
+            task.save_path = new_dir;
+            app_handle.emit("task_updated", &*task).unwrap();
+        }
+        task.clone()
+    };
+    request_save(&state);
+    if was_active { start_download_task(id, app_handle).await?; }
+    moved.map(|_| task)
+}
+// Swaps in a fresh link for an expired one and resumes from the bytes already on disk.
+// With `revalidate`, the new link must report the same size/ETag as the partial file's source.
+#[tauri::command]
+async fn update_task_url(id: String, new_url: String, revalidate: Option<bool>, state: State<'_, AppState>, app_handle: AppHandle) -> Result<DownloadTask, String> {
+    Url::parse(&new_url).map_err(|e| format!("Invalid URL: {}", e))?;
+    let (total_size, etag, status) = {
+        let state_guard = state.persistent.lock().await;
+        let task = state_guard.downloads.iter().find(|t| t.id == id).ok_or("Download not found")?;
+        check_url_policy(&state_guard.settings, task.allow_http, &new_url)?;
+        (task.total_size, task.etag.clone(), task.status.clone())
+    };
+    if status == DownloadStatus::Completed {
+        return Err("Download is already completed".to_string());
+    }
+    if revalidate.unwrap_or(false) {
+        let info = probe_download_info(&http_client_for(&state, &new_url).await, &new_url).await?;
+        if let (Some(new_size), true) = (info.total_size, total_size > 0) {
+            if new_size != total_size {
+                return Err(format!("The new link serves a different file ({} bytes, expected {})", new_size, total_size));
+            }
+        }
+        if let (Some(new_etag), Some(old_etag)) = (&info.etag, &etag) {
+            if !etags_match(new_etag, old_etag) {
+                return Err("The new link serves a different version of the file (ETag changed)".to_string());
+            }
+        }
+    }
+    abort_download(&state, &id).await;
+    let task = {
+        let mut state_guard = state.persistent.lock().await;
+        let task = state_guard.downloads.iter_mut().find(|t| t.id == id).ok_or("Download not found")?;
+        task.url = new_url;
+        task.resume_attempts = 0;
+        task.error_message = None;
+        task.error_page = None;
+        app_handle.emit("task_updated", &*task).unwrap();
+        task.clone()
     };
-    state.persistent.lock().await.downloads.push(new_task.clone());
-    save_state(&state, &app_handle).await.map_err(|e| e.to_string())?;
-    app_handle.emit("task_updated", &new_task).unwrap();
-    if auto_start { start_download_task(id, app_handle.clone()).await?; }
-    Ok(new_task)
+    request_save(&state);
+    if status != DownloadStatus::Paused { start_download_task(id, app_handle).await?; }
+    Ok(task)
 }
-
-#[tauri::command]
-async fn get_all_downloads(state: State<'_, AppState>) -> Result<Vec<DownloadTask>, String> { Ok(state.persistent.lock().await.downloads.clone()) }
+// Checks a completed file again against the size and checksum it was downloaded with, and with
+// `check_remote` against the server's current size and ETag, to catch corruption after disk trouble
+// or a copy from another machine. Without a checksum only the size can be compared. The status stays
+// Completed; the result is kept as the task's `verification`.
 #[tauri::command]
-async fn get_settings(state: State<'_, AppState>) -> Result<AppSettings, String> { Ok(state.persistent.lock().await.settings.clone()) }
-#[tauri::command(rename_all = "camelCase")]
-async fn update_settings(settings: AppSettings, state: State<'_, AppState>, app_handle: AppHandle) -> Result<(), String> {
-    state.persistent.lock().await.settings = settings;
-    save_state(&state, &app_handle).await.map_err(|e| e.to_string())?;
-    Ok(())
+async fn verify_download(id: String, check_remote: Option<bool>, state: State<'_, AppState>, app_handle: AppHandle) -> Result<Verification, String> {
+    let task = state.persistent.lock().await.downloads.iter().find(|t| t.id == id).cloned().ok_or("Download not found")?;
+    if task.status != DownloadStatus::Completed {
+        return Err("Only completed downloads can be verified".to_string());
+    }
+    let path = task_file_path(&task);
+    let mut problems = Vec::new();
+    match tokio::fs::metadata(&path).await {
+        Err(e) => problems.push(format!("Could not read the file: {}", e)),
+        Ok(metadata) => {
+            if task.total_size > 0 && metadata.len() != task.total_size {
+                problems.push(format!("File size is {} bytes, expected {}", metadata.len(), task.total_size));
+            }
+            if let Some((hasher, expected)) = task.checksum.as_deref().and_then(checksum_hasher) {
+                match hash_file(&path, hasher).await {
+                    Ok(actual) if actual != expected => problems.push(format!("Checksum mismatch: expected {}, got {}", expected, actual)),
+                    Ok(_) => {}
+                    Err(e) => problems.push(format!("Could not read the file: {}", e)),
+                }
+            }
+        }
+    }
+    let mut remote_error = None;
+    if check_remote.unwrap_or(false) {
+        match probe_download_info_with_headers(&http_client_for(&state, &task.url).await, &task.url, &task.headers).await {
+            Ok(info) => {
+                if let (Some(size), true) = (info.total_size, task.total_size > 0) {
+                    if size != task.total_size {
+                        problems.push(format!("The server now has {} bytes, expected {}", size, task.total_size));
+                    }
+                }
+                if let (Some(remote), Some(local)) = (&info.etag, &task.etag) {
+                    if !etags_match(remote, local) { problems.push("The server has a different version of the file (ETag changed)".to_string()); }
+                }
+            }
+            Err(e) => remote_error = Some(e),
+        }
+    }
+    let verification = Verification { checked_at: Local::now(), problems, remote_error };
+    {
+        let mut state_guard = state.persistent.lock().await;
+        if let Some(task) = state_guard.downloads.iter_mut().find(|t| t.id == id) {
+            task.verification = Some(verification.clone());
+            app_handle.emit("task_updated", &*task).unwrap();
+        }
+    }
+    request_save(&state);
+    let outcome = if verification.problems.is_empty() { "intact".to_string() } else { verification.problems.join("; ") };
+    tasklog::write(&app_handle, &id, &format!("Verified: {}", outcome)).await;
+    Ok(verification)
 }
+// Throws away all partial data and starts the task over, re-resolving the URL first
 #[tauri::command]
-async fn pause_download(id: String, state: State<'_, AppState>, app_handle: AppHandle) -> Result<(), String> {
-    if let Some(handle) = state.download_handles.lock().await.remove(&id) { handle.abort(); }
-    let mut state_guard = state.persistent.lock().await;
-    if let Some(task) = state_guard.downloads.iter_mut().find(|t| t.id == id) {
-        task.status = DownloadStatus::Paused; task.speed = 0;
+async fn restart_download(id: String, state: State<'_, AppState>, app_handle: AppHandle) -> Result<(), String> {
+    abort_download(&state, &id).await;
+    let (url, file_path) = {
+        let state_guard = state.persistent.lock().await;
+        let task = state_guard.downloads.iter().find(|t| t.id == id).ok_or("Download not found")?;
+        (task.url.clone(), task_file_path(task))
+    };
+    if file_path.exists() {
+        tokio::fs::remove_file(&file_path).await.map_err(|e| format!("Failed to delete partial file: {}", e))?;
+    }
+    let info = probe_download_info(&http_client_for(&state, &url).await, &url).await;
+    if let Err(e) = &info { log::warn!("Could not re-resolve {} before restart: {}", redact::url(&url), redact::text(e)); }
+    {
+        let mut state_guard = state.persistent.lock().await;
+        let task = state_guard.downloads.iter_mut().find(|t| t.id == id).ok_or("Download not found")?;
+        if let Ok(info) = info {
+            task.url = info.final_url;
+            task.total_size = info.total_size.unwrap_or(0);
+        }
+        task.status = DownloadStatus::Queued;
+        task.progress = 0.0;
+        task.downloaded_size = 0;
+        task.segments.clear();
+        task.working_dir = None;
+        task.speed = 0;
+        task.time_remaining = None;
+        task.resume_attempts = 0;
+        task.error_message = None;
+        task.error_page = None;
+        task.completed_at = None;
         app_handle.emit("task_updated", &*task).unwrap();
     }
-    drop(state_guard); save_state(&state, &app_handle).await.map_err(|e| e.to_string())?;
-    Ok(())
-}
-#[tauri::command]
-async fn resume_download(id: String, app_handle: AppHandle) -> Result<(), String> { start_download_task(id, app_handle).await }
-#[tauri::command]
-async fn cancel_download(id: String, state: State<'_, AppState>, app_handle: AppHandle) -> Result<(), String> {
-    if let Some(handle) = state.download_handles.lock().await.remove(&id) { handle.abort(); }
-    state.persistent.lock().await.downloads.retain(|t| t.id != id);
-    save_state(&state, &app_handle).await.map_err(|e| e.to_string())?;
-    app_handle.emit("download_removed", &id).unwrap();
-    Ok(())
+    state.diagnostics.lock().await.remove(&id);
+    request_save(&state);
+    start_download_task(id, app_handle).await
 }
 #[tauri::command]
 async fn open_file(save_path: String, file_name: String) -> Result<(), String> {
@@ -263,7 +3030,112 @@ async fn open_folder(path: String) -> Result<(), String> {
     #[cfg(target_os = "linux")] { Command::new("xdg-open").arg(&path).spawn().map_err(|e| e.to_string())?; }
     Ok(())
 }
+// Unpacks a finished archive now, e.g. again with the password it needed
+#[tauri::command]
+async fn extract_archive(id: String, password: Option<String>, app_handle: AppHandle) -> Result<(), String> {
+    extract::extract(&app_handle, &id, password).await
+}
+// "Show in folder": opens the file manager with the file highlighted, or just its folder when the
+// platform can't select it (or the file isn't there yet)
+#[tauri::command]
+async fn reveal_file(path: String) -> Result<(), String> {
+    let file_path = PathBuf::from(&path);
+    let folder = file_path.parent().map(PathBuf::from).unwrap_or_default();
+    if !file_path.exists() {
+        if !folder.exists() { return Err("File not found".to_string()); }
+        return open_folder(folder.to_string_lossy().to_string()).await;
+    }
+    #[cfg(target_os = "windows")] { Command::new("explorer").arg("/select,").arg(&file_path).spawn().map_err(|e| e.to_string())?; }
+    #[cfg(target_os = "macos")] { Command::new("open").arg("-R").arg(&file_path).spawn().map_err(|e| e.to_string())?; }
+    #[cfg(target_os = "linux")]
+    {
+        // Nautilus, Dolphin, Nemo and most others implement the freedesktop FileManager1 interface
+        let uri = Url::from_file_path(&file_path).map_err(|_| "Invalid file path".to_string())?;
+        let shown = tokio::process::Command::new("dbus-send")
+            .args(["--session", "--print-reply", "--dest=org.freedesktop.FileManager1", "--type=method_call",
+                "/org/freedesktop/FileManager1", "org.freedesktop.FileManager1.ShowItems"])
+            .arg(format!("array:string:{}", uri))
+            .arg("string:")
+            .output()
+            .await
+            .is_ok_and(|output| output.status.success());
+        if !shown { Command::new("xdg-open").arg(&folder).spawn().map_err(|e| e.to_string())?; }
+    }
+    Ok(())
+}
 async fn start_download_task(id: String, app_handle: AppHandle) -> Result<(), String> {
+    let app_state: State<AppState> = app_handle.state();
+    let _starting = app_state.queue.starting.lock().await;
+    {
+        let state: State<AppState> = app_handle.state();
+        // Left as it is for the next launch to pick up
+        if state.shutdown.in_progress() { return Ok(()); }
+        let running: std::collections::HashSet<String> = state.download_handles.lock().await.keys().cloned().collect();
+        let mut state_guard = state.persistent.lock().await;
+        let pause_over_mb = state_guard.settings.metered_pause_over_mb;
+        if let Some(task) = state_guard.downloads.iter_mut().find(|t| t.id == id && metered::should_hold(&state, t, pause_over_mb)) {
+            metered::hold(&state, task);
+            app_handle.emit("task_updated", &*task).unwrap();
+            drop(state_guard);
+            request_save(&state);
+            return Ok(());
+        }
+        if let Some(task) = state_guard.downloads.iter_mut().find(|t| t.id == id && quota::should_hold(&state, t)) {
+            quota::hold(task);
+            app_handle.emit("task_updated", &*task).unwrap();
+            drop(state_guard);
+            request_save(&state);
+            return Ok(());
+        }
+        if state.speed_mode.mode() == speed_mode::SpeedMode::Paused {
+            if let Some(task) = state_guard.downloads.iter_mut().find(|t| t.id == id) {
+                speed_mode::hold(task);
+                app_handle.emit("task_updated", &*task).unwrap();
+            }
+            drop(state_guard);
+            request_save(&state);
+            return Ok(());
+        }
+        let settings = state_guard.settings.clone();
+        if let Some(task) = state_guard.downloads.iter_mut().find(|t| t.id == id) {
+            if guards::hold(&settings, task) {
+                app_handle.emit("task_updated", &*task).unwrap();
+                drop(state_guard);
+                request_save(&state);
+                return Ok(());
+            }
+        }
+        if let Some(task) = state_guard.downloads.iter_mut().find(|t| t.id == id && idle::should_wait(&state, t)) {
+            idle::park(task);
+            app_handle.emit("task_updated", &*task).unwrap();
+            drop(state_guard);
+            request_save(&state);
+            return Ok(());
+        }
+        let waits = state_guard.downloads.iter().find(|t| t.id == id).is_some_and(|t| dependencies::should_wait(&state_guard.downloads, t));
+        if waits {
+            if let Some(task) = state_guard.downloads.iter_mut().find(|t| t.id == id) {
+                dependencies::park(task);
+                app_handle.emit("task_updated", &*task).unwrap();
+            }
+            drop(state_guard);
+            request_save(&state);
+            // A dependency may already have failed
+            state.dependencies.notify();
+            return Ok(());
+        }
+        let waits = state_guard.downloads.iter().find(|t| t.id == id)
+            .is_some_and(|t| queue::must_wait(&state_guard.downloads, &state_guard.settings, &running, t));
+        if waits {
+            if let Some(task) = state_guard.downloads.iter_mut().find(|t| t.id == id) {
+                queue::park(task);
+                app_handle.emit("task_updated", &*task).unwrap();
+            }
+            drop(state_guard);
+            request_save(&state);
+            return Ok(());
+        }
+    }
     let app_handle_clone = app_handle.clone();
     let id_clone = id.clone();
 
@@ -274,6 +3146,16 @@ async fn start_download_task(id: String, app_handle: AppHandle) -> Result<(), St
             p_state.settings.clone()
         };
 
+        partial_cache::restore(&app_handle_clone, &id_clone).await;
+        let live = Arc::new(LiveProgress::default());
+        app_handle_clone.state::<AppState>().live_progress.lock().await.insert(id_clone.clone(), live.clone());
+        // Completed or failed for good, as opposed to paused, removed or vanished
+        let mut finished = false;
+        // Set when a presigned URL was refused, so the next attempt asks the refresh hook for a new one
+        let mut refresh_url = false;
+        // Set when another link refused the download and a fresh one was resolved for the next attempt
+        let mut refresh_link = false;
+
         loop {
             let task_info = {
                 let state: State<AppState> = app_handle_clone.state();
@@ -284,73 +3166,197 @@ async fn start_download_task(id: String, app_handle: AppHandle) -> Result<(), St
                         task.resume_attempts += 1;
                     }
                     task.status = DownloadStatus::Downloading;
+                    task.error_page = None;
+                    task.auth_challenge = None;
                     app_handle_clone.emit("task_updated", &*task).unwrap();
-                    Some((
-                        task.url.clone(), task.save_path.clone(), task.file_name.clone(),
-                        task.downloaded_size, task.resume_attempts
-                    ))
+                    live.connections.store(task.connections, Ordering::Relaxed);
+                    // Chosen when nothing is on disk yet, so a partial file is never left behind
+                    let temp_folder = settings.temp_folder.trim();
+                    if task.working_dir.is_none() && task.downloaded_size == 0 && task.segments.is_empty() && !temp_folder.is_empty() {
+                        task.working_dir = Some(PathBuf::from(temp_folder).join(&task.id).to_string_lossy().to_string());
+                    }
+                    Some((DownloadJob {
+                        url: task.url.clone(), save_path: task.save_path.clone(), file_name: task.file_name.clone(),
+                        resume_from: task.downloaded_size, checksum: task.checksum.clone(), segments: task.segments.clone(),
+                        headers: task.headers.clone(), signature: task.signature.clone(), allow_http: task.allow_http,
+                        allow_metered: task.allow_metered, ignore_quota: task.ignore_quota,
+                        when_idle: task.when_idle, working_dir: task.working_dir.clone(), etag: task.etag.clone(),
+                        proxy: task.proxy.clone(), request: task.request.clone(),
+                    }, task.resume_attempts))
                 } else {
                     None
                 }
             };
 
-            let (url, save_path, file_name, downloaded_size, attempts) = match task_info {
+            let (mut job, attempts) = match task_info {
                 Some(info) => info,
                 None => break,
             };
 
+            // What the task recorded is on disk; anything this attempt writes counts once synced
+            live.downloaded.store(job.resume_from, Ordering::Relaxed);
+            live.synced.store(job.resume_from, Ordering::Relaxed);
+            *live.segments.lock().unwrap() = job.segments.iter().map(|s| Segment { synced: s.pos, ..s.clone() }).collect();
             let attempt_start_time = Instant::now();
             
-            // Clone the values right before they are moved
-            let result = download_file(
-                &id_clone, 
-                &url,      
-                &save_path,
-                &file_name,
-                downloaded_size,
-                &app_handle_clone,
-            ).await;
+            let task_url = job.url.clone();
+            let result = match prepare_task_url(&app_handle_clone, &id_clone, &task_url, &settings, refresh_url).await {
+                Ok(url) => {
+                    job.url = url;
+                    download_file(&id_clone, &job, &live, &app_handle_clone).await
+                }
+                Err(e) => Err(anyhow::anyhow!(e)),
+            };
 
+            if live.cancel.is_cancelled() {
+                tasklog::write(&app_handle_clone, &id_clone, &format!("Stopped at byte {}", live.downloaded.load(Ordering::Relaxed))).await;
+                // Paused or removed: keep the exact offset the writer flushed to disk
+                let state: State<AppState> = app_handle_clone.state();
+                let mut p_state = state.persistent.lock().await;
+                if let Some(task) = p_state.downloads.iter_mut().find(|t| t.id == id_clone) {
+                    (task.downloaded_size, task.segments) = live.durable();
+                }
+                break;
+            }
             if result.is_ok() {
+                finished = true;
                 break;
             }
 
             let attempt_duration = attempt_start_time.elapsed();
-            let error_string = result.err().unwrap().to_string();
+            let error = result.err().unwrap();
+            // Refusals from the redirect policy sit under reqwest's generic redirect error
+            let mut error_string = error.chain().map(|e| e.to_string())
+                .find(|e| e.starts_with(HTTPS_ONLY_ERROR) || e.starts_with(REDIRECT_REFUSED_ERROR) || e.starts_with(HOST_REFUSED_ERROR))
+                .unwrap_or_else(|| error.to_string());
+            update_diagnostics(&app_handle_clone.state(), &id_clone, |d| {
+                d.retries.push(RetryRecord { attempt: attempts, at: Local::now(), error: error_string.clone() });
+            }).await;
+            tasklog::write(&app_handle_clone, &id_clone, &format!("Attempt {} failed after {:.1}s: {}", attempts, attempt_duration.as_secs_f64(), error_string)).await;
+
+            // Retrying without credentials would be refused again; ask for them instead of failing
+            if error_string.starts_with(auth_prompt::AUTH_REQUIRED_ERROR) {
+                if auth_prompt::apply_remembered(&app_handle_clone, &id_clone).await { continue; }
+                let state: State<AppState> = app_handle_clone.state();
+                let mut p_state = state.persistent.lock().await;
+                let challenge = p_state.downloads.iter_mut().find(|t| t.id == id_clone).and_then(|task| {
+                    (task.downloaded_size, task.segments) = live.durable();
+                    task.speed = 0;
+                    task.status = DownloadStatus::Paused;
+                    task.error_message = Some(error_string.clone());
+                    app_handle_clone.emit("task_updated", &*task).unwrap();
+                    task.auth_challenge.clone()
+                });
+                drop(p_state);
+                if let Some(challenge) = challenge {
+                    app_handle_clone.emit("auth_required", auth_prompt::AuthRequired { id: id_clone.clone(), challenge }).unwrap();
+                }
+                break;
+            }
+
+            // Behind a login page every retry would fail the same way; wait for sign-in instead
+            if let Some(message) = captive::check(&app_handle_clone).await {
+                tasklog::write(&app_handle_clone, &id_clone, &message).await;
+                let state: State<AppState> = app_handle_clone.state();
+                let mut p_state = state.persistent.lock().await;
+                if let Some(task) = p_state.downloads.iter_mut().find(|t| t.id == id_clone) {
+                    (task.downloaded_size, task.segments) = live.durable();
+                    task.speed = 0;
+                    task.status = DownloadStatus::CaptivePortal;
+                    task.error_message = Some(message);
+                    app_handle_clone.emit("task_updated", &*task).unwrap();
+                }
+                break;
+            }
+
+            // An expired presigned URL answers 403; that's worth one more try once the hook has replaced it
+            refresh_url = !refresh_url && error_string.contains("403") && !s3::is_s3_url(&task_url)
+                && s3::presigned_expiry(&task_url).is_some() && !settings.url_refresh_command.trim().is_empty();
+            // Links handed out by a page or a plugin expire too; resolving the source again gives a new one
+            refresh_link = !refresh_link && !refresh_url && link_refresh::is_expired(&error_string)
+                && settings.auto_resume_downloads && attempts < settings.max_resume_attempts
+                && link_refresh::can_refresh(&app_handle_clone, &id_clone).await;
+            // Retrying the expired link would only be refused again
+            let refresh_failed = refresh_link && match link_refresh::refresh(&app_handle_clone, &id_clone).await {
+                Ok(resume) => {
+                    if !resume {
+                        live.synced.store(0, Ordering::Relaxed);
+                        live.segments.lock().unwrap().clear();
+                    }
+                    false
+                }
+                Err(e) => {
+                    error_string = format!("Could not refresh the expired link: {}", e);
+                    refresh_link = false;
+                    true
+                }
+            };
 
             // Check for conditions where we should NOT retry
             let should_fail_permanently = 
+                refresh_failed ||
                 !settings.auto_resume_downloads ||
                 attempts >= settings.max_resume_attempts ||
-                (attempts > 0 && attempt_duration < Duration::from_secs(settings.min_fail_duration_seconds)) || // Added attempts > 0 check
-                error_string.contains("403") || error_string.contains("404") || error_string.contains("File size mismatch");
+                (!refresh_url && !refresh_link && (
+                    (attempts > 0 && attempt_duration < Duration::from_secs(settings.min_fail_duration_seconds)) || // Added attempts > 0 check
+                    error_string.contains("403") || error_string.contains("404") || error_string.contains("File size mismatch") ||
+                    error_string.contains(HTML_PAGE_ERROR) || error_string.contains("Checksum mismatch") ||
+                    error_string.starts_with(HTTPS_ONLY_ERROR) || error_string.starts_with(REDIRECT_REFUSED_ERROR) ||
+                    error_string.starts_with(HOST_REFUSED_ERROR)
+                ));
 
             if should_fail_permanently {
+                tasklog::write(&app_handle_clone, &id_clone, "Failed for good").await;
                 let state: State<AppState> = app_handle_clone.state();
                 let mut p_state = state.persistent.lock().await;
-                if let Some(task) = p_state.downloads.iter_mut().find(|t| t.id == id_clone) {
+                let failed = p_state.downloads.iter_mut().find(|t| t.id == id_clone).map(|task| {
+                    (task.downloaded_size, task.segments) = live.durable();
+                    task.speed = 0;
                     task.status = DownloadStatus::Failed;
                     task.error_message = Some(error_string);
                     app_handle_clone.emit("task_updated", &*task).unwrap();
+                    task.clone()
+                });
+                drop(p_state);
+                state.metrics.failures.fetch_add(1, Ordering::Relaxed);
+                state.taskbar.mark_failed();
+                if let Some(task) = failed {
+                    if settings.notify_on_failure { notify_failure(&app_handle_clone, &settings, &task); }
+                    fire_webhooks(&app_handle_clone, WebhookEvent::Failed, Some(&task)).await;
                 }
+                finished = true;
                 break;
             } else {
                 let state: State<AppState> = app_handle_clone.state();
                 let mut p_state = state.persistent.lock().await;
                 if let Some(task) = p_state.downloads.iter_mut().find(|t| t.id == id_clone) {
+                    // Resume from what the attempt actually wrote, not the last aggregated tick
+                    (task.downloaded_size, task.segments) = live.durable();
+                    task.speed = 0;
                     task.status = DownloadStatus::Retrying;
-                    task.error_message = Some(format!("Network error. Retrying in {}s... (Attempt {})", settings.resume_delay_seconds, attempts));
+                    let reason = if error_string.starts_with(STALLED_ERROR) { "Transfer stalled" } else { "Network error" };
+                    task.error_message = Some(format!("{}. Retrying in {}s... (Attempt {})", reason, settings.resume_delay_seconds, attempts));
                     app_handle_clone.emit("task_updated", &*task).unwrap();
                 }
                 drop(p_state);
+                tasklog::write(&app_handle_clone, &id_clone, &format!("Retrying in {}s", settings.resume_delay_seconds)).await;
 
-                tokio::time::sleep(Duration::from_secs(settings.resume_delay_seconds)).await;
+                tokio::select! {
+                    _ = live.cancel.cancelled() => break,
+                    _ = tokio::time::sleep(Duration::from_secs(settings.resume_delay_seconds)) => {}
+                }
             }
         }
 
         let state: State<AppState> = app_handle_clone.state();
         state.download_handles.lock().await.remove(&id_clone);
-        let _ = save_state(&state, &app_handle_clone).await;
+        state.live_progress.lock().await.remove(&id_clone);
+        request_save(&state);
+        state.queue.notify();
+        if finished {
+            state.dependencies.notify();
+            notify_if_queue_empty(&app_handle_clone).await;
+        }
     });
     
     app_handle.state::<AppState>().download_handles.lock().await.insert(id, handle);
@@ -359,43 +3365,103 @@ async fn start_download_task(id: String, app_handle: AppHandle) -> Result<(), St
 
 async fn download_file(
     id: &str, 
-    url: &str, 
-    save_path: &str, 
-    file_name: &str, 
-    resume_from: u64, 
+    job: &DownloadJob,
+    live: &LiveProgress,
     app_handle: &AppHandle
 ) -> anyhow::Result<()> {
-    // Create a more robust client with better timeout settings
-    let client = Client::builder()
-        .user_agent(USER_AGENT)
-        .timeout(Duration::from_secs(60)) // Increase timeout for initial connection
-        .connect_timeout(Duration::from_secs(30))
-        .pool_idle_timeout(Duration::from_secs(90))
-        .pool_max_idle_per_host(10)
-        .tcp_keepalive(Some(Duration::from_secs(60)))
-        .build()?;
+    let DownloadJob { url, save_path, file_name, resume_from, .. } = job;
+    let client = match &job.proxy {
+        Some(proxy) => proxied_client(&app_handle.state(), proxy).await?,
+        None => http_client_for(&app_handle.state(), url).await,
+    };
+    let metrics = app_handle.state::<AppState>().metrics.clone();
+    // Decided per attempt, so switching `allow_metered` applies from the next resume
+    let metered = (!job.allow_metered).then(|| app_handle.state::<AppState>().metered.clone());
+    let quota = (!job.ignore_quota).then(|| app_handle.state::<AppState>().quota.clone());
+    let idle = job.when_idle.then(|| app_handle.state::<AppState>().idle.clone());
+    let speed_limit = app_handle.state::<AppState>().speed_mode.limit.clone();
+    let (write_buffer_size, preallocate, stall_timeout, min_split_size, proxy_url, url_policy) = {
+        let state: State<AppState> = app_handle.state();
+        let settings = &state.persistent.lock().await.settings;
+        (settings.write_buffer_size, settings.preallocate_files, settings.stall_timeout_seconds, settings.min_split_size, settings.proxy_url.clone(),
+            check_url_policy(settings, job.allow_http, url))
+    };
+    // Every attempt, for tasks added before a rule was switched on and for links swapped in since
+    url_policy.map_err(anyhow::Error::msg)?;
+    let proxy_url = job.proxy.clone().or(proxy_url).filter(|p| !p.trim().is_empty());
+    
+    // Never trust a recorded offset beyond what actually reached the disk
+    let file_path = PathBuf::from(job.working_dir.as_ref().unwrap_or(save_path)).join(file_name);
+    let disk_limit = app_handle.state::<AppState>().disk_limits.for_path(&file_path);
+    let file_len = tokio::fs::metadata(&file_path).await.map(|m| m.len()).unwrap_or(0);
+    // A custom request can't ask for a range, so it starts over every time
+    let mut segments = if job.request.is_some() { Vec::new() } else { job.segments.clone() };
+    for segment in &mut segments {
+        segment.pos = segment.pos.min(file_len).max(segment.start);
+        segment.synced = segment.pos;
+    }
+    // A segmented resume reopens the first unfinished part; the others get their own requests later
+    let mut first_segment = segments.iter().position(|s| s.pos < s.end);
+    if first_segment.is_none() { segments.clear(); }
+    let resume_from = match first_segment {
+        Some(index) => segments[index].pos,
+        None if job.request.is_some() => 0,
+        None => (*resume_from).min(file_len),
+    };
+    if remote::handles(url) {
+        return remote::download(id, job, live, app_handle, &file_path, resume_from).await;
+    }
+    
+    // Resolved separately from the request only to time it; the OS cache makes the second lookup free
+    let dns_started = Instant::now();
+    let dns_ms = match Url::parse(url).ok().and_then(|u| Some((u.host_str()?.to_string(), u.port_or_known_default()?))) {
+        Some(host_port) => tokio::net::lookup_host(host_port).await.ok().map(|_| dns_started.elapsed().as_millis() as u64),
+        None => None,
+    };
+    update_diagnostics(&app_handle.state(), id, |d| {
+        *d = TaskDiagnostics { retries: std::mem::take(&mut d.retries), timing: TimingBreakdown { dns_ms, ..Default::default() }, ..Default::default() };
+    }).await;
     
-    let mut request = client.get(url);
-    if resume_from > 0 { 
+    let (mut request, method) = match &job.request {
+        Some(custom) => (custom.build(&client, url).map_err(anyhow::Error::msg)?, custom.method.as_str()),
+        None => (client.get(url), "GET"),
+    };
+    request = with_headers(request, &job.headers);
+    if resume_from > 0 || !segments.is_empty() { 
         request = request.header("Range", format!("bytes={}-", resume_from)); 
+        // A server whose file changed since the partial one was fetched sends it whole instead
+        if let Some(etag) = job.etag.as_deref().filter(|e| !e.starts_with("W/")) { request = request.header("If-Range", etag); }
+        tasklog::write(app_handle, id, &format!("{} {} from byte {}", method, url, resume_from)).await;
+    } else {
+        tasklog::write(app_handle, id, &format!("{} {}", method, url)).await;
     }
     
     // Add retry logic for initial connection
     let mut attempts = 0;
     let max_attempts = 3;
-    let response = loop {
+    let (response, redirect_chain, headers_ms) = loop {
         attempts += 1;
-        match timeout(Duration::from_secs(45), request.try_clone().unwrap().send()).await {
-            Ok(Ok(resp)) => break resp,
-            Ok(Err(e)) if attempts < max_attempts => {
-                log::warn!("Connection attempt {} failed: {}. Retrying...", attempts, e);
+        let sent_at = Instant::now();
+        let send = REDIRECT_CHAIN.scope(RefCell::new(Vec::new()), async {
+            let result = request.try_clone().unwrap().send().await;
+            (result, REDIRECT_CHAIN.with(|chain| chain.take()))
+        });
+        match timeout(Duration::from_secs(45), send).await {
+            Ok((Ok(resp), chain)) => break (resp, chain, sent_at.elapsed().as_millis() as u64),
+            Ok((Err(e), _)) if auth_prompt::is_proxy_refusal(&e) => {
+                return Err(auth_prompt::require(app_handle, id, auth_prompt::proxy_challenge(proxy_url.as_deref())).await);
+            }
+            Ok((Err(e), _)) if attempts < max_attempts => {
+                log::warn!("Connection attempt {} failed: {}. Retrying...", attempts, redact::text(&e.to_string()));
+                tasklog::write(app_handle, id, &format!("Connection attempt {} failed: {}", attempts, e)).await;
                 tokio::time::sleep(Duration::from_secs(2 * attempts as u64)).await;
                 continue;
             }
-            Ok(Err(e)) => return Err(anyhow::anyhow!("Failed to connect after {} attempts: {}", max_attempts, e)),
+            Ok((Err(e), _)) => return Err(anyhow::anyhow!("Failed to connect after {} attempts: {}", max_attempts, e)),
             Err(_) => {
                 if attempts < max_attempts {
                     log::warn!("Connection attempt {} timed out. Retrying...", attempts);
+                    tasklog::write(app_handle, id, &format!("Connection attempt {} timed out", attempts)).await;
                     tokio::time::sleep(Duration::from_secs(2 * attempts as u64)).await;
                     continue;
                 } else {
@@ -406,6 +3472,28 @@ async fn download_file(
     };
     
     let status = response.status();
+    if redirect_chain.len() > 1 { tasklog::write(app_handle, id, &format!("Redirected: {}", redirect_chain.join(" -> "))).await; }
+    tasklog::write(app_handle, id, &format!("{} from {} after {} ms", status, response.url(), headers_ms)).await;
+    update_diagnostics(&app_handle.state(), id, |d| {
+        let headers = response.headers();
+        let header = |name: &str| headers.get(name).and_then(|v| v.to_str().ok()).map(str::to_string);
+        d.redirect_chain = if redirect_chain.is_empty() { vec![response.url().to_string()] } else { redirect_chain };
+        d.status = Some(status.as_u16());
+        d.server = header("server");
+        d.accept_ranges = header("accept-ranges");
+        for (name, value) in headers {
+            let value = String::from_utf8_lossy(value.as_bytes()).into_owned();
+            d.response_headers.entry(name.to_string())
+                .and_modify(|v| { v.push_str(", "); v.push_str(&value); })
+                .or_insert(value);
+        }
+        d.timing.headers_ms = Some(headers_ms);
+    }).await;
+    if status == reqwest::StatusCode::UNAUTHORIZED || status == reqwest::StatusCode::PROXY_AUTHENTICATION_REQUIRED {
+        if let Some(challenge) = auth_prompt::challenge(&response, proxy_url.as_deref()) {
+            return Err(auth_prompt::require(app_handle, id, challenge).await);
+        }
+    }
     if status == reqwest::StatusCode::UNAUTHORIZED || status == reqwest::StatusCode::FORBIDDEN {
         return Err(anyhow::anyhow!("Authorization failed ({}). The link may be protected or expired.", status));
     }
@@ -413,16 +3501,57 @@ async fn download_file(
         return Err(anyhow::anyhow!("Server returned an error: {}", status)); 
     }
     
-    let resume_capability = response.headers()
+    if is_unexpected_html(&response, file_name) {
+        let page = read_capped_body(response, MAX_ERROR_PAGE_BYTES).await;
+        let state: State<AppState> = app_handle.state();
+        let mut state_guard = state.persistent.lock().await;
+        if let Some(task) = state_guard.downloads.iter_mut().find(|t| t.id == id) {
+            task.error_page = Some(page);
+        }
+        return Err(anyhow::anyhow!("{} (the link may have expired or hit a quota)", HTML_PAGE_ERROR));
+    }
+    
+    let resume_capability = job.request.is_none() && response.headers()
         .get("accept-ranges")
         .map(|v| v == "bytes")
         .unwrap_or(false);
+    let etag = header_etag(response.headers());
     
-    let total_size = if resume_from > 0 && response.status() == 206 { 
-        response.content_length().unwrap_or(0) + resume_from 
-    } else { 
-        response.content_length().unwrap_or(0) 
+    // A 200 to a ranged request means the server ignored the range and is sending the whole file
+    let resume_from = if status == reqwest::StatusCode::PARTIAL_CONTENT { resume_from } else {
+        segments.clear();
+        first_segment = None;
+        0
     };
+    let total_size = response.content_length().unwrap_or(0) + resume_from;
+    // Splits append their far half, so the tail is whichever segment ends last, not the last one listed
+    if segments.iter().map(|s| s.end).max().is_some_and(|end| end != total_size) {
+        return Err(anyhow::anyhow!("File size mismatch: the server now reports {} bytes", total_size));
+    }
+    
+    // Servers that honour ranges always go through the segmenter, even at one connection,
+    // so workers can be added when the task's connection count is raised mid-download
+    let segmented = (resume_capability || status == reqwest::StatusCode::PARTIAL_CONTENT) && total_size > 0;
+    if segmented && segments.is_empty() {
+        segments.push(Segment { start: 0, end: total_size, pos: resume_from, active: false, synced: resume_from });
+        first_segment = Some(0);
+    }
+    let resuming = resume_from > 0 || !segments.is_empty();
+    tasklog::write(app_handle, id, &format!(
+        "Size {} bytes, {}, {}",
+        total_size,
+        if resume_capability { "resumable" } else { "not resumable" },
+        if segmented { format!("{} segment(s)", segments.len()) } else { "single stream".to_string() },
+    )).await;
+    let downloaded_before: u64 = segments.iter().map(|s| s.pos - s.start).sum::<u64>().max(resume_from);
+    // Hashed while streaming when the front of the file is still to come, as for a single stream
+    let front_hash = segments.iter().any(|s| s.start == 0 && s.pos == 0).then(|| job.checksum.as_deref().and_then(checksum_hasher)).flatten()
+        .map(|(hasher, expected)| FrontHash { hasher, expected, hashed_to: 0 });
+    *live.segments.lock().unwrap() = segments;
+    
+    live.total_size.store(total_size, Ordering::Relaxed);
+    live.record(downloaded_before, 0);
+    live.synced.store(downloaded_before, Ordering::Relaxed);
     
     // Update task info
     {
@@ -431,91 +3560,133 @@ async fn download_file(
         if let Some(task) = state_guard.downloads.iter_mut().find(|t| t.id == id) {
             task.total_size = total_size;
             task.resume_capability = resume_capability;
+            if etag.is_some() { task.etag = etag; }
             app_handle.emit("task_updated", &*task).unwrap();
         }
     }
     
-    let file_path = PathBuf::from(&save_path).join(&file_name);
     if let Some(parent) = file_path.parent() { 
         tokio::fs::create_dir_all(parent).await?; 
     }
     
-    let mut file = if resume_from > 0 { 
-        tokio::fs::OpenOptions::new().append(true).open(&file_path).await? 
+    let file = if resuming { 
+        // The file may be preallocated past the offset; the tail is overwritten as data arrives
+        let mut file = tokio::fs::OpenOptions::new().write(true).open(&file_path).await?;
+        file.seek(std::io::SeekFrom::Start(resume_from)).await?;
+        file
     } else { 
-        tokio::fs::File::create(&file_path).await? 
+        let file = tokio::fs::File::create(&file_path).await?;
+        if preallocate && total_size > 0 {
+            preallocate_file(&file, total_size).await
+                .map_err(|e| anyhow::anyhow!("Could not reserve {} bytes on disk: {}", total_size, e))?;
+        }
+        file
     };
+    if segmented {
+        drop(file);
+        let config = SegmentWorkerConfig {
+            client: client.clone(), url: url.clone(), headers: job.headers.clone(), file_path: file_path.clone(),
+            write_buffer_size: write_buffer_size.max(8 * 1024) as usize, metrics: metrics.clone(), metered: metered.clone(),
+            quota: quota.clone(), idle: idle.clone(), speed_limit: speed_limit.clone(), disk_limit: disk_limit.clone(),
+            front_hash: Arc::new(std::sync::Mutex::new(front_hash)),
+        };
+        let front_hash = config.front_hash.clone();
+        let first = (first_segment.unwrap_or(0), response);
+        let Some(downloaded) = download_segmented(config, first, live, min_split_size, stall_timeout).await? else { return Ok(()) };
+        // Only what lies past the front segment is read back
+        let front = front_hash.lock().unwrap().take();
+        let hasher = match front {
+            Some(front) => Some((hash_file_from(&file_path, front.hasher, front.hashed_to).await?, front.expected)),
+            None => None,
+        };
+        return finish_download(id, job, &file_path, total_size, downloaded, hasher, app_handle).await;
+    }
+    let mut file = tokio::io::BufWriter::with_capacity(write_buffer_size.max(8 * 1024) as usize, file);
+    
+    // Hash while streaming when starting from zero; resumed files are verified by re-reading afterwards
+    let mut hasher = if resume_from == 0 { job.checksum.as_deref().and_then(checksum_hasher) } else { None };
     
-    // Use a smaller buffer for better memory management
     let mut stream = response.bytes_stream();
     let mut downloaded = resume_from;
     let mut last_update = std::time::Instant::now();
     let mut last_downloaded = downloaded;
+    let mut last_sync = Instant::now();
     let mut consecutive_errors = 0;
+    // Measured from here so time spent preallocating the file isn't blamed on the server
+    let stream_started = Instant::now();
+    let mut awaiting_first_byte = true;
     
-    // Buffer writes to reduce I/O operations
-    let mut write_buffer = Vec::with_capacity(1024 * 1024); // 1MB buffer
+    // Watchdog: a connection that stays open but stops delivering data is dropped and retried
+    let stall_window = Duration::from_secs(stall_timeout);
+    let mut stall_check_at = Instant::now() + stall_window;
+    let mut downloaded_at_check = downloaded;
     
-    while let Some(chunk_result) = stream.next().await {
+    loop {
+        let chunk_result = tokio::select! {
+            _ = live.cancel.cancelled() => {
+                sync_writer(&mut file).await?;
+                live.synced.store(downloaded, Ordering::Relaxed);
+                live.record(downloaded, 0);
+                return Ok(());
+            }
+            _ = tokio::time::sleep_until(stall_check_at), if stall_timeout > 0 => {
+                if downloaded - downloaded_at_check < STALL_MIN_BYTES {
+                    sync_writer(&mut file).await?;
+                    live.synced.store(downloaded, Ordering::Relaxed);
+                    live.record(downloaded, 0);
+                    return Err(anyhow::anyhow!("{}: no data received for {}s", STALLED_ERROR, stall_timeout));
+                }
+                downloaded_at_check = downloaded;
+                stall_check_at = Instant::now() + stall_window;
+                continue;
+            }
+            next = stream.next() => match next {
+                Some(chunk_result) => chunk_result,
+                None => break,
+            },
+        };
         match chunk_result {
             Ok(chunk) => {
                 consecutive_errors = 0; // Reset error counter on success
+                if awaiting_first_byte {
+                    awaiting_first_byte = false;
+                    let first_byte_ms = headers_ms + stream_started.elapsed().as_millis() as u64;
+                    update_diagnostics(&app_handle.state(), id, |d| d.timing.first_byte_ms = Some(first_byte_ms)).await;
+                }
                 
-                // Add to buffer instead of writing immediately
-                write_buffer.extend_from_slice(&chunk);
+                if let Some(metered) = &metered { metered.limit.take(chunk.len()).await; }
+                if let Some(quota) = &quota { quota.limit.take(chunk.len()).await; }
+                if let Some(idle) = &idle { idle.limit.take(chunk.len()).await; }
+                speed_limit.take(chunk.len()).await;
+                if let Some(disk_limit) = &disk_limit { disk_limit.take(chunk.len()).await; }
+                file.write_all(&chunk).await?;
+                if let Some((hasher, _)) = hasher.as_mut() { hasher.update(&chunk); }
                 downloaded += chunk.len() as u64;
-                
-                // Write buffer to disk when it's large enough or at regular intervals
-                if write_buffer.len() >= 512 * 1024 { // Write every 512KB
-                    file.write_all(&write_buffer).await?;
-                    write_buffer.clear();
-                }
+                metrics.bytes_downloaded.fetch_add(chunk.len() as u64, Ordering::Relaxed);
                 
                 // Update progress
-                if last_update.elapsed() > Duration::from_millis(250) { // Update less frequently
-                    // Flush any remaining buffer
-                    if !write_buffer.is_empty() {
-                        file.write_all(&write_buffer).await?;
-                        write_buffer.clear();
-                    }
-                    
+                if last_update.elapsed() > PROGRESS_INTERVAL { // Update less frequently
                     let speed = ((downloaded - last_downloaded) as f64 / last_update.elapsed().as_secs_f64()) as u64;
-                    let time_remaining = if speed > 0 { 
-                        Some((total_size - downloaded) / speed) 
-                    } else { 
-                        None 
-                    };
-                    let progress = if total_size > 0 { 
-                        (downloaded as f64 / total_size as f64) * 100.0 
-                    } else { 
-                        0.0 
-                    };
-                    
-                    {
-                        let state: State<AppState> = app_handle.state();
-                        let mut state_guard = state.persistent.lock().await;
-                        if let Some(task) = state_guard.downloads.iter_mut().find(|t| t.id == id) {
-                            task.downloaded_size = downloaded;
-                            task.progress = progress;
-                            task.speed = speed;
-                            task.time_remaining = time_remaining;
-                            app_handle.emit("task_updated", &*task).unwrap();
-                        }
-                    }
+                    live.record(downloaded, speed);
                     
                     last_update = std::time::Instant::now();
                     last_downloaded = downloaded;
                 }
+                if last_sync.elapsed() >= SYNC_INTERVAL {
+                    sync_writer(&mut file).await?;
+                    live.synced.store(downloaded, Ordering::Relaxed);
+                    last_sync = Instant::now();
+                }
             }
             Err(e) => {
                 consecutive_errors += 1;
-                log::warn!("Error reading chunk (attempt {}): {}", consecutive_errors, e);
+                log::warn!("Error reading chunk (attempt {}): {}", consecutive_errors, redact::text(&e.to_string()));
                 
                 if consecutive_errors >= 5 {
-                    // Flush buffer before failing
-                    if !write_buffer.is_empty() {
-                        let _ = file.write_all(&write_buffer).await;
-                    }
+                    // Sync before failing so the next attempt resumes from what was received
+                    sync_writer(&mut file).await?;
+                    live.synced.store(downloaded, Ordering::Relaxed);
+                    live.record(downloaded, 0);
                     return Err(anyhow::anyhow!("Too many consecutive errors while downloading: {}", e));
                 }
                 
@@ -525,125 +3696,575 @@ async fn download_file(
         }
     }
     
-    // Write any remaining data in buffer
-    if !write_buffer.is_empty() {
-        file.write_all(&write_buffer).await?;
-    }
-    
     // Ensure all data is written to disk
-    file.sync_all().await?;
+    file.flush().await?;
+    file.get_ref().sync_all().await?;
+    live.synced.store(downloaded, Ordering::Relaxed);
+    live.record(downloaded, 0);
     
+    finish_download(id, job, &file_path, total_size, downloaded, hasher, app_handle).await
+}
+
+// Verifies a fully received file and marks the task completed
+async fn finish_download(
+    id: &str,
+    job: &DownloadJob,
+    file_path: &std::path::Path,
+    total_size: u64,
+    downloaded: u64,
+    hasher: Option<(Box<dyn DynDigest + Send>, String)>,
+    app_handle: &AppHandle
+) -> anyhow::Result<()> {
     // Verify file
     {
         let state: State<AppState> = app_handle.state();
         let mut state_guard = state.persistent.lock().await;
         if let Some(task) = state_guard.downloads.iter_mut().find(|t| t.id == id) {
             task.status = DownloadStatus::Verifying;
+            if let Some(signature) = task.signature.as_mut() { signature.verified_by = None; }
+            task.virus_scan = None;
+            task.verification = None;
+            task.content_hash = None;
+            task.duplicate_of = None;
             app_handle.emit("task_updated", &*task).unwrap();
         }
     }
     
-    let metadata = tokio::fs::metadata(&file_path).await?;
-    if total_size > 0 && metadata.len() != total_size { 
-        return Err(anyhow::anyhow!("File size mismatch: expected {}, got {}", total_size, metadata.len())); 
+    // Compare received bytes rather than the file length, which preallocation already set
+    let metadata = tokio::fs::metadata(file_path).await?;
+    if total_size > 0 && (downloaded != total_size || metadata.len() != total_size) { 
+        return Err(anyhow::anyhow!("File size mismatch: expected {}, got {}", total_size, downloaded)); 
+    }
+    if let Some(checksum) = &job.checksum {
+        let (actual, expected) = match hasher {
+            Some((hasher, expected)) => (hex::encode(hasher.finalize()), expected),
+            None => {
+                let (hasher, expected) = checksum_hasher(checksum)
+                    .ok_or_else(|| anyhow::anyhow!("Unsupported checksum format: {}", checksum))?;
+                (hash_file(file_path, hasher).await?, expected)
+            }
+        };
+        if actual != expected {
+            return Err(anyhow::anyhow!("Checksum mismatch: expected {}, got {}", expected, actual));
+        }
+    }
+    let signer = match &job.signature {
+        Some(check) => Some(signature::verify(app_handle, file_path, check).await
+            .map_err(|e| anyhow::anyhow!("Signature check failed: {}", e))?),
+        None => None,
+    };
+    if let Some(working_dir) = &job.working_dir {
+        let target = PathBuf::from(&job.save_path).join(&job.file_name);
+        tokio::fs::create_dir_all(&job.save_path).await?;
+        let (progress_handle, progress_id) = (app_handle.clone(), id.to_string());
+        move_file(file_path, &target, move |moved, total| {
+            let _ = progress_handle.emit("move_progress", serde_json::json!({ "id": progress_id, "moved": moved, "total": total }));
+        }).await.map_err(|e| anyhow::anyhow!("Could not move the file to {}: {}", job.save_path, e))?;
+        let _ = tokio::fs::remove_dir(working_dir).await;
+        tasklog::write(app_handle, id, &format!("Moved from the temp folder to {}", job.save_path)).await;
     }
     
+    tasklog::write(app_handle, id, &format!("Completed, {} bytes", downloaded)).await;
     // Complete download
-    {
+    let completed = {
         let state: State<AppState> = app_handle.state();
         let mut state_guard = state.persistent.lock().await;
-        let show_notifications = state_guard.settings.show_notifications;
-        if let Some(task) = state_guard.downloads.iter_mut().find(|t| t.id == id) {
+        let settings = state_guard.settings.clone();
+        state_guard.downloads.iter_mut().find(|t| t.id == id).map(|task| {
             task.status = DownloadStatus::Completed;
             task.progress = 100.0;
             task.downloaded_size = total_size;
+            task.segments.clear();
             task.speed = 0;
+            task.working_dir = None;
             task.completed_at = Some(Local::now());
+            if let Some(signature) = task.signature.as_mut() { signature.verified_by = signer.clone(); }
             app_handle.emit("task_updated", &*task).unwrap();
             
-            if show_notifications {
-                let _ = app_handle.notification()
-                    .builder()
-                    .title("Download Complete")
-                    .body(&format!("{} has finished downloading", task.file_name))
-                    .show();
-            }
+            let language = i18n::Language::from_setting(&settings.language);
+            let actions = vec![
+                (i18n::tr(language, i18n::Text::Open), notifications::Action::Open(task.id.clone())),
+                (i18n::tr(language, i18n::Text::ShowInFolder), notifications::Action::Reveal(task.id.clone())),
+            ];
+            let body = i18n::tr(language, i18n::Text::FinishedDownloading).replace("{file}", &task.file_name);
+            notifications::show_with_actions(app_handle, &settings, i18n::tr(language, i18n::Text::DownloadComplete), &body, actions);
+            taskbar::bounce(app_handle);
+            task.clone()
+        })
+    };
+    
+    request_save(&app_handle.state());
+    if let Some(task) = completed {
+        let task = recurring::finish_run(app_handle, task).await;
+        match task.file_type.as_str() {
+            "Video" => { tauri::async_runtime::spawn(subtitles::fetch_for_task(app_handle.clone(), task.clone())); }
+            "Audio" => { tauri::async_runtime::spawn(tagging::process_task(app_handle.clone(), task.clone())); }
+            "Archive" => { tauri::async_runtime::spawn(extract::process_task(app_handle.clone(), task.clone())); }
+            _ => {}
         }
+        tauri::async_runtime::spawn(virustotal::check_task(app_handle.clone(), task.clone()));
+        tauri::async_runtime::spawn(duplicates::check_task(app_handle.clone(), task.clone()));
+        fire_webhooks(app_handle, WebhookEvent::Completed, Some(&task)).await;
     }
+    Ok(())
+}
+
+// Everything a segment worker needs, cloned into each spawned connection
+#[derive(Clone)]
+struct SegmentWorkerConfig {
+    client: Client, url: String, headers: BTreeMap<String, String>, file_path: PathBuf, write_buffer_size: usize,
+    metrics: Arc<Metrics>,
+    metered: Option<Arc<metered::Metered>>,
+    quota: Option<Arc<quota::Quota>>,
+    idle: Option<Arc<idle::Idle>>,
+    speed_limit: Arc<metered::SpeedLimit>,
+    disk_limit: Option<Arc<metered::SpeedLimit>>,
+    front_hash: Arc<std::sync::Mutex<Option<FrontHash>>>,
+}
+
+// The checksum of a segmented download as far as the segment starting at 0 has got, fed in order by
+// whichever worker streams that segment
+struct FrontHash { hasher: Box<dyn DynDigest + Send>, expected: String, hashed_to: u64 }
+
+// Hands a free connection its next piece of work: an unfinished segment nobody is streaming, or else
+// the far half of the largest active one, as long as both halves stay at least `min_split_size`
+fn claim_segment(segments: &mut Vec<Segment>, min_split_size: u64) -> Option<usize> {
+    if let Some(index) = segments.iter().position(|s| !s.active && s.pos < s.end) {
+        segments[index].active = true;
+        return Some(index);
+    }
+    let (index, remaining) = segments.iter().enumerate()
+        .filter(|(_, s)| s.active)
+        .map(|(i, s)| (i, s.end.saturating_sub(s.pos)))
+        .max_by_key(|&(_, remaining)| remaining)?;
+    if remaining < 2 * min_split_size.max(1) { return None; }
+    let mid = segments[index].pos + remaining / 2;
+    let end = std::mem::replace(&mut segments[index].end, mid);
+    segments.push(Segment { start: mid, end, pos: mid, active: true, synced: mid });
+    Some(segments.len() - 1)
+}
+
+// Runs the connections of a ranged download, keeping as many open as the task's live connection count
+// allows. Returns the bytes received, or `None` when stopped by a pause or removal.
+async fn download_segmented(
+    config: SegmentWorkerConfig,
+    first: (usize, reqwest::Response),
+    live: &LiveProgress,
+    min_split_size: u64,
+    stall_timeout: u64,
+) -> anyhow::Result<Option<u64>> {
+    let segments = live.segments.clone();
+    let received = || segments.lock().unwrap().iter().map(|s| s.pos - s.start).sum::<u64>();
+    let workers_cancel = live.cancel.child_token();
+    let mut workers = tokio::task::JoinSet::new();
+    // Workers that haven't been asked to stop, newest last, so lowering the count stops the newest first
+    let mut running: Vec<(usize, CancellationToken)> = Vec::new();
+    
+    let (first_index, first_response) = first;
+    let token = workers_cancel.child_token();
+    segments.lock().unwrap()[first_index].active = true;
+    workers.spawn(run_segment_worker(config.clone(), segments.clone(), first_index, Some(first_response), token.clone()));
+    running.push((first_index, token));
+    
+    let mut ticker = tokio::time::interval(PROGRESS_INTERVAL);
+    let mut last_tick = (Instant::now(), received());
+    let stall_window = Duration::from_secs(stall_timeout);
+    let mut stall_check = (Instant::now() + stall_window, received());
+    let mut consecutive_errors = 0;
+    
+    let failure = loop {
+        let target = live.connections.load(Ordering::Relaxed).max(1) as usize;
+        while running.len() > target {
+            if let Some((_, token)) = running.pop() { token.cancel(); }
+        }
+        while running.len() < target {
+            let Some(index) = claim_segment(&mut segments.lock().unwrap(), min_split_size) else { break };
+            let token = workers_cancel.child_token();
+            workers.spawn(run_segment_worker(config.clone(), segments.clone(), index, None, token.clone()));
+            running.push((index, token));
+        }
+        if workers.is_empty() {
+            // Nothing left to claim and nobody streaming: every segment is complete
+            break None;
+        }
+        
+        tokio::select! {
+            _ = live.cancel.cancelled() => break None,
+            _ = live.connections_changed.notified() => {}
+            joined = workers.join_next() => match joined {
+                Some(Ok((index, result))) => {
+                    segments.lock().unwrap()[index].active = false;
+                    running.retain(|(i, _)| *i != index);
+                    match result {
+                        Ok(()) => consecutive_errors = 0,
+                        Err(e) => {
+                            consecutive_errors += 1;
+                            log::warn!("Segment {} failed (error {}): {}", index, consecutive_errors, redact::text(&e.to_string()));
+                            if consecutive_errors >= 5 {
+                                break Some(anyhow::anyhow!("Too many consecutive errors while downloading: {}", e));
+                            }
+                        }
+                    }
+                }
+                Some(Err(e)) => break Some(anyhow::anyhow!("Segment worker crashed: {}", e)),
+                None => {}
+            },
+            _ = ticker.tick() => {
+                let downloaded = received();
+                let elapsed = last_tick.0.elapsed().as_secs_f64();
+                let speed = if elapsed > 0.0 { ((downloaded - last_tick.1) as f64 / elapsed) as u64 } else { 0 };
+                live.record(downloaded, speed);
+                last_tick = (Instant::now(), downloaded);
+                if stall_timeout > 0 && Instant::now() >= stall_check.0 {
+                    if downloaded - stall_check.1 < STALL_MIN_BYTES {
+                        break Some(anyhow::anyhow!("{}: no data received for {}s", STALLED_ERROR, stall_timeout));
+                    }
+                    stall_check = (Instant::now() + stall_window, downloaded);
+                }
+            }
+        }
+    };
     
-    let _ = save_state(&app_handle.state(), &app_handle).await;
+    // Let every worker flush its buffer so the recorded positions are on disk
+    workers_cancel.cancel();
+    while workers.join_next().await.is_some() {}
+    for segment in segments.lock().unwrap().iter_mut() { segment.active = false; }
+    let downloaded = received();
+    live.record(downloaded, 0);
+    if let Some(e) = failure { return Err(e); }
+    if live.cancel.is_cancelled() { return Ok(None); }
+    
+    let file = tokio::fs::OpenOptions::new().write(true).open(&config.file_path).await?;
+    file.sync_all().await?;
+    Ok(Some(downloaded))
+}
+
+// One connection of a segmented download. Streams `segments[index]` until its end, which can move
+// closer while running when `claim_segment` hands the tail to another worker.
+async fn run_segment_worker(
+    config: SegmentWorkerConfig,
+    segments: Arc<std::sync::Mutex<Vec<Segment>>>,
+    index: usize,
+    response: Option<reqwest::Response>,
+    cancel: CancellationToken,
+) -> (usize, anyhow::Result<()>) {
+    let result = async {
+        let (start, pos, end) = {
+            let segments = segments.lock().unwrap();
+            (segments[index].start, segments[index].pos, segments[index].end)
+        };
+        // A front segment reopened anywhere but where the hash stopped can't continue it
+        let hashing = start == 0 && {
+            let mut front_hash = config.front_hash.lock().unwrap();
+            if front_hash.as_ref().is_some_and(|f| f.hashed_to != pos) { *front_hash = None; }
+            front_hash.is_some()
+        };
+        let response = match response {
+            Some(response) => response,
+            None => {
+                let request = with_headers(config.client.get(&config.url), &config.headers)
+                    .header("Range", format!("bytes={}-{}", pos, end - 1));
+                let response = tokio::select! {
+                    _ = cancel.cancelled() => return Ok(()),
+                    response = timeout(Duration::from_secs(45), request.send()) => response
+                        .map_err(|_| anyhow::anyhow!("Connection timed out"))??,
+                };
+                if response.status() != reqwest::StatusCode::PARTIAL_CONTENT {
+                    return Err(anyhow::anyhow!("Server returned {} to a range request", response.status()));
+                }
+                response
+            }
+        };
+        
+        let mut file = tokio::fs::OpenOptions::new().write(true).open(&config.file_path).await?;
+        file.seek(std::io::SeekFrom::Start(pos)).await?;
+        let mut file = tokio::io::BufWriter::with_capacity(config.write_buffer_size, file);
+        let mut stream = response.bytes_stream();
+        let mut last_sync = Instant::now();
+        let outcome = loop {
+            let chunk = tokio::select! {
+                _ = cancel.cancelled() => break Ok(()),
+                next = stream.next() => match next {
+                    Some(Ok(chunk)) => chunk,
+                    Some(Err(e)) => break Err(anyhow::anyhow!("Error reading chunk: {}", e)),
+                    None => break Err(anyhow::anyhow!("Connection closed before the segment was complete")),
+                },
+            };
+            if let Some(metered) = &config.metered { metered.limit.take(chunk.len()).await; }
+            if let Some(quota) = &config.quota { quota.limit.take(chunk.len()).await; }
+            if let Some(idle) = &config.idle { idle.limit.take(chunk.len()).await; }
+            config.speed_limit.take(chunk.len()).await;
+            config.metrics.bytes_downloaded.fetch_add(chunk.len() as u64, Ordering::Relaxed);
+            let take = {
+                let segments = segments.lock().unwrap();
+                (chunk.len() as u64).min(segments[index].end.saturating_sub(segments[index].pos)) as usize
+            };
+            if let Some(disk_limit) = &config.disk_limit { disk_limit.take(take).await; }
+            if let Err(e) = file.write_all(&chunk[..take]).await { break Err(e.into()); }
+            if hashing {
+                if let Some(front) = config.front_hash.lock().unwrap().as_mut() {
+                    front.hasher.update(&chunk[..take]);
+                    front.hashed_to += take as u64;
+                }
+            }
+            // Counted only once written. A split landing inside this chunk has moved `end` back to where
+            // the new segment starts; its worker writes the same bytes there.
+            let done = {
+                let mut segments = segments.lock().unwrap();
+                let segment = &mut segments[index];
+                segment.pos = (segment.pos + take as u64).min(segment.end);
+                segment.pos >= segment.end
+            };
+            if done { break Ok(()); }
+            if last_sync.elapsed() >= SYNC_INTERVAL {
+                if let Err(e) = sync_segment(&mut file, &segments, index).await { break Err(e.into()); }
+                last_sync = Instant::now();
+            }
+        };
+        // Also after a failed write, so what did reach the buffer isn't lost
+        if let Err(e) = sync_segment(&mut file, &segments, index).await {
+            // The buffered bytes may be gone; only the last synced position is sure to be on disk
+            let mut segments = segments.lock().unwrap();
+            segments[index].pos = segments[index].synced;
+            return Err(e.into());
+        }
+        outcome
+    }.await;
+    (index, result)
+}
+
+// Flushes a writer and syncs the file, so offsets recorded afterwards survive a crash
+async fn sync_writer(file: &mut tokio::io::BufWriter<tokio::fs::File>) -> std::io::Result<()> {
+    file.flush().await?;
+    file.get_ref().sync_data().await
+}
+async fn sync_segment(file: &mut tokio::io::BufWriter<tokio::fs::File>, segments: &std::sync::Mutex<Vec<Segment>>, index: usize) -> std::io::Result<()> {
+    sync_writer(file).await?;
+    let mut segments = segments.lock().unwrap();
+    segments[index].synced = segments[index].pos;
     Ok(())
 }
 
 #[tauri::command]
 async fn remove_download(id: String, state: State<'_, AppState>, app_handle: AppHandle) -> Result<(), String> {
     // Cancel if still downloading
-    if let Some(handle) = state.download_handles.lock().await.remove(&id) {
-        handle.abort();
-    }
+    abort_download(&state, &id).await;
     
-    // Remove from list
-    state.persistent.lock().await.downloads.retain(|t| t.id != id);
-    save_state(&state, &app_handle).await.map_err(|e| e.to_string())?;
+    // Move to the removed list
+    take_downloads(&state, &app_handle, std::slice::from_ref(&id), false).await;
     app_handle.emit("download_removed", &id).unwrap();
     Ok(())
 }
 
+// Downloads removed within `removed_retention_hours`, most recent first
+#[tauri::command]
+async fn list_removed_downloads(state: State<'_, AppState>) -> Result<Vec<removed::RemovedDownload>, String> {
+    Ok(state.persistent.lock().await.removed.iter().rev().cloned().collect())
+}
+#[tauri::command]
+async fn restore_download(id: String, state: State<'_, AppState>, app_handle: AppHandle) -> Result<DownloadTask, String> {
+    let task = removed::restore(&mut *state.persistent.lock().await, &id)?;
+    request_save(&state);
+    app_handle.emit("tasks_added", [&task]).unwrap();
+    Ok(task)
+}
+// Forgets every removed download now instead of waiting for them to expire
+#[tauri::command]
+async fn empty_removed_downloads(state: State<'_, AppState>, app_handle: AppHandle) -> Result<(), String> {
+    let ids: Vec<String> = std::mem::take(&mut state.persistent.lock().await.removed).into_iter().map(|r| r.task.id).collect();
+    request_save(&state);
+    removed::remove_logs(&app_handle, ids).await;
+    Ok(())
+}
+
 #[tauri::command]
 async fn delete_download_with_file(id: String, state: State<'_, AppState>, app_handle: AppHandle) -> Result<(), String> {
     // Cancel if still downloading
-    if let Some(handle) = state.download_handles.lock().await.remove(&id) {
-        handle.abort();
-    }
+    abort_download(&state, &id).await;
     
     // Get file path before removing
     let file_path = {
         let state_guard = state.persistent.lock().await;
         state_guard.downloads.iter()
             .find(|t| t.id == id)
-            .map(|t| PathBuf::from(&t.save_path).join(&t.file_name))
+            .map(|t| (task_file_path(t), t.working_dir.clone()))
     };
     
     // Delete file if it exists
-    if let Some(path) = file_path {
+    if let Some((path, working_dir)) = file_path {
         if path.exists() {
             tokio::fs::remove_file(&path).await.map_err(|e| format!("Failed to delete file: {}", e))?;
         }
+        if let Some(dir) = working_dir { let _ = tokio::fs::remove_dir(dir).await; }
     }
     
-    // Remove from list
-    state.persistent.lock().await.downloads.retain(|t| t.id != id);
-    save_state(&state, &app_handle).await.map_err(|e| e.to_string())?;
+    // Move to the removed list; only the entry can come back
+    take_downloads(&state, &app_handle, std::slice::from_ref(&id), true).await;
     app_handle.emit("download_removed", &id).unwrap();
     Ok(())
 }
 
+// Brings the window back from the tray or the Dock
+fn show_main_window(app_handle: &AppHandle) {
+    if let Some(window) = app_handle.get_webview_window("main") {
+        let _ = window.unminimize();
+        let _ = window.show();
+        let _ = window.set_focus();
+    }
+}
+#[tauri::command]
+async fn updates_enabled(app_handle: AppHandle) -> bool {
+    updater::configured(&app_handle)
+}
+#[tauri::command]
+async fn check_for_update(app_handle: AppHandle) -> Result<Option<updater::UpdateInfo>, String> {
+    updater::check(&app_handle).await
+}
+#[tauri::command]
+async fn download_update(app_handle: AppHandle) -> Result<(), String> {
+    updater::download(&app_handle).await
+}
+#[tauri::command]
+async fn install_update(app_handle: AppHandle) -> Result<(), String> {
+    updater::install(&app_handle).await
+}
 #[tauri::command]
 async fn handle_cli_args(args: Vec<String>) -> Result<(), String> {
     for arg in args.iter().skip(1) { if arg.starts_with("http://") || arg.starts_with("https://") { return Ok(()); } } Ok(())
 }
 fn main() {
-    env_logger::init();
     tauri::Builder::default()
+        // A second launch (a jump-list task, a link opened with the app) hands its arguments over here
+        .plugin(tauri_plugin_single_instance::init(|app, args, _cwd| {
+            match quick_actions::QuickAction::from_args(&args) {
+                Some(action) => quick_actions::run(app, action),
+                None => {
+                    show_main_window(app);
+                    for arg in args.iter().skip(1) { if arg.starts_with("http://") || arg.starts_with("https://") { app.emit("cli-url", arg).unwrap(); } }
+                }
+            }
+        }))
+        .plugin(tauri_plugin_autostart::init(tauri_plugin_autostart::MacosLauncher::LaunchAgent, Some(vec![autostart::MINIMIZED_ARG])))
         .plugin(tauri_plugin_dialog::init()).plugin(tauri_plugin_notification::init()).plugin(tauri_plugin_opener::init())
         .setup(|app| {
             let app_handle = app.handle().clone(); let state_path = get_state_path(&app_handle)?;
-            let initial_state = if state_path.exists() {
+            let mut initial_state: PersistentState = if state_path.exists() {
                 let content = fs::read_to_string(state_path)?;
                 serde_json::from_str(&content).unwrap_or_default()
             } else { PersistentState::default() };
+            let logging = app.path().app_log_dir().map_err(|e| e.to_string())
+                .and_then(|dir| logging::init(dir, &initial_state.settings.log_level))
+                .map_err(|e| eprintln!("Logging to files is unavailable: {}", e))
+                .ok();
+            mirror::mark_interrupted(&mut initial_state.mirrors);
+            extract::mark_interrupted(&mut initial_state.downloads);
+            let vault = tauri::async_runtime::block_on(vault::Vault::load(&mut initial_state));
+            redact::mark_lost(&mut initial_state.downloads);
+            let autostart_enabled = initial_state.settings.autostart;
+            let language = i18n::Language::from_setting(&initial_state.settings.language);
+            let cookie_jar = Arc::new(cookies::CookieJar::load(&app_handle, initial_state.settings.persist_cookies));
+            let (http_client, host_clients) = build_http_client(&initial_state.settings, cookie_jar.clone())
+                .and_then(|client| Ok((client, build_host_clients(&initial_state.settings, cookie_jar.clone())?)))
+                .or_else(|e| {
+                    log::warn!("Invalid network settings, using defaults: {}", e);
+                    build_http_client(&AppSettings::default(), cookie_jar.clone()).map(|client| (client, Vec::new()))
+                })?;
+            let disk_limits = disk_limits::DiskLimits::default();
+            disk_limits.apply(&initial_state.settings);
             app.manage(AppState {
                 persistent: Arc::new(Mutex::new(initial_state)),
                 download_handles: Arc::new(Mutex::new(std::collections::HashMap::new())),
+                live_progress: Arc::new(Mutex::new(std::collections::HashMap::new())),
+                http_client: Arc::new(Mutex::new(http_client)),
+                host_clients: Arc::new(Mutex::new(host_clients)),
+                cookie_jar,
+                save_requested: Arc::new(tokio::sync::Notify::new()),
+                diagnostics: Arc::new(Mutex::new(std::collections::HashMap::new())),
+                api_server: Mutex::new(None),
+                click_n_load_server: Mutex::new(None),
+                mirror_crawls: Mutex::new(std::collections::HashMap::new()),
+                metrics: Arc::new(Metrics::default()),
+                metered: Arc::new(metered::Metered::default()),
+                quota: Arc::new(quota::Quota::default()),
+                idle: Arc::new(idle::Idle::default()),
+                captive: captive::CaptivePortal::default(),
+                taskbar: taskbar::Taskbar::default(),
+                shutdown: shutdown::Shutdown::default(),
+                updater: updater::PendingUpdate::default(),
+                dependencies: dependencies::Dependencies::default(),
+                queue: queue::Queue::default(),
+                speed_mode: speed_mode::SpeedSwitch::default(),
+                speed_history: speed_history::SpeedHistories::default(),
+                disk_limits,
+                partial_cache: partial_cache::PartialCache::default(),
+                vault,
+                logging,
             });
+            tauri::async_runtime::spawn(run_persistence(app_handle.clone()));
+            tauri::async_runtime::spawn(run_progress_aggregator(app_handle.clone()));
+            tauri::async_runtime::spawn(api::apply_settings(app_handle.clone()));
+            tauri::async_runtime::spawn(clicknload::apply_settings(app_handle.clone()));
+            tauri::async_runtime::spawn(subscriptions::run_subscriptions(app_handle.clone()));
+            tauri::async_runtime::spawn(removed::run_purge(app_handle.clone()));
+            tauri::async_runtime::spawn(retention::run_cleanup(app_handle.clone()));
+            tauri::async_runtime::spawn(recurring::run_recurring(app_handle.clone()));
+            tauri::async_runtime::spawn(remote_watch::run_remote_watch(app_handle.clone()));
+            tauri::async_runtime::spawn(cookies::run_cookie_save(app_handle.clone()));
+            tauri::async_runtime::spawn(dependencies::run_dependencies(app_handle.clone()));
+            tauri::async_runtime::spawn(queue::run_queue(app_handle.clone()));
+            tauri::async_runtime::spawn(metered::run_metered_watch(app_handle.clone()));
+            tauri::async_runtime::spawn(quota::run_quota(app_handle.clone()));
+            tauri::async_runtime::spawn(idle::run_idle_watch(app_handle.clone()));
+            tauri::async_runtime::spawn(netwatch::run_network_watch(app_handle.clone()));
+            tauri::async_runtime::spawn(power::run_power_watch(app_handle.clone()));
+            tauri::async_runtime::spawn(captive::run_captive_watch(app_handle.clone()));
+            tauri::async_runtime::spawn(guards::run_space_watch(app_handle.clone()));
+            tauri::async_runtime::spawn(taskbar::run_taskbar_progress(app_handle.clone()));
+            tauri::async_runtime::spawn(shutdown::run_signal_watch(app_handle.clone()));
             let args: Vec<String> = std::env::args().collect();
+            if args.iter().any(|a| a == autostart::MINIMIZED_ARG) {
+                if let Some(window) = app_handle.get_webview_window("main") { let _ = window.hide(); }
+            }
+            if autostart_enabled {
+                if let Err(e) = autostart::apply(&app_handle, true) { log::warn!("{}", e); }
+            }
+            tauri::async_runtime::spawn(resume_interrupted(app_handle.clone()));
+            // Only release builds signed for updates carry the updater config; see `updater`
+            if updater::configured(&app_handle) { app_handle.plugin(tauri_plugin_updater::Builder::new().build())?; }
+            tauri::async_runtime::spawn(updater::resume_after_update(app_handle.clone()));
+            let handle = app_handle.clone();
+            tauri::async_runtime::spawn(async move { speed_mode::resume_held(&handle).await });
             for arg in args.iter().skip(1) { if arg.starts_with("http://") || arg.starts_with("https://") { app.emit("cli-url", arg).unwrap(); } }
+            quick_actions::install(&app_handle);
+            if let Err(e) = tray::install(&app_handle, language) { log::warn!("Could not set up the tray menu: {}", e); }
+            if let Some(action) = quick_actions::QuickAction::from_args(&args) { quick_actions::run(&app_handle, action); }
             Ok(())
         })
+        .on_tray_icon_event(|app, event| {
+            if let tauri::tray::TrayIconEvent::Click { button: tauri::tray::MouseButton::Left, .. } = event { show_main_window(app); }
+        })
+        .on_window_event(|window, event| {
+            match event {
+                tauri::WindowEvent::Focused(true) => window.state::<AppState>().taskbar.acknowledge(),
+                tauri::WindowEvent::CloseRequested { api, .. } => tray::on_close_requested(window, api),
+                _ => {}
+            }
+        })
         .invoke_handler(tauri::generate_handler![
-            get_download_info, add_download, get_all_downloads, get_settings, update_settings,
-            pause_download, resume_download, cancel_download, open_file, open_folder,
+            get_download_info, add_download, get_all_downloads, get_settings, update_settings, export_settings, import_settings,
+            pause_download, resume_download, provide_credentials, confirm_download, cancel_download, open_file, open_folder,
             choose_download_folder, handle_cli_args, remove_download, delete_download_with_file,
+            restart_download, rename_download, set_save_path, update_task_url, add_downloads,
+            pause_downloads, resume_downloads, cancel_downloads, list_removed_downloads, restore_download, empty_removed_downloads, get_task_details, verify_download, get_dashboard, get_segment_map, get_speed_history, get_task_log, collect_diagnostics, backup_app_data, restore_app_data, choose_save_file, export_history, set_task_connections, set_speed_mode, get_speed_mode, move_task, move_to_top, set_task_dependencies, set_task_repeat, set_task_watch, resolve_duplicate, clear_cookies, import_curl, export_task_command,
+            scan_page, reveal_file, extract_archive, choose_import_file, import_aria2_file, import_dlc_file, list_plugins,
+            list_accounts, add_account, remove_account, check_account, check_links, get_github_release, get_huggingface_repo, set_s3_secret_key,
+            inspect_server_certificate, read_certificate_file, set_allow_metered, set_ignore_quota, get_data_usage, set_when_idle,
+            list_subscriptions, add_subscription, update_subscription, remove_subscription, check_subscription,
+            start_mirror, list_mirrors, cancel_mirror, remove_mirror, browse_directory, enqueue_directory, list_remote_directory,
+            scan_gallery, download_gallery, updates_enabled, check_for_update, download_update, install_update,
         ])
-        .run(tauri::generate_context!()).expect("error while running tauri application");
+        .build(tauri::generate_context!()).expect("error while building tauri application")
+        .run(|app_handle, event| {
+            if let tauri::RunEvent::ExitRequested { code, api, .. } = event { shutdown::on_exit_requested(app_handle, code, &api); }
+        });
 }
@@ -0,0 +1,171 @@
+// Metered-connection awareness. The connection is checked every little while (NetworkManager over
+// D-Bus on Linux, the connection cost on Windows; other platforms always count as unmetered). While
+// metered, large downloads are held (paused and resumed once the connection isn't metered any more)
+// and the rest share a speed cap. Tasks with `allow_metered` set are left alone.
+
+use std::collections::HashSet;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+
+use tauri::{AppHandle, Emitter, Manager, State};
+use tokio::time::{Duration, Instant};
+
+use crate::i18n::{tr, Language, Text};
+use crate::{AppSettings, AppState, DownloadStatus, DownloadTask};
+
+const CHECK_INTERVAL: Duration = Duration::from_secs(30);
+const HELD_MESSAGE: &str = "Held while on a metered connection";
+
+#[derive(Default)]
+pub(crate) struct Metered {
+    active: AtomicBool,
+    // Tasks paused by the policy, to be resumed when the connection stops being metered
+    held: std::sync::Mutex<HashSet<String>>,
+    pub(crate) limit: SpeedLimit,
+}
+
+// Shared cap in bytes per second across every download it applies to; 0 is no cap
+#[derive(Default)]
+pub(crate) struct SpeedLimit {
+    bytes_per_second: AtomicU64,
+    next_free: std::sync::Mutex<Option<Instant>>,
+}
+
+impl SpeedLimit {
+    pub(crate) fn set(&self, bytes_per_second: u64) {
+        self.bytes_per_second.store(bytes_per_second, Ordering::Relaxed);
+    }
+
+    // Waits until `bytes` more fit under the cap
+    pub(crate) async fn take(&self, bytes: usize) {
+        let rate = self.bytes_per_second.load(Ordering::Relaxed);
+        if rate == 0 { return; }
+        let wait = {
+            let mut next_free = self.next_free.lock().unwrap();
+            let now = Instant::now();
+            let start = next_free.filter(|t| *t > now).unwrap_or(now);
+            *next_free = Some(start + Duration::from_secs_f64(bytes as f64 / rate as f64));
+            start - now
+        };
+        if !wait.is_zero() { tokio::time::sleep(wait).await; }
+    }
+}
+
+// Whether a download about to start should wait for an unmetered connection
+pub(crate) fn should_hold(state: &AppState, task: &DownloadTask, pause_over_mb: u64) -> bool {
+    if !state.metered.active.load(Ordering::Relaxed) || task.allow_metered || pause_over_mb == 0 { return false; }
+    // Unknown sizes count as large
+    task.total_size == 0 || task.total_size.saturating_sub(task.downloaded_size) > pause_over_mb * 1024 * 1024
+}
+
+// Marks a task as held; the caller has stopped it (or never started it)
+pub(crate) fn hold(state: &AppState, task: &mut DownloadTask) {
+    task.status = DownloadStatus::Paused;
+    task.speed = 0;
+    task.error_message = Some(HELD_MESSAGE.to_string());
+    state.metered.held.lock().unwrap().insert(task.id.clone());
+}
+
+pub(crate) fn release(state: &AppState, id: &str) -> bool {
+    state.metered.held.lock().unwrap().remove(id)
+}
+
+pub(crate) async fn run_metered_watch(app_handle: AppHandle) {
+    // Downloads held when the app last closed are picked up again on the first unmetered check
+    let mut was_metered = {
+        let state: State<AppState> = app_handle.state();
+        let held: HashSet<String> = state.persistent.lock().await.downloads.iter()
+            .filter(|t| t.status == DownloadStatus::Paused && t.error_message.as_deref() == Some(HELD_MESSAGE))
+            .map(|t| t.id.clone())
+            .collect();
+        let any = !held.is_empty();
+        *state.metered.held.lock().unwrap() = held;
+        any
+    };
+    loop {
+        let settings = app_handle.state::<AppState>().persistent.lock().await.settings.clone();
+        let metered = settings.metered_detection && detect().await.unwrap_or(false);
+        let state: State<AppState> = app_handle.state();
+        state.metered.active.store(metered, Ordering::Relaxed);
+        state.metered.limit.set(if metered { settings.metered_speed_limit_kbps * 1024 } else { 0 });
+        if metered {
+            hold_running(&app_handle, &settings).await;
+        } else if was_metered {
+            resume_held(&app_handle).await;
+        }
+        if metered != was_metered { app_handle.emit("metered_changed", metered).unwrap(); }
+        was_metered = metered;
+        tokio::time::sleep(CHECK_INTERVAL).await;
+    }
+}
+
+async fn hold_running(app_handle: &AppHandle, settings: &AppSettings) {
+    let state: State<AppState> = app_handle.state();
+    let running: HashSet<String> = state.download_handles.lock().await.keys().cloned().collect();
+    let to_hold: Vec<String> = state.persistent.lock().await.downloads.iter()
+        .filter(|t| running.contains(&t.id) && should_hold(&state, t, settings.metered_pause_over_mb))
+        .map(|t| t.id.clone())
+        .collect();
+    if to_hold.is_empty() { return; }
+    for id in &to_hold { crate::abort_download(&state, id).await; }
+    {
+        let mut state_guard = state.persistent.lock().await;
+        for task in state_guard.downloads.iter_mut().filter(|t| to_hold.contains(&t.id)) {
+            hold(&state, task);
+            app_handle.emit("task_updated", &*task).unwrap();
+        }
+    }
+    crate::request_save(&state);
+    let language = Language::from_setting(&settings.language);
+    let body = tr(language, Text::DownloadsHeldBody).replace("{count}", &to_hold.len().to_string());
+    crate::notifications::show(app_handle, settings, tr(language, Text::DownloadsHeld), &body);
+}
+
+async fn resume_held(app_handle: &AppHandle) {
+    let state: State<AppState> = app_handle.state();
+    let held: Vec<String> = state.metered.held.lock().unwrap().drain().collect();
+    // Only those still paused by the policy; the user may have removed or restarted some meanwhile
+    let resumable: Vec<String> = state.persistent.lock().await.downloads.iter()
+        .filter(|t| held.contains(&t.id) && t.status == DownloadStatus::Paused && t.error_message.as_deref() == Some(HELD_MESSAGE))
+        .map(|t| t.id.clone())
+        .collect();
+    for id in resumable {
+        if let Err(e) = crate::start_download_task(id, app_handle.clone()).await {
+            log::warn!("Could not resume a held download: {}", e);
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+async fn detect() -> Option<bool> {
+    // NMMetered: 0 unknown, 1 yes, 2 no, 3 guessed yes, 4 guessed no
+    let output = tokio::process::Command::new("busctl")
+        .args(["--system", "get-property", "org.freedesktop.NetworkManager", "/org/freedesktop/NetworkManager", "org.freedesktop.NetworkManager", "Metered"])
+        .output().await.ok()?;
+    if !output.status.success() { return None; }
+    match String::from_utf8_lossy(&output.stdout).trim() {
+        "u 1" | "u 3" => Some(true),
+        "u 2" | "u 4" => Some(false),
+        _ => None,
+    }
+}
+
+#[cfg(target_os = "windows")]
+async fn detect() -> Option<bool> {
+    const SCRIPT: &str = "[Windows.Networking.Connectivity.NetworkInformation,Windows.Networking.Connectivity,ContentType=WindowsRuntime] | Out-Null; \
+        $p = [Windows.Networking.Connectivity.NetworkInformation]::GetInternetConnectionProfile(); \
+        if ($p) { $p.GetConnectionCost().NetworkCostType }";
+    let mut command = tokio::process::Command::new("powershell");
+    command.args(["-NoProfile", "-NonInteractive", "-Command", SCRIPT]);
+    command.creation_flags(0x0800_0000); // CREATE_NO_WINDOW
+    let output = command.output().await.ok()?;
+    match String::from_utf8_lossy(&output.stdout).trim() {
+        "Fixed" | "Variable" => Some(true),
+        "Unrestricted" => Some(false),
+        _ => None,
+    }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "windows")))]
+async fn detect() -> Option<bool> {
+    None
+}
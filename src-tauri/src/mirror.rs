@@ -0,0 +1,373 @@
+// Recursive website mirroring, like `wget --mirror --page-requisites --convert-links`. Pages under the
+// start URL's directory on the same host are crawled breadth-first up to a link depth and saved with
+// their links rewritten to the local copies; every other file they reference (images, scripts,
+// stylesheets, downloads) is queued as a download task whose `parent_id` is the mirror job.
+// Links inside stylesheets and scripts aren't followed.
+
+use std::collections::{HashSet, VecDeque};
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, Local};
+use percent_encoding::{percent_decode_str, utf8_percent_encode, AsciiSet, CONTROLS};
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Emitter, Manager, State};
+use tokio::time::Duration;
+use tokio_util::sync::CancellationToken;
+use url::Url;
+
+use crate::{AddDownloadPayload, AppState};
+
+// Characters that must be escaped when a local path goes back into an `href`
+const HREF_PATH: &AsciiSet = &CONTROLS.add(b' ').add(b'"').add(b'#').add(b'%').add(b'<').add(b'>').add(b'?').add(b'`');
+// `<a>` targets with these extensions, or none, are fetched as pages; others are queued as files
+// straight away. Extensionless ones that turn out not to be HTML are queued as files then.
+const PAGE_EXTENSIONS: &[&str] = &["html", "htm", "xhtml", "shtml", "php", "asp", "aspx", "jsp", "cgi"];
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum MirrorStatus { Crawling, Completed, Cancelled, Failed }
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct MirrorJob {
+    pub(crate) id: String,
+    pub(crate) start_url: String,
+    // The site lands in `<folder>/<host>/...`
+    pub(crate) folder: String,
+    pub(crate) max_depth: u32,
+    pub(crate) max_pages: u32,
+    pub(crate) include: Vec<String>,
+    pub(crate) exclude: Vec<String>,
+    pub(crate) status: MirrorStatus,
+    pub(crate) pages_saved: u32,
+    pub(crate) files_queued: u32,
+    // Pages that couldn't be fetched or saved; the crawl carries on without them
+    pub(crate) page_errors: u32,
+    pub(crate) error: Option<String>,
+    pub(crate) created_at: DateTime<Local>,
+    pub(crate) completed_at: Option<DateTime<Local>>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct MirrorRequest {
+    pub(crate) start_url: String,
+    #[serde(default)]
+    pub(crate) folder: Option<String>,
+    #[serde(default = "default_depth")]
+    pub(crate) max_depth: u32,
+    #[serde(default = "default_max_pages")]
+    pub(crate) max_pages: u32,
+    // `*` wildcards matched against the whole URL; an empty include list admits everything
+    #[serde(default)]
+    pub(crate) include: Vec<String>,
+    #[serde(default)]
+    pub(crate) exclude: Vec<String>,
+}
+
+fn default_depth() -> u32 { 2 }
+fn default_max_pages() -> u32 { 500 }
+
+// What a link on a page is to the crawl
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum LinkKind { Page, Asset }
+
+struct Scope { host: String, path_prefix: String, include: Vec<String>, exclude: Vec<String> }
+
+impl Scope {
+    fn new(start: &Url, include: &[String], exclude: &[String]) -> Self {
+        let path = start.path();
+        let path_prefix = path[..path.rfind('/').map_or(0, |i| i + 1)].to_string();
+        let patterns = |list: &[String]| list.iter().map(|p| p.trim().to_string()).filter(|p| !p.is_empty()).collect();
+        Scope { host: site_key(start), path_prefix, include: patterns(include), exclude: patterns(exclude) }
+    }
+
+    fn admits(&self, url: &Url) -> bool {
+        let url = url.as_str();
+        (self.include.is_empty() || self.include.iter().any(|p| wildcard_match(p, url)))
+            && !self.exclude.iter().any(|p| wildcard_match(p, url))
+    }
+
+    fn crawls(&self, url: &Url) -> bool {
+        site_key(url) == self.host && url.path().starts_with(&self.path_prefix) && self.admits(url)
+    }
+}
+
+fn site_key(url: &Url) -> String {
+    match url.port() {
+        Some(port) => format!("{}_{}", url.host_str().unwrap_or_default(), port),
+        None => url.host_str().unwrap_or_default().to_string(),
+    }
+}
+
+// `*` matches any run of characters, everything else itself
+pub(crate) fn wildcard_match(pattern: &str, text: &str) -> bool {
+    let parts: Vec<&str> = pattern.split('*').collect();
+    if parts.len() == 1 { return pattern == text; }
+    let (first, last) = (parts[0], parts[parts.len() - 1]);
+    if !text.starts_with(first) || text.len() < first.len() + last.len() || !text.ends_with(last) { return false; }
+    let mut rest = &text[first.len()..text.len() - last.len()];
+    for part in &parts[1..parts.len() - 1] {
+        match rest.find(part) {
+            Some(i) => rest = &rest[i + part.len()..],
+            None => return false,
+        }
+    }
+    true
+}
+
+fn is_page_like(url: &Url) -> bool {
+    let name = url.path_segments().and_then(|mut s| s.next_back()).unwrap_or_default();
+    match name.rsplit_once('.') {
+        Some((_, ext)) => PAGE_EXTENSIONS.contains(&ext.to_ascii_lowercase().as_str()),
+        None => true,
+    }
+}
+
+fn sanitize(segment: &str) -> String {
+    let name: String = segment.chars()
+        .map(|c| if c.is_control() || matches!(c, '/' | '\\' | '<' | '>' | ':' | '"' | '|' | '?' | '*') { '_' } else { c })
+        .collect();
+    if crate::is_valid_file_name(&name) { name } else { "_".to_string() }
+}
+
+// Where a URL is stored, relative to the mirror folder: `<host>/<path>`, directories as `index.html`,
+// the query folded into the name and `.html` added to extensionless pages so they open in a browser.
+// A non-HTML file at an extensionless `<a>` link is stored without the `.html`, which leaves links to
+// it pointing at the wrong name; that case is rare enough not to fetch every link twice over.
+fn local_path(url: &Url, kind: LinkKind) -> PathBuf {
+    let mut segments: Vec<String> = url.path_segments().map(|s| s.map(|s| percent_decode_str(s).decode_utf8_lossy().to_string()).collect())
+        .unwrap_or_default();
+    let mut name = segments.pop().filter(|n| !n.is_empty()).unwrap_or_else(|| "index.html".to_string());
+    let (stem, ext) = match name.rsplit_once('.') {
+        Some((stem, ext)) if !stem.is_empty() => (stem.to_string(), Some(ext.to_string())),
+        _ => (name.clone(), None),
+    };
+    let ext = match ext {
+        None if kind == LinkKind::Page => Some("html".to_string()),
+        ext => ext,
+    };
+    if let Some(query) = url.query().filter(|q| !q.is_empty()) {
+        name = format!("{}@{}", stem, query);
+        if let Some(ext) = &ext { name = format!("{}.{}", name, ext); }
+    } else if let Some(ext) = &ext {
+        name = format!("{}.{}", stem, ext);
+    }
+    let mut path = PathBuf::from(sanitize(&site_key(url)));
+    for segment in segments.iter().filter(|s| !s.is_empty()) { path.push(sanitize(segment)); }
+    path.push(sanitize(&name));
+    path
+}
+
+// `to`, relative to the directory holding `from`, as an `href`
+fn relative_href(from: &Path, to: &Path) -> String {
+    let from_dir: Vec<_> = from.parent().map(|p| p.components().collect()).unwrap_or_default();
+    let to_parts: Vec<_> = to.components().collect();
+    let common = from_dir.iter().zip(&to_parts).take_while(|(a, b)| a == b).count();
+    let mut parts: Vec<String> = vec!["..".to_string(); from_dir.len() - common];
+    parts.extend(to_parts[common..].iter().map(|c| utf8_percent_encode(&c.as_os_str().to_string_lossy(), HREF_PATH).to_string()));
+    parts.join("/")
+}
+
+// Rewrites the page's links (mirrored ones to local paths, the rest to absolute URLs, so dropping
+// `<base>` can't break them) and returns the serialized page with the links to follow.
+// Kept free of awaits: kuchikiki's DOM isn't `Send`.
+fn process_page(html: &str, page_url: &Url, page_path: &Path, wanted: impl Fn(&Url, LinkKind) -> bool) -> (String, Vec<(Url, LinkKind)>) {
+    use kuchikiki::traits::TendrilSink;
+    let document = kuchikiki::parse_html().one(html);
+    let base = document.select_first("base[href]").ok()
+        .and_then(|base| base.attributes.borrow().get("href").and_then(|href| page_url.join(href.trim()).ok()))
+        .unwrap_or_else(|| page_url.clone());
+    if let Ok(bases) = document.select("base") {
+        for base in bases.collect::<Vec<_>>() { base.as_node().detach(); }
+    }
+    let selectors: &[(&str, &str, LinkKind)] = &[
+        ("a[href], area[href]", "href", LinkKind::Page),
+        ("iframe[src], frame[src]", "src", LinkKind::Page),
+        ("img[src], script[src], embed[src], video[src], audio[src], source[src], track[src], input[src]", "src", LinkKind::Asset),
+        ("video[poster]", "poster", LinkKind::Asset),
+        ("link[href]", "href", LinkKind::Asset),
+    ];
+
+    let mut links = Vec::new();
+    for (selector, attribute, kind) in selectors {
+        let Ok(elements) = document.select(selector) else { continue };
+        for element in elements {
+            let mut attributes = element.attributes.borrow_mut();
+            // Only stylesheets and icons are page requisites; `canonical`, `alternate` and the like aren't
+            if element.name.local.as_ref() == "link" {
+                let rel = attributes.get("rel").unwrap_or_default().to_ascii_lowercase();
+                if !rel.split_whitespace().any(|r| matches!(r, "stylesheet" | "icon" | "apple-touch-icon" | "manifest")) { continue; }
+            }
+            // The rewritten `src` is the local copy; a `srcset` would send the browser back online
+            attributes.remove("srcset");
+            let Some(raw) = attributes.get(*attribute).map(|v| v.trim().to_string()) else { continue };
+            if raw.starts_with('#') { continue; }
+            let Ok(mut link) = base.join(&raw) else { continue };
+            if !matches!(link.scheme(), "http" | "https") { continue; }
+            let fragment = link.fragment().map(str::to_string);
+            link.set_fragment(None);
+            let rewritten = if wanted(&link, *kind) {
+                let kind = if *kind == LinkKind::Page && !is_page_like(&link) { LinkKind::Asset } else { *kind };
+                let href = relative_href(page_path, &local_path(&link, kind));
+                links.push((link, kind));
+                match fragment {
+                    Some(fragment) => format!("{}#{}", href, fragment),
+                    None => href,
+                }
+            } else {
+                base.join(&raw).map(|u| u.to_string()).unwrap_or(raw)
+            };
+            attributes.insert(*attribute, rewritten);
+        }
+    }
+    (document.to_string(), links)
+}
+
+pub(crate) fn new_job(request: &MirrorRequest, folder: String) -> Result<MirrorJob, String> {
+    let start = Url::parse(request.start_url.trim()).map_err(|e| format!("Invalid URL: {}", e))?;
+    if !matches!(start.scheme(), "http" | "https") { return Err("Only http and https sites can be mirrored".to_string()); }
+    Ok(MirrorJob {
+        id: format!("mirror-{}", uuid::Uuid::new_v4()), start_url: start.to_string(), folder,
+        max_depth: request.max_depth, max_pages: request.max_pages.max(1),
+        include: request.include.clone(), exclude: request.exclude.clone(),
+        status: MirrorStatus::Crawling, pages_saved: 0, files_queued: 0, page_errors: 0, error: None,
+        created_at: Local::now(), completed_at: None,
+    })
+}
+
+// Crawls were running when the app last quit; they don't resume
+pub(crate) fn mark_interrupted(jobs: &mut [MirrorJob]) {
+    for job in jobs.iter_mut().filter(|j| j.status == MirrorStatus::Crawling) {
+        job.status = MirrorStatus::Cancelled;
+        job.error = Some("Interrupted when the app closed".to_string());
+    }
+}
+
+async fn update_job(app_handle: &AppHandle, id: &str, update: impl FnOnce(&mut MirrorJob)) {
+    let state: State<AppState> = app_handle.state();
+    let updated = {
+        let mut state_guard = state.persistent.lock().await;
+        let Some(job) = state_guard.mirrors.iter_mut().find(|j| j.id == id) else { return };
+        update(job);
+        job.clone()
+    };
+    crate::request_save(&state);
+    app_handle.emit("mirror_updated", &updated).unwrap();
+}
+
+pub(crate) async fn run(app_handle: AppHandle, job: MirrorJob, cancel: CancellationToken) {
+    let result = crawl(&app_handle, &job, &cancel).await;
+    let status = match &result {
+        _ if cancel.is_cancelled() => MirrorStatus::Cancelled,
+        Ok(()) => MirrorStatus::Completed,
+        Err(_) => MirrorStatus::Failed,
+    };
+    update_job(&app_handle, &job.id, |j| {
+        j.status = status;
+        j.error = result.err();
+        j.completed_at = Some(Local::now());
+    }).await;
+    let state: State<AppState> = app_handle.state();
+    state.mirror_crawls.lock().await.remove(&job.id);
+}
+
+async fn crawl(app_handle: &AppHandle, job: &MirrorJob, cancel: &CancellationToken) -> Result<(), String> {
+    let state: State<AppState> = app_handle.state();
+    let client = crate::http_client(&state).await;
+    let start = Url::parse(&job.start_url).map_err(|e| format!("Invalid URL: {}", e))?;
+    let scope = Scope::new(&start, &job.include, &job.exclude);
+    let root = PathBuf::from(&job.folder);
+
+    let mut queue = VecDeque::from([(start.clone(), 0u32)]);
+    let mut visited: HashSet<String> = HashSet::from([start.to_string()]);
+    let mut queued_files: HashSet<String> = HashSet::new();
+    let mut pages_saved = 0;
+    while let Some((url, depth)) = queue.pop_front() {
+        if cancel.is_cancelled() || pages_saved >= job.max_pages { break; }
+        let response = tokio::select! {
+            _ = cancel.cancelled() => break,
+            response = client.get(url.as_str())
+                .header("Accept", "text/html,application/xhtml+xml,application/xml;q=0.9,*/*;q=0.8")
+                .timeout(Duration::from_secs(30))
+                .send() => response,
+        };
+        let response = match response {
+            Ok(response) if response.status().is_success() => response,
+            Ok(response) if url == start => return Err(format!("Server returned error: {}", response.status())),
+            Err(e) if url == start => return Err(format!("Request failed: {}", e)),
+            _ => {
+                update_job(app_handle, &job.id, |j| j.page_errors += 1).await;
+                continue;
+            }
+        };
+        let is_html = response.headers().get(reqwest::header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .is_some_and(|v| v.to_ascii_lowercase().starts_with("text/html") || v.to_ascii_lowercase().starts_with("application/xhtml"));
+        if !is_html {
+            // A file linked like a page; dropping the response closes it without reading the body
+            let payloads = file_payloads(job, &root, [url], &mut queued_files);
+            queue_files(app_handle, job, payloads).await?;
+            continue;
+        }
+
+        // Redirects may leave the site; such pages are saved but not followed
+        let page_url = response.url().clone();
+        let html = crate::read_capped_body(response, crate::MAX_SCAN_PAGE_BYTES).await;
+        let follow = depth < job.max_depth && scope.crawls(&page_url);
+        // Stored under the URL it was linked by, which is what the other pages' links were rewritten to
+        let local = local_path(&url, LinkKind::Page);
+        let (rewritten, links) = process_page(&html, &page_url, &local, |link, kind| match kind {
+            LinkKind::Page => follow && scope.crawls(link),
+            LinkKind::Asset => scope.admits(link),
+        });
+        let path = root.join(local);
+        let saved = match path.parent() {
+            Some(dir) => tokio::fs::create_dir_all(dir).await.and(tokio::fs::write(&path, rewritten).await),
+            None => Ok(()),
+        };
+        if let Err(e) = saved {
+            if url == start { return Err(format!("Could not save {}: {}", path.display(), e)); }
+            update_job(app_handle, &job.id, |j| j.page_errors += 1).await;
+            continue;
+        }
+        pages_saved += 1;
+
+        let mut files = Vec::new();
+        for (link, kind) in links {
+            match kind {
+                LinkKind::Page => if visited.insert(link.to_string()) { queue.push_back((link, depth + 1)) },
+                LinkKind::Asset => files.push(link),
+            }
+        }
+        let payloads = file_payloads(job, &root, files, &mut queued_files);
+        queue_files(app_handle, job, payloads).await?;
+        update_job(app_handle, &job.id, |j| j.pages_saved = pages_saved).await;
+    }
+    Ok(())
+}
+
+fn file_payloads(
+    job: &MirrorJob, root: &Path, links: impl IntoIterator<Item = Url>, queued: &mut HashSet<String>,
+) -> Vec<AddDownloadPayload> {
+    links.into_iter()
+        .filter(|link| queued.insert(link.to_string()))
+        .filter_map(|link| {
+            let path = root.join(local_path(&link, LinkKind::Asset));
+            Some(AddDownloadPayload {
+                file_name: path.file_name()?.to_string_lossy().to_string(),
+                custom_path: Some(path.parent()?.to_string_lossy().to_string()),
+                url: link.to_string(), total_size: None, checksum: None,
+                headers: Default::default(), redirect_chain: Vec::new(), parent_id: Some(job.id.clone()), signature: None, allow_http: false, proxy: None, request: None, source_url: None,
+            })
+        })
+        .collect()
+}
+
+async fn queue_files(app_handle: &AppHandle, job: &MirrorJob, payloads: Vec<AddDownloadPayload>) -> Result<(), String> {
+    if payloads.is_empty() { return Ok(()); }
+    let results = crate::add_downloads(payloads, app_handle.state(), app_handle.clone()).await?;
+    let added = results.iter().filter(|r| r.task.is_some()).count() as u32;
+    update_job(app_handle, &job.id, |j| j.files_queued += added).await;
+    Ok(())
+}
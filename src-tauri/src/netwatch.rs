@@ -0,0 +1,59 @@
+// Pauses running downloads the moment the machine's network changes (connection lost, Wi-Fi to
+// Ethernet, a VPN coming up or down) and resumes them once it has settled with a usable address,
+// rather than letting every task spend its resume attempts against a dead or replaced route. There's
+// no portable change notification, so the OS interface table is sampled every couple of seconds.
+
+use std::collections::{BTreeSet, HashSet};
+use std::net::IpAddr;
+
+use network_interface::{NetworkInterface, NetworkInterfaceConfig};
+use tauri::{AppHandle, Emitter, Manager};
+use tokio::time::Duration;
+
+use crate::AppState;
+
+const SAMPLE_INTERVAL: Duration = Duration::from_secs(2);
+const PAUSED_MESSAGE: &str = "Paused while the network changed";
+
+// Every usable (interface, address) pair; empty means offline
+fn snapshot() -> Option<BTreeSet<(String, IpAddr)>> {
+    let interfaces = NetworkInterface::show().ok()?;
+    Some(interfaces.into_iter()
+        .flat_map(|i| i.addr.into_iter().map(move |a| (i.name.clone(), a.ip())))
+        .filter(|(_, ip)| !ip.is_loopback() && !ip.is_unspecified() && !is_link_local(ip))
+        .collect())
+}
+
+fn is_link_local(ip: &IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(v4) => v4.is_link_local(),
+        IpAddr::V6(v6) => (v6.segments()[0] & 0xffc0) == 0xfe80,
+    }
+}
+
+pub(crate) async fn run_network_watch(app_handle: AppHandle) {
+    let mut last = snapshot();
+    // Tasks this watcher paused, resumed once the network is usable again
+    let mut paused: HashSet<String> = HashSet::new();
+    // The new state has to hold for one more sample before downloads resume, so DHCP and VPN
+    // handshakes can finish
+    let mut settled = true;
+    loop {
+        tokio::time::sleep(SAMPLE_INTERVAL).await;
+        let Some(current) = snapshot() else { continue };
+        let enabled = app_handle.state::<AppState>().persistent.lock().await.settings.pause_on_network_change;
+        if last.as_ref() != Some(&current) {
+            let online = !current.is_empty();
+            log::info!("Network changed ({} usable addresses)", current.len());
+            app_handle.emit("network_changed", online).unwrap();
+            if enabled { paused.extend(crate::pause_running(&app_handle, PAUSED_MESSAGE).await); }
+            last = Some(current);
+            settled = false;
+            continue;
+        }
+        if !settled && !current.is_empty() {
+            settled = true;
+            crate::resume_paused(&app_handle, std::mem::take(&mut paused), PAUSED_MESSAGE).await;
+        }
+    }
+}
@@ -0,0 +1,54 @@
+// Which IP family connections use and which local interface or address they leave from, e.g. to keep
+// downloads on a VPN interface. The family is applied by resolving names ourselves and filtering or
+// ordering the addresses; hyper still falls back to the other family when a preferred one stalls.
+
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+
+use reqwest::dns::{Addrs, Name, Resolve, Resolving};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "camelCase")]
+pub(crate) enum IpFamily { #[default] Auto, PreferIpv4, PreferIpv6, Ipv4Only, Ipv6Only }
+
+struct FamilyResolver(IpFamily);
+
+impl Resolve for FamilyResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        let family = self.0;
+        Box::pin(async move {
+            let mut addrs: Vec<SocketAddr> = tokio::net::lookup_host((name.as_str(), 0)).await?.collect();
+            match family {
+                IpFamily::Auto => {}
+                IpFamily::PreferIpv4 => addrs.sort_by_key(|a| a.is_ipv6()),
+                IpFamily::PreferIpv6 => addrs.sort_by_key(|a| a.is_ipv4()),
+                IpFamily::Ipv4Only => addrs.retain(SocketAddr::is_ipv4),
+                IpFamily::Ipv6Only => addrs.retain(SocketAddr::is_ipv6),
+            }
+            if addrs.is_empty() {
+                let family = if family == IpFamily::Ipv4Only { "IPv4" } else { "IPv6" };
+                return Err(format!("{} has no {} address", name.as_str(), family).into());
+            }
+            Ok(Box::new(addrs.into_iter()) as Addrs)
+        })
+    }
+}
+
+// `bind` is a local IP address or, where the OS supports it, an interface name such as `wg0`
+pub(crate) fn apply(mut builder: reqwest::ClientBuilder, family: IpFamily, bind: &str) -> Result<reqwest::ClientBuilder, String> {
+    if family != IpFamily::Auto {
+        builder = builder.dns_resolver(Arc::new(FamilyResolver(family)));
+    }
+    let bind = bind.trim();
+    if bind.is_empty() { return Ok(builder); }
+    if let Ok(address) = bind.parse::<IpAddr>() {
+        let mismatched = (family == IpFamily::Ipv4Only && address.is_ipv6()) || (family == IpFamily::Ipv6Only && address.is_ipv4());
+        if mismatched { return Err(format!("{} doesn't match the IP family setting", address)); }
+        return Ok(builder.local_address(address));
+    }
+    #[cfg(any(target_os = "android", target_os = "fuchsia", target_os = "linux", target_os = "macos", target_os = "ios"))]
+    { Ok(builder.interface(bind)) }
+    #[cfg(not(any(target_os = "android", target_os = "fuchsia", target_os = "linux", target_os = "macos", target_os = "ios")))]
+    { Err(format!("Binding to an interface by name isn't supported here; use the address of {} instead", bind)) }
+}
@@ -0,0 +1,114 @@
+// Desktop notifications, honouring the on/off switch and quiet hours. During quiet hours a
+// notification is only logged, so an overnight batch doesn't ping synced devices.
+
+use chrono::{Local, NaiveTime};
+use tauri::{AppHandle, Manager};
+use tauri_plugin_notification::NotificationExt;
+
+#[cfg(target_os = "linux")]
+use crate::i18n::{tr, Language, Text};
+use crate::{AppSettings, AppState};
+
+// What a notification button does
+#[derive(Debug, Clone)]
+pub(crate) enum Action {
+    // Continue a failed download where it stopped
+    Retry(String),
+    // Discard what was downloaded and start over
+    Restart(String),
+    // Open a finished download with its default app
+    Open(String),
+    // Show a download in the file manager
+    Reveal(String),
+}
+
+pub(crate) fn show(app_handle: &AppHandle, settings: &AppSettings, title: &str, body: &str) {
+    show_with_actions(app_handle, settings, title, body, Vec::new());
+}
+
+// Only Linux notification servers report which button was pressed; elsewhere the notification is
+// shown without buttons. Buttons work from the backend alone, so they still act while the window is
+// closed to the tray; clicking the notification itself brings the window back.
+pub(crate) fn show_with_actions(app_handle: &AppHandle, settings: &AppSettings, title: &str, body: &str, actions: Vec<(&'static str, Action)>) {
+    if !settings.show_notifications { return; }
+    if in_quiet_hours(settings, Local::now().time()) {
+        log::info!("Quiet hours, not notifying: {}: {}", title, body);
+        return;
+    }
+    #[cfg(target_os = "linux")]
+    if !actions.is_empty() {
+        let (app_handle, title, body) = (app_handle.clone(), title.to_string(), body.to_string());
+        let language = Language::from_setting(&settings.language);
+        // Showing and waiting both block on D-Bus; the wait lasts until the notification is dismissed
+        tauri::async_runtime::spawn_blocking(move || {
+            let mut notification = notify_rust::Notification::new();
+            notification.appname("Velodown").summary(&title).body(&body);
+            notification.action("default", tr(language, Text::Show));
+            for (index, (label, _)) in actions.iter().enumerate() {
+                notification.action(&index.to_string(), label);
+            }
+            match notification.show() {
+                Ok(handle) => handle.wait_for_action(|id| {
+                    if id == "default" {
+                        crate::show_main_window(&app_handle);
+                    } else if let Some((_, action)) = id.parse::<usize>().ok().and_then(|index| actions.into_iter().nth(index)) {
+                        perform(&app_handle, action);
+                    }
+                }),
+                Err(e) => {
+                    log::warn!("Could not show a notification with buttons: {}", e);
+                    let _ = app_handle.notification().builder().title(title).body(body).show();
+                }
+            }
+        });
+        return;
+    }
+    #[cfg(not(target_os = "linux"))]
+    let _ = actions;
+    let _ = app_handle.notification().builder().title(title).body(body).show();
+}
+
+fn perform(app_handle: &AppHandle, action: Action) {
+    let app_handle = app_handle.clone();
+    tauri::async_runtime::spawn(async move {
+        let result = match action {
+            Action::Retry(id) => crate::retry_download(id, app_handle.clone()).await,
+            Action::Restart(id) => crate::restart_download(id, app_handle.state(), app_handle.clone()).await,
+            Action::Open(id) => open(&app_handle, &id).await,
+            Action::Reveal(id) => match file_path(&app_handle, &id).await {
+                Some((save_path, file_name)) => crate::reveal_file(std::path::Path::new(&save_path).join(file_name).to_string_lossy().to_string()).await,
+                None => Ok(()),
+            },
+        };
+        if let Err(e) = result { log::warn!("Notification action failed: {}", e); }
+    });
+}
+
+// A file VirusTotal flagged since the notification went up isn't opened from it; the window asks first
+async fn open(app_handle: &AppHandle, id: &str) -> Result<(), String> {
+    let flagged = app_handle.state::<AppState>().persistent.lock().await.downloads.iter()
+        .any(|t| t.id == id && t.virus_scan.as_ref().is_some_and(|scan| scan.flagged()));
+    if flagged {
+        crate::show_main_window(app_handle);
+        return Ok(());
+    }
+    match file_path(app_handle, id).await {
+        Some((save_path, file_name)) => crate::open_file(save_path, file_name).await,
+        None => Ok(()),
+    }
+}
+
+// Where a download is now; `None` once it's been removed from the list
+async fn file_path(app_handle: &AppHandle, id: &str) -> Option<(String, String)> {
+    app_handle.state::<AppState>().persistent.lock().await.downloads.iter()
+        .find(|t| t.id == id)
+        .map(|t| (t.save_path.clone(), t.file_name.clone()))
+}
+
+// `HH:MM` bounds; a start after the end spans midnight
+fn in_quiet_hours(settings: &AppSettings, now: NaiveTime) -> bool {
+    if !settings.quiet_hours_enabled { return false; }
+    let parse = |time: &str| NaiveTime::parse_from_str(time.trim(), "%H:%M").ok();
+    let (Some(start), Some(end)) = (parse(&settings.quiet_hours_start), parse(&settings.quiet_hours_end)) else { return false };
+    if start <= end { now >= start && now < end } else { now >= start || now < end }
+}
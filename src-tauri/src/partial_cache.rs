@@ -0,0 +1,165 @@
+// A cache of partial downloads, so adding a cancelled download again picks up where it stopped. When
+// an unfinished download with a strong ETag is cancelled keeping its file, and the user asks to cache
+// it, the partial file moves into `partial-cache` in the app data folder, named by a hash of its URL
+// and ETag; one cancelled with its file deleted still goes to the trash. A
+// new download of the same URL (compared by `canonical_url`) starts from those bytes; the resume asks
+// with `If-Range`, so a server whose file has changed since sends it whole instead. `partial_cache_mb`
+// caps the cache, evicting the oldest entries first.
+
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tauri::{AppHandle, Emitter, Manager, State};
+use tokio::sync::Mutex;
+
+use crate::{AppState, DownloadStatus, Segment};
+
+const MB: u64 = 1024 * 1024;
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+struct Entry {
+    url: String,
+    etag: String,
+    total_size: u64,
+    downloaded_size: u64,
+    #[serde(default)]
+    segments: Vec<Segment>,
+    cached_at: DateTime<Local>,
+}
+
+impl Entry {
+    fn file_name(&self) -> String {
+        hex::encode(Sha256::digest(format!("{}\n{}", self.url, self.etag)))
+    }
+}
+
+#[derive(Default)]
+pub(crate) struct PartialCache {
+    // Read from `index.json` on first use; oldest first
+    entries: Mutex<Option<Vec<Entry>>>,
+}
+
+fn cache_dir(app_handle: &AppHandle) -> Option<PathBuf> {
+    Some(app_handle.path().app_data_dir().ok()?.join("partial-cache"))
+}
+
+async fn load(dir: &Path) -> Vec<Entry> {
+    match tokio::fs::read(dir.join("index.json")).await {
+        Ok(bytes) => serde_json::from_slice(&bytes).unwrap_or_default(),
+        Err(_) => Vec::new(),
+    }
+}
+
+async fn save(dir: &Path, entries: &[Entry]) {
+    let result = async {
+        tokio::fs::create_dir_all(dir).await?;
+        tokio::fs::write(dir.join("index.json"), serde_json::to_vec(entries)?).await
+    }.await;
+    if let Err(e) = result { log::warn!("Could not save the partial download cache: {}", e); }
+}
+
+// Moves the partial file of a download being cancelled into the cache; false when it doesn't qualify,
+// and the caller deletes it as usual
+pub(crate) async fn store(app_handle: &AppHandle, id: &str) -> bool {
+    let state: State<AppState> = app_handle.state();
+    let (limit, entry, path) = {
+        let state_guard = state.persistent.lock().await;
+        let Some(task) = state_guard.downloads.iter().find(|t| t.id == id) else { return false };
+        let Some(etag) = task.etag.clone().filter(|e| !e.starts_with("W/")) else { return false };
+        // A download with a custom request starts over every time, so its bytes are no use
+        if task.status == DownloadStatus::Completed || task.downloaded_size == 0 || task.total_size == 0 || task.request.is_some() { return false; }
+        let entry = Entry {
+            url: task.url.clone(), etag, total_size: task.total_size, downloaded_size: task.downloaded_size,
+            segments: task.segments.clone(), cached_at: Local::now(),
+        };
+        (state_guard.settings.partial_cache_mb * MB, entry, crate::task_file_path(task))
+    };
+    // A partial file is as large as the whole one when preallocated
+    if limit == 0 || entry.total_size > limit || !path.exists() { return false; }
+    let Some(dir) = cache_dir(app_handle) else { return false };
+    let mut entries = state.partial_cache.entries.lock().await;
+    let entries = match &mut *entries {
+        Some(entries) => entries,
+        empty => empty.insert(load(&dir).await),
+    };
+    if let Err(e) = tokio::fs::create_dir_all(&dir).await {
+        log::warn!("Could not create the partial download cache: {}", e);
+        return false;
+    }
+    let name = entry.file_name();
+    if let Err(e) = crate::move_file(&path, &dir.join(&name), |_, _| {}).await {
+        log::warn!("Could not cache the partial download {}: {}", path.display(), e);
+        return false;
+    }
+    entries.retain(|e| e.file_name() != name);
+    entries.push(entry);
+    evict(&dir, entries, limit).await;
+    save(&dir, entries).await;
+    true
+}
+
+// Drops the oldest entries until the cache fits in `limit` bytes
+async fn evict(dir: &Path, entries: &mut Vec<Entry>, limit: u64) {
+    let mut sizes = Vec::with_capacity(entries.len());
+    for entry in entries.iter() {
+        sizes.push(tokio::fs::metadata(dir.join(entry.file_name())).await.map(|m| m.len()).unwrap_or(0));
+    }
+    let mut total: u64 = sizes.iter().sum();
+    let mut evicted = 0;
+    while total > limit && evicted < entries.len() {
+        let _ = tokio::fs::remove_file(dir.join(entries[evicted].file_name())).await;
+        total -= sizes[evicted];
+        evicted += 1;
+    }
+    entries.drain(..evicted);
+}
+
+// Puts cached bytes in place for a download starting with nothing on disk
+pub(crate) async fn restore(app_handle: &AppHandle, id: &str) {
+    let state: State<AppState> = app_handle.state();
+    let (url, path) = {
+        let state_guard = state.persistent.lock().await;
+        let Some(task) = state_guard.downloads.iter().find(|t| t.id == id) else { return };
+        if task.downloaded_size > 0 || !task.segments.is_empty() || task.working_dir.is_some() { return; }
+        (task.url.clone(), crate::task_file_path(task))
+    };
+    if state.persistent.lock().await.settings.partial_cache_mb == 0 || path.exists() { return; }
+    let Some(dir) = cache_dir(app_handle) else { return };
+    let entry = {
+        let mut entries = state.partial_cache.entries.lock().await;
+        let entries = match &mut *entries {
+            Some(entries) => entries,
+            empty => empty.insert(load(&dir).await),
+        };
+        let url = crate::canonical_url(&url);
+        let Some(index) = entries.iter().rposition(|e| crate::canonical_url(&e.url) == url) else { return };
+        let entry = entries.remove(index);
+        save(&dir, entries).await;
+        entry
+    };
+    let cached = dir.join(entry.file_name());
+    let moved = async {
+        if let Some(parent) = path.parent() { tokio::fs::create_dir_all(parent).await?; }
+        crate::move_file(&cached, &path, |_, _| {}).await
+    }.await;
+    if let Err(e) = moved {
+        log::warn!("Could not reuse the cached part of {}: {}", url, e);
+        let _ = tokio::fs::remove_file(&cached).await;
+        return;
+    }
+    {
+        let mut state_guard = state.persistent.lock().await;
+        let Some(task) = state_guard.downloads.iter_mut().find(|t| t.id == id) else { return };
+        task.total_size = entry.total_size;
+        task.downloaded_size = entry.downloaded_size;
+        task.segments = entry.segments;
+        task.etag = Some(entry.etag);
+        task.progress = entry.downloaded_size as f64 / entry.total_size as f64 * 100.0;
+        app_handle.emit("task_updated", &*task).unwrap();
+    }
+    crate::request_save(&state);
+    crate::tasklog::write(app_handle, id, &format!("Reusing {} cached bytes from an earlier download", entry.downloaded_size)).await;
+}
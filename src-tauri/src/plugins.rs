@@ -0,0 +1,168 @@
+// Link resolver plugins: programs that turn a page or file-host URL into direct download URLs.
+// Each plugin is a folder under `<app data>/plugins` holding a `plugin.json` manifest:
+//
+//     { "name": "Example host", "hosts": ["example.com"], "command": ["python3", "resolve.py"] }
+//
+// The command runs inside the plugin folder and gets one JSON request on stdin:
+//
+//     {"action": "resolve", "url": "..", "account": {"username": "..", "secret": ".."}}
+//
+// and prints `{"urls": [..], "fileName": "..", "headers": {..}, "account": {..}}` to stdout, where
+// `urls` are mirrors in order of preference and the other fields are optional. `account` is only
+// sent when a usable premium account exists for the host; the plugin reports back on it as
+// `{"valid": true, "trafficLeft": bytes, "validUntil": "<RFC 3339>"}`. `{"action": "check"}` requests
+// carry only the account and expect only that report. A non-zero exit is reported as a failure.
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Emitter, Manager, State};
+use tokio::io::AsyncWriteExt;
+use tokio::time::{timeout, Duration};
+use url::Url;
+
+use crate::accounts::{self, Account, AccountReport};
+use crate::AppState;
+
+const MANIFEST_FILE: &str = "plugin.json";
+const RESOLVE_TIMEOUT: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct PluginManifest {
+    pub(crate) name: String,
+    // Matched against the URL host and its subdomains
+    pub(crate) hosts: Vec<String>,
+    pub(crate) command: Vec<String>,
+    #[serde(skip_deserializing)]
+    pub(crate) path: PathBuf,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct Resolved {
+    pub(crate) urls: Vec<String>,
+    #[serde(default)]
+    pub(crate) file_name: Option<String>,
+    #[serde(default)]
+    pub(crate) headers: BTreeMap<String, String>,
+    #[serde(default)]
+    account: Option<AccountReport>,
+}
+
+#[derive(Debug, Deserialize)]
+struct CheckResponse { account: AccountReport }
+
+pub(crate) fn plugins_dir(app_handle: &AppHandle) -> anyhow::Result<PathBuf> {
+    Ok(app_handle.path().app_data_dir()?.join("plugins"))
+}
+
+// Reads every manifest in the plugins folder; broken ones are logged and skipped
+pub(crate) async fn discover(app_handle: &AppHandle) -> Vec<PluginManifest> {
+    let Ok(dir) = plugins_dir(app_handle) else { return Vec::new() };
+    let Ok(mut entries) = tokio::fs::read_dir(&dir).await else { return Vec::new() };
+    let mut plugins = Vec::new();
+    while let Ok(Some(entry)) = entries.next_entry().await {
+        let manifest_path = entry.path().join(MANIFEST_FILE);
+        let Ok(content) = tokio::fs::read_to_string(&manifest_path).await else { continue };
+        match serde_json::from_str::<PluginManifest>(&content) {
+            Ok(manifest) if !manifest.command.is_empty() => plugins.push(PluginManifest { path: entry.path(), ..manifest }),
+            Ok(_) => log::warn!("Plugin {} has an empty command", manifest_path.display()),
+            Err(e) => log::warn!("Invalid plugin manifest {}: {}", manifest_path.display(), e),
+        }
+    }
+    plugins.sort_by(|a, b| a.name.cmp(&b.name));
+    plugins
+}
+
+fn matches_host(hosts: &[String], host: &str) -> bool {
+    hosts.iter().any(|h| {
+        let h = h.trim().trim_start_matches("*.").to_ascii_lowercase();
+        host == h || host.strip_suffix(&h).is_some_and(|rest| rest.ends_with('.'))
+    })
+}
+
+async fn plugin_for_host(app_handle: &AppHandle, host: &str) -> Option<PluginManifest> {
+    discover(app_handle).await.into_iter().find(|p| matches_host(&p.hosts, host))
+}
+
+// Runs the first plugin claiming the URL's host, with the first usable account for that host.
+// `None` means no plugin handles it.
+pub(crate) async fn resolve(app_handle: &AppHandle, url: &str) -> Result<Option<(String, Resolved)>, String> {
+    let Some(host) = Url::parse(url).ok().and_then(|u| u.host_str().map(str::to_ascii_lowercase)) else { return Ok(None) };
+    let Some(plugin) = plugin_for_host(app_handle, &host).await else { return Ok(None) };
+    let state: State<AppState> = app_handle.state();
+    let account = state.persistent.lock().await.accounts.iter()
+        .find(|a| a.usable() && matches_host(std::slice::from_ref(&a.host), &host))
+        .cloned();
+    let credentials = match &account {
+        Some(account) => match accounts::load_secret(&account.id).await {
+            Ok(secret) => Some(serde_json::json!({ "username": account.username, "secret": secret })),
+            Err(e) => {
+                log::warn!("Resolving without account {}: {}", account.username, e);
+                None
+            }
+        },
+        None => None,
+    };
+    let request = serde_json::json!({ "action": "resolve", "url": url, "account": credentials });
+    let mut resolved: Resolved = run(&plugin, &request).await.map_err(|e| format!("Plugin {} failed: {}", plugin.name, e))?;
+    if let (Some(account), Some(report)) = (account.filter(|_| credentials.is_some()), resolved.account.take()) {
+        update_account(app_handle, &account.id, report).await;
+    }
+    if resolved.urls.is_empty() { return Err(format!("Plugin {} found no download links", plugin.name)); }
+    Ok(Some((plugin.name, resolved)))
+}
+
+// Asks the host's plugin whether the account works and how much traffic it has left
+pub(crate) async fn check_account(app_handle: &AppHandle, account: &Account) -> Result<Account, String> {
+    let plugin = plugin_for_host(app_handle, &account.host.to_ascii_lowercase()).await
+        .ok_or_else(|| format!("No plugin handles {}", account.host))?;
+    let secret = accounts::load_secret(&account.id).await?;
+    let request = serde_json::json!({ "action": "check", "account": { "username": account.username, "secret": secret } });
+    let response: CheckResponse = run(&plugin, &request).await.map_err(|e| format!("Plugin {} failed: {}", plugin.name, e))?;
+    update_account(app_handle, &account.id, response.account).await.ok_or_else(|| "Account not found".to_string())
+}
+
+async fn update_account(app_handle: &AppHandle, id: &str, report: AccountReport) -> Option<Account> {
+    let state: State<AppState> = app_handle.state();
+    let account = {
+        let mut state_guard = state.persistent.lock().await;
+        let account = state_guard.accounts.iter_mut().find(|a| a.id == id)?;
+        account.apply_report(report);
+        account.clone()
+    };
+    crate::request_save(&state);
+    app_handle.emit("account_updated", &account).unwrap();
+    Some(account)
+}
+
+async fn run<T: serde::de::DeserializeOwned>(plugin: &PluginManifest, request: &serde_json::Value) -> Result<T, String> {
+    let mut child = tokio::process::Command::new(program_path(&plugin.path, &plugin.command[0]))
+        .args(&plugin.command[1..])
+        .current_dir(&plugin.path)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()
+        .map_err(|e| e.to_string())?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(request.to_string().as_bytes()).await.map_err(|e| e.to_string())?;
+    }
+    let output = timeout(RESOLVE_TIMEOUT, child.wait_with_output()).await
+        .map_err(|_| "timed out".to_string())?
+        .map_err(|e| e.to_string())?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(stderr.trim().lines().last().unwrap_or("exited with an error").to_string());
+    }
+    serde_json::from_slice(&output.stdout).map_err(|e| format!("invalid output: {}", e))
+}
+
+// `./resolver` style commands are relative to the plugin folder; bare names go through PATH
+fn program_path(plugin_dir: &Path, program: &str) -> PathBuf {
+    if program.contains(['/', '\\']) { plugin_dir.join(program) } else { PathBuf::from(program) }
+}
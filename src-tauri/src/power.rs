@@ -0,0 +1,84 @@
+// Sleep and wake. On Linux, logind announces suspend through `PrepareForSleep`; a delay inhibitor
+// keeps the machine awake just long enough to flush and pause every running download, which resume
+// with fresh retry counters on wake. Elsewhere (or without logind) nothing warns before sleep, so a
+// wake is recognised by the wall clock jumping past a short timer, and running downloads, whose
+// connections died in the meantime, are restarted the same way.
+
+use std::time::SystemTime;
+
+use tauri::AppHandle;
+use tokio::time::Duration;
+
+const PAUSED_MESSAGE: &str = "Paused while the computer was asleep";
+const CLOCK_TICK: Duration = Duration::from_secs(5);
+// Wall-clock time beyond the tick that counts as having been asleep
+const SLEEP_THRESHOLD: Duration = Duration::from_secs(30);
+
+pub(crate) async fn run_power_watch(app_handle: AppHandle) {
+    #[cfg(target_os = "linux")]
+    match logind::watch(&app_handle).await {
+        Ok(()) => return,
+        Err(e) => log::info!("No sleep notifications from logind ({}); watching the clock instead", e),
+    }
+    watch_clock(&app_handle).await;
+}
+
+async fn watch_clock(app_handle: &AppHandle) {
+    let mut before = SystemTime::now();
+    loop {
+        tokio::time::sleep(CLOCK_TICK).await;
+        let now = SystemTime::now();
+        let asleep = now.duration_since(before).is_ok_and(|elapsed| elapsed > CLOCK_TICK + SLEEP_THRESHOLD);
+        before = now;
+        if asleep {
+            log::info!("Woke from sleep; restarting running downloads");
+            let paused = crate::pause_running(app_handle, PAUSED_MESSAGE).await;
+            crate::resume_paused(app_handle, paused, PAUSED_MESSAGE).await;
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod logind {
+    use futures::StreamExt;
+    use tauri::AppHandle;
+
+    use super::PAUSED_MESSAGE;
+
+    #[zbus::proxy(
+        interface = "org.freedesktop.login1.Manager",
+        default_service = "org.freedesktop.login1",
+        default_path = "/org/freedesktop/login1"
+    )]
+    trait Manager {
+        fn inhibit(&self, what: &str, who: &str, why: &str, mode: &str) -> zbus::Result<zbus::zvariant::OwnedFd>;
+
+        #[zbus(signal)]
+        fn prepare_for_sleep(&self, start: bool) -> zbus::Result<()>;
+    }
+
+    // Returns only if logind can't be reached or stops sending signals
+    pub(super) async fn watch(app_handle: &AppHandle) -> zbus::Result<()> {
+        let connection = zbus::Connection::system().await?;
+        let manager = ManagerProxy::new(&connection).await?;
+        let mut signals = manager.receive_prepare_for_sleep().await?;
+        // Sleep waits (up to logind's InhibitDelayMaxSec) until this descriptor is closed
+        let mut inhibitor = Some(inhibit(&manager).await?);
+        let mut paused = Vec::new();
+        while let Some(signal) = signals.next().await {
+            if signal.args()?.start {
+                paused = crate::pause_running(app_handle, PAUSED_MESSAGE).await;
+                crate::flush_state(app_handle).await;
+                drop(inhibitor.take());
+            } else {
+                inhibitor = inhibit(&manager).await.ok();
+                crate::resume_paused(app_handle, std::mem::take(&mut paused), PAUSED_MESSAGE).await;
+            }
+        }
+        Ok(())
+    }
+
+    async fn inhibit(manager: &ManagerProxy<'_>) -> zbus::Result<zbus::zvariant::OwnedFd> {
+        manager.inhibit("sleep", "Velodown", "Saving download progress", "delay").await
+    }
+}
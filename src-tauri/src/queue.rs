@@ -0,0 +1,87 @@
+// Strictly sequential downloading, for connections that can't take several at once. With
+// `sequential_downloads` on, a started task waits while any other download is running or queued
+// ahead of it; with its category (the file type) in `sequential_categories`, only downloads of the
+// same category count. Waiting tasks start in list order (which `move_task` changes), one at a time,
+// as each download stops.
+
+use std::collections::HashSet;
+
+use tauri::{AppHandle, Manager, State};
+use tokio::sync::{Mutex, Notify};
+
+use crate::{AppSettings, AppState, DownloadStatus, DownloadTask};
+
+const WAITING_MESSAGE: &str = "Waiting its turn in the queue";
+
+#[derive(Default)]
+pub(crate) struct Queue {
+    // A download stopped or the settings changed; the next one may go
+    changed: Notify,
+    // Held from the queue check until the download's handle is registered, so two starts can't
+    // both find the queue free
+    pub(crate) starting: Mutex<()>,
+}
+
+impl Queue {
+    pub(crate) fn notify(&self) {
+        self.changed.notify_one();
+    }
+}
+
+enum Scope<'a> { All, Category(&'a str) }
+
+impl Scope<'_> {
+    fn covers(&self, task: &DownloadTask) -> bool {
+        match self {
+            Scope::All => true,
+            Scope::Category(category) => task.file_type == *category,
+        }
+    }
+}
+
+fn scope<'a>(settings: &AppSettings, task: &'a DownloadTask) -> Option<Scope<'a>> {
+    if settings.sequential_downloads { return Some(Scope::All); }
+    settings.sequential_categories.contains(&task.file_type).then_some(Scope::Category(&task.file_type))
+}
+
+// Whether `task` has to wait for a running download, or one waiting ahead of it, in its scope
+pub(crate) fn must_wait(tasks: &[DownloadTask], settings: &AppSettings, running: &HashSet<String>, task: &DownloadTask) -> bool {
+    let Some(scope) = scope(settings, task) else { return false };
+    tasks.iter().any(|t| t.id != task.id && running.contains(&t.id) && scope.covers(t))
+        || tasks.iter().take_while(|t| t.id != task.id).any(|t| is_waiting(t) && scope.covers(t))
+}
+
+pub(crate) fn park(task: &mut DownloadTask) {
+    task.status = DownloadStatus::Queued;
+    task.speed = 0;
+    task.error_message = Some(WAITING_MESSAGE.to_string());
+}
+
+fn is_waiting(task: &DownloadTask) -> bool {
+    task.status == DownloadStatus::Queued && task.error_message.as_deref() == Some(WAITING_MESSAGE)
+}
+
+pub(crate) async fn run_queue(app_handle: AppHandle) {
+    let state: State<AppState> = app_handle.state();
+    // The first pass picks up tasks left waiting when the app last closed
+    loop {
+        let running: HashSet<String> = state.download_handles.lock().await.keys().cloned().collect();
+        let ready: Vec<String> = {
+            let mut state_guard = state.persistent.lock().await;
+            // In list order, each waiting task blocks the ones behind it in its scope, so at most
+            // one per scope is picked
+            let ready: Vec<String> = state_guard.downloads.iter()
+                .filter(|t| is_waiting(t) && !must_wait(&state_guard.downloads, &state_guard.settings, &running, t))
+                .map(|t| t.id.clone())
+                .collect();
+            for task in state_guard.downloads.iter_mut().filter(|t| ready.contains(&t.id)) { task.error_message = None; }
+            ready
+        };
+        for id in ready {
+            if let Err(e) = crate::start_download_task(id, app_handle.clone()).await {
+                log::warn!("Could not start the next queued download: {}", e);
+            }
+        }
+        state.queue.changed.notified().await;
+    }
+}
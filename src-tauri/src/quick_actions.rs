@@ -0,0 +1,169 @@
+// Shortcuts offered outside the window: tasks in the Windows jump list and items in the macOS dock
+// menu. A jump-list task relaunches the app with `--quick-action=<name>`, which the single-instance
+// plugin hands over to the copy already running; dock menu items call straight in.
+
+use tauri::{AppHandle, Emitter, Manager, State};
+
+use crate::{AppState, DownloadStatus};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum QuickAction { PauseAll, ResumeAll, AddFromClipboard, OpenDownloadsFolder }
+
+impl QuickAction {
+    const ALL: [QuickAction; 4] = [QuickAction::PauseAll, QuickAction::ResumeAll, QuickAction::AddFromClipboard, QuickAction::OpenDownloadsFolder];
+
+    fn name(self) -> &'static str {
+        match self {
+            QuickAction::PauseAll => "pause-all",
+            QuickAction::ResumeAll => "resume-all",
+            QuickAction::AddFromClipboard => "add-from-clipboard",
+            QuickAction::OpenDownloadsFolder => "open-downloads-folder",
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            QuickAction::PauseAll => "Pause All",
+            QuickAction::ResumeAll => "Resume All",
+            QuickAction::AddFromClipboard => "Add from Clipboard",
+            QuickAction::OpenDownloadsFolder => "Open Downloads Folder",
+        }
+    }
+
+    pub(crate) fn from_args(args: &[String]) -> Option<QuickAction> {
+        let name = args.iter().find_map(|a| a.strip_prefix("--quick-action="))?;
+        QuickAction::ALL.into_iter().find(|action| action.name() == name)
+    }
+}
+
+pub(crate) fn run(app_handle: &AppHandle, action: QuickAction) {
+    let app_handle = app_handle.clone();
+    tauri::async_runtime::spawn(async move {
+        let state: State<AppState> = app_handle.state();
+        let result = match action {
+            QuickAction::PauseAll => {
+                let ids = task_ids(&state, |s| matches!(s, DownloadStatus::Queued | DownloadStatus::Downloading | DownloadStatus::Retrying)).await;
+                crate::pause_downloads(ids, app_handle.state(), app_handle.clone()).await
+            }
+            QuickAction::ResumeAll => {
+                let ids = task_ids(&state, |s| *s == DownloadStatus::Paused).await;
+                crate::resume_downloads(ids, app_handle.state(), app_handle.clone()).await.map(|_| ())
+            }
+            // The clipboard is read by the page, which then opens the add form with it
+            QuickAction::AddFromClipboard => {
+                crate::show_main_window(&app_handle);
+                app_handle.emit("quick_action", action.name()).map_err(|e| e.to_string())
+            }
+            QuickAction::OpenDownloadsFolder => {
+                let folder = state.persistent.lock().await.settings.download_folder.clone();
+                crate::open_folder(folder).await
+            }
+        };
+        if let Err(e) = result { log::warn!("{} failed: {}", action.label(), e); }
+    });
+}
+
+async fn task_ids(state: &AppState, status: impl Fn(&DownloadStatus) -> bool) -> Vec<String> {
+    state.persistent.lock().await.downloads.iter().filter(|t| status(&t.status)).map(|t| t.id.clone()).collect()
+}
+
+// Called once at startup
+pub(crate) fn install(app_handle: &AppHandle) {
+    #[cfg(target_os = "windows")]
+    if let Err(e) = jump_list::install() { log::warn!("Could not set up the jump list: {}", e); }
+    #[cfg(target_os = "macos")]
+    dock_menu::install(app_handle);
+    #[cfg(not(target_os = "macos"))]
+    let _ = app_handle;
+}
+
+#[cfg(target_os = "windows")]
+mod jump_list {
+    use windows::core::{Interface, HSTRING};
+    use windows::Win32::Storage::EnhancedStorage::PKEY_Title;
+    use windows::Win32::System::Com::StructuredStorage::PROPVARIANT;
+    use windows::Win32::System::Com::{CoCreateInstance, CoInitializeEx, CLSCTX_INPROC_SERVER, COINIT_APARTMENTTHREADED};
+    use windows::Win32::UI::Shell::PropertiesSystem::IPropertyStore;
+    use windows::Win32::UI::Shell::{DestinationList, EnumerableObjectCollection, ICustomDestinationList, IObjectArray, IObjectCollection, IShellLinkW, ShellLink};
+
+    use super::QuickAction;
+
+    // Replaces the "Tasks" section of the jump list with one shortcut per quick action
+    pub(super) fn install() -> Result<(), String> {
+        let exe = HSTRING::from(std::env::current_exe().map_err(|e| e.to_string())?.as_os_str());
+        unsafe {
+            // Already initialised on this thread is fine
+            let _ = CoInitializeEx(None, COINIT_APARTMENTTHREADED);
+            let list: ICustomDestinationList = CoCreateInstance(&DestinationList, None, CLSCTX_INPROC_SERVER).map_err(|e| e.to_string())?;
+            let mut max_slots = 0u32;
+            let _removed: IObjectArray = list.BeginList(&mut max_slots).map_err(|e| e.to_string())?;
+            let tasks: IObjectCollection = CoCreateInstance(&EnumerableObjectCollection, None, CLSCTX_INPROC_SERVER).map_err(|e| e.to_string())?;
+            for action in QuickAction::ALL {
+                let link: IShellLinkW = CoCreateInstance(&ShellLink, None, CLSCTX_INPROC_SERVER).map_err(|e| e.to_string())?;
+                link.SetPath(&exe).map_err(|e| e.to_string())?;
+                link.SetArguments(&HSTRING::from(format!("--quick-action={}", action.name()))).map_err(|e| e.to_string())?;
+                link.SetIconLocation(&exe, 0).map_err(|e| e.to_string())?;
+                // A task's caption is the link's title property, not its description
+                let properties: IPropertyStore = link.cast().map_err(|e| e.to_string())?;
+                properties.SetValue(&PKEY_Title, &PROPVARIANT::from(action.label())).map_err(|e| e.to_string())?;
+                properties.Commit().map_err(|e| e.to_string())?;
+                tasks.AddObject(&link).map_err(|e| e.to_string())?;
+            }
+            list.AddUserTasks(&tasks.cast::<IObjectArray>().map_err(|e| e.to_string())?).map_err(|e| e.to_string())?;
+            list.CommitList().map_err(|e| e.to_string())?;
+        }
+        Ok(())
+    }
+}
+
+// AppKit asks the application delegate for `applicationDockMenu:`; Tauri's delegate doesn't answer
+// it, so the method is added to the delegate's class at runtime, along with the action the items
+// send (items without a target go up the responder chain, which ends at the delegate).
+#[cfg(target_os = "macos")]
+mod dock_menu {
+    use std::sync::OnceLock;
+
+    use objc2::rc::Retained;
+    use objc2::runtime::{AnyClass, AnyObject, Imp, Sel};
+    use objc2::{sel, MainThreadMarker, MainThreadOnly};
+    use objc2_app_kit::{NSApplication, NSMenu, NSMenuItem};
+    use objc2_foundation::NSString;
+    use tauri::AppHandle;
+
+    use super::QuickAction;
+
+    static APP_HANDLE: OnceLock<AppHandle> = OnceLock::new();
+
+    pub(super) fn install(app_handle: &AppHandle) {
+        // Setup runs on the main thread
+        let Some(mtm) = MainThreadMarker::new() else { return };
+        let _ = APP_HANDLE.set(app_handle.clone());
+        let Some(delegate) = NSApplication::sharedApplication(mtm).delegate() else { return };
+        let delegate: &AnyObject = (*delegate).as_ref();
+        let class = delegate.class() as *const AnyClass as *mut AnyClass;
+        unsafe {
+            let dock_menu: unsafe extern "C-unwind" fn(&AnyObject, Sel, &AnyObject) -> *mut NSMenu = dock_menu;
+            let perform: unsafe extern "C-unwind" fn(&AnyObject, Sel, &NSMenuItem) = perform;
+            objc2::ffi::class_addMethod(class, sel!(applicationDockMenu:), std::mem::transmute::<_, Imp>(dock_menu), c"@@:@".as_ptr());
+            objc2::ffi::class_addMethod(class, sel!(velodownQuickAction:), std::mem::transmute::<_, Imp>(perform), c"v@:@".as_ptr());
+        }
+    }
+
+    unsafe extern "C-unwind" fn dock_menu(_this: &AnyObject, _cmd: Sel, _sender: &AnyObject) -> *mut NSMenu {
+        let mtm = MainThreadMarker::new().expect("AppKit calls the delegate on the main thread");
+        let menu = NSMenu::new(mtm);
+        for (tag, action) in QuickAction::ALL.into_iter().enumerate() {
+            let item = unsafe {
+                NSMenuItem::initWithTitle_action_keyEquivalent(NSMenuItem::alloc(mtm), &NSString::from_str(action.label()), Some(sel!(velodownQuickAction:)), &NSString::new())
+            };
+            item.setTag(tag as isize);
+            menu.addItem(&item);
+        }
+        Retained::autorelease_return(menu)
+    }
+
+    unsafe extern "C-unwind" fn perform(_this: &AnyObject, _cmd: Sel, sender: &NSMenuItem) {
+        let action = usize::try_from(sender.tag()).ok().and_then(|tag| QuickAction::ALL.get(tag).copied());
+        if let (Some(app_handle), Some(action)) = (APP_HANDLE.get(), action) { super::run(app_handle, action); }
+    }
+}
@@ -0,0 +1,171 @@
+// A transfer allowance for capped connections: `data_quota_gb` per day or per billing month
+// (starting on `quota_reset_day`). Usage is taken from the bytes-downloaded counter behind `/metrics`
+// and kept in the state file, so it survives restarts. Past `quota_throttle_percent` downloads are
+// capped at `quota_throttle_kbps`; once the quota is used up they're paused, and new ones held, until
+// the next period. Tasks with `ignore_quota` are never capped or held, though their bytes still count.
+// The same counter keeps today's total for the dashboard, quota or not.
+
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+
+use chrono::{Datelike, Local, Months, NaiveDate};
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Emitter, Manager, State};
+use tokio::time::Duration;
+
+use crate::i18n::{tr, Language, Text};
+use crate::metered::SpeedLimit;
+use crate::{AppSettings, AppState, DownloadStatus, DownloadTask};
+
+pub(crate) const HELD_MESSAGE: &str = "Paused until the data quota resets";
+const CHECK_TICK: Duration = Duration::from_secs(10);
+const GB: u64 = 1024 * 1024 * 1024;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub(crate) enum QuotaPeriod { Daily, Monthly }
+
+// Bytes downloaded in the period starting on `period_start`
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct Usage {
+    pub(crate) period_start: Option<NaiveDate>,
+    pub(crate) bytes: u64,
+}
+
+impl Usage {
+    // Counts `bytes` in the period starting on `start`, starting over if that's a new one; returns the total
+    fn add(&mut self, start: NaiveDate, bytes: u64) -> u64 {
+        if self.period_start != Some(start) { *self = Usage { period_start: Some(start), bytes: 0 }; }
+        self.bytes += bytes;
+        self.bytes
+    }
+}
+
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct UsageReport {
+    used: u64,
+    // 0 when no quota is set
+    quota: u64,
+    resets_on: NaiveDate,
+}
+
+#[derive(Default)]
+pub(crate) struct Quota {
+    exhausted: AtomicBool,
+    // The counter value already added to the usage
+    counted: AtomicU64,
+    // Applied to every download that doesn't ignore the quota
+    pub(crate) limit: SpeedLimit,
+}
+
+fn period_start(settings: &AppSettings, today: NaiveDate) -> NaiveDate {
+    match settings.data_quota_period {
+        QuotaPeriod::Daily => today,
+        QuotaPeriod::Monthly => {
+            // Days past the 28th don't exist in every month
+            let day = settings.quota_reset_day.clamp(1, 28) as u32;
+            let this_month = today.with_day(day).unwrap_or(today);
+            if this_month <= today { this_month } else { this_month - Months::new(1) }
+        }
+    }
+}
+
+fn period_end(settings: &AppSettings, start: NaiveDate) -> NaiveDate {
+    match settings.data_quota_period {
+        QuotaPeriod::Daily => start.succ_opt().unwrap_or(start),
+        QuotaPeriod::Monthly => start + Months::new(1),
+    }
+}
+
+// Whether a download about to start should wait for the next period
+pub(crate) fn should_hold(state: &AppState, task: &DownloadTask) -> bool {
+    !task.ignore_quota && state.quota.exhausted.load(Ordering::Relaxed)
+}
+
+pub(crate) fn hold(task: &mut DownloadTask) {
+    task.status = DownloadStatus::Paused;
+    task.speed = 0;
+    task.error_message = Some(HELD_MESSAGE.to_string());
+}
+
+fn is_held(task: &DownloadTask) -> bool {
+    task.status == DownloadStatus::Paused && task.error_message.as_deref() == Some(HELD_MESSAGE)
+}
+
+pub(crate) fn report(persistent: &crate::PersistentState) -> UsageReport {
+    let settings = &persistent.settings;
+    let start = period_start(settings, Local::now().date_naive());
+    let used = if persistent.data_usage.period_start == Some(start) { persistent.data_usage.bytes } else { 0 };
+    UsageReport { used, quota: settings.data_quota_gb * GB, resets_on: period_end(settings, start) }
+}
+
+// Bytes downloaded since midnight, including those not yet added by the last check
+pub(crate) fn transferred_today(state: &AppState, persistent: &crate::PersistentState) -> u64 {
+    let counted = if persistent.data_today.period_start == Some(Local::now().date_naive()) { persistent.data_today.bytes } else { 0 };
+    let pending = state.metrics.bytes_downloaded.load(Ordering::Relaxed).saturating_sub(state.quota.counted.load(Ordering::Relaxed));
+    counted + pending
+}
+
+pub(crate) async fn run_quota(app_handle: AppHandle) {
+    loop {
+        check(&app_handle).await;
+        tokio::time::sleep(CHECK_TICK).await;
+    }
+}
+
+async fn check(app_handle: &AppHandle) {
+    let state: State<AppState> = app_handle.state();
+    let total = state.metrics.bytes_downloaded.load(Ordering::Relaxed);
+    let added = total.saturating_sub(state.quota.counted.swap(total, Ordering::Relaxed));
+    let (settings, used) = {
+        let mut state_guard = state.persistent.lock().await;
+        let today = Local::now().date_naive();
+        let start = period_start(&state_guard.settings, today);
+        let used = state_guard.data_usage.add(start, added);
+        state_guard.data_today.add(today, added);
+        (state_guard.settings.clone(), used)
+    };
+    if added > 0 { crate::request_save(&state); }
+    let quota = settings.data_quota_gb * GB;
+    let used_up = quota > 0 && used >= quota;
+    let near = quota > 0 && settings.quota_throttle_percent > 0 && used >= quota / 100 * settings.quota_throttle_percent as u64;
+    state.quota.limit.set(if near { settings.quota_throttle_kbps * 1024 } else { 0 });
+    let was_used_up = state.quota.exhausted.swap(used_up, Ordering::Relaxed);
+    if used_up {
+        let held = hold_running(app_handle).await;
+        if !was_used_up {
+            log::warn!("Data quota used up ({} bytes); {} download(s) paused", used, held);
+            let language = Language::from_setting(&settings.language);
+            crate::notifications::show(app_handle, &settings, tr(language, Text::QuotaUsedUp), tr(language, Text::QuotaUsedUpBody));
+        }
+    } else {
+        // Also picks up downloads held when the app last closed
+        let held: Vec<String> = state.persistent.lock().await.downloads.iter()
+            .filter(|t| is_held(t))
+            .map(|t| t.id.clone())
+            .collect();
+        if !held.is_empty() { crate::resume_paused(app_handle, held, HELD_MESSAGE).await; }
+    }
+}
+
+// Pauses the running downloads that don't ignore the quota; returns how many
+async fn hold_running(app_handle: &AppHandle) -> usize {
+    let state: State<AppState> = app_handle.state();
+    let running: Vec<String> = state.download_handles.lock().await.keys().cloned().collect();
+    let to_hold: Vec<String> = state.persistent.lock().await.downloads.iter()
+        .filter(|t| running.contains(&t.id) && !t.ignore_quota)
+        .map(|t| t.id.clone())
+        .collect();
+    if to_hold.is_empty() { return 0; }
+    futures::future::join_all(to_hold.iter().map(|id| crate::abort_download(&state, id))).await;
+    {
+        let mut state_guard = state.persistent.lock().await;
+        for task in state_guard.downloads.iter_mut().filter(|t| to_hold.contains(&t.id)) {
+            hold(task);
+            app_handle.emit("task_updated", &*task).unwrap();
+        }
+    }
+    crate::request_save(&state);
+    to_hold.len()
+}
@@ -0,0 +1,240 @@
+// Downloads that repeat on an interval, such as a nightly database dump. When a run is due and the
+// previous one has finished, the task's original link is resolved again and the file fetched once more,
+// either under a timestamped name or over the same file. Overwriting runs download to a `.next` file
+// that only replaces the old one once complete, so a failed run leaves the last good copy. The last few
+// runs are kept on the task.
+
+use std::path::Path;
+
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Emitter, Manager, State};
+use tokio::time::Duration;
+
+use crate::{AppState, DownloadStatus, DownloadTask};
+
+const CHECK_TICK: Duration = Duration::from_secs(60);
+const MAX_RUNS: usize = 10;
+const STAGING_SUFFIX: &str = ".next";
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub(crate) enum RepeatNaming {
+    // `name-YYYYMMDD-HHMMSS.ext`, a new file every run
+    Timestamped,
+    Overwrite,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct Repeat {
+    pub(crate) every_hours: u32,
+    pub(crate) naming: RepeatNaming,
+    // The link as added, resolved afresh each run, and the file name runs are named after
+    pub(crate) source_url: String,
+    pub(crate) base_name: String,
+    pub(crate) next_run: DateTime<Local>,
+    // Oldest first; the last one is still going when it has no `finished_at`
+    #[serde(default)]
+    pub(crate) runs: Vec<RepeatRun>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct RepeatRun {
+    pub(crate) started_at: DateTime<Local>,
+    #[serde(default)]
+    pub(crate) finished_at: Option<DateTime<Local>>,
+    pub(crate) file_name: String,
+    #[serde(default)]
+    pub(crate) size: u64,
+    #[serde(default)]
+    pub(crate) error: Option<String>,
+}
+
+impl Repeat {
+    pub(crate) fn new(task: &DownloadTask, every_hours: u32, naming: RepeatNaming) -> Result<Self, String> {
+        if every_hours == 0 { return Err("The interval must be at least an hour".to_string()); }
+        // Each run starts with a HEAD of the link, which means nothing for a POST export
+        if task.request.is_some() { return Err("Only downloads made with a plain GET can repeat".to_string()); }
+        let from = task.completed_at.unwrap_or_else(Local::now);
+        Ok(Repeat {
+            every_hours,
+            naming,
+            source_url: task.redirect_chain.first().cloned().unwrap_or_else(|| task.url.clone()),
+            base_name: task.file_name.clone(),
+            next_run: from + interval(every_hours),
+            runs: Vec::new(),
+        })
+    }
+
+    fn open_run(&mut self) -> Option<&mut RepeatRun> {
+        self.runs.last_mut().filter(|run| run.finished_at.is_none())
+    }
+
+    // Moves `next_run` past now in whole intervals, so a machine that was off doesn't run a backlog
+    fn advance(&mut self) {
+        let now = Local::now();
+        while self.next_run <= now { self.next_run += interval(self.every_hours); }
+    }
+
+    fn close_run(&mut self, size: u64, error: Option<String>) {
+        if let Some(run) = self.open_run() {
+            run.finished_at = Some(Local::now());
+            run.size = size;
+            run.error = error;
+        }
+        self.advance();
+    }
+}
+
+fn interval(every_hours: u32) -> chrono::Duration {
+    chrono::Duration::hours(every_hours as i64)
+}
+
+fn run_file_name(repeat: &Repeat, at: DateTime<Local>) -> String {
+    match repeat.naming {
+        RepeatNaming::Overwrite => format!("{}{}", repeat.base_name, STAGING_SUFFIX),
+        RepeatNaming::Timestamped => timestamped_name(&repeat.base_name, at),
+    }
+}
+
+// `name-YYYYMMDD-HHMMSS.ext`
+pub(crate) fn timestamped_name(base_name: &str, at: DateTime<Local>) -> String {
+    let stamp = at.format("%Y%m%d-%H%M%S");
+    match Path::new(base_name).extension().and_then(|e| e.to_str()) {
+        Some(ext) => {
+            let stem = &base_name[..base_name.len() - ext.len() - 1];
+            format!("{}-{}.{}", stem, stamp, ext)
+        }
+        None => format!("{}-{}", base_name, stamp),
+    }
+}
+
+// Turns a finished task back into a queued one fetching `info` into `file_name`; the caller starts it
+pub(crate) fn requeue(task: &mut DownloadTask, info: crate::DownloadInfo, file_name: String) {
+    task.url = info.final_url;
+    task.redirect_chain = info.redirect_chain;
+    task.total_size = info.total_size.unwrap_or(0);
+    task.etag = None;
+    task.file_name = file_name;
+    task.status = DownloadStatus::Queued;
+    task.progress = 0.0;
+    task.downloaded_size = 0;
+    task.segments.clear();
+    task.speed = 0;
+    task.time_remaining = None;
+    task.resume_attempts = 0;
+    task.error_message = None;
+    task.error_page = None;
+    task.completed_at = None;
+    task.extraction = None;
+    task.virus_scan = None;
+    task.verification = None;
+    task.content_hash = None;
+    task.duplicate_of = None;
+}
+
+// Called when a download completes, before anything else looks at the file: records the run and puts
+// an overwriting run's file in place of the previous one. Returns the task as it now is.
+pub(crate) async fn finish_run(app_handle: &AppHandle, task: DownloadTask) -> DownloadTask {
+    let Some(repeat) = task.repeat.as_ref().filter(|r| r.runs.last().is_some_and(|run| run.finished_at.is_none())) else {
+        return task;
+    };
+    let mut error = None;
+    let mut file_name = task.file_name.clone();
+    if repeat.naming == RepeatNaming::Overwrite && task.file_name != repeat.base_name {
+        let folder = Path::new(&task.save_path);
+        match tokio::fs::rename(folder.join(&task.file_name), folder.join(&repeat.base_name)).await {
+            Ok(()) => file_name = repeat.base_name.clone(),
+            Err(e) => error = Some(format!("Could not replace {}: {}", repeat.base_name, e)),
+        }
+    }
+    let state: State<AppState> = app_handle.state();
+    let mut state_guard = state.persistent.lock().await;
+    let Some(current) = state_guard.downloads.iter_mut().find(|t| t.id == task.id) else { return task };
+    current.file_name = file_name;
+    if let Some(repeat) = current.repeat.as_mut() { repeat.close_run(current.total_size, error); }
+    app_handle.emit("task_updated", &*current).unwrap();
+    let current = current.clone();
+    drop(state_guard);
+    crate::request_save(&state);
+    current
+}
+
+pub(crate) async fn run_recurring(app_handle: AppHandle) {
+    loop {
+        tokio::time::sleep(CHECK_TICK).await;
+        let due = {
+            let state: State<AppState> = app_handle.state();
+            let mut state_guard = state.persistent.lock().await;
+            let now = Local::now();
+            let mut due = Vec::new();
+            for task in state_guard.downloads.iter_mut() {
+                let status = task.status.clone();
+                let error = task.error_message.clone();
+                let Some(repeat) = task.repeat.as_mut() else { continue };
+                // A run that gave up is recorded here; completed ones are recorded by `finish_run`
+                if status == DownloadStatus::Failed && repeat.open_run().is_some() {
+                    repeat.close_run(0, error.or(Some("Failed".to_string())));
+                    app_handle.emit("task_updated", &*task).unwrap();
+                    crate::request_save(&state);
+                    continue;
+                }
+                // Anything else (running, queued, paused by the user) waits for the next check
+                let finished = matches!(status, DownloadStatus::Completed | DownloadStatus::Failed);
+                if finished && repeat.next_run <= now { due.push(task.id.clone()); }
+            }
+            due
+        };
+        for id in due {
+            if let Err(e) = start_run(&app_handle, &id).await {
+                log::warn!("Could not start the scheduled run of {}: {}", id, e);
+            }
+        }
+    }
+}
+
+async fn start_run(app_handle: &AppHandle, id: &str) -> Result<(), String> {
+    let state: State<AppState> = app_handle.state();
+    let (source_url, headers) = {
+        let state_guard = state.persistent.lock().await;
+        let task = state_guard.downloads.iter().find(|t| t.id == id).ok_or("Download not found")?;
+        let repeat = task.repeat.as_ref().ok_or("Not a recurring download")?;
+        (repeat.source_url.clone(), task.headers.clone())
+    };
+    let client = crate::http_client_for(&state, &source_url).await;
+    let info = crate::probe_download_info_with_headers(&client, &source_url, &headers).await;
+    {
+        let mut state_guard = state.persistent.lock().await;
+        let task = state_guard.downloads.iter_mut().find(|t| t.id == id).ok_or("Download not found")?;
+        let Some(repeat) = task.repeat.as_mut() else { return Ok(()) };
+        let now = Local::now();
+        let info = match info {
+            Ok(info) => info,
+            Err(e) => {
+                // Counts as a run, so the history shows the link stopped working
+                repeat.runs.push(RepeatRun { started_at: now, finished_at: None, file_name: String::new(), size: 0, error: None });
+                repeat.close_run(0, Some(e.clone()));
+                trim(repeat);
+                app_handle.emit("task_updated", &*task).unwrap();
+                drop(state_guard);
+                crate::request_save(&state);
+                return Err(e);
+            }
+        };
+        let file_name = run_file_name(repeat, now);
+        repeat.runs.push(RepeatRun { started_at: now, finished_at: None, file_name: file_name.clone(), size: 0, error: None });
+        trim(repeat);
+        crate::tasklog::write(app_handle, id, &format!("Scheduled run, saving as {}", file_name)).await;
+        requeue(task, info, file_name);
+        app_handle.emit("task_updated", &*task).unwrap();
+    }
+    crate::request_save(&state);
+    crate::start_download_task(id.to_string(), app_handle.clone()).await
+}
+
+fn trim(repeat: &mut Repeat) {
+    let over = repeat.runs.len().saturating_sub(MAX_RUNS);
+    repeat.runs.drain(..over);
+}
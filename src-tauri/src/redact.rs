@@ -0,0 +1,184 @@
+// Masks tokens and credentials in the copies of tasks written to state.json and in log lines, while
+// the engine keeps the real values in memory. Masked: URL passwords, query parameters that look like
+// tokens, keys or signatures (pre-signed S3 links included), auth/cookie headers, and fields like those
+// in request bodies. `vault` keeps what a saved task had masked in the OS keyring.
+
+use std::borrow::Cow;
+
+use url::Url;
+
+use crate::{DownloadStatus, DownloadTask};
+
+pub(crate) const MARK: &str = "REDACTED";
+
+// Whole-word pieces of a parameter name (split on `-`, `_`, `.`) that mark it as a secret
+const SECRET_WORDS: &[&str] = &[
+    "token", "auth", "key", "apikey", "sig", "signature", "secret", "password", "passwd", "pwd", "pass",
+    "session", "sessionid", "sid", "credential", "credentials", "jwt", "policy",
+];
+// Substrings that mark a name as a secret however it's written (`accessToken`, `client_secret`)
+const SECRET_FRAGMENTS: &[&str] = &["token", "secret", "password", "apikey", "api_key", "signature", "credential"];
+
+fn is_secret_name(name: &str) -> bool {
+    let name = name.to_ascii_lowercase();
+    SECRET_FRAGMENTS.iter().any(|f| name.contains(f))
+        || name.split(['-', '_', '.']).any(|word| SECRET_WORDS.contains(&word))
+}
+
+fn is_secret_header(name: &str) -> bool {
+    matches!(name.to_ascii_lowercase().as_str(), "authorization" | "proxy-authorization" | "cookie") || is_secret_name(name)
+}
+
+pub(crate) fn url(raw: &str) -> Cow<'_, str> {
+    let Ok(mut parsed) = Url::parse(raw) else { return Cow::Borrowed(raw) };
+    let mut changed = false;
+    if parsed.password().is_some_and(|p| p != MARK) {
+        let _ = parsed.set_password(Some(MARK));
+        changed = true;
+    }
+    if let Some(query) = parsed.query().filter(|q| !q.is_empty()).and_then(form) {
+        parsed.set_query(Some(&query));
+        changed = true;
+    }
+    if changed { Cow::Owned(parsed.to_string()) } else { Cow::Borrowed(raw) }
+}
+
+// `name=value&...` pairs with the secret ones masked, or `None` when there are none. Rewritten by hand
+// so the parameters that stay keep their exact encoding.
+fn form(pairs: &str) -> Option<String> {
+    let mut changed = false;
+    let pairs: Vec<String> = pairs.split('&').map(|pair| match pair.split_once('=') {
+        Some((name, value)) if value != MARK && is_secret_name(&percent_encoding::percent_decode_str(name).decode_utf8_lossy()) => {
+            changed = true;
+            format!("{}={}", name, MARK)
+        }
+        _ => pair.to_string(),
+    }).collect();
+    changed.then(|| pairs.join("&"))
+}
+
+// A request body, masked as JSON when it is JSON and as form fields otherwise
+fn body(raw: &str) -> Option<String> {
+    match serde_json::from_str::<serde_json::Value>(raw) {
+        Ok(mut value) if value.is_object() || value.is_array() => {
+            json(&mut value);
+            let masked = value.to_string();
+            (masked != raw).then_some(masked)
+        }
+        _ => form(raw),
+    }
+}
+
+// Masks every URL inside free text such as error messages; reqwest puts the URL in its errors
+pub(crate) fn text(raw: &str) -> Cow<'_, str> {
+    if !raw.contains("://") { return Cow::Borrowed(raw); }
+    let mut out = String::with_capacity(raw.len());
+    let mut rest = raw;
+    while let Some(start) = ["https://", "http://", "ftp://", "ftps://", "sftp://", "s3://"].iter().filter_map(|s| rest.find(s)).min() {
+        out.push_str(&rest[..start]);
+        let len = rest[start..].find(|c: char| c.is_whitespace() || matches!(c, '"' | '\'' | '<' | '>' | '(' | ')')).unwrap_or(rest.len() - start);
+        out.push_str(&url(&rest[start..start + len]));
+        rest = &rest[start + len..];
+    }
+    out.push_str(rest);
+    if out == raw { Cow::Borrowed(raw) } else { Cow::Owned(out) }
+}
+
+// Free-form JSON such as the settings, for a diagnostics bundle: strings under secret-looking names
+// are masked outright, and URLs anywhere else lose their tokens
+pub(crate) fn json(value: &mut serde_json::Value) {
+    json_under(value, false);
+}
+
+fn json_under(value: &mut serde_json::Value, secret: bool) {
+    match value {
+        serde_json::Value::String(s) if secret && !s.is_empty() => *s = MARK.to_string(),
+        serde_json::Value::String(s) => {
+            if let Cow::Owned(masked) = text(s) { *s = masked; }
+        }
+        serde_json::Value::Array(items) => {
+            for item in items { json_under(item, secret); }
+        }
+        serde_json::Value::Object(map) => {
+            for (name, item) in map.iter_mut() { json_under(item, secret || is_secret_header(name)); }
+        }
+        _ => {}
+    }
+}
+
+// Undoes `json` on an imported copy where it can: a masked string takes the value at the same place in
+// `current`, if that one masks to the same thing. Masked strings with nothing to restore from are
+// emptied, and their paths returned.
+pub(crate) fn restore(incoming: &mut serde_json::Value, current: Option<&serde_json::Value>, path: &str) -> Vec<String> {
+    match incoming {
+        serde_json::Value::String(s) if s.contains(MARK) => {
+            let original = current.and_then(|c| c.as_str())
+                .filter(|original| !original.is_empty() && (s == MARK || text(original) == s.as_str()));
+            match original {
+                Some(original) => {
+                    *s = original.to_string();
+                    Vec::new()
+                }
+                None => {
+                    s.clear();
+                    vec![path.to_string()]
+                }
+            }
+        }
+        serde_json::Value::Array(items) => items.iter_mut().enumerate()
+            .flat_map(|(i, item)| restore(item, current.and_then(|c| c.get(i)), &format!("{}[{}]", path, i)))
+            .collect(),
+        serde_json::Value::Object(map) => map.iter_mut()
+            .flat_map(|(name, item)| {
+                let path = if path.is_empty() { name.clone() } else { format!("{}.{}", path, name) };
+                restore(item, current.and_then(|c| c.get(name)), &path)
+            })
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+// The copy of a task that may be written to disk
+pub(crate) fn task(task: &DownloadTask) -> DownloadTask {
+    let mut task = task.clone();
+    task.url = url(&task.url).into_owned();
+    for hop in &mut task.redirect_chain { *hop = url(hop).into_owned(); }
+    if let Some(source) = &mut task.source_url { *source = url(source).into_owned(); }
+    for (name, value) in &mut task.headers {
+        if is_secret_header(name) { *value = MARK.to_string(); }
+    }
+    if let Some(proxy) = &mut task.proxy { *proxy = url(proxy).into_owned(); }
+    if let Some(raw) = task.request.as_mut().and_then(|r| r.body.as_mut()) {
+        if let Some(masked) = body(raw) { *raw = masked; }
+    }
+    if let Some(message) = &mut task.error_message { *message = text(message).into_owned(); }
+    if let Some(check) = &mut task.signature {
+        check.url = url(&check.url).into_owned();
+        check.key = url(&check.key).into_owned();
+    }
+    task
+}
+
+// A task read from state.json with some of its values masked
+pub(crate) fn is_masked(task: &DownloadTask) -> bool {
+    let masked = |value: &str| value.contains(MARK);
+    masked(&task.url) || task.redirect_chain.iter().any(|hop| masked(hop)) || task.source_url.as_deref().is_some_and(masked)
+        || task.headers.values().any(|value| value == MARK) || task.proxy.as_deref().is_some_and(masked)
+        || task.request.as_ref().and_then(|r| r.body.as_deref()).is_some_and(masked)
+        || task.signature.as_ref().is_some_and(|c| masked(&c.url) || masked(&c.key))
+}
+
+// Run on tasks loaded from state.json once `vault` has put back what it kept: masked headers that are
+// left are dropped rather than sent, and unfinished tasks that lost a credential say so instead of
+// failing with an auth error later
+pub(crate) fn mark_lost(tasks: &mut [DownloadTask]) {
+    for task in tasks.iter_mut() {
+        let before = task.headers.len();
+        task.headers.retain(|_, value| value != MARK);
+        let lost = task.headers.len() != before || task.url.contains(MARK) || task.proxy.as_ref().is_some_and(|p| p.contains(MARK))
+            || task.request.as_ref().and_then(|r| r.body.as_ref()).is_some_and(|b| b.contains(MARK));
+        if lost && task.status != DownloadStatus::Completed {
+            task.error_message = Some("Its credentials weren't saved; update the link to resume".to_string());
+        }
+    }
+}
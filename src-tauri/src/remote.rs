@@ -0,0 +1,316 @@
+// FTP, FTPS and SFTP links. These don't go through the HTTP client: `ftp` and `sftp` speak the
+// protocols, and this module gives the rest of the app what it needs from them: a probe for the add
+// dialog, a recursive listing the dialog shows as a tree to pick files from, and the transfer, which
+// resumes from the bytes on disk like a single-stream HTTP download. Proxies don't apply to these.
+
+use std::collections::VecDeque;
+use std::path::Path;
+use std::sync::atomic::Ordering;
+
+use percent_encoding::percent_decode_str;
+use serde::Serialize;
+use tauri::{AppHandle, Emitter, Manager, State};
+use tokio::io::{AsyncSeekExt, AsyncWriteExt};
+use tokio::time::{Duration, Instant};
+use url::Url;
+
+use crate::{ftp, sftp, AppState, DownloadInfo, DownloadJob, LiveProgress};
+
+// Directories visited by one listing, as for open directories
+const MAX_DIRECTORIES: usize = 200;
+
+pub(crate) struct RemoteEntry {
+    pub(crate) name: String,
+    pub(crate) is_dir: bool,
+    pub(crate) size: Option<u64>,
+    pub(crate) modified: Option<String>,
+}
+
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct RemoteTree {
+    // Always ending in `/`
+    pub(crate) url: String,
+    // The listed directory's own name, the folder its files are saved under
+    pub(crate) name: String,
+    pub(crate) files: Vec<TreeFile>,
+    // Set when subdirectories past `MAX_DIRECTORIES` were left out
+    pub(crate) truncated: bool,
+}
+
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct TreeFile {
+    // Below the listed directory, `/`-separated
+    pub(crate) path: String,
+    pub(crate) url: String,
+    pub(crate) size: Option<u64>,
+    pub(crate) modified: Option<String>,
+}
+
+pub(crate) fn handles(url: &str) -> bool {
+    Url::parse(url).is_ok_and(|u| matches!(u.scheme(), "ftp" | "ftps" | "sftp"))
+}
+
+enum Remote {
+    Ftp(Box<ftp::Connection>),
+    Sftp(Box<sftp::Session>),
+}
+
+impl Remote {
+    async fn connect(app_handle: &AppHandle, url: &Url) -> Result<Remote, String> {
+        match url.scheme() {
+            "sftp" => Ok(Remote::Sftp(Box::new(sftp::Session::open(url).await?))),
+            "ftps" => {
+                let host = url.host_str().ok_or("The link has no host")?;
+                let tls = tls_config(app_handle, host).await?;
+                Ok(Remote::Ftp(Box::new(ftp::Connection::open(url, Some(tls)).await?)))
+            }
+            _ => Ok(Remote::Ftp(Box::new(ftp::Connection::open(url, None).await?))),
+        }
+    }
+
+    fn path(&self, url: &Url) -> String {
+        match self {
+            Remote::Ftp(_) => ftp::path(url),
+            Remote::Sftp(_) => sftp::path(url),
+        }
+    }
+
+    async fn stat(&mut self, path: &str) -> Result<(bool, Option<u64>), String> {
+        match self {
+            Remote::Ftp(connection) => connection.stat(path).await,
+            Remote::Sftp(session) => session.stat(path).await,
+        }
+    }
+
+    async fn list(&mut self, path: &str) -> Result<Vec<RemoteEntry>, String> {
+        match self {
+            Remote::Ftp(connection) => connection.list(path).await,
+            Remote::Sftp(session) => session.list(path).await,
+        }
+    }
+
+    // Starts the transfer at `offset`, or at 0 when the server can't resume; returns where it started
+    async fn retrieve(&mut self, path: &str, offset: u64) -> Result<u64, String> {
+        match self {
+            Remote::Ftp(connection) => Ok(if connection.retrieve(path, offset).await? { offset } else { 0 }),
+            Remote::Sftp(session) => session.retrieve(path, offset).await.map(|_| offset),
+        }
+    }
+
+    async fn read_chunk(&mut self) -> Result<Option<Vec<u8>>, String> {
+        match self {
+            Remote::Ftp(connection) => connection.read_chunk().await,
+            Remote::Sftp(session) => session.read_chunk().await,
+        }
+    }
+
+    fn resumable(&self) -> bool {
+        match self {
+            Remote::Ftp(connection) => connection.resumable(),
+            Remote::Sftp(_) => true,
+        }
+    }
+}
+
+// Trusted certificates and the client certificate for an FTPS host, as its HTTP client would have
+async fn tls_config(app_handle: &AppHandle, host: &str) -> Result<rustls::ClientConfig, String> {
+    let state: State<AppState> = app_handle.state();
+    let state_guard = state.persistent.lock().await;
+    let settings = &state_guard.settings;
+    let trusted: Vec<&crate::tls_trust::TrustedCertificate> = settings.trusted_certificates.iter()
+        .filter(|c| c.host_pattern().is_some_and(|p| crate::host_rules::matches(&p, host)))
+        .collect();
+    let identity = settings.client_certificates.iter().find(|c| c.host_pattern().is_some_and(|p| crate::host_rules::matches(&p, host)))
+        .or_else(|| settings.client_certificates.iter().find(|c| c.host_pattern().is_none()));
+    let identity = identity.map(|c| c.pem()).transpose()?;
+    crate::tls_trust::client_config(&trusted, identity.as_deref())
+}
+
+fn parse(url: &str) -> Result<Url, String> {
+    Url::parse(url.trim()).map_err(|e| format!("Invalid URL: {}", e))
+}
+
+// The last path segment, decoded
+fn last_segment(url: &Url) -> Option<String> {
+    url.path_segments()?.rfind(|s| !s.is_empty())
+        .map(|s| percent_decode_str(s).decode_utf8_lossy().to_string())
+        .filter(|name| crate::is_valid_file_name(name))
+}
+
+pub(crate) async fn probe(app_handle: &AppHandle, url: &str) -> Result<DownloadInfo, String> {
+    let parsed = parse(url)?;
+    let mut remote = Remote::connect(app_handle, &parsed).await?;
+    let (is_dir, size) = remote.stat(&remote.path(&parsed)).await?;
+    if is_dir { return Err("This link is a folder; list it to pick its files".to_string()); }
+    let file_name = last_segment(&parsed).unwrap_or_else(|| "download".to_string());
+    Ok(DownloadInfo {
+        final_url: parsed.to_string(), file_type: crate::get_file_type(&file_name), file_name, total_size: size,
+        etag: None, resolved_by: None, headers: Default::default(), redirect_chain: Vec::new(), redirect_warning: None,
+    })
+}
+
+// Every file below a directory, subdirectories included; None when the link is a file
+pub(crate) async fn list_tree(app_handle: &AppHandle, url: &str) -> Result<Option<RemoteTree>, String> {
+    let mut root = parse(url)?;
+    let mut remote = Remote::connect(app_handle, &root).await?;
+    if !remote.stat(&remote.path(&root)).await?.0 { return Ok(None); }
+    if !root.path().ends_with('/') { root.set_path(&format!("{}/", root.path())); }
+    let name = last_segment(&root).filter(|name| name != "~")
+        .unwrap_or_else(|| root.host_str().unwrap_or("download").to_string());
+
+    let mut files = Vec::new();
+    let mut truncated = false;
+    let mut pending = VecDeque::from([(root.clone(), String::new())]);
+    let mut visited = 1;
+    while let Some((directory, prefix)) = pending.pop_front() {
+        let entries = match remote.list(&remote.path(&directory)).await {
+            Ok(entries) => entries,
+            Err(e) if prefix.is_empty() => return Err(e),
+            // A subdirectory that fails to list is skipped rather than failing the whole listing
+            Err(e) => {
+                log::warn!("Could not list {}: {}", crate::redact::url(directory.as_str()), crate::redact::text(&e));
+                continue;
+            }
+        };
+        for entry in entries {
+            if !crate::is_valid_file_name(&entry.name) { continue; }
+            let mut child = directory.clone();
+            child.path_segments_mut().map_err(|_| "Invalid URL")?.pop_if_empty().push(&entry.name);
+            let path = if prefix.is_empty() { entry.name } else { format!("{}/{}", prefix, entry.name) };
+            if entry.is_dir {
+                if visited >= MAX_DIRECTORIES {
+                    truncated = true;
+                    continue;
+                }
+                visited += 1;
+                child.path_segments_mut().map_err(|_| "Invalid URL")?.push("");
+                pending.push_back((child, path));
+            } else {
+                files.push(TreeFile { path, url: child.to_string(), size: entry.size, modified: entry.modified });
+            }
+        }
+    }
+    files.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(Some(RemoteTree { url: root.to_string(), name, files, truncated }))
+}
+
+// The transfer for a task with an FTP, FTPS or SFTP link, from `resume_from` (already clamped to the
+// file on disk) when the server can resume
+pub(crate) async fn download(id: &str, job: &DownloadJob, live: &LiveProgress, app_handle: &AppHandle, file_path: &Path, resume_from: u64) -> anyhow::Result<()> {
+    let url = parse(&job.url).map_err(anyhow::Error::msg)?;
+    let metrics = app_handle.state::<AppState>().metrics.clone();
+    let metered = (!job.allow_metered).then(|| app_handle.state::<AppState>().metered.clone());
+    let quota = (!job.ignore_quota).then(|| app_handle.state::<AppState>().quota.clone());
+    let idle = job.when_idle.then(|| app_handle.state::<AppState>().idle.clone());
+    let speed_limit = app_handle.state::<AppState>().speed_mode.limit.clone();
+    let disk_limit = app_handle.state::<AppState>().disk_limits.for_path(file_path);
+    let (write_buffer_size, preallocate, stall_timeout) = {
+        let state: State<AppState> = app_handle.state();
+        let settings = &state.persistent.lock().await.settings;
+        (settings.write_buffer_size, settings.preallocate_files, settings.stall_timeout_seconds)
+    };
+
+    let mut remote = Remote::connect(app_handle, &url).await.map_err(anyhow::Error::msg)?;
+    let path = remote.path(&url);
+    let (is_dir, size) = remote.stat(&path).await.map_err(anyhow::Error::msg)?;
+    if is_dir { return Err(anyhow::anyhow!("The link is a folder, not a file")); }
+    let total_size = size.unwrap_or(0);
+    // A file now shorter than the part on disk was replaced
+    let resume_from = if total_size > 0 && resume_from > total_size { 0 } else { resume_from };
+    let resume_from = remote.retrieve(&path, resume_from).await.map_err(anyhow::Error::msg)?;
+    let resume_capability = remote.resumable();
+    crate::tasklog::write(app_handle, id, &format!(
+        "RETR {}{}, size {} bytes, {}",
+        crate::redact::url(url.as_str()),
+        if resume_from > 0 { format!(" from byte {}", resume_from) } else { String::new() },
+        total_size,
+        if resume_capability { "resumable" } else { "not resumable" },
+    )).await;
+
+    live.segments.lock().unwrap().clear();
+    live.total_size.store(total_size, Ordering::Relaxed);
+    live.record(resume_from, 0);
+    live.synced.store(resume_from, Ordering::Relaxed);
+    {
+        let state: State<AppState> = app_handle.state();
+        let mut state_guard = state.persistent.lock().await;
+        if let Some(task) = state_guard.downloads.iter_mut().find(|t| t.id == id) {
+            task.total_size = total_size;
+            task.resume_capability = resume_capability;
+            app_handle.emit("task_updated", &*task).unwrap();
+        }
+    }
+
+    if let Some(parent) = file_path.parent() { tokio::fs::create_dir_all(parent).await?; }
+    let file = if resume_from > 0 {
+        let mut file = tokio::fs::OpenOptions::new().write(true).open(file_path).await?;
+        file.seek(std::io::SeekFrom::Start(resume_from)).await?;
+        file
+    } else {
+        let file = tokio::fs::File::create(file_path).await?;
+        if preallocate && total_size > 0 {
+            crate::preallocate_file(&file, total_size).await
+                .map_err(|e| anyhow::anyhow!("Could not reserve {} bytes on disk: {}", total_size, e))?;
+        }
+        file
+    };
+    let mut file = tokio::io::BufWriter::with_capacity(write_buffer_size.max(8 * 1024) as usize, file);
+    let mut hasher = if resume_from == 0 { job.checksum.as_deref().and_then(crate::checksum_hasher) } else { None };
+
+    let mut downloaded = resume_from;
+    let mut last_update = std::time::Instant::now();
+    let mut last_downloaded = downloaded;
+    let mut last_sync = Instant::now();
+    // A chunk can't be half read and picked up again, so a stall is one read that takes the whole window
+    let stall_window = Duration::from_secs(stall_timeout);
+    loop {
+        let chunk = tokio::select! {
+            _ = live.cancel.cancelled() => Err(None),
+            _ = tokio::time::sleep(stall_window), if stall_timeout > 0 => {
+                Err(Some(anyhow::anyhow!("{}: no data received for {}s", crate::STALLED_ERROR, stall_timeout)))
+            }
+            chunk = remote.read_chunk() => chunk.map_err(|e| Some(anyhow::Error::msg(e))),
+        };
+        let chunk = match chunk {
+            Ok(Some(chunk)) => chunk,
+            Ok(None) => break,
+            Err(error) => {
+                // Synced so the next attempt resumes from what was received
+                crate::sync_writer(&mut file).await?;
+                live.synced.store(downloaded, Ordering::Relaxed);
+                live.record(downloaded, 0);
+                return error.map_or(Ok(()), Err);
+            }
+        };
+
+        if let Some(metered) = &metered { metered.limit.take(chunk.len()).await; }
+        if let Some(quota) = &quota { quota.limit.take(chunk.len()).await; }
+        if let Some(idle) = &idle { idle.limit.take(chunk.len()).await; }
+        speed_limit.take(chunk.len()).await;
+        if let Some(disk_limit) = &disk_limit { disk_limit.take(chunk.len()).await; }
+        file.write_all(&chunk).await?;
+        if let Some((hasher, _)) = hasher.as_mut() { hasher.update(&chunk); }
+        downloaded += chunk.len() as u64;
+        metrics.bytes_downloaded.fetch_add(chunk.len() as u64, Ordering::Relaxed);
+
+        if last_update.elapsed() > crate::PROGRESS_INTERVAL {
+            let speed = ((downloaded - last_downloaded) as f64 / last_update.elapsed().as_secs_f64()) as u64;
+            live.record(downloaded, speed);
+            last_update = std::time::Instant::now();
+            last_downloaded = downloaded;
+        }
+        if last_sync.elapsed() >= crate::SYNC_INTERVAL {
+            crate::sync_writer(&mut file).await?;
+            live.synced.store(downloaded, Ordering::Relaxed);
+            last_sync = Instant::now();
+        }
+    }
+
+    file.flush().await?;
+    file.get_ref().sync_all().await?;
+    live.synced.store(downloaded, Ordering::Relaxed);
+    live.record(downloaded, 0);
+    crate::finish_download(id, job, file_path, total_size, downloaded, hasher, app_handle).await
+}
@@ -0,0 +1,180 @@
+// Watching the source of a finished download for a newer version, for things like nightly builds.
+// Every `every_hours` a conditional HEAD (`If-None-Match` with the ETag we have, `If-Modified-Since`
+// with the version's date) asks the server whether the file changed. When it has, the user is notified,
+// or with `auto_download` the new version is fetched under a timestamped name and the old file is kept.
+
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, Local, Utc};
+use reqwest::header::{IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED};
+use reqwest::{Client, StatusCode};
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Emitter, Manager, State};
+use tokio::time::Duration;
+
+use crate::i18n::{tr, Language, Text};
+use crate::{AppState, DownloadStatus, DownloadTask};
+
+const CHECK_TICK: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct Watch {
+    pub(crate) every_hours: u32,
+    // Fetch a new version on its own instead of only telling the user about it
+    pub(crate) auto_download: bool,
+    // The link as added, and the file name new versions are named after
+    source_url: String,
+    base_name: String,
+    // Validators of the version we have
+    #[serde(default)]
+    etag: Option<String>,
+    #[serde(default)]
+    modified: Option<DateTime<Utc>>,
+    pub(crate) next_check: DateTime<Local>,
+    #[serde(default)]
+    last_checked: Option<DateTime<Local>>,
+    // When the server was found to have a version we don't, until it's fetched
+    #[serde(default)]
+    changed_at: Option<DateTime<Local>>,
+    #[serde(default)]
+    error: Option<String>,
+}
+
+impl Watch {
+    pub(crate) fn new(task: &DownloadTask, every_hours: u32, auto_download: bool) -> Result<Self, String> {
+        if every_hours == 0 { return Err("The interval must be at least an hour".to_string()); }
+        if task.request.is_some() { return Err("Only downloads made with a plain GET can be watched".to_string()); }
+        Ok(Watch {
+            every_hours,
+            auto_download,
+            source_url: task.redirect_chain.first().cloned().unwrap_or_else(|| task.url.clone()),
+            base_name: task.file_name.clone(),
+            etag: task.etag.clone(),
+            modified: task.completed_at.map(|t| t.with_timezone(&Utc)),
+            next_check: Local::now() + interval(every_hours),
+            last_checked: None,
+            changed_at: None,
+            error: None,
+        })
+    }
+}
+
+fn interval(every_hours: u32) -> chrono::Duration {
+    chrono::Duration::hours(every_hours as i64)
+}
+
+// What the server reports for a version we don't have
+struct Version {
+    etag: Option<String>,
+    modified: Option<DateTime<Utc>>,
+}
+
+pub(crate) async fn run_remote_watch(app_handle: AppHandle) {
+    loop {
+        tokio::time::sleep(CHECK_TICK).await;
+        let due: Vec<String> = {
+            let state: State<AppState> = app_handle.state();
+            let state_guard = state.persistent.lock().await;
+            let now = Local::now();
+            // Anything not completed (being re-fetched, say) waits for the next check
+            state_guard.downloads.iter()
+                .filter(|t| t.status == DownloadStatus::Completed && t.watch.as_ref().is_some_and(|w| w.next_check <= now))
+                .map(|t| t.id.clone())
+                .collect()
+        };
+        for id in due { check(&app_handle, &id).await; }
+    }
+}
+
+async fn check(app_handle: &AppHandle, id: &str) {
+    let state: State<AppState> = app_handle.state();
+    let Some((watch, headers)) = state.persistent.lock().await.downloads.iter()
+        .find(|t| t.id == id)
+        .and_then(|t| Some((t.watch.clone()?, t.headers.clone())))
+    else { return };
+    let client = crate::http_client_for(&state, &watch.source_url).await;
+    let mut result = newer_version(&client, &watch, &headers).await;
+    // Fetching needs the new version's size and final link
+    let info = match &result {
+        Ok(Some(_)) if watch.auto_download => match crate::probe_download_info_with_headers(&client, &watch.source_url, &headers).await {
+            Ok(info) => Some(info),
+            Err(e) => {
+                result = Err(e);
+                None
+            }
+        },
+        _ => None,
+    };
+    let (file_name, settings) = {
+        let mut state_guard = state.persistent.lock().await;
+        let settings = state_guard.settings.clone();
+        let Some(task) = state_guard.downloads.iter_mut().find(|t| t.id == id) else { return };
+        let Some(current) = task.watch.as_mut() else { return };
+        let now = Local::now();
+        current.last_checked = Some(now);
+        current.next_check = now + interval(current.every_hours);
+        current.error = None;
+        let mut file_name = None;
+        match result {
+            Ok(None) => {}
+            Ok(Some(version)) => {
+                // The next check asks about versions after this one
+                current.etag = version.etag.or(current.etag.take());
+                current.modified = version.modified.or(current.modified);
+                current.changed_at = Some(now);
+                file_name = Some(task.file_name.clone());
+                if let Some(info) = info {
+                    current.changed_at = None;
+                    let name = crate::recurring::timestamped_name(&current.base_name, now);
+                    crate::recurring::requeue(task, info, name.clone());
+                    // The old checksum belongs to the old version
+                    task.checksum = None;
+                    file_name = Some(name);
+                }
+            }
+            Err(e) => current.error = Some(e),
+        }
+        app_handle.emit("task_updated", &*task).unwrap();
+        (file_name, settings)
+    };
+    crate::request_save(&state);
+    let Some(file_name) = file_name else { return };
+    if watch.auto_download {
+        crate::tasklog::write(app_handle, id, &format!("The source changed; fetching the new version as {}", file_name)).await;
+        if let Err(e) = crate::start_download_task(id.to_string(), app_handle.clone()).await {
+            log::warn!("Could not fetch the new version of {}: {}", file_name, e);
+        }
+    } else {
+        crate::tasklog::write(app_handle, id, "The source has a newer version").await;
+        let language = Language::from_setting(&settings.language);
+        let body = tr(language, Text::NewVersionBody).replace("{file}", &file_name);
+        crate::notifications::show(app_handle, &settings, tr(language, Text::NewVersion), &body);
+    }
+}
+
+// Asks whether the server has a version other than `watch`'s; `None` when it doesn't
+async fn newer_version(client: &Client, watch: &Watch, headers: &BTreeMap<String, String>) -> Result<Option<Version>, String> {
+    let mut request = crate::with_headers(client.head(&watch.source_url), headers).timeout(Duration::from_secs(30));
+    if let Some(etag) = &watch.etag { request = request.header(IF_NONE_MATCH, etag); }
+    if let Some(modified) = watch.modified {
+        request = request.header(IF_MODIFIED_SINCE, modified.format("%a, %d %b %Y %H:%M:%S GMT").to_string());
+    }
+    let response = request.send().await.map_err(|e| format!("Request failed: {}", e))?;
+    let status = response.status();
+    if status == StatusCode::NOT_MODIFIED { return Ok(None); }
+    if !status.is_success() { return Err(format!("Server returned error: {}", status)); }
+    let version = Version {
+        etag: crate::header_etag(response.headers()),
+        modified: response.headers().get(LAST_MODIFIED)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| DateTime::parse_from_rfc2822(v).ok())
+            .map(|t| t.with_timezone(&Utc)),
+    };
+    // Servers that ignore the conditions answer 200 either way, so compare the validators too
+    let changed = match (&version.etag, &watch.etag) {
+        (Some(new), Some(old)) => !crate::etags_match(new, old),
+        _ => matches!((version.modified, watch.modified), (Some(new), Some(old)) if new > old),
+    };
+    Ok(changed.then_some(version))
+}
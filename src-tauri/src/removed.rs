@@ -0,0 +1,96 @@
+// Downloads taken off the list are kept for `removed_retention_hours` before they're gone for good, so
+// an accidental removal, a multi-select one included, can be undone with `restore_download`. Only the
+// entry is kept; a file sent to the system trash along with it has to be brought back from there, and
+// an unfinished one starts over. Their download logs stay until the entry expires.
+
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Emitter, Manager, State};
+use tokio::time::Duration;
+
+use crate::{AppState, DownloadStatus, DownloadTask, PersistentState};
+
+const PURGE_TICK: Duration = Duration::from_secs(10 * 60);
+// Beyond this the oldest go first, whatever their age, so state.json stays a sensible size
+const MAX_REMOVED: usize = 500;
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct RemovedDownload {
+    pub(crate) task: DownloadTask,
+    pub(crate) removed_at: DateTime<Local>,
+    // The file was deleted or sent to the system trash with it
+    #[serde(default)]
+    pub(crate) file_deleted: bool,
+}
+
+// Keeps `tasks`, already taken off the list, for undoing; returns the ids dropped for good, whose logs
+// can go. With retention off nothing is kept.
+pub(crate) fn keep(state: &mut PersistentState, tasks: Vec<DownloadTask>, file_deleted: bool) -> Vec<String> {
+    if state.settings.removed_retention_hours == 0 {
+        return tasks.into_iter().map(|t| t.id).collect();
+    }
+    let removed_at = Local::now();
+    state.removed.extend(tasks.into_iter().map(|task| RemovedDownload { task, removed_at, file_deleted }));
+    expire(state)
+}
+
+// Drops what's past the retention period or over the cap; returns their ids
+fn expire(state: &mut PersistentState) -> Vec<String> {
+    let retention = chrono::Duration::hours(state.settings.removed_retention_hours as i64);
+    let now = Local::now();
+    let mut expired = Vec::new();
+    state.removed.retain(|r| {
+        let keep = now - r.removed_at < retention;
+        if !keep { expired.push(r.task.id.clone()); }
+        keep
+    });
+    let over = state.removed.len().saturating_sub(MAX_REMOVED);
+    expired.extend(state.removed.drain(..over).map(|r| r.task.id));
+    expired
+}
+
+// Puts a removed download back on the list, paused if it hadn't finished
+pub(crate) fn restore(state: &mut PersistentState, id: &str) -> Result<DownloadTask, String> {
+    let index = state.removed.iter().position(|r| r.task.id == id).ok_or("Not among the removed downloads")?;
+    let entry = &state.removed[index];
+    let url = crate::canonical_url(&entry.task.url);
+    if state.downloads.iter().any(|t| crate::canonical_url(&t.url) == url && t.save_path == entry.task.save_path) {
+        return Err("The same download is already in the list".to_string());
+    }
+    let RemovedDownload { mut task, file_deleted, .. } = state.removed.remove(index);
+    if task.status != DownloadStatus::Completed {
+        task.status = DownloadStatus::Paused;
+        task.speed = 0;
+        task.time_remaining = None;
+        // Resuming into a new file must start over
+        if file_deleted {
+            task.progress = 0.0;
+            task.downloaded_size = 0;
+            task.segments.clear();
+        }
+    }
+    state.downloads.push(task.clone());
+    Ok(task)
+}
+
+pub(crate) async fn remove_logs(app_handle: &AppHandle, ids: Vec<String>) {
+    for id in ids { crate::tasklog::remove(app_handle, &id).await; }
+}
+
+pub(crate) async fn run_purge(app_handle: AppHandle) {
+    loop {
+        let expired = {
+            let state: State<AppState> = app_handle.state();
+            let mut state_guard = state.persistent.lock().await;
+            let expired = expire(&mut state_guard);
+            if !expired.is_empty() { crate::request_save(&state); }
+            expired
+        };
+        if !expired.is_empty() {
+            app_handle.emit("removed_downloads_expired", &expired).unwrap();
+            remove_logs(&app_handle, expired).await;
+        }
+        tokio::time::sleep(PURGE_TICK).await;
+    }
+}
@@ -0,0 +1,67 @@
+// Tidying old completed downloads off the list: those finished more than `cleanup_after_days` ago, and
+// any beyond the newest `cleanup_keep_last`, are removed every so often. They go to the removed list
+// like a manual removal, and with `cleanup_delete_files` their files go to the system trash first.
+
+use tauri::{AppHandle, Emitter, Manager, State};
+use tokio::time::Duration;
+
+use crate::extract::ExtractionStatus;
+use crate::{AppSettings, AppState, DownloadStatus, DownloadTask};
+
+const CLEANUP_TICK: Duration = Duration::from_secs(60 * 60);
+// Lets the rest of startup settle first
+const FIRST_CLEANUP_DELAY: Duration = Duration::from_secs(60);
+
+fn enabled(settings: &AppSettings) -> bool {
+    settings.cleanup_after_days > 0 || settings.cleanup_keep_last > 0
+}
+
+// Completed downloads the settings say should go, skipping recurring ones and any still being extracted
+fn due(tasks: &[DownloadTask], settings: &AppSettings) -> Vec<String> {
+    let mut completed: Vec<&DownloadTask> = tasks.iter()
+        .filter(|t| t.status == DownloadStatus::Completed && t.repeat.is_none())
+        .filter(|t| t.extraction.as_ref().is_none_or(|e| e.status != ExtractionStatus::Extracting))
+        .collect();
+    // Newest first; ones without a finish time count as oldest
+    completed.sort_by_key(|t| std::cmp::Reverse(t.completed_at));
+    let cutoff = chrono::Local::now() - chrono::Duration::days(settings.cleanup_after_days as i64);
+    completed.iter().enumerate()
+        .filter(|(index, task)| {
+            (settings.cleanup_keep_last > 0 && *index >= settings.cleanup_keep_last as usize)
+                || (settings.cleanup_after_days > 0 && task.completed_at.is_none_or(|at| at < cutoff))
+        })
+        .map(|(_, task)| task.id.clone())
+        .collect()
+}
+
+pub(crate) async fn run_cleanup(app_handle: AppHandle) {
+    tokio::time::sleep(FIRST_CLEANUP_DELAY).await;
+    loop {
+        cleanup(&app_handle).await;
+        tokio::time::sleep(CLEANUP_TICK).await;
+    }
+}
+
+async fn cleanup(app_handle: &AppHandle) {
+    let state: State<AppState> = app_handle.state();
+    let (mut ids, delete_files) = {
+        let state_guard = state.persistent.lock().await;
+        if !enabled(&state_guard.settings) { return; }
+        (due(&state_guard.downloads, &state_guard.settings), state_guard.settings.cleanup_delete_files)
+    };
+    if ids.is_empty() { return; }
+    if delete_files {
+        let mut kept = Vec::new();
+        for id in &ids {
+            // Leave the entry alone if its file couldn't be trashed, so it's tried again next time
+            if let Err(e) = crate::trash_task_file(&state, id).await {
+                log::warn!("Cleanup could not delete the file of {}: {}", id, e);
+                kept.push(id.clone());
+            }
+        }
+        ids.retain(|id| !kept.contains(id));
+    }
+    log::info!("Cleanup removed {} completed download(s)", ids.len());
+    crate::take_downloads(&state, app_handle, &ids, delete_files).await;
+    app_handle.emit("downloads_cleaned", &ids).unwrap();
+}
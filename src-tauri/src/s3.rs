@@ -0,0 +1,160 @@
+// S3 sources and presigned URLs. `s3://bucket/key` tasks keep that URL and are presigned (SigV4)
+// afresh for every attempt, so they never expire mid-queue; ranged and segmented transfers then work
+// as for any HTTPS server. Presigned HTTPS URLs pasted from elsewhere can't be re-signed here, so an
+// optional shell hook is asked for a fresh one once they expire.
+
+use chrono::{DateTime, NaiveDateTime, TimeZone, Utc};
+use hmac::{Hmac, Mac};
+use sha2::{Digest, Sha256};
+use url::Url;
+
+use crate::AppSettings;
+
+// The longest SigV4 allows; segment workers keep opening requests for as long as a transfer runs
+const PRESIGN_EXPIRES_SECS: u64 = 7 * 24 * 60 * 60;
+// Refresh a little early so a request doesn't race the deadline
+const EXPIRY_MARGIN_SECS: i64 = 60;
+const KEYRING_ID: &str = "s3-secret-access-key";
+
+pub(crate) struct Credentials {
+    access_key_id: String,
+    secret_access_key: String,
+    session_token: Option<String>,
+}
+
+pub(crate) fn is_s3_url(url: &str) -> bool {
+    url.starts_with("s3://")
+}
+
+// Settings first, then the usual AWS environment variables
+pub(crate) async fn credentials(settings: &AppSettings) -> Result<Credentials, String> {
+    let access_key_id = Some(settings.s3_access_key_id.trim().to_string()).filter(|k| !k.is_empty());
+    if let Some(access_key_id) = access_key_id {
+        let secret_access_key = crate::accounts::load_secret(KEYRING_ID).await?;
+        return Ok(Credentials { access_key_id, secret_access_key, session_token: None });
+    }
+    match (std::env::var("AWS_ACCESS_KEY_ID"), std::env::var("AWS_SECRET_ACCESS_KEY")) {
+        (Ok(access_key_id), Ok(secret_access_key)) => Ok(Credentials {
+            access_key_id, secret_access_key, session_token: std::env::var("AWS_SESSION_TOKEN").ok(),
+        }),
+        _ => Err("No S3 credentials; add an access key in Settings".to_string()),
+    }
+}
+
+pub(crate) async fn store_secret_access_key(secret: &str) -> Result<(), String> {
+    crate::accounts::store_secret(KEYRING_ID, secret).await
+}
+
+fn region(settings: &AppSettings) -> String {
+    Some(settings.s3_region.trim().to_string()).filter(|r| !r.is_empty())
+        .or_else(|| std::env::var("AWS_REGION").ok())
+        .unwrap_or_else(|| "us-east-1".to_string())
+}
+
+// RFC 3986 unreserved characters pass; everything else is %-encoded, `/` too unless it separates a key
+fn uri_encode(value: &str, keep_slash: bool) -> String {
+    value.bytes().map(|b| match b {
+        b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => (b as char).to_string(),
+        b'/' if keep_slash => "/".to_string(),
+        _ => format!("%{:02X}", b),
+    }).collect()
+}
+
+fn hmac_sha256(key: &[u8], data: &str) -> Vec<u8> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts any key length");
+    mac.update(data.as_bytes());
+    mac.finalize().into_bytes().to_vec()
+}
+
+// A presigned GET URL for `s3://bucket/key`, valid for a week from `now`
+pub(crate) fn presign(s3_url: &str, settings: &AppSettings, credentials: &Credentials, now: DateTime<Utc>) -> Result<String, String> {
+    let (bucket, key) = s3_url.strip_prefix("s3://").and_then(|rest| rest.split_once('/'))
+        .filter(|(bucket, key)| !bucket.is_empty() && !key.is_empty())
+        .ok_or_else(|| format!("Not an s3://bucket/key URL: {}", s3_url))?;
+    let region = region(settings);
+    let endpoint = settings.s3_endpoint.trim().trim_end_matches('/');
+    let base = if !endpoint.is_empty() {
+        // Custom endpoints (MinIO, R2, ...) are addressed path-style
+        format!("{}/{}", endpoint, bucket)
+    } else if settings.s3_path_style || bucket.contains('.') {
+        // Dotted bucket names don't match the wildcard certificate of virtual-hosted style
+        format!("https://s3.{}.amazonaws.com/{}", region, bucket)
+    } else {
+        format!("https://{}.s3.{}.amazonaws.com", bucket, region)
+    };
+    let url = Url::parse(&format!("{}/{}", base, uri_encode(key, true))).map_err(|e| format!("Invalid S3 URL: {}", e))?;
+    let host = match url.port() {
+        Some(port) => format!("{}:{}", url.host_str().unwrap_or_default(), port),
+        None => url.host_str().unwrap_or_default().to_string(),
+    };
+
+    let amz_date = now.format("%Y%m%dT%H%M%SZ").to_string();
+    let date = now.format("%Y%m%d").to_string();
+    let scope = format!("{}/{}/s3/aws4_request", date, region);
+    let mut query = vec![
+        ("X-Amz-Algorithm", "AWS4-HMAC-SHA256".to_string()),
+        ("X-Amz-Credential", format!("{}/{}", credentials.access_key_id, scope)),
+        ("X-Amz-Date", amz_date.clone()),
+        ("X-Amz-Expires", PRESIGN_EXPIRES_SECS.to_string()),
+        ("X-Amz-SignedHeaders", "host".to_string()),
+    ];
+    if let Some(token) = &credentials.session_token { query.push(("X-Amz-Security-Token", token.clone())); }
+    query.sort();
+    let canonical_query = query.iter()
+        .map(|(k, v)| format!("{}={}", uri_encode(k, false), uri_encode(v, false)))
+        .collect::<Vec<_>>()
+        .join("&");
+    let canonical_request = format!("GET\n{}\n{}\nhost:{}\n\nhost\nUNSIGNED-PAYLOAD", url.path(), canonical_query, host);
+    let string_to_sign = format!(
+        "AWS4-HMAC-SHA256\n{}\n{}\n{}", amz_date, scope, hex::encode(Sha256::digest(canonical_request.as_bytes())),
+    );
+    let signing_key = ["s3", "aws4_request"].iter().fold(
+        hmac_sha256(&hmac_sha256(format!("AWS4{}", credentials.secret_access_key).as_bytes(), &date), &region),
+        |key, part| hmac_sha256(&key, part),
+    );
+    let signature = hex::encode(hmac_sha256(&signing_key, &string_to_sign));
+    Ok(format!("{}?{}&X-Amz-Signature={}", url, canonical_query, signature))
+}
+
+// When a presigned URL stops working: AWS/GCS V4 (`X-Amz-Date` + `X-Amz-Expires`), AWS V2 (`Expires`
+// as a Unix time) or an Azure SAS (`se`). `None` for anything that doesn't look presigned.
+pub(crate) fn presigned_expiry(url: &str) -> Option<DateTime<Utc>> {
+    let url = Url::parse(url).ok()?;
+    let param = |names: &[&str]| url.query_pairs().find(|(k, _)| names.iter().any(|n| k.eq_ignore_ascii_case(n))).map(|(_, v)| v.into_owned());
+    if let (Some(date), Some(expires)) = (param(&["X-Amz-Date", "X-Goog-Date"]), param(&["X-Amz-Expires", "X-Goog-Expires"])) {
+        let signed = NaiveDateTime::parse_from_str(&date, "%Y%m%dT%H%M%SZ").ok()?;
+        return Some(Utc.from_utc_datetime(&signed) + chrono::Duration::seconds(expires.parse().ok()?));
+    }
+    if param(&["Signature"]).is_some() {
+        if let Some(expires) = param(&["Expires"]) {
+            return DateTime::from_timestamp(expires.parse().ok()?, 0);
+        }
+    }
+    if param(&["sig"]).is_some() {
+        return DateTime::parse_from_rfc3339(&param(&["se"])?).ok().map(|d| d.with_timezone(&Utc));
+    }
+    None
+}
+
+pub(crate) fn needs_refresh(url: &str) -> bool {
+    presigned_expiry(url).is_some_and(|expiry| expiry - chrono::Duration::seconds(EXPIRY_MARGIN_SECS) <= Utc::now())
+}
+
+// Runs the user's refresh hook with the stale URL in `VELODOWN_URL`; its first output line is the new URL
+pub(crate) async fn run_refresh_hook(command: &str, url: &str) -> Result<String, String> {
+    #[cfg(target_os = "windows")]
+    let mut hook = { let mut c = tokio::process::Command::new("cmd"); c.args(["/C", command]); c };
+    #[cfg(not(target_os = "windows"))]
+    let mut hook = { let mut c = tokio::process::Command::new("sh"); c.args(["-c", command]); c };
+    let output = tokio::time::timeout(std::time::Duration::from_secs(60), hook.env("VELODOWN_URL", url).kill_on_drop(true).output())
+        .await
+        .map_err(|_| "URL refresh hook timed out".to_string())?
+        .map_err(|e| format!("URL refresh hook failed: {}", e))?;
+    if !output.status.success() {
+        return Err(format!("URL refresh hook failed: {}", String::from_utf8_lossy(&output.stderr).trim()));
+    }
+    String::from_utf8_lossy(&output.stdout).lines().map(str::trim).find(|l| !l.is_empty())
+        .filter(|l| Url::parse(l).is_ok())
+        .map(str::to_string)
+        .ok_or_else(|| "URL refresh hook printed no URL".to_string())
+}
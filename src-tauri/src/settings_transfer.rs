@@ -0,0 +1,122 @@
+// Moving settings between machines as a JSON file. Tokens, keys and passwords are masked unless the
+// export asks for them; importing a masked value keeps this machine's. Host rules (blocked and allowed
+// hosts, client and trusted certificates) are optional in an export. An import only touches the keys
+// the file has, and can be previewed first as a list of changes plus warnings about what won't work
+// here, such as folders that don't exist.
+
+use std::path::Path;
+
+use chrono::Local;
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+use crate::{logging, redact, AppSettings};
+
+const FORMAT: &str = "velodown-settings";
+const FORMAT_VERSION: u64 = 1;
+const HOST_RULE_KEYS: [&str; 4] = ["blockedHosts", "allowedHosts", "clientCertificates", "trustedCertificates"];
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct SettingChange {
+    key: String,
+    current: Value,
+    incoming: Value,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct SettingsImport {
+    changes: Vec<SettingChange>,
+    warnings: Vec<String>,
+}
+
+pub(crate) fn export(settings: &AppSettings, include_host_rules: bool, include_secrets: bool) -> Result<String, String> {
+    let mut value = serde_json::to_value(settings).map_err(|e| e.to_string())?;
+    if let Some(map) = value.as_object_mut() {
+        if !include_host_rules {
+            for key in HOST_RULE_KEYS { map.remove(key); }
+        }
+    }
+    if !include_secrets { redact::json(&mut value); }
+    let file = serde_json::json!({
+        "format": FORMAT,
+        "version": FORMAT_VERSION,
+        "appVersion": env!("CARGO_PKG_VERSION"),
+        "exportedAt": Local::now(),
+        "settings": value,
+    });
+    serde_json::to_string_pretty(&file).map_err(|e| e.to_string())
+}
+
+// Reads `content` against `current`, returning the settings it would produce and what changes
+pub(crate) fn import(content: &str, current: &AppSettings) -> Result<(AppSettings, SettingsImport), String> {
+    let file: Value = serde_json::from_str(content).map_err(|e| format!("Not a settings file: {}", e))?;
+    if file.get("format").and_then(Value::as_str) != Some(FORMAT) {
+        return Err("Not a settings file exported from this app".to_string());
+    }
+    let version = file.get("version").and_then(Value::as_u64).unwrap_or(0);
+    if version > FORMAT_VERSION {
+        return Err("This settings file is from a newer version of the app; update first".to_string());
+    }
+    let Some(Value::Object(incoming)) = file.get("settings").cloned() else {
+        return Err("The settings file has no settings in it".to_string());
+    };
+
+    let current_value = serde_json::to_value(current).map_err(|e| e.to_string())?;
+    let Value::Object(mut merged) = current_value.clone() else { unreachable!("settings serialize to an object") };
+    let mut warnings = Vec::new();
+    let mut unknown = Vec::new();
+    for (key, mut value) in incoming {
+        if !merged.contains_key(&key) {
+            unknown.push(key);
+            continue;
+        }
+        for path in redact::restore(&mut value, current_value.get(&key), &key) {
+            warnings.push(format!("{} was masked in the export and isn't set here; enter it again", path));
+        }
+        merged.insert(key, value);
+    }
+    if !unknown.is_empty() {
+        warnings.push(format!("Ignored settings this version doesn't know: {}", unknown.join(", ")));
+    }
+
+    let settings: AppSettings = serde_json::from_value(Value::Object(merged.clone()))
+        .map_err(|e| format!("The settings file has an invalid value: {}", e))?;
+    logging::check_level(&settings.log_level)?;
+    warnings.extend(local_warnings(&settings));
+
+    let Value::Object(current_map) = current_value else { unreachable!("settings serialize to an object") };
+    let changes = changes(&current_map, &merged);
+    Ok((settings, SettingsImport { changes, warnings }))
+}
+
+fn changes(current: &Map<String, Value>, merged: &Map<String, Value>) -> Vec<SettingChange> {
+    merged.iter()
+        .filter(|(key, value)| current.get(*key) != Some(value))
+        .map(|(key, value)| {
+            let (mut current, mut incoming) = (current.get(key).cloned().unwrap_or(Value::Null), value.clone());
+            // The preview is shown on screen; secrets in it stay masked
+            redact::json(&mut current);
+            redact::json(&mut incoming);
+            SettingChange { key: key.clone(), current, incoming }
+        })
+        .collect()
+}
+
+// Paths that came from another machine and don't exist on this one
+fn local_warnings(settings: &AppSettings) -> Vec<String> {
+    let mut warnings = Vec::new();
+    let mut check = |label: &str, path: &str| {
+        if !path.trim().is_empty() && !Path::new(path).exists() {
+            warnings.push(format!("{} {} doesn't exist on this machine", label, path));
+        }
+    };
+    check("Download folder", &settings.download_folder);
+    check("Extract folder", &settings.extract_folder);
+    for certificate in &settings.client_certificates {
+        check("Client certificate", &certificate.path);
+        check("Client certificate key", &certificate.key_path);
+    }
+    warnings
+}
@@ -0,0 +1,321 @@
+// SFTP through the system's OpenSSH client (`ssh -s host sftp`), the way sshfs does it, so keys,
+// agents, known_hosts and ~/.ssh/config apply as they do on the command line. ssh runs in batch mode:
+// it can't ask for a password or to accept an unknown host key, so a key or agent has to be set up and
+// the host known from one interactive `ssh` beforehand. Speaks version 3 of the protocol, which every
+// server offers. Paths are absolute; `/~/` at the start of one is the login directory, as in curl.
+
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::process::Stdio;
+
+use percent_encoding::percent_decode_str;
+use tokio::io::{AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::process::{Child, ChildStdin, ChildStdout};
+use tokio::time::{timeout, Duration};
+use url::Url;
+
+use crate::remote::RemoteEntry;
+
+// Covers ssh's own connect timeout and the login after it
+const REPLY_TIMEOUT: Duration = Duration::from_secs(60);
+const MAX_PACKET: usize = 256 * 1024;
+const CHUNK_SIZE: u32 = 32 * 1024;
+// Reads kept in flight; one at a time, latency would cap the speed
+const READ_AHEAD: usize = 16;
+
+const FXP_INIT: u8 = 1;
+const FXP_VERSION: u8 = 2;
+const FXP_OPEN: u8 = 3;
+const FXP_CLOSE: u8 = 4;
+const FXP_READ: u8 = 5;
+const FXP_OPENDIR: u8 = 11;
+const FXP_READDIR: u8 = 12;
+const FXP_STAT: u8 = 17;
+const FXP_STATUS: u8 = 101;
+const FXP_HANDLE: u8 = 102;
+const FXP_DATA: u8 = 103;
+const FXP_NAME: u8 = 104;
+const FXP_ATTRS: u8 = 105;
+const FX_EOF: u32 = 1;
+const FXF_READ: u32 = 1;
+
+pub(crate) struct Session {
+    child: Child,
+    stdin: ChildStdin,
+    stdout: BufReader<ChildStdout>,
+    next_id: u32,
+    // Replies that arrived ahead of the one being waited for
+    pending: HashMap<u32, Vec<u8>>,
+    // Reads abandoned after a short read; their replies are dropped
+    stale: HashSet<u32>,
+    file: Option<RemoteFile>,
+}
+
+struct RemoteFile {
+    handle: Vec<u8>,
+    next_offset: u64,
+    // Request id and length of each read in flight, in offset order
+    in_flight: VecDeque<(u32, u32)>,
+}
+
+struct Attributes {
+    size: Option<u64>,
+    permissions: Option<u32>,
+    modified: Option<u32>,
+}
+
+impl Attributes {
+    fn is_dir(&self) -> bool { self.permissions.is_some_and(|p| p & 0o170000 == 0o040000) }
+    fn is_file(&self) -> bool { self.permissions.is_none_or(|p| p & 0o170000 == 0o100000) }
+}
+
+// The path an SFTP link names
+pub(crate) fn path(url: &Url) -> String {
+    let path = percent_decode_str(url.path()).decode_utf8_lossy().into_owned();
+    match path.strip_prefix("/~") {
+        Some("") => ".".to_string(),
+        Some(rest) if rest.starts_with('/') => format!(".{}", rest),
+        _ if path.is_empty() => "/".to_string(),
+        _ => path,
+    }
+}
+
+impl Session {
+    pub(crate) async fn open(url: &Url) -> Result<Session, String> {
+        let host = url.host_str().ok_or("The link has no host")?.trim_start_matches('[').trim_end_matches(']');
+        let mut command = tokio::process::Command::new("ssh");
+        command.args(["-o", "BatchMode=yes", "-o", "ConnectTimeout=30"]);
+        if let Some(port) = url.port() { command.arg("-p").arg(port.to_string()); }
+        let user = percent_decode_str(url.username()).decode_utf8_lossy();
+        if !user.is_empty() { command.arg("-l").arg(user.as_ref()); }
+        // `--` so a host can't pass for an option
+        command.args(["-s", "--", host, "sftp"])
+            .stdin(Stdio::piped()).stdout(Stdio::piped()).stderr(Stdio::piped())
+            .kill_on_drop(true);
+        #[cfg(target_os = "windows")]
+        command.creation_flags(0x0800_0000); // CREATE_NO_WINDOW
+        let mut child = command.spawn().map_err(|e| format!("Could not run ssh: {}", e))?;
+        let stdin = child.stdin.take().expect("piped stdin");
+        let stdout = BufReader::new(child.stdout.take().expect("piped stdout"));
+        let mut session = Session { child, stdin, stdout, next_id: 0, pending: HashMap::new(), stale: HashSet::new(), file: None };
+
+        let mut init = vec![FXP_INIT];
+        init.extend_from_slice(&3u32.to_be_bytes());
+        session.send(init).await?;
+        let reply = session.read_packet().await?;
+        if reply.first() != Some(&FXP_VERSION) { return Err("The server doesn't speak SFTP".to_string()); }
+        Ok(session)
+    }
+
+    pub(crate) async fn stat(&mut self, path: &str) -> Result<(bool, Option<u64>), String> {
+        let mut reply = self.request(FXP_STAT, &[string(path.as_bytes())]).await?;
+        match reply.kind() {
+            FXP_ATTRS => {
+                let attributes = reply.attributes()?;
+                Ok((attributes.is_dir(), attributes.size))
+            }
+            _ => Err(reply.status_error(&format!("Looking up {}", path))),
+        }
+    }
+
+    pub(crate) async fn list(&mut self, path: &str) -> Result<Vec<RemoteEntry>, String> {
+        let mut reply = self.request(FXP_OPENDIR, &[string(path.as_bytes())]).await?;
+        if reply.kind() != FXP_HANDLE { return Err(reply.status_error(&format!("Listing {}", path))); }
+        let handle = reply.string()?;
+        let mut entries = Vec::new();
+        loop {
+            let mut reply = self.request(FXP_READDIR, &[string(&handle)]).await?;
+            match reply.kind() {
+                FXP_NAME => {
+                    for _ in 0..reply.u32()? {
+                        let name = String::from_utf8_lossy(&reply.string()?).into_owned();
+                        let _long_name = reply.string()?;
+                        let attributes = reply.attributes()?;
+                        if name == "." || name == ".." { continue; }
+                        // Links and devices aren't followed
+                        if !attributes.is_dir() && !attributes.is_file() { continue; }
+                        entries.push(RemoteEntry {
+                            name, is_dir: attributes.is_dir(),
+                            size: attributes.size.filter(|_| !attributes.is_dir()),
+                            modified: attributes.modified
+                                .and_then(|t| chrono::DateTime::from_timestamp(t as i64, 0))
+                                .map(|t| t.format("%Y-%m-%d %H:%M").to_string()),
+                        });
+                    }
+                }
+                FXP_STATUS if reply.status() == Some(FX_EOF) => break,
+                _ => return Err(reply.status_error(&format!("Listing {}", path))),
+            }
+        }
+        self.close(handle).await;
+        Ok(entries)
+    }
+
+    // Opens `path` for `read_chunk`, starting at `offset`
+    pub(crate) async fn retrieve(&mut self, path: &str, offset: u64) -> Result<(), String> {
+        // Flags, then attributes with none set
+        let mut reply = self.request(FXP_OPEN, &[string(path.as_bytes()), FXF_READ.to_be_bytes().to_vec(), 0u32.to_be_bytes().to_vec()]).await?;
+        if reply.kind() != FXP_HANDLE { return Err(reply.status_error(&format!("Opening {}", path))); }
+        self.file = Some(RemoteFile { handle: reply.string()?, next_offset: offset, in_flight: VecDeque::new() });
+        Ok(())
+    }
+
+    // The next bytes of the file `retrieve` opened, or None at its end
+    pub(crate) async fn read_chunk(&mut self) -> Result<Option<Vec<u8>>, String> {
+        let Some(mut file) = self.file.take() else { return Ok(None) };
+        while file.in_flight.len() < READ_AHEAD {
+            let id = self.send_request(FXP_READ, &[string(&file.handle), file.next_offset.to_be_bytes().to_vec(), CHUNK_SIZE.to_be_bytes().to_vec()]).await?;
+            file.in_flight.push_back((id, CHUNK_SIZE));
+            file.next_offset += CHUNK_SIZE as u64;
+        }
+        let (id, len) = file.in_flight.pop_front().expect("reads in flight");
+        let mut reply = Reply(self.reply(id).await?, 5);
+        match reply.kind() {
+            FXP_DATA => {
+                let data = reply.string()?;
+                if data.len() < len as usize {
+                    // Short of the end, servers cap reads below the size asked for; the reads after it
+                    // would leave a gap, so they're sent again from where this one stopped
+                    let end = file.next_offset - file.in_flight.iter().map(|(_, len)| *len as u64).sum::<u64>() - len as u64 + data.len() as u64;
+                    self.stale.extend(file.in_flight.drain(..).map(|(id, _)| id));
+                    file.next_offset = end;
+                }
+                self.file = Some(file);
+                Ok(Some(data))
+            }
+            FXP_STATUS if reply.status() == Some(FX_EOF) => {
+                self.stale.extend(file.in_flight.drain(..).map(|(id, _)| id));
+                self.close(file.handle).await;
+                Ok(None)
+            }
+            _ => Err(reply.status_error("Reading")),
+        }
+    }
+
+    async fn close(&mut self, handle: Vec<u8>) {
+        let _ = self.request(FXP_CLOSE, &[string(&handle)]).await;
+    }
+
+    async fn request(&mut self, kind: u8, fields: &[Vec<u8>]) -> Result<Reply, String> {
+        let id = self.send_request(kind, fields).await?;
+        Ok(Reply(self.reply(id).await?, 5))
+    }
+
+    async fn send_request(&mut self, kind: u8, fields: &[Vec<u8>]) -> Result<u32, String> {
+        let id = self.next_id;
+        self.next_id = self.next_id.wrapping_add(1);
+        let mut packet = vec![kind];
+        packet.extend_from_slice(&id.to_be_bytes());
+        for field in fields { packet.extend_from_slice(field); }
+        self.send(packet).await?;
+        Ok(id)
+    }
+
+    async fn send(&mut self, packet: Vec<u8>) -> Result<(), String> {
+        let mut framed = (packet.len() as u32).to_be_bytes().to_vec();
+        framed.extend(packet);
+        match self.stdin.write_all(&framed).await {
+            Ok(()) => Ok(()),
+            Err(_) => Err(self.ssh_error().await),
+        }
+    }
+
+    // The reply to request `id`; servers may answer out of order
+    async fn reply(&mut self, id: u32) -> Result<Vec<u8>, String> {
+        if let Some(packet) = self.pending.remove(&id) { return Ok(packet); }
+        loop {
+            let packet = self.read_packet().await?;
+            let Some(reply_id) = packet.get(1..5).map(|b| u32::from_be_bytes(b.try_into().unwrap())) else {
+                return Err("The server sent a malformed reply".to_string());
+            };
+            if reply_id == id { return Ok(packet); }
+            if !self.stale.remove(&reply_id) { self.pending.insert(reply_id, packet); }
+        }
+    }
+
+    async fn read_packet(&mut self) -> Result<Vec<u8>, String> {
+        let read = async {
+            let len = self.stdout.read_u32().await? as usize;
+            if len == 0 || len > MAX_PACKET { return Err(std::io::Error::other("bad packet length")); }
+            let mut packet = vec![0; len];
+            self.stdout.read_exact(&mut packet).await?;
+            Ok(packet)
+        };
+        match timeout(REPLY_TIMEOUT, read).await {
+            Ok(Ok(packet)) => Ok(packet),
+            Ok(Err(e)) if e.kind() == std::io::ErrorKind::UnexpectedEof => Err(self.ssh_error().await),
+            Ok(Err(e)) => Err(format!("The SFTP session broke: {}", e)),
+            Err(_) => Err("The server stopped answering".to_string()),
+        }
+    }
+
+    // Why ssh quit, from the last thing it printed (`Permission denied (publickey)`, say)
+    async fn ssh_error(&mut self) -> String {
+        let mut errors = String::new();
+        if let Some(stderr) = self.child.stderr.as_mut() {
+            let _ = timeout(Duration::from_secs(2), stderr.read_to_string(&mut errors)).await;
+        }
+        match errors.lines().rfind(|l| !l.trim().is_empty()) {
+            Some(line) => format!("ssh: {}", line.trim()),
+            None => "ssh closed the connection".to_string(),
+        }
+    }
+}
+
+// A length-prefixed string field
+fn string(bytes: &[u8]) -> Vec<u8> {
+    let mut field = (bytes.len() as u32).to_be_bytes().to_vec();
+    field.extend_from_slice(bytes);
+    field
+}
+
+// A reply packet and how far it has been read; the type and request id come first
+struct Reply(Vec<u8>, usize);
+
+impl Reply {
+    fn kind(&self) -> u8 { self.0[0] }
+
+    fn take(&mut self, len: usize) -> Result<&[u8], String> {
+        let field = self.0.get(self.1..self.1 + len).ok_or("The server sent a truncated reply")?;
+        self.1 += len;
+        Ok(field)
+    }
+
+    fn u32(&mut self) -> Result<u32, String> { Ok(u32::from_be_bytes(self.take(4)?.try_into().unwrap())) }
+
+    fn u64(&mut self) -> Result<u64, String> { Ok(u64::from_be_bytes(self.take(8)?.try_into().unwrap())) }
+
+    fn string(&mut self) -> Result<Vec<u8>, String> {
+        let len = self.u32()? as usize;
+        Ok(self.take(len)?.to_vec())
+    }
+
+    fn attributes(&mut self) -> Result<Attributes, String> {
+        let flags = self.u32()?;
+        let size = if flags & 0x1 != 0 { Some(self.u64()?) } else { None };
+        if flags & 0x2 != 0 { self.take(8)?; }
+        let permissions = if flags & 0x4 != 0 { Some(self.u32()?) } else { None };
+        let modified = if flags & 0x8 != 0 {
+            self.u32()?;
+            Some(self.u32()?)
+        } else { None };
+        if flags & 0x8000_0000 != 0 {
+            for _ in 0..self.u32()? {
+                self.string()?;
+                self.string()?;
+            }
+        }
+        Ok(Attributes { size, permissions, modified })
+    }
+
+    // The code of a status reply
+    fn status(&self) -> Option<u32> {
+        Some(u32::from_be_bytes(self.0.get(5..9)?.try_into().unwrap())).filter(|_| self.kind() == FXP_STATUS)
+    }
+
+    fn status_error(&mut self, what: &str) -> String {
+        let Some(code) = self.status() else { return format!("{} failed: unexpected reply {}", what, self.kind()) };
+        self.1 = 9;
+        let message = self.string().map(|m| String::from_utf8_lossy(&m).trim().to_string()).unwrap_or_default();
+        if message.is_empty() { format!("{} failed (SFTP status {})", what, code) } else { format!("{} failed: {}", what, message) }
+    }
+}
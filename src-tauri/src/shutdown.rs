@@ -0,0 +1,91 @@
+// Exiting cleanly: every way out (Quit in the tray, closing the last window, Cmd+Q, SIGTERM from a
+// logout or service manager, Ctrl+C in a terminal) goes through `on_exit_requested`, which holds the
+// exit until running downloads have stopped the way pausing stops them, with buffers flushed and
+// offsets recorded, and state has been written once. Otherwise tokio just drops the tasks mid-write.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use tauri::{AppHandle, ExitRequestApi, Manager, State};
+
+use crate::AppState;
+
+#[derive(Default)]
+pub(crate) struct Shutdown {
+    started: AtomicBool,
+    finished: AtomicBool,
+}
+
+impl Shutdown {
+    // Nothing new is started once this is set
+    pub(crate) fn in_progress(&self) -> bool {
+        self.started.load(Ordering::Relaxed)
+    }
+}
+
+pub(crate) fn on_exit_requested(app_handle: &AppHandle, code: Option<i32>, api: &ExitRequestApi) {
+    let shutdown = &app_handle.state::<AppState>().shutdown;
+    // A restart for an update has already stopped the downloads itself, and can't be held anyway
+    if shutdown.finished.load(Ordering::Relaxed) || code == Some(tauri::RESTART_EXIT_CODE) { return; }
+    api.prevent_exit();
+    if shutdown.started.swap(true, Ordering::Relaxed) { return; }
+    let app_handle = app_handle.clone();
+    tauri::async_runtime::spawn(async move {
+        run(&app_handle).await;
+        app_handle.state::<AppState>().shutdown.finished.store(true, Ordering::Relaxed);
+        app_handle.exit(code.unwrap_or(0));
+    });
+}
+
+// Statuses are left as they are, so `resume_interrupted` picks the downloads up on the next launch
+async fn run(app_handle: &AppHandle) {
+    let state: State<AppState> = app_handle.state();
+    let running: Vec<String> = state.download_handles.lock().await.keys().cloned().collect();
+    if !running.is_empty() { log::info!("Stopping {} download(s) before exiting", running.len()); }
+    futures::future::join_all(running.iter().map(|id| crate::abort_download(&state, id))).await;
+    crate::flush_state(app_handle).await;
+    crate::cookies::save(app_handle).await;
+}
+
+// Termination signals become an ordinary exit request; a second one while downloads are still
+// stopping exits at once
+pub(crate) async fn run_signal_watch(app_handle: AppHandle) {
+    let mut first = true;
+    while wait_for_signal().await {
+        if !first {
+            log::warn!("Exiting without waiting for downloads to stop");
+            std::process::exit(130);
+        }
+        first = false;
+        app_handle.exit(0);
+    }
+}
+
+#[cfg(unix)]
+async fn wait_for_signal() -> bool {
+    use tokio::signal::unix::{signal, SignalKind};
+    let (Ok(mut terminate), Ok(mut interrupt), Ok(mut hangup)) = (signal(SignalKind::terminate()), signal(SignalKind::interrupt()), signal(SignalKind::hangup())) else {
+        log::warn!("Could not listen for termination signals");
+        return false;
+    };
+    tokio::select! {
+        _ = terminate.recv() => true,
+        _ = interrupt.recv() => true,
+        _ = hangup.recv() => true,
+    }
+}
+
+// Windows gives a console app a few seconds after a close, logoff or shutdown event
+#[cfg(windows)]
+async fn wait_for_signal() -> bool {
+    use tokio::signal::windows::{ctrl_c, ctrl_close, ctrl_logoff, ctrl_shutdown};
+    let (Ok(mut ctrl_c), Ok(mut close), Ok(mut logoff), Ok(mut shutdown)) = (ctrl_c(), ctrl_close(), ctrl_logoff(), ctrl_shutdown()) else {
+        log::warn!("Could not listen for console events");
+        return false;
+    };
+    tokio::select! {
+        _ = ctrl_c.recv() => true,
+        _ = close.recv() => true,
+        _ = logoff.recv() => true,
+        _ = shutdown.recv() => true,
+    }
+}
@@ -0,0 +1,151 @@
+// Detached OpenPGP signatures checked with the user's `gpg` before a download counts as completed.
+// A trusted key given as an armored block (or a URL to one) is imported into a throwaway GnuPG home,
+// so only that key can vouch for the file; a fingerprint instead uses the user's own keyring and must
+// match the key that made the signature.
+
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+
+use futures::StreamExt;
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager, State};
+use tokio::io::AsyncWriteExt;
+use tokio::time::Duration;
+
+use crate::AppState;
+
+// Signatures and public keys are a few KiB; keyrings with many keys still fit well under this
+const MAX_FETCH_BYTES: usize = 1024 * 1024;
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct SignatureCheck {
+    // The detached `.sig`/`.asc` signature of the file
+    pub(crate) url: String,
+    // Armored public key, a URL to one, or the fingerprint of a key in the user's keyring
+    pub(crate) key: String,
+    // Fingerprint of the key the signature checked out against
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) verified_by: Option<String>,
+}
+
+impl SignatureCheck {
+    pub(crate) fn validate(&self) -> Result<(), String> {
+        let url = url::Url::parse(self.url.trim()).map_err(|e| format!("Invalid signature URL: {}", e))?;
+        if !matches!(url.scheme(), "http" | "https") { return Err("Signature URL must be http or https".to_string()); }
+        let key = self.key.trim();
+        if key.is_empty() { return Err("A trusted key is needed to check the signature".to_string()); }
+        if fingerprint(key).is_none() && !is_url(key) && !key.contains("BEGIN PGP PUBLIC KEY BLOCK") {
+            return Err("The trusted key must be an armored public key, a URL to one, or a full fingerprint".to_string());
+        }
+        Ok(())
+    }
+}
+
+fn is_url(key: &str) -> bool {
+    key.starts_with("https://") || key.starts_with("http://")
+}
+
+// v4 fingerprints are 40 hex digits and v5/v6 ones 64; short key IDs are too easy to collide with
+fn fingerprint(key: &str) -> Option<String> {
+    let hex: String = key.chars().filter(|c| !c.is_whitespace()).collect::<String>().trim_start_matches("0x").to_ascii_uppercase();
+    (matches!(hex.len(), 40 | 64) && hex.chars().all(|c| c.is_ascii_hexdigit())).then_some(hex)
+}
+
+// The fingerprint of the signing key when `file` carries a good signature from the trusted key
+pub(crate) async fn verify(app_handle: &AppHandle, file: &Path, check: &SignatureCheck) -> Result<String, String> {
+    let state: State<AppState> = app_handle.state();
+    let gpg = {
+        let state_guard = state.persistent.lock().await;
+        Some(state_guard.settings.gpg_path.trim().to_string()).filter(|p| !p.is_empty()).unwrap_or_else(|| "gpg".to_string())
+    };
+    let client = crate::http_client(&state).await;
+    let signature = fetch(&client, check.url.trim()).await.map_err(|e| format!("Could not fetch the signature: {}", e))?;
+
+    let home = std::env::temp_dir().join(format!("velodown-gpg-{}", uuid::Uuid::new_v4()));
+    let result = verify_in(&gpg, &client, &home, file, &signature, check.key.trim()).await;
+    let _ = tokio::fs::remove_dir_all(&home).await;
+    result
+}
+
+async fn verify_in(gpg: &str, client: &Client, home: &Path, file: &Path, signature: &[u8], key: &str) -> Result<String, String> {
+    tokio::fs::create_dir_all(home).await.map_err(|e| e.to_string())?;
+    // gpg refuses homes other users can read
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let _ = tokio::fs::set_permissions(home, std::fs::Permissions::from_mode(0o700)).await;
+    }
+    let trusted = fingerprint(key);
+    let isolated = trusted.is_none().then(|| home.to_path_buf());
+    if trusted.is_none() {
+        let armored = if is_url(key) {
+            fetch(client, key).await.map_err(|e| format!("Could not fetch the trusted key: {}", e))?
+        } else {
+            key.as_bytes().to_vec()
+        };
+        let (ok, _, errors) = run(gpg, isolated.as_deref(), &["--import"], Some(&armored)).await?;
+        if !ok { return Err(format!("Could not import the trusted key: {}", last_line(&errors))); }
+    }
+
+    let signature_path = home.join("signature");
+    tokio::fs::write(&signature_path, signature).await.map_err(|e| e.to_string())?;
+    let (signature_arg, file_arg) = (signature_path.to_string_lossy().to_string(), file.to_string_lossy().to_string());
+    let (_, status, errors) = run(gpg, isolated.as_deref(), &["--status-fd", "1", "--verify", &signature_arg, &file_arg], None).await?;
+
+    // `[GNUPG:] KEYWORD args...` lines; see doc/DETAILS in the GnuPG sources
+    let lines: Vec<Vec<&str>> = status.lines()
+        .filter_map(|l| l.strip_prefix("[GNUPG:] "))
+        .map(|l| l.split_whitespace().collect())
+        .collect();
+    let has = |keyword: &str| lines.iter().any(|l| l.first() == Some(&keyword));
+    if has("BADSIG") { return Err("Bad signature: the file doesn't match it".to_string()); }
+    if has("REVKEYSIG") { return Err("Signed with a revoked key".to_string()); }
+    if has("NO_PUBKEY") { return Err("Signed by a key other than the trusted one".to_string()); }
+    // VALIDSIG <signing-key fingerprint> ... <primary-key fingerprint>
+    let Some(valid) = lines.iter().find(|l| l.first() == Some(&"VALIDSIG")).filter(|_| has("GOODSIG") || has("EXPKEYSIG")) else {
+        return Err(format!("Signature didn't verify: {}", last_line(&errors)));
+    };
+    let signer = valid.get(1).map(|s| s.to_ascii_uppercase()).unwrap_or_default();
+    let primary = valid.last().map(|s| s.to_ascii_uppercase()).unwrap_or_default();
+    if let Some(trusted) = trusted {
+        if trusted != signer && trusted != primary {
+            return Err(format!("Signed by {}, not the trusted key", primary));
+        }
+    }
+    Ok(primary)
+}
+
+// Whether gpg exited successfully, with its stdout and stderr
+async fn run(gpg: &str, home: Option<&Path>, args: &[&str], input: Option<&[u8]>) -> Result<(bool, String, String), String> {
+    let mut command = tokio::process::Command::new(gpg);
+    if let Some(home) = home { command.arg("--homedir").arg(PathBuf::from(home)); }
+    command.args(["--batch", "--no-tty"]).args(args)
+        .stdin(if input.is_some() { Stdio::piped() } else { Stdio::null() })
+        .stdout(Stdio::piped()).stderr(Stdio::piped());
+    #[cfg(target_os = "windows")]
+    command.creation_flags(0x0800_0000); // CREATE_NO_WINDOW
+    let mut child = command.spawn().map_err(|e| format!("Could not run {}: {}", gpg, e))?;
+    if let (Some(input), Some(mut stdin)) = (input, child.stdin.take()) {
+        stdin.write_all(input).await.map_err(|e| e.to_string())?;
+    }
+    let output = child.wait_with_output().await.map_err(|e| e.to_string())?;
+    Ok((output.status.success(), String::from_utf8_lossy(&output.stdout).into_owned(), String::from_utf8_lossy(&output.stderr).into_owned()))
+}
+
+fn last_line(text: &str) -> &str {
+    text.lines().rfind(|l| !l.trim().is_empty()).unwrap_or("unknown error").trim()
+}
+
+async fn fetch(client: &Client, url: &str) -> Result<Vec<u8>, String> {
+    let response = client.get(url).timeout(Duration::from_secs(30)).send().await.map_err(|e| e.to_string())?;
+    if !response.status().is_success() { return Err(format!("server returned {}", response.status())); }
+    let mut body = Vec::new();
+    let mut stream = response.bytes_stream();
+    while let Some(chunk) = stream.next().await {
+        body.extend_from_slice(&chunk.map_err(|e| e.to_string())?);
+        if body.len() > MAX_FETCH_BYTES { return Err("response too large".to_string()); }
+    }
+    Ok(body)
+}
@@ -0,0 +1,160 @@
+// Request templates for sites that need the same extras on every download, such as an API key header
+// for a self-hosted server. A template is bound to a host pattern (as in `host_rules`); the first one
+// matching a link adds its headers, a cookie and a referer to the probe and the task. What a download
+// already carries wins over the template. For sites whose links end in opaque ids, a template can also
+// name the file from a query parameter or a pattern over the URL; that only replaces a name guessed
+// from the URL itself, never one the server sent.
+
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+use crate::AddDownloadPayload;
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Default)]
+#[serde(rename_all = "camelCase", default)]
+pub(crate) struct SiteTemplate {
+    // `example.com` (and subdomains) or a `*` pattern
+    pub(crate) host: String,
+    pub(crate) headers: BTreeMap<String, String>,
+    // `name=value; ...`, or the path of a cookies.txt file exported from a browser
+    pub(crate) cookie: String,
+    // With `{url}`, `{origin}` and `{host}` standing for the download's; empty sends none
+    pub(crate) referer: String,
+    // The query parameter whose value names the file, tried before `file_name_pattern`
+    pub(crate) file_name_param: String,
+    // Matched against the URL; the `name` group, else the first group, else the whole match names the file
+    pub(crate) file_name_pattern: String,
+    // Added to a derived name that has no extension of its own, e.g. `mp4`
+    pub(crate) file_name_extension: String,
+}
+
+impl SiteTemplate {
+    fn host_pattern(&self) -> Option<String> {
+        Some(self.host.trim().trim_end_matches('.').to_ascii_lowercase()).filter(|h| !h.is_empty())
+    }
+
+    // The headers this template adds to requests for `url`
+    pub(crate) fn headers(&self, url: &Url) -> BTreeMap<String, String> {
+        let mut headers: BTreeMap<String, String> = self.headers.iter()
+            .filter(|(name, value)| !name.trim().is_empty() && !value.trim().is_empty())
+            .map(|(name, value)| (name.trim().to_string(), value.trim().to_string()))
+            .collect();
+        if let Some(cookie) = self.cookie_for(url) { headers.insert("Cookie".to_string(), cookie); }
+        let referer = self.referer.trim();
+        if !referer.is_empty() {
+            let origin = url.origin().ascii_serialization();
+            let referer = referer.replace("{url}", url.as_str()).replace("{origin}", &origin).replace("{host}", url.host_str().unwrap_or_default());
+            headers.insert("Referer".to_string(), referer);
+        }
+        headers
+    }
+
+    fn cookie_for(&self, url: &Url) -> Option<String> {
+        let cookie = self.cookie.trim();
+        if cookie.is_empty() { return None; }
+        if cookie.contains('=') { return Some(cookie.to_string()); }
+        match std::fs::read_to_string(cookie) {
+            Ok(content) => Some(cookies_txt(&content, url)).filter(|c| !c.is_empty()),
+            Err(e) => {
+                log::warn!("Could not read the cookie file {}: {}", cookie, e);
+                None
+            }
+        }
+    }
+
+    pub(crate) fn file_name(&self, url: &str) -> Option<String> {
+        let name = self.name_from_param(url).or_else(|| self.name_from_pattern(url))?;
+        let extension = self.file_name_extension.trim().trim_start_matches('.');
+        let name = if extension.is_empty() || name.contains('.') { name } else { format!("{}.{}", name, extension) };
+        Some(name).filter(|name| crate::is_valid_file_name(name))
+    }
+
+    fn name_from_param(&self, url: &str) -> Option<String> {
+        let param = self.file_name_param.trim();
+        if param.is_empty() { return None; }
+        Url::parse(url).ok()?.query_pairs()
+            .find(|(name, _)| name == param)
+            .map(|(_, value)| value.trim().to_string())
+            .filter(|value| !value.is_empty())
+    }
+
+    fn name_from_pattern(&self, url: &str) -> Option<String> {
+        let pattern = self.file_name_pattern.trim();
+        if pattern.is_empty() { return None; }
+        let captures = regex::Regex::new(pattern).ok()?.captures(url)?;
+        let found = captures.name("name").or_else(|| captures.get(1)).or_else(|| captures.get(0))?;
+        Some(percent_encoding::percent_decode_str(found.as_str()).decode_utf8_lossy().trim().to_string()).filter(|name| !name.is_empty())
+    }
+}
+
+// Whether `name` is only what's left when nothing better is known: the URL's last path segment, or the
+// `download_<timestamp>.tmp` placeholder
+pub(crate) fn is_guessed_name(name: &str, url: &str) -> bool {
+    (name.starts_with("download_") && name.ends_with(".tmp"))
+        || Url::parse(url).ok().as_ref().and_then(crate::file_name_from_url).is_some_and(|guess| guess == name)
+}
+
+// The name a template gives the file behind `urls`, the link as added first: a redirect to a CDN often
+// drops the query parameter that had the name
+pub(crate) fn file_name<'a>(templates: &[SiteTemplate], urls: impl IntoIterator<Item = &'a String>) -> Option<String> {
+    urls.into_iter().find_map(|url| find(templates, url)?.file_name(url))
+}
+
+// The template for `url`: the first whose host matches
+pub(crate) fn find<'a>(templates: &'a [SiteTemplate], url: &str) -> Option<&'a SiteTemplate> {
+    let url = Url::parse(url).ok()?;
+    let host = url.host_str()?.trim_end_matches('.').to_ascii_lowercase();
+    templates.iter().find(|t| t.host_pattern().is_some_and(|pattern| crate::host_rules::matches(&pattern, &host)))
+}
+
+pub(crate) fn validate(templates: &[SiteTemplate]) -> Result<(), String> {
+    for template in templates {
+        if template.host_pattern().is_none() { return Err("Every site template needs a host".to_string()); }
+        let pattern = template.file_name_pattern.trim();
+        if !pattern.is_empty() {
+            regex::Regex::new(pattern).map_err(|e| format!("Invalid file name pattern for {}: {}", template.host.trim(), e))?;
+        }
+    }
+    Ok(())
+}
+
+// Adds the matching template's headers the payload doesn't have, and its file name over a guessed one
+pub(crate) fn apply(templates: &[SiteTemplate], payload: &mut AddDownloadPayload) {
+    if is_guessed_name(&payload.file_name, &payload.url) {
+        if let Some(file_name) = file_name(templates, payload.redirect_chain.first().into_iter().chain([&payload.url])) {
+            payload.file_name = file_name;
+        }
+    }
+    let Some(template) = find(templates, &payload.url) else { return };
+    let Ok(url) = Url::parse(&payload.url) else { return };
+    for (name, value) in template.headers(&url) {
+        if !payload.headers.keys().any(|existing| existing.eq_ignore_ascii_case(&name)) { payload.headers.insert(name, value); }
+    }
+}
+
+// The `name=value; ...` header for `url` from a Netscape cookies.txt file: tab-separated domain,
+// subdomains flag, path, secure flag, expiry, name and value, one cookie a line
+fn cookies_txt(content: &str, url: &Url) -> String {
+    let host = url.host_str().unwrap_or_default().to_ascii_lowercase();
+    let now = chrono::Utc::now().timestamp();
+    content.lines()
+        // curl and browsers' exporters mark HttpOnly cookies this way; other `#` lines are comments
+        .map(|line| line.strip_prefix("#HttpOnly_").unwrap_or(line))
+        .filter(|line| !line.starts_with('#'))
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split('\t').collect();
+            let [domain, subdomains, path, secure, expires, name, value] = fields[..] else { return None };
+            let domain = domain.trim_start_matches('.').to_ascii_lowercase();
+            let domain_matches = host == domain
+                || (subdomains.eq_ignore_ascii_case("TRUE") && host.strip_suffix(&domain).is_some_and(|rest| rest.ends_with('.')));
+            let expires: i64 = expires.parse().unwrap_or(0);
+            (domain_matches && url.path().starts_with(path)
+                && (!secure.eq_ignore_ascii_case("TRUE") || url.scheme() == "https")
+                && (expires == 0 || expires > now))
+                .then(|| format!("{}={}", name, value))
+        })
+        .collect::<Vec<_>>()
+        .join("; ")
+}
@@ -0,0 +1,95 @@
+// Each download's speed over time, for a speed graph. Samples come from the progress aggregator and
+// are averaged into buckets at three resolutions, each a ring of fixed size: per second for the last
+// five minutes, per 10 seconds for the last hour and per 10 minutes for the last day. A query gets the
+// finest resolution that covers its window. Kept in memory only, and dropped with the task.
+
+use std::collections::{HashMap, VecDeque};
+
+use chrono::Utc;
+use serde::Serialize;
+
+// (bucket length in seconds, buckets kept)
+const TIERS: [(i64, usize); 3] = [(1, 300), (10, 360), (600, 144)];
+
+#[derive(Debug, Serialize, Clone, Copy)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct Sample {
+    // Unix seconds at the start of the bucket
+    at: i64,
+    bytes_per_second: u64,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct SpeedHistory {
+    step_seconds: i64,
+    // Oldest first; buckets without any sample (the download was paused) are missing
+    samples: Vec<Sample>,
+}
+
+struct Tier {
+    step: i64,
+    capacity: usize,
+    samples: VecDeque<Sample>,
+    // The bucket being filled: its start, and the sum and count of what went in so far
+    open: Option<(i64, u64, u64)>,
+}
+
+impl Tier {
+    fn new((step, capacity): (i64, usize)) -> Self {
+        Tier { step, capacity, samples: VecDeque::new(), open: None }
+    }
+
+    fn record(&mut self, now: i64, speed: u64) {
+        let bucket = now - now.rem_euclid(self.step);
+        match &mut self.open {
+            Some((start, sum, count)) if *start == bucket => {
+                *sum += speed;
+                *count += 1;
+            }
+            open => {
+                if let Some((start, sum, count)) = open.replace((bucket, speed, 1)) {
+                    if self.samples.len() == self.capacity { self.samples.pop_front(); }
+                    self.samples.push_back(Sample { at: start, bytes_per_second: sum / count });
+                }
+            }
+        }
+    }
+
+    fn since(&self, from: i64) -> Vec<Sample> {
+        let open = self.open.map(|(at, sum, count)| Sample { at, bytes_per_second: sum / count });
+        self.samples.iter().copied().chain(open).filter(|s| s.at >= from).collect()
+    }
+}
+
+#[derive(Default)]
+pub(crate) struct SpeedHistories {
+    tasks: std::sync::Mutex<HashMap<String, Vec<Tier>>>,
+}
+
+impl SpeedHistories {
+    pub(crate) fn record<'a>(&self, speeds: impl IntoIterator<Item = (&'a str, u64)>) {
+        let now = Utc::now().timestamp();
+        let mut tasks = self.tasks.lock().unwrap();
+        for (id, speed) in speeds {
+            let tiers = tasks.entry(id.to_string()).or_insert_with(|| TIERS.into_iter().map(Tier::new).collect());
+            for tier in tiers { tier.record(now, speed); }
+        }
+    }
+
+    // The last `window_seconds` at the finest resolution that reaches back that far
+    pub(crate) fn get(&self, id: &str, window_seconds: u64) -> SpeedHistory {
+        let tasks = self.tasks.lock().unwrap();
+        let window = window_seconds as i64;
+        let (step, _) = TIERS.iter().copied().find(|(step, capacity)| step * *capacity as i64 >= window).unwrap_or(TIERS[TIERS.len() - 1]);
+        let samples = tasks.get(id)
+            .and_then(|tiers| tiers.iter().find(|t| t.step == step))
+            .map(|tier| tier.since(Utc::now().timestamp() - window))
+            .unwrap_or_default();
+        SpeedHistory { step_seconds: step, samples }
+    }
+
+    pub(crate) fn forget(&self, ids: &[String]) {
+        self.tasks.lock().unwrap().retain(|id, _| !ids.contains(id));
+    }
+}
@@ -0,0 +1,73 @@
+// One switch for how hard downloads may use the connection: unlimited, capped at
+// `background_speed_limit_kbps` across all downloads, or everything paused. Meant for a quick change
+// from the tray or a hotkey when the bandwidth is needed elsewhere, so it isn't saved: the app always
+// starts unlimited, and downloads paused by the switch resume when leaving the paused mode or at the
+// next launch.
+
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Emitter, Manager, State};
+
+use crate::metered::SpeedLimit;
+use crate::{AppSettings, AppState, DownloadStatus, DownloadTask};
+
+const PAUSED_MESSAGE: &str = "Paused by the speed switch";
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "camelCase")]
+pub(crate) enum SpeedMode {
+    #[default]
+    Unlimited,
+    Background,
+    Paused,
+}
+
+#[derive(Default)]
+pub(crate) struct SpeedSwitch {
+    mode: std::sync::Mutex<SpeedMode>,
+    // Applied to every download, on top of the metered-connection cap
+    pub(crate) limit: Arc<SpeedLimit>,
+}
+
+impl SpeedSwitch {
+    pub(crate) fn mode(&self) -> SpeedMode {
+        *self.mode.lock().unwrap()
+    }
+
+    // Puts the cap in line with the mode and the current setting
+    pub(crate) fn apply_limit(&self, settings: &AppSettings) {
+        let background = self.mode() == SpeedMode::Background;
+        self.limit.set(if background { settings.background_speed_limit_kbps * 1024 } else { 0 });
+    }
+}
+
+// Marks a task held by the paused mode instead of starting it; the caller has the lock
+pub(crate) fn hold(task: &mut DownloadTask) {
+    task.status = DownloadStatus::Paused;
+    task.speed = 0;
+    task.error_message = Some(PAUSED_MESSAGE.to_string());
+}
+
+pub(crate) async fn set(app_handle: &AppHandle, mode: SpeedMode) {
+    let state: State<AppState> = app_handle.state();
+    let previous = std::mem::replace(&mut *state.speed_mode.mode.lock().unwrap(), mode);
+    state.speed_mode.apply_limit(&state.persistent.lock().await.settings);
+    if previous == mode { return; }
+    log::info!("Speed mode {:?}", mode);
+    app_handle.emit("speed_mode_changed", mode).unwrap();
+    if mode == SpeedMode::Paused {
+        crate::pause_running(app_handle, PAUSED_MESSAGE).await;
+    } else if previous == SpeedMode::Paused {
+        resume_held(app_handle).await;
+    }
+}
+
+// Everything the paused mode stopped or held back, including downloads started while it was on
+pub(crate) async fn resume_held(app_handle: &AppHandle) {
+    let ids: Vec<String> = app_handle.state::<AppState>().persistent.lock().await.downloads.iter()
+        .filter(|t| t.status == DownloadStatus::Paused && t.error_message.as_deref() == Some(PAUSED_MESSAGE))
+        .map(|t| t.id.clone())
+        .collect();
+    crate::resume_paused(app_handle, ids, PAUSED_MESSAGE).await;
+}
@@ -0,0 +1,186 @@
+// Feed subscriptions: RSS and Atom feeds polled on an interval, with new enclosures (podcast
+// episodes, release files) queued into the subscription's folder under a file-name template.
+
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Emitter, Manager, State};
+use tokio::time::Duration;
+use url::Url;
+
+use crate::{AddDownloadPayload, AppState};
+
+// How often due subscriptions are looked for; each one has its own interval on top
+const POLL_TICK: Duration = Duration::from_secs(60);
+// Enclosure ids remembered per feed; feeds only list their latest items, so old ids can go
+const MAX_SEEN: usize = 500;
+pub(crate) const DEFAULT_TEMPLATE: &str = "{date} - {title}.{ext}";
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct Subscription {
+    pub(crate) id: String,
+    pub(crate) feed_url: String,
+    // Taken from the feed on the first check unless set
+    #[serde(default)]
+    pub(crate) title: String,
+    // Empty means the default download folder
+    pub(crate) folder: String,
+    // `{feed}`, `{title}`, `{date}` (YYYY-MM-DD), `{filename}` (from the URL) and `{ext}`
+    pub(crate) filename_template: String,
+    pub(crate) interval_minutes: u32,
+    // Episodes queued on the first check; older ones are only marked as seen
+    #[serde(default)]
+    pub(crate) initial_items: usize,
+    #[serde(default = "enabled_by_default")]
+    pub(crate) enabled: bool,
+    #[serde(default)]
+    pub(crate) last_checked: Option<DateTime<Local>>,
+    #[serde(default)]
+    pub(crate) error: Option<String>,
+    // Guids (or enclosure URLs) already queued or skipped
+    #[serde(default)]
+    pub(crate) seen: Vec<String>,
+}
+
+fn enabled_by_default() -> bool { true }
+
+#[derive(Debug)]
+struct FeedItem { id: String, url: String, title: String, date: Option<DateTime<Local>>, size: Option<u64> }
+
+pub(crate) async fn run_subscriptions(app_handle: AppHandle) {
+    loop {
+        tokio::time::sleep(POLL_TICK).await;
+        let due: Vec<String> = {
+            let state: State<AppState> = app_handle.state();
+            let state_guard = state.persistent.lock().await;
+            state_guard.subscriptions.iter()
+                .filter(|s| s.enabled && s.last_checked.is_none_or(|at| {
+                    Local::now() - at >= chrono::Duration::minutes(s.interval_minutes.max(1) as i64)
+                }))
+                .map(|s| s.id.clone())
+                .collect()
+        };
+        for id in due {
+            if let Err(e) = check(&app_handle, &id).await {
+                log::warn!("Feed check failed for {}: {}", id, e);
+            }
+        }
+    }
+}
+
+// Fetches the feed and queues enclosures not seen before; returns how many were queued
+pub(crate) async fn check(app_handle: &AppHandle, id: &str) -> Result<usize, String> {
+    let state: State<AppState> = app_handle.state();
+    let subscription = state.persistent.lock().await.subscriptions.iter().find(|s| s.id == id).cloned()
+        .ok_or("Subscription not found")?;
+    let fetched = fetch_feed(&crate::http_client(&state).await, &subscription.feed_url).await;
+
+    let (payloads, updated) = {
+        let mut state_guard = state.persistent.lock().await;
+        let Some(subscription) = state_guard.subscriptions.iter_mut().find(|s| s.id == id) else { return Ok(0) };
+        subscription.last_checked = Some(Local::now());
+        let payloads = match fetched {
+            Ok((feed_title, items)) => {
+                subscription.error = None;
+                if subscription.title.is_empty() { subscription.title = feed_title; }
+                new_payloads(subscription, items)
+            }
+            Err(e) => {
+                subscription.error = Some(e);
+                Vec::new()
+            }
+        };
+        (payloads, subscription.clone())
+    };
+    crate::request_save(&state);
+    app_handle.emit("subscription_updated", &updated).unwrap();
+    if let Some(e) = updated.error { return Err(e); }
+    let count = payloads.len();
+    if count > 0 { crate::add_downloads(payloads, state, app_handle.clone()).await?; }
+    Ok(count)
+}
+
+fn new_payloads(subscription: &mut Subscription, mut items: Vec<FeedItem>) -> Vec<AddDownloadPayload> {
+    // Newest first, so the first check can keep just the latest few
+    items.sort_by_key(|item| std::cmp::Reverse(item.date));
+    let first_check = subscription.seen.is_empty();
+    let mut payloads = Vec::new();
+    for (index, item) in items.into_iter().enumerate() {
+        if subscription.seen.contains(&item.id) { continue; }
+        subscription.seen.push(item.id.clone());
+        if first_check && index >= subscription.initial_items { continue; }
+        payloads.push(AddDownloadPayload {
+            file_name: render_template(subscription, &item),
+            url: item.url, total_size: item.size, custom_path: Some(subscription.folder.clone()).filter(|f| !f.is_empty()),
+            checksum: None, headers: BTreeMap::new(), redirect_chain: Vec::new(), parent_id: None, signature: None, allow_http: false, proxy: None, request: None, source_url: None,
+        });
+    }
+    let overflow = subscription.seen.len().saturating_sub(MAX_SEEN);
+    subscription.seen.drain(..overflow);
+    // Oldest first in the queue, matching the order they were published
+    payloads.reverse();
+    payloads
+}
+
+fn render_template(subscription: &Subscription, item: &FeedItem) -> String {
+    let url_name = Url::parse(&item.url).ok().as_ref().and_then(crate::file_name_from_url).unwrap_or_else(|| "episode".to_string());
+    let (stem, ext) = url_name.rsplit_once('.').map_or((url_name.as_str(), "bin"), |(stem, ext)| (stem, ext));
+    let template = if subscription.filename_template.trim().is_empty() { DEFAULT_TEMPLATE } else { subscription.filename_template.as_str() };
+    let name = template
+        .replace("{feed}", &subscription.title)
+        .replace("{title}", &item.title)
+        .replace("{date}", &item.date.map(|d| d.format("%Y-%m-%d").to_string()).unwrap_or_default())
+        .replace("{filename}", stem)
+        .replace("{ext}", ext);
+    // Titles routinely contain `/`, `:` or `?`
+    let name: String = name.chars()
+        .map(|c| if c.is_control() || matches!(c, '/' | '\\' | '<' | '>' | ':' | '"' | '|' | '?' | '*') { '_' } else { c })
+        .collect();
+    let name = name.trim().trim_matches('.').to_string();
+    if crate::is_valid_file_name(&name) { name } else { url_name }
+}
+
+async fn fetch_feed(client: &reqwest::Client, feed_url: &str) -> Result<(String, Vec<FeedItem>), String> {
+    let response = client.get(feed_url).timeout(Duration::from_secs(30)).send().await
+        .map_err(|e| format!("Request failed: {}", e))?;
+    if !response.status().is_success() {
+        return Err(format!("Server returned error: {}", response.status()));
+    }
+    let body = response.text().await.map_err(|e| format!("Request failed: {}", e))?;
+    parse_feed(&body, feed_url)
+}
+
+// RSS 2.0 `<item><enclosure url length>` and Atom `<entry><link rel="enclosure" href length>`
+fn parse_feed(xml: &str, feed_url: &str) -> Result<(String, Vec<FeedItem>), String> {
+    let document = roxmltree::Document::parse(xml).map_err(|e| format!("Invalid feed: {}", e))?;
+    let base = Url::parse(feed_url).ok();
+    let resolve = |href: &str| base.as_ref().and_then(|b| b.join(href.trim()).ok()).map(|u| u.to_string());
+    let child_text = |node: roxmltree::Node, tag: &str| {
+        node.children().find(|c| c.tag_name().name() == tag).and_then(|c| c.text()).map(|t| t.trim().to_string())
+    };
+    let root = document.root_element();
+    let channel = if root.tag_name().name() == "rss" { root.children().find(|c| c.has_tag_name("channel")) } else { Some(root) };
+    let title = channel.and_then(|c| child_text(c, "title")).unwrap_or_default();
+
+    let mut items = Vec::new();
+    for node in root.descendants().filter(|n| matches!(n.tag_name().name(), "item" | "entry")) {
+        let enclosure = node.children().find(|c| {
+            c.tag_name().name() == "enclosure" || (c.tag_name().name() == "link" && c.attribute("rel") == Some("enclosure"))
+        });
+        let Some(enclosure) = enclosure else { continue };
+        let Some(url) = enclosure.attribute("url").or_else(|| enclosure.attribute("href")).and_then(resolve) else { continue };
+        let date = ["pubDate", "published", "updated"].iter().find_map(|tag| child_text(node, tag)).and_then(|d| {
+            DateTime::parse_from_rfc2822(&d).or_else(|_| DateTime::parse_from_rfc3339(&d)).ok()
+        });
+        items.push(FeedItem {
+            id: child_text(node, "guid").or_else(|| child_text(node, "id")).unwrap_or_else(|| url.clone()),
+            title: child_text(node, "title").unwrap_or_default(),
+            date: date.map(|d| d.with_timezone(&Local)),
+            size: enclosure.attribute("length").and_then(|l| l.parse().ok()).filter(|&l| l > 0),
+            url,
+        });
+    }
+    Ok((title, items))
+}
@@ -0,0 +1,170 @@
+// Subtitles fetched next to finished video downloads from OpenSubtitles (REST API v1). Searches use the
+// file's OpenSubtitles hash, which matches the exact release, plus its name; `<video>.<lang>.srt` is
+// written for each configured language that has a match. Downloads need a user login beyond the
+// anonymous quota, taken from a Premium Account for opensubtitles.com when one is set up.
+
+use std::path::{Path, PathBuf};
+
+use reqwest::Client;
+use serde::Deserialize;
+use tauri::{AppHandle, Manager, State};
+use tokio::io::{AsyncReadExt, AsyncSeekExt};
+use url::Url;
+
+use crate::{accounts, AppState, DownloadTask};
+
+const API: &str = "https://api.opensubtitles.com/api/v1";
+// The API rejects requests without an application user agent
+const API_USER_AGENT: &str = concat!("velodown v", env!("CARGO_PKG_VERSION"));
+const HASH_CHUNK: u64 = 64 * 1024;
+
+#[derive(Deserialize)]
+struct SearchResponse { data: Vec<SearchResult> }
+
+#[derive(Deserialize)]
+struct SearchResult { attributes: SubtitleAttributes }
+
+#[derive(Deserialize)]
+struct SubtitleAttributes {
+    language: Option<String>,
+    #[serde(default)]
+    download_count: u64,
+    #[serde(default)]
+    moviehash_match: bool,
+    files: Vec<SubtitleFile>,
+}
+
+#[derive(Deserialize)]
+struct SubtitleFile { file_id: u64 }
+
+#[derive(Deserialize)]
+struct LoginResponse { token: String }
+
+#[derive(Deserialize)]
+struct DownloadResponse { link: String }
+
+// Runs after a video finishes; failures are only logged, the download itself is done
+pub(crate) async fn fetch_for_task(app_handle: AppHandle, task: DownloadTask) {
+    let settings = {
+        let state: State<AppState> = app_handle.state();
+        let state_guard = state.persistent.lock().await;
+        state_guard.settings.clone()
+    };
+    let api_key = settings.opensubtitles_api_key.trim().to_string();
+    if !settings.subtitles_enabled || api_key.is_empty() { return; }
+    let languages: Vec<String> = settings.subtitle_languages.split(',')
+        .map(|l| l.trim().to_ascii_lowercase())
+        .filter(|l| !l.is_empty())
+        .collect();
+    let video = PathBuf::from(&task.save_path).join(&task.file_name);
+    match fetch_subtitles(&app_handle, &video, &api_key, &languages).await {
+        Ok(written) if written.is_empty() => log::info!("No subtitles found for {}", task.file_name),
+        Ok(written) => log::info!("Saved subtitles for {}: {}", task.file_name, written.join(", ")),
+        Err(e) => log::warn!("Subtitle lookup for {} failed: {}", task.file_name, e),
+    }
+}
+
+async fn fetch_subtitles(app_handle: &AppHandle, video: &Path, api_key: &str, languages: &[String]) -> Result<Vec<String>, String> {
+    let state: State<AppState> = app_handle.state();
+    let client = crate::http_client(&state).await;
+    let hash = movie_hash(video).await.map_err(|e| format!("Could not hash {}: {}", video.display(), e))?;
+    let stem = video.file_stem().map(|s| s.to_string_lossy().to_string()).unwrap_or_default();
+
+    // Parameters sorted and lowercase, as the API asks, so responses come from its cache
+    let mut search = Url::parse(&format!("{}/subtitles", API)).map_err(|e| e.to_string())?;
+    {
+        let mut query = search.query_pairs_mut();
+        if !languages.is_empty() { query.append_pair("languages", &languages.join(",")); }
+        if let Some(hash) = &hash { query.append_pair("moviehash", hash); }
+        query.append_pair("query", &stem.to_lowercase());
+    }
+    let response = api_request(client.get(search), api_key).send().await.map_err(|e| format!("Request failed: {}", e))?;
+    if !response.status().is_success() {
+        return Err(format!("OpenSubtitles returned error: {}", response.status()));
+    }
+    let body = response.bytes().await.map_err(|e| format!("Request failed: {}", e))?;
+    let results: SearchResponse = serde_json::from_slice(&body).map_err(|e| format!("Unexpected OpenSubtitles response: {}", e))?;
+
+    let mut token = None;
+    let mut written = Vec::new();
+    let wanted: Vec<Option<&str>> = if languages.is_empty() { vec![None] } else { languages.iter().map(|l| Some(l.as_str())).collect() };
+    for language in wanted {
+        // Hash matches are the same release, so their timing fits; otherwise the most downloaded
+        let best = results.data.iter()
+            .map(|r| &r.attributes)
+            .filter(|a| language.is_none_or(|l| a.language.as_deref().is_some_and(|al| al.eq_ignore_ascii_case(l))))
+            .filter(|a| !a.files.is_empty())
+            .max_by_key(|a| (a.moviehash_match, a.download_count));
+        let Some(best) = best else { continue };
+        let target = video.with_file_name(format!("{}.{}.srt", stem, best.language.as_deref().or(language).unwrap_or("und")));
+        if tokio::fs::try_exists(&target).await.unwrap_or(false) { continue; }
+        if token.is_none() { token = Some(login(app_handle, &client, api_key).await); }
+        let link = download_link(&client, api_key, token.clone().flatten().as_deref(), best.files[0].file_id).await?;
+        let response = client.get(&link).send().await.map_err(|e| format!("Request failed: {}", e))?;
+        if !response.status().is_success() {
+            return Err(format!("Subtitle download returned error: {}", response.status()));
+        }
+        let subtitle = response.bytes().await.map_err(|e| format!("Request failed: {}", e))?;
+        tokio::fs::write(&target, &subtitle).await.map_err(|e| format!("Could not write {}: {}", target.display(), e))?;
+        written.push(target.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default());
+    }
+    Ok(written)
+}
+
+fn api_request(request: reqwest::RequestBuilder, api_key: &str) -> reqwest::RequestBuilder {
+    request.header("Api-Key", api_key).header(reqwest::header::USER_AGENT, API_USER_AGENT).header(reqwest::header::ACCEPT, "application/json")
+}
+
+fn json_body(request: reqwest::RequestBuilder, body: &serde_json::Value) -> reqwest::RequestBuilder {
+    request.header(reqwest::header::CONTENT_TYPE, "application/json").body(body.to_string())
+}
+
+// A session token from the opensubtitles.com account, if one is configured and logs in
+async fn login(app_handle: &AppHandle, client: &Client, api_key: &str) -> Option<String> {
+    let state: State<AppState> = app_handle.state();
+    let account = state.persistent.lock().await.accounts.iter()
+        .find(|a| a.usable() && a.host.trim_start_matches("www.").trim_start_matches("api.") == "opensubtitles.com")
+        .cloned()?;
+    let password = accounts::load_secret(&account.id).await.map_err(|e| log::warn!("OpenSubtitles login skipped: {}", e)).ok()?;
+    let body = serde_json::json!({ "username": account.username, "password": password });
+    let response = json_body(api_request(client.post(format!("{}/login", API)), api_key), &body).send().await.ok()?;
+    if !response.status().is_success() {
+        log::warn!("OpenSubtitles login for {} failed: {}", account.username, response.status());
+        return None;
+    }
+    let login: LoginResponse = serde_json::from_slice(&response.bytes().await.ok()?).ok()?;
+    Some(login.token)
+}
+
+async fn download_link(client: &Client, api_key: &str, token: Option<&str>, file_id: u64) -> Result<String, String> {
+    let mut request = api_request(client.post(format!("{}/download", API)), api_key);
+    if let Some(token) = token { request = request.bearer_auth(token); }
+    let response = json_body(request, &serde_json::json!({ "file_id": file_id })).send().await
+        .map_err(|e| format!("Request failed: {}", e))?;
+    match response.status().as_u16() {
+        200 => {}
+        401 | 406 => return Err("OpenSubtitles download quota reached; add an opensubtitles.com account for more".to_string()),
+        _ => return Err(format!("OpenSubtitles returned error: {}", response.status())),
+    }
+    let body = response.bytes().await.map_err(|e| format!("Request failed: {}", e))?;
+    let download: DownloadResponse = serde_json::from_slice(&body).map_err(|e| format!("Unexpected OpenSubtitles response: {}", e))?;
+    Ok(download.link)
+}
+
+// The OpenSubtitles hash: the file size plus the 64-bit little-endian words of its first and last
+// 64 KiB, wrapping. `None` for files too small to have one.
+async fn movie_hash(path: &Path) -> std::io::Result<Option<String>> {
+    let mut file = tokio::fs::File::open(path).await?;
+    let size = file.metadata().await?.len();
+    if size < HASH_CHUNK * 2 { return Ok(None); }
+    let mut hash = size;
+    let mut chunk = vec![0u8; HASH_CHUNK as usize];
+    for offset in [0, size - HASH_CHUNK] {
+        file.seek(std::io::SeekFrom::Start(offset)).await?;
+        file.read_exact(&mut chunk).await?;
+        hash = chunk.chunks_exact(8).fold(hash, |sum, word| {
+            sum.wrapping_add(u64::from_le_bytes(word.try_into().expect("chunks of 8")))
+        });
+    }
+    Ok(Some(format!("{:016x}", hash)))
+}
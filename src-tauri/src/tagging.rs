@@ -0,0 +1,277 @@
+// Audio post-processing: fills in missing tags on finished audio downloads and optionally files them
+// into `Artist/Album` folders. Tags already in the file win; gaps are filled from the user's
+// file-name pattern, then from the Open Graph music metadata of the page the file came from (its
+// Referer). MP3s get ID3v2.4 and FLACs Vorbis comments; other formats are only moved.
+
+use std::path::{Path, PathBuf};
+
+use id3::TagLike;
+use tauri::{AppHandle, Emitter, Manager, State};
+use tokio::time::Duration;
+
+use crate::{AppState, DownloadTask};
+
+const FLAC_MAGIC: &[u8] = b"fLaC";
+const FLAC_VORBIS_COMMENT: u8 = 4;
+
+// (block type, body) pairs of a FLAC file's metadata
+type FlacBlocks<'a> = Vec<(u8, &'a [u8])>;
+
+#[derive(Debug, Default, Clone, PartialEq)]
+struct AudioTags {
+    artist: Option<String>,
+    album: Option<String>,
+    title: Option<String>,
+    track: Option<u32>,
+    year: Option<i32>,
+    genre: Option<String>,
+}
+
+impl AudioTags {
+    fn fill_from(&mut self, other: &AudioTags) {
+        self.artist = self.artist.take().or_else(|| other.artist.clone());
+        self.album = self.album.take().or_else(|| other.album.clone());
+        self.title = self.title.take().or_else(|| other.title.clone());
+        self.track = self.track.or(other.track);
+        self.year = self.year.or(other.year);
+        self.genre = self.genre.take().or_else(|| other.genre.clone());
+    }
+}
+
+pub(crate) async fn process_task(app_handle: AppHandle, task: DownloadTask) {
+    let settings = app_handle.state::<AppState>().persistent.lock().await.settings.clone();
+    if !settings.audio_tagging_enabled { return; }
+    let path = PathBuf::from(&task.save_path).join(&task.file_name);
+    let extension = path.extension().map(|e| e.to_string_lossy().to_ascii_lowercase()).unwrap_or_default();
+
+    let existing = {
+        let (path, extension) = (path.clone(), extension.clone());
+        tokio::task::spawn_blocking(move || read_tags(&path, &extension)).await.unwrap_or_default()
+    };
+    let mut tags = existing.clone();
+    let stem = path.file_stem().map(|s| s.to_string_lossy().to_string()).unwrap_or_default();
+    if let Some(from_name) = tags_from_file_name(&settings.audio_filename_pattern, &stem) { tags.fill_from(&from_name); }
+    if let Some(referer) = task.headers.iter().find(|(k, _)| k.eq_ignore_ascii_case("referer")).map(|(_, v)| v.clone()) {
+        tags.fill_from(&page_tags(&app_handle, &referer).await);
+    }
+
+    if matches!(extension.as_str(), "mp3" | "flac") && tags != existing {
+        let (path, extension, updated) = (path.clone(), extension.clone(), tags.clone());
+        let written = tokio::task::spawn_blocking(move || write_tags(&path, &extension, &updated)).await
+            .map_err(|e| e.to_string())
+            .and_then(|r| r);
+        if let Err(e) = written { log::warn!("Could not tag {}: {}", task.file_name, e); }
+    }
+    if settings.audio_organize {
+        if let Err(e) = organize(&app_handle, &task, &tags).await { log::warn!("Could not move {}: {}", task.file_name, e); }
+    }
+}
+
+// `{artist} - {album} - {track} - {title}`-style patterns matched against the whole file stem;
+// anything between the placeholders must appear literally
+fn tags_from_file_name(pattern: &str, stem: &str) -> Option<AudioTags> {
+    let pattern = pattern.trim();
+    if pattern.is_empty() { return None; }
+    let mut regex = String::from("^");
+    let mut rest = pattern;
+    while let Some(open) = rest.find('{') {
+        regex.push_str(&regex::escape(&rest[..open]));
+        let close = rest[open..].find('}')? + open;
+        let field = &rest[open + 1..close];
+        if !matches!(field, "artist" | "album" | "title" | "track" | "year" | "genre") { return None; }
+        regex.push_str(&format!("(?P<{}>.+?)", field));
+        rest = &rest[close + 1..];
+    }
+    regex.push_str(&regex::escape(rest));
+    regex.push('$');
+    let captures = regex::Regex::new(&regex).ok()?.captures(stem)?;
+    let text = |name: &str| captures.name(name).map(|m| m.as_str().trim().to_string()).filter(|s| !s.is_empty());
+    Some(AudioTags {
+        artist: text("artist"), album: text("album"), title: text("title"), genre: text("genre"),
+        track: text("track").and_then(|t| t.split('/').next()?.trim().parse().ok()),
+        year: text("year").and_then(|y| y.parse().ok()),
+    })
+}
+
+// Open Graph music properties (`music:musician`, `og:audio:artist`, ...) of the source page
+async fn page_tags(app_handle: &AppHandle, page_url: &str) -> AudioTags {
+    let client = crate::http_client(&app_handle.state()).await;
+    let response = match client.get(page_url).header("Accept", "text/html").timeout(Duration::from_secs(20)).send().await {
+        Ok(response) if response.status().is_success() => response,
+        _ => return AudioTags::default(),
+    };
+    let html = crate::read_capped_body(response, crate::MAX_SCAN_PAGE_BYTES).await;
+    parse_page_tags(&html)
+}
+
+// Kept free of awaits: kuchikiki's DOM isn't `Send`
+fn parse_page_tags(html: &str) -> AudioTags {
+    use kuchikiki::traits::TendrilSink;
+    let document = kuchikiki::parse_html().one(html);
+    let meta = |names: &[&str]| -> Option<String> {
+        document.select("meta[content]").ok()?.find_map(|m| {
+            let attributes = m.attributes.borrow();
+            let key = attributes.get("property").or_else(|| attributes.get("name"))?;
+            names.contains(&key).then(|| attributes.get("content").unwrap_or_default().trim().to_string()).filter(|v| !v.is_empty())
+        })
+    };
+    AudioTags {
+        artist: meta(&["og:audio:artist", "music:musician", "music:creator"]),
+        album: meta(&["og:audio:album", "music:album"]),
+        title: meta(&["og:audio:title", "og:title"]),
+        track: meta(&["music:album:track"]).and_then(|t| t.parse().ok()),
+        year: meta(&["music:release_date"]).and_then(|d| d.get(..4)?.parse().ok()),
+        genre: None,
+    }
+}
+
+fn read_tags(path: &Path, extension: &str) -> AudioTags {
+    match extension {
+        "mp3" => match id3::Tag::read_from_path(path) {
+            Ok(tag) => AudioTags {
+                artist: tag.artist().map(str::to_string), album: tag.album().map(str::to_string),
+                title: tag.title().map(str::to_string), track: tag.track(), year: tag.year(), genre: tag.genre().map(str::to_string),
+            },
+            Err(_) => AudioTags::default(),
+        },
+        "flac" => {
+            let comments = std::fs::read(path).ok().and_then(|data| {
+                let (blocks, _) = flac_blocks(&data)?;
+                blocks.iter().find(|(kind, _)| *kind == FLAC_VORBIS_COMMENT).and_then(|(_, body)| parse_vorbis_comment(body))
+            });
+            let Some((_, comments)) = comments else { return AudioTags::default() };
+            let get = |key: &str| comments.iter().find(|(k, _)| k.eq_ignore_ascii_case(key)).map(|(_, v)| v.clone());
+            AudioTags {
+                artist: get("ARTIST"), album: get("ALBUM"), title: get("TITLE"), genre: get("GENRE"),
+                track: get("TRACKNUMBER").and_then(|t| t.split('/').next()?.parse().ok()),
+                year: get("DATE").and_then(|d| d.get(..4)?.parse().ok()),
+            }
+        }
+        _ => AudioTags::default(),
+    }
+}
+
+fn write_tags(path: &Path, extension: &str, tags: &AudioTags) -> Result<(), String> {
+    match extension {
+        "mp3" => {
+            let mut tag = id3::no_tag_ok(id3::Tag::read_from_path(path)).map_err(|e| e.to_string())?.unwrap_or_default();
+            if let Some(artist) = &tags.artist { tag.set_artist(artist.as_str()); }
+            if let Some(album) = &tags.album { tag.set_album(album.as_str()); }
+            if let Some(title) = &tags.title { tag.set_title(title.as_str()); }
+            if let Some(genre) = &tags.genre { tag.set_genre(genre.as_str()); }
+            if let Some(track) = tags.track { tag.set_track(track); }
+            if let Some(year) = tags.year { tag.set_year(year); }
+            tag.write_to_path(path, id3::Version::Id3v24).map_err(|e| e.to_string())
+        }
+        "flac" => write_flac_comments(path, tags),
+        _ => Ok(()),
+    }
+}
+
+// The metadata blocks (type, body) and the offset where the audio frames start
+fn flac_blocks(data: &[u8]) -> Option<(FlacBlocks<'_>, usize)> {
+    if !data.starts_with(FLAC_MAGIC) { return None; }
+    let mut blocks = Vec::new();
+    let mut pos = FLAC_MAGIC.len();
+    loop {
+        let header = data.get(pos..pos + 4)?;
+        let length = u32::from_be_bytes([0, header[1], header[2], header[3]]) as usize;
+        blocks.push((header[0] & 0x7f, data.get(pos + 4..pos + 4 + length)?));
+        pos += 4 + length;
+        if header[0] & 0x80 != 0 { return Some((blocks, pos)); }
+    }
+}
+
+// Vendor string and `KEY=value` pairs; all lengths are little-endian, unlike the block headers
+fn parse_vorbis_comment(body: &[u8]) -> Option<(String, Vec<(String, String)>)> {
+    let read_u32 = |pos: usize| body.get(pos..pos + 4).map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]) as usize);
+    let vendor_len = read_u32(0)?;
+    let vendor = String::from_utf8_lossy(body.get(4..4 + vendor_len)?).to_string();
+    let mut pos = 4 + vendor_len;
+    let count = read_u32(pos)?;
+    pos += 4;
+    let mut comments = Vec::with_capacity(count.min(1024));
+    for _ in 0..count {
+        let len = read_u32(pos)?;
+        let comment = String::from_utf8_lossy(body.get(pos + 4..pos + 4 + len)?).to_string();
+        pos += 4 + len;
+        if let Some((key, value)) = comment.split_once('=') { comments.push((key.to_string(), value.to_string())); }
+    }
+    Some((vendor, comments))
+}
+
+// Rewrites the file with the tag fields replaced in (or added as) its VORBIS_COMMENT block
+fn write_flac_comments(path: &Path, tags: &AudioTags) -> Result<(), String> {
+    let data = std::fs::read(path).map_err(|e| e.to_string())?;
+    let (blocks, audio_start) = flac_blocks(&data).ok_or("Not a FLAC file")?;
+    let (vendor, mut comments) = blocks.iter().find(|(kind, _)| *kind == FLAC_VORBIS_COMMENT)
+        .and_then(|(_, body)| parse_vorbis_comment(body))
+        .unwrap_or_else(|| ("velodown".to_string(), Vec::new()));
+    let fields = [
+        ("ARTIST", tags.artist.clone()), ("ALBUM", tags.album.clone()), ("TITLE", tags.title.clone()),
+        ("GENRE", tags.genre.clone()), ("TRACKNUMBER", tags.track.map(|t| t.to_string())), ("DATE", tags.year.map(|y| y.to_string())),
+    ];
+    for (key, value) in fields {
+        let Some(value) = value else { continue };
+        comments.retain(|(k, _)| !k.eq_ignore_ascii_case(key));
+        comments.push((key.to_string(), value));
+    }
+    let mut comment_block = Vec::new();
+    comment_block.extend((vendor.len() as u32).to_le_bytes());
+    comment_block.extend(vendor.as_bytes());
+    comment_block.extend((comments.len() as u32).to_le_bytes());
+    for (key, value) in &comments {
+        let comment = format!("{}={}", key, value);
+        comment_block.extend((comment.len() as u32).to_le_bytes());
+        comment_block.extend(comment.as_bytes());
+    }
+
+    // STREAMINFO must stay first; the new comment block goes right after it
+    let mut new_blocks: FlacBlocks = blocks.into_iter().filter(|(kind, _)| *kind != FLAC_VORBIS_COMMENT).collect();
+    new_blocks.insert(1.min(new_blocks.len()), (FLAC_VORBIS_COMMENT, &comment_block));
+    let mut output = Vec::with_capacity(data.len() + comment_block.len());
+    output.extend(FLAC_MAGIC);
+    for (index, (kind, body)) in new_blocks.iter().enumerate() {
+        let last = if index + 1 == new_blocks.len() { 0x80 } else { 0 };
+        let length = (body.len() as u32).to_be_bytes();
+        output.extend([kind | last, length[1], length[2], length[3]]);
+        output.extend(*body);
+    }
+    output.extend(&data[audio_start..]);
+
+    // Written aside and renamed over, so a failure never leaves a truncated file
+    let temp = path.with_extension("flac.tagging");
+    std::fs::write(&temp, &output).and_then(|_| std::fs::rename(&temp, path)).map_err(|e| {
+        let _ = std::fs::remove_file(&temp);
+        e.to_string()
+    })
+}
+
+// Moves the file to `<save folder>/<Artist>/<Album>/` and points the task at it
+async fn organize(app_handle: &AppHandle, task: &DownloadTask, tags: &AudioTags) -> Result<(), String> {
+    let Some(artist) = tags.artist.as_deref().map(folder_name).filter(|a| !a.is_empty()) else { return Ok(()) };
+    let album = tags.album.as_deref().map(folder_name).filter(|a| !a.is_empty()).unwrap_or_else(|| "Unknown Album".to_string());
+    let folder = PathBuf::from(&task.save_path).join(artist).join(album);
+    let target = folder.join(&task.file_name);
+    if tokio::fs::try_exists(&target).await.unwrap_or(false) { return Err(format!("{} already exists", target.display())); }
+    tokio::fs::create_dir_all(&folder).await.map_err(|e| e.to_string())?;
+    tokio::fs::rename(PathBuf::from(&task.save_path).join(&task.file_name), &target).await.map_err(|e| e.to_string())?;
+
+    let state: State<AppState> = app_handle.state();
+    {
+        let mut state_guard = state.persistent.lock().await;
+        if let Some(moved) = state_guard.downloads.iter_mut().find(|t| t.id == task.id) {
+            moved.save_path = folder.to_string_lossy().to_string();
+            app_handle.emit("task_updated", &*moved).unwrap();
+        }
+    }
+    crate::request_save(&state);
+    Ok(())
+}
+
+fn folder_name(name: &str) -> String {
+    let name: String = name.chars()
+        .map(|c| if c.is_control() || matches!(c, '/' | '\\' | '<' | '>' | ':' | '"' | '|' | '?' | '*') { '_' } else { c })
+        .collect();
+    name.trim().trim_matches('.').to_string()
+}
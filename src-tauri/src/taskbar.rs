@@ -0,0 +1,162 @@
+// Combined progress of the running downloads on the taskbar button (Windows), the dock icon (macOS)
+// or the launcher entry (Linux desktops with libunity), so it's visible without opening the window.
+// A download failing turns it to the error state, with a red badge on Windows, until the window is
+// next focused. On macOS the dock icon also carries the number of active downloads and bounces once
+// when one completes. Linux docks (KDE, Dash to Dock, Plank) get the same count and progress through
+// the Unity launcher D-Bus signal, since most desktops don't ship libunity for Tauri to use.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use tauri::window::{ProgressBarState, ProgressBarStatus};
+use tauri::{AppHandle, Manager, State};
+use tokio::time::Duration;
+
+use crate::{AppState, DownloadStatus};
+
+const UPDATE_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Default)]
+pub(crate) struct Taskbar {
+    failed: AtomicBool,
+}
+
+impl Taskbar {
+    pub(crate) fn mark_failed(&self) {
+        self.failed.store(true, Ordering::Relaxed);
+    }
+
+    // The user has seen the window, and with it whatever failed
+    pub(crate) fn acknowledge(&self) {
+        self.failed.store(false, Ordering::Relaxed);
+    }
+}
+
+// What's on the button; compared between ticks so the OS is only called on a change
+#[derive(Clone, Copy, PartialEq)]
+enum Shown { Hidden, Indeterminate, Progress(u64), Failed(u64) }
+
+pub(crate) async fn run_taskbar_progress(app_handle: AppHandle) {
+    let mut last = Shown::Hidden;
+    let mut last_active = 0;
+    #[cfg(target_os = "linux")]
+    let launcher = launcher::Launcher::connect().await;
+    loop {
+        tokio::time::sleep(UPDATE_INTERVAL).await;
+        let state: State<AppState> = app_handle.state();
+        let (active, downloaded, total, unknown_size): (i64, u64, u64, bool) = {
+            let state_guard = state.persistent.lock().await;
+            state_guard.downloads.iter()
+                .filter(|t| matches!(t.status, DownloadStatus::Downloading | DownloadStatus::Retrying | DownloadStatus::Verifying))
+                .fold((0, 0, 0, false), |(active, downloaded, total, unknown), t| {
+                    (active + 1, downloaded + t.downloaded_size, total + t.total_size, unknown || t.total_size == 0)
+                })
+        };
+        let percent = (downloaded.min(total) * 100).checked_div(total).unwrap_or(0);
+        let shown = if state.taskbar.failed.load(Ordering::Relaxed) {
+            Shown::Failed(if active > 0 { percent } else { 100 })
+        } else if active == 0 {
+            Shown::Hidden
+        } else if unknown_size {
+            Shown::Indeterminate
+        } else {
+            Shown::Progress(percent)
+        };
+        if shown == last && active == last_active { continue; }
+        #[cfg(target_os = "linux")]
+        if let Some(launcher) = &launcher { launcher.update(active, shown).await; }
+        let Some(window) = app_handle.get_webview_window("main") else { continue };
+        #[cfg(target_os = "macos")]
+        if active != last_active {
+            let _ = window.set_badge_label((active > 0).then(|| active.to_string()));
+        }
+        last_active = active;
+        if shown == last { continue; }
+        let (status, progress) = match shown {
+            Shown::Hidden => (ProgressBarStatus::None, None),
+            Shown::Indeterminate => (ProgressBarStatus::Indeterminate, None),
+            Shown::Progress(percent) => (ProgressBarStatus::Normal, Some(percent)),
+            Shown::Failed(percent) => (ProgressBarStatus::Error, Some(percent)),
+        };
+        if let Err(e) = window.set_progress_bar(ProgressBarState { status: Some(status), progress }) {
+            log::debug!("Could not update the taskbar progress: {}", e);
+        }
+        #[cfg(target_os = "windows")]
+        {
+            let failed = matches!(shown, Shown::Failed(_));
+            if failed != matches!(last, Shown::Failed(_)) {
+                let _ = window.set_overlay_icon(failed.then(error_badge));
+            }
+        }
+        last = shown;
+    }
+}
+
+// Draws the eye to a finished download while the app is in the background
+pub(crate) fn bounce(app_handle: &AppHandle) {
+    #[cfg(target_os = "macos")]
+    if let Some(window) = app_handle.get_webview_window("main") {
+        let _ = window.request_user_attention(Some(tauri::UserAttentionType::Informational));
+    }
+    #[cfg(not(target_os = "macos"))]
+    let _ = app_handle;
+}
+
+// A red dot for the corner of the taskbar button
+#[cfg(target_os = "windows")]
+fn error_badge() -> tauri::image::Image<'static> {
+    const SIZE: u32 = 16;
+    let center = (SIZE as f32 - 1.0) / 2.0;
+    let rgba = (0..SIZE * SIZE).flat_map(|i| {
+        let (x, y) = ((i % SIZE) as f32 - center, (i / SIZE) as f32 - center);
+        let inside = x * x + y * y <= center * center;
+        if inside { [0xE5, 0x39, 0x35, 0xFF] } else { [0, 0, 0, 0] }
+    }).collect();
+    tauri::image::Image::new_owned(rgba, SIZE, SIZE)
+}
+
+#[cfg(target_os = "linux")]
+mod launcher {
+    use std::collections::HashMap;
+
+    use zbus::zvariant::Value;
+
+    use super::Shown;
+
+    const PATH: &str = "/com/velodown/LauncherEntry";
+
+    pub(super) struct Launcher {
+        connection: zbus::Connection,
+        // The desktop entry docks know the app by
+        app_uri: String,
+    }
+
+    impl Launcher {
+        pub(super) async fn connect() -> Option<Launcher> {
+            let connection = zbus::Connection::session().await
+                .map_err(|e| log::debug!("No session bus for launcher progress: {}", e))
+                .ok()?;
+            // Set by GLib-based launchers; otherwise the name the packages install the entry under
+            let desktop_file = std::env::var("GIO_LAUNCHED_DESKTOP_FILE").ok()
+                .and_then(|path| std::path::Path::new(&path).file_name().map(|name| name.to_string_lossy().to_string()))
+                .unwrap_or_else(|| format!("{}.desktop", env!("CARGO_PKG_NAME")));
+            Some(Launcher { connection, app_uri: format!("application://{}", desktop_file) })
+        }
+
+        pub(super) async fn update(&self, active: i64, shown: Shown) {
+            let progress = match shown {
+                Shown::Progress(percent) | Shown::Failed(percent) => Some(percent as f64 / 100.0),
+                Shown::Hidden | Shown::Indeterminate => None,
+            };
+            let properties: HashMap<&str, Value> = HashMap::from([
+                ("count", Value::from(active)),
+                ("count-visible", Value::from(active > 0)),
+                ("progress", Value::from(progress.unwrap_or(0.0))),
+                ("progress-visible", Value::from(progress.is_some() && active > 0)),
+                ("urgent", Value::from(matches!(shown, Shown::Failed(_)))),
+            ]);
+            let body = (self.app_uri.as_str(), properties);
+            let signal = self.connection.emit_signal(None::<&str>, PATH, "com.canonical.Unity.LauncherEntry", "Update", &body);
+            if let Err(e) = signal.await { log::debug!("Could not update the launcher entry: {}", e); }
+        }
+    }
+}
@@ -0,0 +1,64 @@
+// A log per download of what its attempts did: requests, redirects, responses, retries, errors and a
+// speed sample every few seconds, so "it just failed" can be answered after the fact. Lines go to
+// `<app data>/logs/tasks/<id>.log`, rolled over to `.log.1` past a size cap, with URLs redacted.
+// Writing is best-effort; a log that can't be written never affects the download.
+
+use std::path::PathBuf;
+
+use chrono::Local;
+use tauri::{AppHandle, Manager};
+use tokio::io::AsyncWriteExt;
+use tokio::time::Duration;
+
+use crate::redact;
+
+const MAX_LOG_BYTES: u64 = 256 * 1024;
+pub(crate) const SPEED_SAMPLE_INTERVAL: Duration = Duration::from_secs(10);
+
+pub(crate) fn dir(app_handle: &AppHandle) -> Option<PathBuf> {
+    Some(app_handle.path().app_data_dir().ok()?.join("logs").join("tasks"))
+}
+
+fn log_path(app_handle: &AppHandle, id: &str) -> Option<PathBuf> {
+    // Ids are generated, but keep anything odd from escaping the directory
+    if id.is_empty() || !id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') { return None; }
+    Some(dir(app_handle)?.join(format!("{}.log", id)))
+}
+
+pub(crate) async fn write(app_handle: &AppHandle, id: &str, message: &str) {
+    let Some(path) = log_path(app_handle, id) else { return };
+    let line = format!("{} {}\n", Local::now().format("%Y-%m-%d %H:%M:%S%.3f"), redact::text(message));
+    if let Err(e) = append(&path, &line).await {
+        log::debug!("Could not write the log for {}: {}", id, e);
+    }
+}
+
+async fn append(path: &PathBuf, line: &str) -> std::io::Result<()> {
+    if let Some(parent) = path.parent() { tokio::fs::create_dir_all(parent).await?; }
+    if tokio::fs::metadata(path).await.is_ok_and(|m| m.len() >= MAX_LOG_BYTES) {
+        tokio::fs::rename(path, path.with_extension("log.1")).await?;
+    }
+    let mut file = tokio::fs::OpenOptions::new().create(true).append(true).open(path).await?;
+    file.write_all(line.as_bytes()).await
+}
+
+// The last `tail` lines, oldest first, reaching into the rolled-over file when needed; everything
+// when `tail` is `None`
+pub(crate) async fn read(app_handle: &AppHandle, id: &str, tail: Option<usize>) -> Result<Vec<String>, String> {
+    let path = log_path(app_handle, id).ok_or("Invalid download id")?;
+    let mut text = tokio::fs::read_to_string(path.with_extension("log.1")).await.unwrap_or_default();
+    match tokio::fs::read_to_string(&path).await {
+        Ok(current) => text.push_str(&current),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+        Err(e) => return Err(format!("Could not read the log: {}", e)),
+    }
+    let lines: Vec<String> = text.lines().map(str::to_string).collect();
+    let skip = tail.map_or(0, |tail| lines.len().saturating_sub(tail));
+    Ok(lines.into_iter().skip(skip).collect())
+}
+
+pub(crate) async fn remove(app_handle: &AppHandle, id: &str) {
+    let Some(path) = log_path(app_handle, id) else { return };
+    let _ = tokio::fs::remove_file(path.with_extension("log.1")).await;
+    let _ = tokio::fs::remove_file(&path).await;
+}
@@ -0,0 +1,157 @@
+// Certificates the user chose to trust for particular hosts, so internal servers behind a private CA
+// or a self-signed certificate work without turning verification off. For a matching host a trusted
+// CA is added to the public roots, and a server certificate identical to a trusted one is accepted
+// outright (pinned). Certificates are only added after the user confirms the fingerprint shown.
+
+use std::sync::Arc;
+
+use rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
+use rustls::client::WebPkiServerVerifier;
+use rustls::pki_types::pem::PemObject;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer, ServerName, UnixTime};
+use rustls::{DigitallySignedStruct, RootCertStore, SignatureScheme};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tokio::time::Duration;
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Default)]
+#[serde(rename_all = "camelCase", default)]
+pub(crate) struct TrustedCertificate {
+    // `example.com` (and subdomains) or a `*` pattern
+    pub(crate) host: String,
+    // PEM-encoded
+    pub(crate) certificate: String,
+    // SHA-256 of the DER certificate, as the user confirmed it
+    pub(crate) fingerprint: String,
+    pub(crate) subject: String,
+}
+
+impl TrustedCertificate {
+    pub(crate) fn host_pattern(&self) -> Option<String> {
+        Some(self.host.trim().trim_end_matches('.').to_ascii_lowercase()).filter(|h| !h.is_empty())
+    }
+}
+
+// What the user is asked to confirm before a certificate is trusted
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct CertificateInfo {
+    subject: String,
+    issuer: String,
+    fingerprint: String,
+    certificate: String,
+}
+
+fn describe(der: &[u8]) -> Result<CertificateInfo, String> {
+    let parsed = p12_keystore::Certificate::from_der(der).map_err(|e| format!("Not a valid certificate: {}", e))?;
+    Ok(CertificateInfo {
+        subject: parsed.subject().to_string(),
+        issuer: parsed.issuer().to_string(),
+        fingerprint: fingerprint(der),
+        certificate: crate::client_certs::pem_block("CERTIFICATE", der),
+    })
+}
+
+fn fingerprint(der: &[u8]) -> String {
+    Sha256::digest(der).iter().map(|b| format!("{:02X}", b)).collect::<Vec<_>>().join(":")
+}
+
+// The certificate a server presents, fetched without verifying it; only a bare GET is sent, and the
+// body is never read
+pub(crate) async fn inspect_server(host: &str, proxy: Option<&str>) -> Result<CertificateInfo, String> {
+    let host = host.trim();
+    let url = if host.contains("://") { host.to_string() } else { format!("https://{}/", host) };
+    let parsed = url::Url::parse(&url).map_err(|e| format!("Invalid host: {}", e))?;
+    if parsed.scheme() != "https" { return Err("Only HTTPS servers have a certificate to trust".to_string()); }
+    let mut builder = reqwest::Client::builder()
+        .danger_accept_invalid_certs(true)
+        .tls_info(true)
+        .redirect(reqwest::redirect::Policy::none())
+        .timeout(Duration::from_secs(20));
+    if let Some(proxy) = proxy.map(str::trim).filter(|p| !p.is_empty()) {
+        builder = builder.proxy(reqwest::Proxy::all(proxy).map_err(|e| e.to_string())?);
+    }
+    let client = builder.build().map_err(|e| e.to_string())?;
+    let response = client.get(parsed).send().await.map_err(|e| format!("Could not connect: {}", e))?;
+    let der = response.extensions().get::<reqwest::tls::TlsInfo>()
+        .and_then(|info| info.peer_certificate())
+        .ok_or("The server didn't present a certificate")?;
+    describe(der)
+}
+
+// A CA or server certificate from a file, PEM or DER; the first one when a PEM file holds several
+pub(crate) fn read_file(path: &str) -> Result<CertificateInfo, String> {
+    let data = std::fs::read(path.trim()).map_err(|e| format!("Could not read {}: {}", path.trim(), e))?;
+    // DER opens with a SEQUENCE tag; anything else is taken for PEM
+    if data.first() == Some(&0x30) { return describe(&data); }
+    let der = CertificateDer::pem_slice_iter(&data).next()
+        .ok_or("No certificate in the file")?
+        .map_err(|e| format!("Invalid PEM: {}", e))?;
+    describe(&der)
+}
+
+// TLS settings for a host with trusted certificates, presenting `identity` (PEM chain and key) when
+// the host also has a client certificate
+pub(crate) fn client_config(trusted: &[&TrustedCertificate], identity: Option<&[u8]>) -> Result<rustls::ClientConfig, String> {
+    let provider = Arc::new(rustls::crypto::ring::default_provider());
+    let mut roots = RootCertStore { roots: webpki_roots::TLS_SERVER_ROOTS.to_vec() };
+    let mut pins = Vec::new();
+    for cert in trusted {
+        let der = CertificateDer::pem_slice_iter(cert.certificate.as_bytes()).next()
+            .ok_or_else(|| format!("Trusted certificate for {} is empty", cert.host))?
+            .map_err(|e| format!("Trusted certificate for {} is invalid: {}", cert.host, e))?;
+        pins.push(Sha256::digest(&der).to_vec());
+        // A self-signed server certificate isn't a valid anchor; the pin covers it
+        let _ = roots.add(der);
+    }
+    let inner = WebPkiServerVerifier::builder_with_provider(Arc::new(roots), provider.clone())
+        .build()
+        .map_err(|e| e.to_string())?;
+    let builder = rustls::ClientConfig::builder_with_provider(provider)
+        .with_safe_default_protocol_versions()
+        .map_err(|e| e.to_string())?
+        .dangerous()
+        .with_custom_certificate_verifier(Arc::new(PinningVerifier { pins, inner }));
+    match identity {
+        Some(pem) => {
+            let chain = CertificateDer::pem_slice_iter(pem).collect::<Result<Vec<_>, _>>().map_err(|e| format!("Invalid client certificate: {}", e))?;
+            let key = PrivateKeyDer::from_pem_slice(pem).map_err(|e| format!("Invalid client key: {}", e))?;
+            builder.with_client_auth_cert(chain, key).map_err(|e| format!("Invalid client certificate: {}", e))
+        }
+        None => Ok(builder.with_no_client_auth()),
+    }
+}
+
+#[derive(Debug)]
+struct PinningVerifier {
+    pins: Vec<Vec<u8>>,
+    inner: Arc<WebPkiServerVerifier>,
+}
+
+impl ServerCertVerifier for PinningVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        intermediates: &[CertificateDer<'_>],
+        server_name: &ServerName<'_>,
+        ocsp_response: &[u8],
+        now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        if self.pins.iter().any(|pin| pin.as_slice() == Sha256::digest(end_entity).as_slice()) {
+            return Ok(ServerCertVerified::assertion());
+        }
+        self.inner.verify_server_cert(end_entity, intermediates, server_name, ocsp_response, now)
+    }
+
+    fn verify_tls12_signature(&self, message: &[u8], cert: &CertificateDer<'_>, dss: &DigitallySignedStruct) -> Result<HandshakeSignatureValid, rustls::Error> {
+        self.inner.verify_tls12_signature(message, cert, dss)
+    }
+
+    fn verify_tls13_signature(&self, message: &[u8], cert: &CertificateDer<'_>, dss: &DigitallySignedStruct) -> Result<HandshakeSignatureValid, rustls::Error> {
+        self.inner.verify_tls13_signature(message, cert, dss)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.inner.supported_verify_schemes()
+    }
+}
@@ -0,0 +1,47 @@
+// The tray icon's menu, and closing the window to the tray: with `close_to_tray` on, the close button
+// only hides the window and downloads carry on; the tray icon brings it back, and Quit in its menu is
+// the way out. The menu also has the speed switch (see `speed_mode`).
+
+use tauri::menu::{Menu, MenuItem};
+use tauri::{AppHandle, CloseRequestApi, Manager, Window};
+
+use crate::i18n::{tr, Language, Text};
+use crate::speed_mode::SpeedMode;
+use crate::AppState;
+
+// Called once at startup, so a language change shows here after a restart; the icon itself comes
+// from the config, under the default id
+pub(crate) fn install(app_handle: &AppHandle, language: Language) -> tauri::Result<()> {
+    let Some(tray) = app_handle.tray_by_id("main") else { return Ok(()) };
+    let show = MenuItem::with_id(app_handle, "show", tr(language, Text::ShowApp), true, None::<&str>)?;
+    let full_speed = MenuItem::with_id(app_handle, "speed-unlimited", tr(language, Text::FullSpeed), true, None::<&str>)?;
+    let background_speed = MenuItem::with_id(app_handle, "speed-background", tr(language, Text::BackgroundSpeed), true, None::<&str>)?;
+    let pause_all = MenuItem::with_id(app_handle, "speed-paused", tr(language, Text::PauseAll), true, None::<&str>)?;
+    let quit = MenuItem::with_id(app_handle, "quit", tr(language, Text::Quit), true, None::<&str>)?;
+    tray.set_menu(Some(Menu::with_items(app_handle, &[&show, &full_speed, &background_speed, &pause_all, &quit])?))?;
+    // Left click shows the window; the menu is on right click
+    tray.set_show_menu_on_left_click(false)?;
+    tray.on_menu_event(|app_handle, event| match event.id().as_ref() {
+        "show" => crate::show_main_window(app_handle),
+        "speed-unlimited" => set_speed_mode(app_handle, SpeedMode::Unlimited),
+        "speed-background" => set_speed_mode(app_handle, SpeedMode::Background),
+        "speed-paused" => set_speed_mode(app_handle, SpeedMode::Paused),
+        // Goes through the graceful shutdown in `shutdown`
+        "quit" => app_handle.exit(0),
+        _ => {}
+    });
+    Ok(())
+}
+
+fn set_speed_mode(app_handle: &AppHandle, mode: SpeedMode) {
+    let app_handle = app_handle.clone();
+    tauri::async_runtime::spawn(async move { crate::speed_mode::set(&app_handle, mode).await });
+}
+
+// Runs on the main thread, outside the async runtime, so the settings lock can be taken blocking
+pub(crate) fn on_close_requested(window: &Window, api: &CloseRequestApi) {
+    let close_to_tray = window.state::<AppState>().persistent.blocking_lock().settings.close_to_tray;
+    if !close_to_tray { return; }
+    api.prevent_close();
+    let _ = window.hide();
+}
@@ -0,0 +1,108 @@
+// Self-update through the Tauri updater, in three steps the settings page drives: check, download,
+// install. Installing replaces the binary and relaunches, so running downloads are paused first, the
+// way sleep pauses them, and state is written; the relaunched app resumes exactly those.
+//
+// The repo carries no signing key, so plain builds have no updater: the plugin isn't registered and the
+// settings page hides the section. A release build signed for updates merges in a config holding the
+// public key, e.g. `npm run tauri build -- --config updater.json` with
+// `{"bundle": {"createUpdaterArtifacts": true}, "plugins": {"updater": {"pubkey": "...", "endpoints":
+// ["https://github.com/mlm-games/velodown/releases/latest/download/latest.json"]}}}`, and
+// `TAURI_SIGNING_PRIVATE_KEY` set so the bundler signs the packages and writes `latest.json`.
+
+use serde::Serialize;
+use tauri::{AppHandle, Emitter, Manager, State};
+use tauri_plugin_updater::{Update, UpdaterExt};
+use tokio::sync::Mutex;
+
+use crate::AppState;
+
+// Marks downloads paused for the install, so only those are resumed after it
+const UPDATING_MESSAGE: &str = "Paused while the app updates";
+
+#[derive(Default)]
+pub(crate) struct PendingUpdate {
+    update: Mutex<Option<Update>>,
+    // The downloaded package, verified against the public key
+    package: Mutex<Option<Vec<u8>>>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct UpdateInfo {
+    version: String,
+    current_version: String,
+    notes: Option<String>,
+}
+
+#[derive(Clone, Serialize)]
+struct UpdateProgress {
+    downloaded: u64,
+    total: Option<u64>,
+}
+
+pub(crate) fn configured(app_handle: &AppHandle) -> bool {
+    app_handle.config().plugins.0.get("updater")
+        .and_then(|config| config.get("pubkey"))
+        .and_then(|key| key.as_str())
+        .is_some_and(|key| !key.trim().is_empty())
+}
+
+pub(crate) async fn check(app_handle: &AppHandle) -> Result<Option<UpdateInfo>, String> {
+    if !configured(app_handle) { return Err("This build wasn't set up for updates".to_string()); }
+    let update = app_handle.updater().map_err(|e| e.to_string())?
+        .check().await
+        .map_err(|e| format!("Could not check for updates: {}", e))?;
+    let state: State<AppState> = app_handle.state();
+    let pending = &state.updater;
+    *pending.package.lock().await = None;
+    let info = update.as_ref().map(|update| UpdateInfo {
+        version: update.version.clone(),
+        current_version: update.current_version.clone(),
+        notes: update.body.clone(),
+    });
+    *pending.update.lock().await = update;
+    Ok(info)
+}
+
+// Emits `update_progress` as the package arrives; downloads keep running meanwhile
+pub(crate) async fn download(app_handle: &AppHandle) -> Result<(), String> {
+    let state: State<AppState> = app_handle.state();
+    let pending = &state.updater;
+    let update = pending.update.lock().await.clone().ok_or("No update to download; check for one first")?;
+    let mut downloaded = 0u64;
+    let package = update.download(
+        |chunk, total| {
+            downloaded += chunk as u64;
+            let _ = app_handle.emit("update_progress", UpdateProgress { downloaded, total });
+        },
+        || {},
+    ).await.map_err(|e| format!("Could not download the update: {}", e))?;
+    *pending.package.lock().await = Some(package);
+    Ok(())
+}
+
+// Doesn't return on success: the app restarts into the new version (on Windows the installer exits it)
+pub(crate) async fn install(app_handle: &AppHandle) -> Result<(), String> {
+    let state: State<AppState> = app_handle.state();
+    let pending = &state.updater;
+    let update = pending.update.lock().await.clone().ok_or("No update to install; check for one first")?;
+    let package = pending.package.lock().await.take().ok_or("The update hasn't been downloaded yet")?;
+    let paused = crate::pause_running(app_handle, UPDATING_MESSAGE).await;
+    crate::flush_state(app_handle).await;
+    if let Err(e) = update.install(&package) {
+        // Still on the old version; carry on as before
+        *pending.package.lock().await = Some(package);
+        crate::resume_paused(app_handle, paused, UPDATING_MESSAGE).await;
+        return Err(format!("Could not install the update: {}", e));
+    }
+    app_handle.restart();
+}
+
+// Called once at startup, after a relaunch into the new version
+pub(crate) async fn resume_after_update(app_handle: AppHandle) {
+    let paused: Vec<String> = app_handle.state::<AppState>().persistent.lock().await.downloads.iter()
+        .filter(|t| t.error_message.as_deref() == Some(UPDATING_MESSAGE))
+        .map(|t| t.id.clone())
+        .collect();
+    if !paused.is_empty() { crate::resume_paused(&app_handle, paused, UPDATING_MESSAGE).await; }
+}
@@ -0,0 +1,184 @@
+// The real values behind what `redact` masks in state.json. With `redact_saved_secrets` on, the
+// credentials masked in a saved download (its URL and redirect hops, secret headers, the proxy
+// password, a request body, the signature links) go into the OS keyring under `saved:<task id>`, and
+// are put back when state.json is loaded, so an unfinished download resumes after a restart as it
+// would have without the setting. Keyring entries are only written when they change, and dropped
+// with their task. What the keyring couldn't take is lost on restart; `redact::mark_lost` says so.
+// The API tokens in the settings always go into the keyring, under `saved:<setting name>`.
+
+use std::collections::{BTreeMap, HashMap};
+
+use serde::{Deserialize, Serialize};
+use tokio::sync::Mutex;
+
+use crate::{accounts, redact, removed, AppSettings, DownloadTask, PersistentState};
+
+type Field = fn(&mut AppSettings) -> &mut String;
+const TOKENS: [(&str, Field); 4] = [
+    ("github_token", |s| &mut s.github_token),
+    ("huggingface_token", |s| &mut s.huggingface_token),
+    ("opensubtitles_api_key", |s| &mut s.opensubtitles_api_key),
+    ("virustotal_api_key", |s| &mut s.virustotal_api_key),
+];
+
+#[derive(Debug, Serialize, Deserialize, Default, PartialEq)]
+#[serde(rename_all = "camelCase", default)]
+struct TaskSecrets {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    redirect_chain: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    source_url: Option<String>,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    headers: BTreeMap<String, String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    proxy: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    body: Option<String>,
+    // The signature's URL and key
+    #[serde(skip_serializing_if = "Option::is_none")]
+    signature: Option<(String, String)>,
+}
+
+// What the keyring was last given, by entry id, so unchanged entries aren't written again every save
+#[derive(Default)]
+pub(crate) struct Vault {
+    written: Mutex<HashMap<String, String>>,
+}
+
+fn entry_id(name: &str) -> String {
+    format!("saved:{}", name)
+}
+
+fn body(task: &DownloadTask) -> Option<&String> {
+    task.request.as_ref().and_then(|r| r.body.as_ref())
+}
+
+// The values of `task` that differ in its masked copy
+fn secrets(task: &DownloadTask, masked: &DownloadTask) -> TaskSecrets {
+    TaskSecrets {
+        url: (task.url != masked.url).then(|| task.url.clone()),
+        redirect_chain: (task.redirect_chain != masked.redirect_chain).then(|| task.redirect_chain.clone()),
+        source_url: task.source_url.clone().filter(|_| task.source_url != masked.source_url),
+        headers: task.headers.iter()
+            .filter(|(name, value)| masked.headers.get(*name) != Some(*value))
+            .map(|(name, value)| (name.clone(), value.clone()))
+            .collect(),
+        proxy: task.proxy.clone().filter(|_| task.proxy != masked.proxy),
+        body: body(task).filter(|_| body(task) != body(masked)).cloned(),
+        signature: task.signature.as_ref().zip(masked.signature.as_ref())
+            .filter(|(real, masked)| real.url != masked.url || real.key != masked.key)
+            .map(|(real, _)| (real.url.clone(), real.key.clone())),
+    }
+}
+
+// Only into fields that are still masked, in case the task changed after the keyring was written
+fn restore(task: &mut DownloadTask, secrets: TaskSecrets) {
+    let masked = |value: &str| value.contains(redact::MARK);
+    if let Some(url) = secrets.url.filter(|_| masked(&task.url)) { task.url = url; }
+    if let Some(chain) = secrets.redirect_chain.filter(|_| task.redirect_chain.iter().any(|hop| masked(hop))) {
+        task.redirect_chain = chain;
+    }
+    if let Some(source) = secrets.source_url.filter(|_| task.source_url.as_deref().is_some_and(masked)) {
+        task.source_url = Some(source);
+    }
+    for (name, value) in secrets.headers {
+        if let Some(existing) = task.headers.get_mut(&name).filter(|v| *v == redact::MARK) { *existing = value; }
+    }
+    if let Some(proxy) = secrets.proxy.filter(|_| task.proxy.as_deref().is_some_and(masked)) { task.proxy = Some(proxy); }
+    if let Some(saved) = task.request.as_mut().and_then(|r| r.body.as_mut()).filter(|b| masked(b)) {
+        if let Some(body) = secrets.body { *saved = body; }
+    }
+    if let Some(check) = task.signature.as_mut().filter(|c| masked(&c.url) || masked(&c.key)) {
+        if let Some((url, key)) = secrets.signature { (check.url, check.key) = (url, key); }
+    }
+}
+
+// Masks the tokens and, with the setting on, the tasks in `value`, the serialized `state`, returning
+// the keyring entries that keep what was masked
+pub(crate) fn mask(state: &PersistentState, value: &mut serde_json::Value) -> serde_json::Result<BTreeMap<String, String>> {
+    let mut entries = BTreeMap::new();
+    let mut settings = state.settings.clone();
+    for (name, field) in TOKENS {
+        let token = field(&mut settings);
+        if !token.is_empty() { entries.insert(entry_id(name), std::mem::replace(token, redact::MARK.to_string())); }
+    }
+    value["settings"] = serde_json::to_value(&settings)?;
+    if !state.settings.redact_saved_secrets { return Ok(entries); }
+    let mut mask_task = |task: &DownloadTask| -> serde_json::Result<DownloadTask> {
+        let masked = redact::task(task);
+        let secrets = secrets(task, &masked);
+        if secrets != TaskSecrets::default() { entries.insert(entry_id(&task.id), serde_json::to_string(&secrets)?); }
+        Ok(masked)
+    };
+    let downloads = state.downloads.iter().map(&mut mask_task).collect::<serde_json::Result<Vec<_>>>()?;
+    let removed = state.removed.iter()
+        .map(|r| Ok(removed::RemovedDownload { task: mask_task(&r.task)?, ..r.clone() }))
+        .collect::<serde_json::Result<Vec<_>>>()?;
+    value["downloads"] = serde_json::to_value(downloads)?;
+    value["removed"] = serde_json::to_value(removed)?;
+    Ok(entries)
+}
+
+impl Vault {
+    pub(crate) async fn load(state: &mut PersistentState) -> Vault {
+        let vault = Vault::default();
+        vault.unmask(state).await;
+        vault
+    }
+
+    // Puts back what the keyring holds for the tokens and masked tasks of a state.json just read
+    pub(crate) async fn unmask(&self, state: &mut PersistentState) {
+        let mut written = self.written.lock().await;
+        for (name, field) in TOKENS {
+            let token = field(&mut state.settings);
+            if token != redact::MARK { continue; }
+            let id = entry_id(name);
+            match accounts::load_secret(&id).await {
+                Ok(secret) => {
+                    *token = secret.clone();
+                    written.insert(id, secret);
+                }
+                // Never sent as is
+                Err(e) => {
+                    log::warn!("The saved {} is gone: {}", name, e);
+                    token.clear();
+                }
+            }
+        }
+        let tasks = state.downloads.iter_mut().chain(state.removed.iter_mut().map(|r| &mut r.task))
+            .filter(|task| redact::is_masked(task));
+        let loaded = futures::future::join_all(tasks.map(|task| async {
+            let id = entry_id(&task.id);
+            let secret = accounts::load_secret(&id).await;
+            (task, id, secret)
+        })).await;
+        for (task, id, secret) in loaded {
+            let Ok(secret) = secret else { continue };
+            match serde_json::from_str(&secret) {
+                Ok(secrets) => restore(task, secrets),
+                Err(e) => log::warn!("Could not read the saved credentials of {}: {}", task.file_name, e),
+            }
+            written.insert(id, secret);
+        }
+    }
+
+    // Run before state.json is written, so it never masks a value the keyring doesn't have yet.
+    // Entries that failed aren't retried until they change, to not warn on every save.
+    pub(crate) async fn save(&self, entries: BTreeMap<String, String>) {
+        let mut written = self.written.lock().await;
+        for (id, secret) in &entries {
+            if written.get(id) == Some(secret) { continue; }
+            if let Err(e) = accounts::store_secret(id, secret).await {
+                log::warn!("{} wasn't saved: {}", id, e);
+            }
+            written.insert(id.clone(), secret.clone());
+        }
+        let gone: Vec<String> = written.keys().filter(|id| !entries.contains_key(*id)).cloned().collect();
+        for id in gone {
+            if let Err(e) = accounts::delete_secret(&id).await { log::warn!("{}", e); }
+            written.remove(&id);
+        }
+    }
+}
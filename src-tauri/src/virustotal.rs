@@ -0,0 +1,124 @@
+// VirusTotal lookups for finished downloads. Only the file's SHA-256 is sent, never the file: a hash
+// VirusTotal has seen comes back with its latest analysis, and an unknown one is reported as such.
+// Free API keys allow 4 lookups a minute, so rate-limited requests wait and retry.
+
+use chrono::{DateTime, Local};
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Emitter, Manager, State};
+use tokio::time::Duration;
+
+use crate::{AppState, DownloadTask};
+
+const API: &str = "https://www.virustotal.com/api/v3/files";
+const RATE_LIMIT_RETRIES: u32 = 3;
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct VirusScan {
+    pub(crate) sha256: String,
+    // False when VirusTotal has no report for this hash
+    pub(crate) known: bool,
+    pub(crate) malicious: u32,
+    pub(crate) suspicious: u32,
+    // Engines that gave any verdict
+    pub(crate) engines: u32,
+    pub(crate) checked_at: DateTime<Local>,
+}
+
+impl VirusScan {
+    pub(crate) fn flagged(&self) -> bool {
+        self.malicious > 0 || self.suspicious > 0
+    }
+}
+
+#[derive(Deserialize)]
+struct FileResponse { data: FileObject }
+
+#[derive(Deserialize)]
+struct FileObject { attributes: FileAttributes }
+
+#[derive(Deserialize)]
+struct FileAttributes { last_analysis_stats: AnalysisStats }
+
+#[derive(Deserialize)]
+struct AnalysisStats {
+    #[serde(default)]
+    malicious: u32,
+    #[serde(default)]
+    suspicious: u32,
+    #[serde(default)]
+    undetected: u32,
+    #[serde(default)]
+    harmless: u32,
+}
+
+// Runs after a download finishes; failures are only logged
+pub(crate) async fn check_task(app_handle: AppHandle, task: DownloadTask) {
+    let settings = app_handle.state::<AppState>().persistent.lock().await.settings.clone();
+    let api_key = settings.virustotal_api_key.trim().to_string();
+    if !settings.virustotal_enabled || api_key.is_empty() { return; }
+    match scan(&app_handle, &task, &api_key).await {
+        Ok(scan) => {
+            if scan.flagged() {
+                log::warn!("VirusTotal flags {}: {} malicious, {} suspicious of {}", task.file_name, scan.malicious, scan.suspicious, scan.engines);
+            }
+            let state: State<AppState> = app_handle.state();
+            {
+                let mut state_guard = state.persistent.lock().await;
+                if let Some(task) = state_guard.downloads.iter_mut().find(|t| t.id == task.id) {
+                    task.virus_scan = Some(scan);
+                    app_handle.emit("task_updated", &*task).unwrap();
+                }
+            }
+            crate::request_save(&state);
+        }
+        Err(e) => log::warn!("VirusTotal lookup for {} failed: {}", task.file_name, e),
+    }
+}
+
+async fn scan(app_handle: &AppHandle, task: &DownloadTask, api_key: &str) -> Result<VirusScan, String> {
+    // A SHA-256 checksum was verified on completion, so it is the file's hash already
+    let verified = task.checksum.as_deref().and_then(crate::checksum_hasher)
+        .filter(|(hasher, _)| hasher.output_size() == 32)
+        .map(|(_, digest)| digest);
+    let sha256 = match verified {
+        Some(digest) => digest,
+        None => {
+            let path = std::path::PathBuf::from(&task.save_path).join(&task.file_name);
+            crate::hash_file(&path, Box::new(sha2::Sha256::default())).await.map_err(|e| format!("Could not hash {}: {}", path.display(), e))?
+        }
+    };
+
+    let client = crate::http_client(&app_handle.state()).await;
+    let mut attempt = 0;
+    let response = loop {
+        let response = client.get(format!("{}/{}", API, sha256))
+            .header("x-apikey", api_key)
+            .header(reqwest::header::ACCEPT, "application/json")
+            .timeout(Duration::from_secs(30))
+            .send()
+            .await
+            .map_err(|e| format!("Request failed: {}", e))?;
+        if response.status() != StatusCode::TOO_MANY_REQUESTS || attempt == RATE_LIMIT_RETRIES { break response; }
+        attempt += 1;
+        tokio::time::sleep(Duration::from_secs(60)).await;
+    };
+    let unknown = VirusScan { sha256: sha256.clone(), known: false, malicious: 0, suspicious: 0, engines: 0, checked_at: Local::now() };
+    match response.status() {
+        StatusCode::NOT_FOUND => return Ok(unknown),
+        StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => return Err("VirusTotal rejected the API key".to_string()),
+        status if !status.is_success() => return Err(format!("VirusTotal returned error: {}", status)),
+        _ => {}
+    }
+    let body = response.bytes().await.map_err(|e| format!("Request failed: {}", e))?;
+    let report: FileResponse = serde_json::from_slice(&body).map_err(|e| format!("Unexpected VirusTotal response: {}", e))?;
+    let stats = report.data.attributes.last_analysis_stats;
+    Ok(VirusScan {
+        known: true,
+        malicious: stats.malicious,
+        suspicious: stats.suspicious,
+        engines: stats.malicious + stats.suspicious + stats.undetected + stats.harmless,
+        ..unknown
+    })
+}